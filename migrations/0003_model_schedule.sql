@@ -0,0 +1,3 @@
+ALTER TABLE models
+    ADD COLUMN enabled_from BIGINT,
+    ADD COLUMN enabled_until BIGINT;
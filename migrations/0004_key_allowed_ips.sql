@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS key_allowed_ips (
+    key_id TEXT NOT NULL REFERENCES client_keys(id) ON DELETE CASCADE,
+    cidr TEXT NOT NULL,
+    PRIMARY KEY (key_id, cidr)
+);
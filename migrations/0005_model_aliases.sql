@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS model_aliases (
+    alias TEXT PRIMARY KEY,
+    target TEXT NOT NULL
+);
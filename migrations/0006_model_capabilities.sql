@@ -0,0 +1,6 @@
+-- Every model in the seed list supports images, tools and extended thinking,
+-- so existing rows default to fully capable.
+ALTER TABLE models
+    ADD COLUMN supports_vision BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN supports_tools BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN supports_thinking BOOLEAN NOT NULL DEFAULT TRUE;
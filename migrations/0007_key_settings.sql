@@ -0,0 +1,3 @@
+-- JSON-encoded per-key overrides (see `KeySettings`). Empty object = defaults.
+ALTER TABLE client_keys
+    ADD COLUMN settings TEXT NOT NULL DEFAULT '{}';
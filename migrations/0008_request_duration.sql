@@ -0,0 +1,3 @@
+-- created_at now holds the request start; duration_ms is the time until usage
+-- was recorded (end of stream for streaming requests). NULL for older rows.
+ALTER TABLE request_log ADD COLUMN IF NOT EXISTS duration_ms BIGINT;
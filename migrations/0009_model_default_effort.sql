@@ -0,0 +1,3 @@
+-- Thinking effort applied to OpenAI requests for this model that set neither
+-- reasoning_effort nor a model suffix. NULL = no thinking by default.
+ALTER TABLE models ADD COLUMN IF NOT EXISTS default_effort TEXT;
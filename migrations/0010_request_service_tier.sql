@@ -0,0 +1,3 @@
+-- Anthropic service_tier requested for each logged request (`auto` or
+-- `standard_only`). NULL = none set; Anthropic's default applies.
+ALTER TABLE request_log ADD COLUMN IF NOT EXISTS service_tier TEXT;
@@ -0,0 +1,11 @@
+-- Message batches submitted through the proxy, so only the submitting key
+-- can read a batch and its usage is recorded exactly once when the results
+-- are first read in full.
+CREATE TABLE IF NOT EXISTS message_batches (
+    id TEXT PRIMARY KEY,
+    key_id TEXT NOT NULL REFERENCES client_keys(id) ON DELETE CASCADE,
+    created_at BIGINT NOT NULL,
+    usage_recorded BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_batches_key_id ON message_batches(key_id);
@@ -0,0 +1,3 @@
+-- Per-model lookups over a time range (request-log browsing, model usage
+-- rollups) otherwise scan every row since the start of the range.
+CREATE INDEX IF NOT EXISTS idx_request_log_model_created ON request_log(model, created_at);
@@ -0,0 +1,10 @@
+-- Subscription utilization (percent) recorded on every full usage fetch,
+-- for graphing. Rows older than 30 days are pruned by the proxy.
+CREATE TABLE IF NOT EXISTS subscription_samples (
+    id BIGSERIAL PRIMARY KEY,
+    sampled_at BIGINT NOT NULL,
+    five_hour_utilization DOUBLE PRECISION,
+    seven_day_utilization DOUBLE PRECISION
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscription_samples_sampled_at ON subscription_samples(sampled_at);
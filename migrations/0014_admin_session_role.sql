@@ -0,0 +1,3 @@
+-- Role a session was opened with: `full`, or `read_only` for the optional
+-- read-only admin login (GET-only access to the admin API).
+ALTER TABLE admin_sessions ADD COLUMN IF NOT EXISTS role TEXT NOT NULL DEFAULT 'full';
@@ -0,0 +1,13 @@
+-- Successful non-streaming responses stored per (key, Idempotency-Key) so
+-- client retries are answered without a second upstream call. Rows older
+-- than an hour are pruned by the proxy.
+CREATE TABLE IF NOT EXISTS idempotent_responses (
+    key_id TEXT NOT NULL REFERENCES client_keys(id) ON DELETE CASCADE,
+    idempotency_key TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (key_id, idempotency_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotent_responses_created_at ON idempotent_responses(created_at);
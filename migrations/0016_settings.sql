@@ -0,0 +1,7 @@
+-- Small named values the proxy keeps across restarts (JSON-encoded), such
+-- as the last known subscription window reset times.
+CREATE TABLE IF NOT EXISTS settings (
+    name TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
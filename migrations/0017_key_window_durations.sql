@@ -0,0 +1,6 @@
+-- Length of each key's rate-limit windows. Keys on the defaults (5 hours,
+-- 7 days) follow the subscription's own reset times; others roll over on
+-- their own schedule.
+ALTER TABLE client_keys
+    ADD COLUMN five_hour_window_ms BIGINT NOT NULL DEFAULT 18000000,
+    ADD COLUMN weekly_window_ms BIGINT NOT NULL DEFAULT 604800000;
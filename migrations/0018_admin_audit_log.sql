@@ -0,0 +1,16 @@
+-- Admin login a session belongs to, for the audit log. Sessions opened
+-- before this migration have no username.
+ALTER TABLE admin_sessions ADD COLUMN IF NOT EXISTS username TEXT NOT NULL DEFAULT '';
+
+-- Append-only record of mutating admin requests. Request and response
+-- bodies are never stored, so passwords and generated keys can't leak here.
+CREATE TABLE IF NOT EXISTS admin_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    created_at BIGINT NOT NULL,
+    username TEXT NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    status INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_admin_audit_log_created ON admin_audit_log(created_at, id);
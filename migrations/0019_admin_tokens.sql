@@ -0,0 +1,10 @@
+-- Long-lived bearer tokens for scripting the admin API. Only the SHA-256
+-- of each token is stored; revoked tokens are kept for the record.
+CREATE TABLE IF NOT EXISTS admin_tokens (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    role TEXT NOT NULL DEFAULT 'full',
+    created_at BIGINT NOT NULL,
+    revoked_at BIGINT
+);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS key_allowed_origins (
+    key_id TEXT NOT NULL REFERENCES client_keys(id) ON DELETE CASCADE,
+    origin TEXT NOT NULL,
+    PRIMARY KEY (key_id, origin)
+);
@@ -0,0 +1,5 @@
+-- OpenAI `user` the client attributed each logged request to, for per
+-- end-user analytics under one proxy key. NULL = not given.
+ALTER TABLE request_log ADD COLUMN IF NOT EXISTS end_user TEXT;
+CREATE INDEX IF NOT EXISTS idx_request_log_end_user_created ON request_log(end_user, created_at)
+    WHERE end_user IS NOT NULL;
@@ -0,0 +1 @@
+ALTER TABLE client_keys ADD COLUMN IF NOT EXISTS force_model TEXT;
@@ -1,19 +1,24 @@
 use axum::{
+    Json,
     extract::Request,
-    extract::State,
-    http::{StatusCode, header},
+    extract::{ConnectInfo, State},
+    http::{Method, StatusCode, header},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 use std::{
+    net::SocketAddr,
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 use subtle::ConstantTimeEq;
 use tracing::warn;
 
+use crate::admin_tokens;
+use crate::client_ip::resolve_client_ip;
+use crate::routes::admin::ErrorResponse;
 use crate::{AppState, db};
 
 /// Session TTL: 30 days (with sliding expiration on each request)
@@ -22,16 +27,91 @@ pub(crate) const SESSION_TTL_SECS: u64 = 30 * 24 * 3600;
 pub struct AdminCredentials {
     pub username: String,
     pub password: String,
+    /// Optional second login (username, password) that can view the admin
+    /// API but not change anything.
+    pub read_only: Option<(String, String)>,
 }
 
+impl AdminCredentials {
+    /// Role granted to a username/password pair, if it matches either login.
+    pub(crate) fn role_for(&self, username: &str, password: &str) -> Option<AdminRole> {
+        if credentials_match(username, password, &self.username, &self.password) {
+            return Some(AdminRole::Full);
+        }
+        match &self.read_only {
+            Some((user, pass)) if credentials_match(username, password, user, pass) => {
+                Some(AdminRole::ReadOnly)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Constant-time comparison of a login against the expected one.
+fn credentials_match(
+    username: &str,
+    password: &str,
+    expected_user: &str,
+    expected_pass: &str,
+) -> bool {
+    let user_match = username.as_bytes().ct_eq(expected_user.as_bytes());
+    let pass_match = password.as_bytes().ct_eq(expected_pass.as_bytes());
+    (user_match & pass_match).into()
+}
+
+/// What an authenticated admin may do. Stored with each session and attached
+/// to admin requests as an extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminRole {
+    Full,
+    /// GET-only access: dashboards and listings, no mutations
+    ReadOnly,
+}
+
+impl AdminRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::ReadOnly => "read_only",
+        }
+    }
+
+    /// Unknown values are treated as read-only so a bad row never grants
+    /// more access than intended.
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "full" => Self::Full,
+            _ => Self::ReadOnly,
+        }
+    }
+
+    fn allows(self, method: &Method) -> bool {
+        match self {
+            Self::Full => true,
+            Self::ReadOnly => matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS),
+        }
+    }
+}
+
+/// Login behind an admin request, attached as a request extension next to
+/// its [`AdminRole`] for the audit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminUser(pub String);
+
+/// Recorded as the admin when `CLAUDE_PROXY_DISABLE_AUTH` is set.
+const ANONYMOUS_ADMIN: &str = "anonymous";
+
 /// Save a session token to the database.
-pub(crate) async fn save_session(token: &str, expires_at: u64) {
+pub(crate) async fn save_session(token: &str, expires_at: u64, role: AdminRole, username: &str) {
     if let Ok(conn) = db::get_conn().await
         && let Err(e) = sqlx::query!(
-            "INSERT INTO admin_sessions (token, expires_at) VALUES ($1, $2) \
-             ON CONFLICT (token) DO UPDATE SET expires_at = EXCLUDED.expires_at",
+            "INSERT INTO admin_sessions (token, expires_at, role, username) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (token) DO UPDATE SET expires_at = EXCLUDED.expires_at, role = EXCLUDED.role, \
+             username = EXCLUDED.username",
             token,
             expires_at as i64,
+            role.as_str(),
+            username,
         )
         .execute(&conn)
         .await
@@ -40,24 +120,17 @@ pub(crate) async fn save_session(token: &str, expires_at: u64) {
     }
 }
 
-/// Validate a session token, returns true if valid and not expired.
-/// Also extends the session (sliding expiration) if it's valid.
-pub(crate) async fn validate_session(token: &str) -> bool {
-    let Ok(conn) = db::get_conn().await else {
-        return false;
-    };
-    let Ok(row) = sqlx::query!(
-        "SELECT expires_at FROM admin_sessions WHERE token = $1",
+/// Validate a session token, returning its role and login if valid and not
+/// expired. Also extends the session (sliding expiration) if it's valid.
+pub(crate) async fn validate_session(token: &str) -> Option<(AdminRole, AdminUser)> {
+    let conn = db::get_conn().await.ok()?;
+    let row = sqlx::query!(
+        "SELECT expires_at, role, username FROM admin_sessions WHERE token = $1",
         token
     )
     .fetch_optional(&conn)
     .await
-    else {
-        return false;
-    };
-    let Some(row) = row else {
-        return false;
-    };
+    .ok()??;
     let expires_at = row.expires_at;
     let now = now_secs() as i64;
     if now >= expires_at {
@@ -68,7 +141,7 @@ pub(crate) async fn validate_session(token: &str) -> bool {
         {
             warn!("Failed to delete expired admin session: {e}");
         }
-        return false;
+        return None;
     }
 
     // Sliding expiration: renew if more than 1 day has passed since last renewal.
@@ -84,7 +157,7 @@ pub(crate) async fn validate_session(token: &str) -> bool {
     {
         warn!("Failed to refresh admin session expiry: {e}");
     }
-    true
+    Some((AdminRole::parse(&row.role), AdminUser(row.username)))
 }
 
 /// Remove a session token from the database.
@@ -109,16 +182,95 @@ pub(crate) fn session_expires_at() -> u64 {
     now_secs() + SESSION_TTL_SECS
 }
 
-pub(crate) fn session_cookie(token: &str, secure: bool) -> String {
-    let secure_flag = if secure { "; Secure" } else { "" };
-    format!(
-        "admin_session={token}; HttpOnly; SameSite=Strict; Path=/admin; Max-Age={SESSION_TTL_SECS}{secure_flag}"
-    )
+/// Default cookie `Path`: where the admin UI and API are mounted.
+pub const DEFAULT_COOKIE_PATH: &str = "/admin";
+
+/// `SameSite` attribute of the session cookie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Sent on cross-site requests too, for an admin UI on another origin.
+    /// Browsers only accept it together with `Secure`.
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "strict" => Some(Self::Strict),
+            "lax" => Some(Self::Lax),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
 }
 
-pub(crate) fn clear_session_cookie(secure: bool) -> String {
-    let secure_flag = if secure { "; Secure" } else { "" };
-    format!("admin_session=; HttpOnly; SameSite=Strict; Path=/admin; Max-Age=0{secure_flag}")
+/// Attributes of the `admin_session` cookie, for deployments behind a
+/// reverse proxy that mounts the admin UI elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CookieSettings {
+    pub path: String,
+    pub same_site: SameSite,
+    pub secure: bool,
+}
+
+impl CookieSettings {
+    /// From `CLAUDE_PROXY_COOKIE_PATH` / `CLAUDE_PROXY_COOKIE_SAMESITE`
+    /// (defaults `/admin`, `Strict`). `secure` is forced on for
+    /// `SameSite=None`, which browsers reject otherwise.
+    pub fn new(path: Option<&str>, same_site: Option<&str>, secure: bool) -> Result<Self, String> {
+        let path = path.unwrap_or(DEFAULT_COOKIE_PATH);
+        if !path.starts_with('/')
+            || path
+                .chars()
+                .any(|c| c == ';' || c == ',' || c.is_whitespace() || c.is_control())
+        {
+            return Err(format!(
+                "CLAUDE_PROXY_COOKIE_PATH: `{path}` must start with / and contain no spaces, `;` or `,`"
+            ));
+        }
+        let same_site = match same_site {
+            Some(value) => SameSite::parse(value).ok_or_else(|| {
+                format!("CLAUDE_PROXY_COOKIE_SAMESITE: `{value}` is not one of strict/lax/none")
+            })?,
+            None => SameSite::Strict,
+        };
+        Ok(Self {
+            path: path.to_string(),
+            same_site,
+            secure: secure || same_site == SameSite::None,
+        })
+    }
+
+    fn attributes(&self, max_age: u64) -> String {
+        let secure_flag = if self.secure { "; Secure" } else { "" };
+        format!(
+            "HttpOnly; SameSite={}; Path={}; Max-Age={max_age}{secure_flag}",
+            self.same_site.as_str(),
+            self.path
+        )
+    }
+
+    pub(crate) fn session_cookie(&self, token: &str) -> String {
+        format!(
+            "admin_session={token}; {}",
+            self.attributes(SESSION_TTL_SECS)
+        )
+    }
+
+    /// Expire the cookie; attributes must match the ones it was set with.
+    pub(crate) fn clear_session_cookie(&self) -> String {
+        format!("admin_session=; {}", self.attributes(0))
+    }
 }
 
 /// Parse a named cookie from the Cookie header.
@@ -133,29 +285,33 @@ pub(crate) fn parse_cookie(header: &str, name: &str) -> Option<String> {
     })
 }
 
-/// Middleware for admin routes authentication (session cookie or Basic Auth).
+/// Middleware for admin routes authentication (session cookie, admin bearer
+/// token or Basic Auth).
+///
+/// Basic Auth failures count against the same per-IP throttle as the login
+/// endpoint, so the header can't be used to brute-force the password.
 pub(crate) async fn admin_auth_middleware(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     State(state): State<Arc<AppState>>,
     request: Request,
     next: Next,
 ) -> Response {
     if state.disable_auth {
-        return next.run(request).await;
+        let user = AdminUser(ANONYMOUS_ADMIN.into());
+        return authorize(AdminRole::Full, user, request, next).await;
     }
 
-    let creds = &state.admin_credentials;
-
     // Check for session cookie first.
     if let Some(cookie_header) = request
         .headers()
         .get(header::COOKIE)
         .and_then(|v| v.to_str().ok())
         && let Some(token) = parse_cookie(cookie_header, "admin_session")
-        && validate_session(&token).await
+        && let Some((role, user)) = validate_session(&token).await
     {
-        let mut response = next.run(request).await;
+        let mut response = authorize(role, user, request, next).await;
         // Refresh cookie Max-Age to keep browser cookie in sync with sliding expiration.
-        let cookie = session_cookie(&token, state.secure_cookies);
+        let cookie = state.session_cookie.session_cookie(&token);
         if let Ok(value) = cookie.parse() {
             response.headers_mut().insert(header::SET_COOKIE, value);
         }
@@ -171,6 +327,20 @@ pub(crate) async fn admin_auth_middleware(
         return unauthorized_response();
     };
 
+    if let Some(presented) = auth_value.strip_prefix("Bearer ") {
+        return match admin_tokens::authenticate(presented.trim()).await {
+            Ok(Some(token)) => {
+                let user = AdminUser(format!("token:{}", token.name));
+                authorize(token.role, user, request, next).await
+            }
+            Ok(None) => unauthorized_response(),
+            Err(e) => {
+                warn!("Admin token lookup failed: {e}");
+                unauthorized_response()
+            }
+        };
+    }
+
     let Some(encoded) = auth_value.strip_prefix("Basic ") else {
         return unauthorized_response();
     };
@@ -187,16 +357,251 @@ pub(crate) async fn admin_auth_middleware(
         return unauthorized_response();
     };
 
-    let user_match = provided_user.as_bytes().ct_eq(creds.username.as_bytes());
-    let pass_match = provided_pass.as_bytes().ct_eq(creds.password.as_bytes());
+    let ip = resolve_client_ip(peer.ip(), request.headers(), state.trusted_proxy_hops);
+    let throttle = &state.login_throttle;
+    if let Err(remaining) = throttle.check(ip, Instant::now()) {
+        return too_many_attempts(remaining.as_secs().max(1));
+    }
+
+    match state
+        .admin_credentials
+        .role_for(provided_user, provided_pass)
+    {
+        Some(role) => {
+            throttle.record_success(ip);
+            let user = AdminUser(provided_user.to_string());
+            authorize(role, user, request, next).await
+        }
+        None => {
+            if let Some(lockout) = throttle.record_failure(ip, Instant::now()) {
+                warn!(
+                    "Admin Basic Auth locked out for {} after repeated failures ({}s)",
+                    ip,
+                    lockout.as_secs()
+                );
+            }
+            unauthorized_response()
+        }
+    }
+}
 
-    if user_match.into() && pass_match.into() {
-        next.run(request).await
-    } else {
-        unauthorized_response()
+/// Run the request as `role`, rejecting anything but reads from read-only
+/// admins with 403. Handlers can read the role and [`AdminUser`] from the
+/// request extensions.
+async fn authorize(role: AdminRole, user: AdminUser, mut request: Request, next: Next) -> Response {
+    if !role.allows(request.method()) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: "Read-only admin access".into(),
+            }),
+        )
+            .into_response();
     }
+    request.extensions_mut().insert(role);
+    request.extensions_mut().insert(user);
+    next.run(request).await
 }
 
 fn unauthorized_response() -> Response {
     (StatusCode::UNAUTHORIZED, "Unauthorized").into_response()
 }
+
+/// 429 for a client IP locked out by the login throttle.
+pub(crate) fn too_many_attempts(retry_after_secs: u64) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_secs.to_string())],
+        Json(ErrorResponse {
+            error: "Too many failed login attempts, try again later".into(),
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        middleware,
+        routing::{delete, get},
+    };
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    use crate::login_throttle::LoginThrottle;
+    use crate::test_support::test_state;
+
+    fn credentials() -> AdminCredentials {
+        AdminCredentials {
+            username: "admin".into(),
+            password: "secret".into(),
+            read_only: Some(("viewer".into(), "peek".into())),
+        }
+    }
+
+    /// Key routes behind `authorize` for a session opened with `role`.
+    fn app(role: AdminRole) -> Router {
+        Router::new()
+            .route("/keys/list", get(|| async { "[]" }))
+            .route("/keys/{id}", delete(|| async { "deleted" }))
+            .layer(middleware::from_fn(move |request: Request, next: Next| {
+                authorize(role, AdminUser("admin".into()), request, next)
+            }))
+    }
+
+    async fn status(role: AdminRole, method: Method, uri: &str) -> StatusCode {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        app(role).oneshot(request).await.unwrap().status()
+    }
+
+    /// `GET /keys/list` through the real middleware with Basic Auth
+    /// `admin:<password>` from a fixed client address.
+    async fn basic_auth_status(state: &Arc<AppState>, password: &str) -> StatusCode {
+        let app = Router::new()
+            .route("/keys/list", get(|| async { "[]" }))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                admin_auth_middleware,
+            ));
+        let credentials = STANDARD.encode(format!("admin:{password}"));
+        let mut request = Request::builder()
+            .uri("/keys/list")
+            .header(header::AUTHORIZATION, format!("Basic {credentials}"))
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 40000))));
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    fn throttled_state(max_failures: usize) -> Arc<AppState> {
+        Arc::new(AppState {
+            login_throttle: LoginThrottle::new(
+                max_failures,
+                Duration::from_secs(300),
+                Duration::from_secs(900),
+            ),
+            ..test_state()
+        })
+    }
+
+    #[test]
+    fn test_default_cookie_attributes() {
+        let settings = CookieSettings::new(None, None, false).unwrap();
+        assert_eq!(
+            settings.session_cookie("abc"),
+            format!(
+                "admin_session=abc; HttpOnly; SameSite=Strict; Path=/admin; Max-Age={SESSION_TTL_SECS}"
+            )
+        );
+        assert_eq!(
+            settings.clear_session_cookie(),
+            "admin_session=; HttpOnly; SameSite=Strict; Path=/admin; Max-Age=0"
+        );
+    }
+
+    #[test]
+    fn test_configured_cookie_attributes() {
+        let settings = CookieSettings::new(Some("/proxy/admin"), Some("lax"), true).unwrap();
+        assert_eq!(
+            settings.session_cookie("abc"),
+            format!(
+                "admin_session=abc; HttpOnly; SameSite=Lax; Path=/proxy/admin; Max-Age={SESSION_TTL_SECS}; Secure"
+            )
+        );
+
+        // SameSite=None forces Secure even on a plain-HTTP bind
+        let cross_site = CookieSettings::new(None, Some("None"), false).unwrap();
+        assert!(cross_site.secure);
+        assert_eq!(
+            cross_site.clear_session_cookie(),
+            "admin_session=; HttpOnly; SameSite=None; Path=/admin; Max-Age=0; Secure"
+        );
+    }
+
+    #[test]
+    fn test_invalid_cookie_settings_rejected() {
+        for path in ["admin", "/admin; Domain=evil.com", "/a b"] {
+            assert!(
+                CookieSettings::new(Some(path), None, false)
+                    .is_err_and(|e| e.contains("CLAUDE_PROXY_COOKIE_PATH")),
+                "{path}"
+            );
+        }
+        assert!(
+            CookieSettings::new(None, Some("relaxed"), false)
+                .is_err_and(|e| e.contains("CLAUDE_PROXY_COOKIE_SAMESITE"))
+        );
+    }
+
+    #[test]
+    fn test_role_for_credentials() {
+        let creds = credentials();
+        assert_eq!(creds.role_for("admin", "secret"), Some(AdminRole::Full));
+        assert_eq!(creds.role_for("viewer", "peek"), Some(AdminRole::ReadOnly));
+        assert_eq!(creds.role_for("viewer", "secret"), None);
+        assert_eq!(creds.role_for("admin", "peek"), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_session_can_list_but_not_delete_keys() {
+        let role = AdminRole::parse("read_only");
+        assert_eq!(
+            status(role, Method::GET, "/keys/list").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(role, Method::DELETE, "/keys/key-1").await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeated_bad_basic_auth_is_locked_out() {
+        let state = throttled_state(3);
+        for _ in 0..3 {
+            assert_eq!(
+                basic_auth_status(&state, "wrong").await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        // Locked out: not even the right password gets through now
+        assert_eq!(
+            basic_auth_status(&state, "secret").await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_good_basic_auth_clears_failures() {
+        let state = throttled_state(3);
+        for _ in 0..2 {
+            basic_auth_status(&state, "wrong").await;
+        }
+        assert_eq!(basic_auth_status(&state, "secret").await, StatusCode::OK);
+        for _ in 0..2 {
+            assert_eq!(
+                basic_auth_status(&state, "wrong").await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        assert_eq!(basic_auth_status(&state, "secret").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_full_session_can_delete_keys() {
+        let role = AdminRole::parse("full");
+        assert_eq!(
+            status(role, Method::DELETE, "/keys/key-1").await,
+            StatusCode::OK
+        );
+    }
+}
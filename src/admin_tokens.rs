@@ -0,0 +1,202 @@
+//! Bearer tokens for the admin API.
+//!
+//! Scripts and CI can call the admin API with `Authorization: Bearer
+//! sk-admin-...` instead of embedding the admin password. Tokens are minted
+//! and revoked through `/admin/tokens`; only their SHA-256 is stored, and
+//! each request looks the token up by that hash again, so revocation takes
+//! effect on the next request.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngExt;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::admin_session::AdminRole;
+use crate::auth::client_keys::{i64_to_u64, opt_i64_to_u64};
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+use crate::subscription::timestamp_millis;
+
+const TOKEN_PREFIX: &str = "sk-admin-";
+
+/// A minted token, without the secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminToken {
+    pub id: String,
+    pub name: String,
+    pub token_hash: String,
+    pub role: AdminRole,
+    pub created_at: u64,
+    pub revoked_at: Option<u64>,
+}
+
+struct AdminTokenRow {
+    id: String,
+    name: String,
+    token_hash: String,
+    role: String,
+    created_at: i64,
+    revoked_at: Option<i64>,
+}
+
+impl From<AdminTokenRow> for AdminToken {
+    fn from(row: AdminTokenRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            token_hash: row.token_hash,
+            role: AdminRole::parse(&row.role),
+            created_at: i64_to_u64(row.created_at),
+            revoked_at: opt_i64_to_u64(row.revoked_at),
+        }
+    }
+}
+
+/// Mint a token. The secret is returned once and never stored.
+pub async fn mint(name: &str, role: AdminRole) -> Result<(AdminToken, String), ProxyError> {
+    let secret = generate_token();
+    let token = AdminToken {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        token_hash: hash_token(&secret),
+        role,
+        created_at: timestamp_millis(),
+        revoked_at: None,
+    };
+    let conn = db::get_conn().await?;
+    sqlx::query!(
+        "INSERT INTO admin_tokens (id, name, token_hash, role, created_at) VALUES ($1, $2, $3, $4, $5)",
+        token.id,
+        token.name,
+        token.token_hash,
+        token.role.as_str(),
+        token.created_at as i64,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to create admin token")?;
+    Ok((token, secret))
+}
+
+/// All tokens, revoked ones included, newest first.
+pub async fn list() -> Result<Vec<AdminToken>, ProxyError> {
+    let conn = db::get_conn().await?;
+    let rows = sqlx::query_as!(
+        AdminTokenRow,
+        "SELECT id, name, token_hash, role, created_at, revoked_at FROM admin_tokens \
+         ORDER BY created_at DESC"
+    )
+    .fetch_all(&conn)
+    .await
+    .db_context("Failed to list admin tokens")?;
+    Ok(rows.into_iter().map(AdminToken::from).collect())
+}
+
+/// Revoke a token. Returns `false` if it doesn't exist or was already
+/// revoked.
+pub async fn revoke(id: &str) -> Result<bool, ProxyError> {
+    let conn = db::get_conn().await?;
+    let affected = sqlx::query!(
+        "UPDATE admin_tokens SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL",
+        timestamp_millis() as i64,
+        id,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to revoke admin token")?
+    .rows_affected();
+    Ok(affected > 0)
+}
+
+/// Generate a fresh `sk-admin-*` secret from 32 random bytes.
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill(&mut bytes);
+    format!("{TOKEN_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes))
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// The active token matching `presented`, if any.
+pub async fn authenticate(presented: &str) -> Result<Option<AdminToken>, ProxyError> {
+    if !presented.starts_with(TOKEN_PREFIX) {
+        return Ok(None);
+    }
+    let conn = db::get_conn().await?;
+    let row = sqlx::query_as!(
+        AdminTokenRow,
+        "SELECT id, name, token_hash, role, created_at, revoked_at FROM admin_tokens \
+         WHERE token_hash = $1 AND revoked_at IS NULL",
+        hash_token(presented),
+    )
+    .fetch_optional(&conn)
+    .await
+    .db_context("Failed to look up admin token")?;
+    Ok(row.map(AdminToken::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin_session::admin_auth_middleware;
+    use crate::test_support::{test_state, with_db};
+    use axum::{
+        Router,
+        body::Body,
+        extract::{ConnectInfo, Request},
+        http::{StatusCode, header},
+        middleware,
+        routing::get,
+    };
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn bearer_status(token: &str) -> StatusCode {
+        let state = Arc::new(test_state());
+        let app = Router::new()
+            .route("/keys/list", get(|| async { "[]" }))
+            .layer(middleware::from_fn_with_state(state, admin_auth_middleware));
+        let mut request = Request::builder()
+            .uri("/keys/list")
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))));
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_token_works_until_revoked() {
+        with_db(async {
+            let (token, secret) = mint("ci", AdminRole::Full).await.unwrap();
+            mint("deploy", AdminRole::ReadOnly).await.unwrap();
+            assert_eq!(bearer_status(&secret).await, StatusCode::OK);
+            assert_eq!(
+                bearer_status("sk-admin-wrong").await,
+                StatusCode::UNAUTHORIZED
+            );
+
+            assert!(revoke(&token.id).await.unwrap());
+            assert_eq!(bearer_status(&secret).await, StatusCode::UNAUTHORIZED);
+            // Already revoked
+            assert!(!revoke(&token.id).await.unwrap());
+        });
+    }
+
+    #[test]
+    fn test_only_the_hash_is_kept() {
+        let secret = generate_token();
+        assert!(secret.starts_with(TOKEN_PREFIX));
+        let hash = hash_token(&secret);
+        assert_eq!(hash.len(), 64);
+        assert!(!hash.contains(&secret));
+        assert_ne!(generate_token(), secret);
+    }
+}
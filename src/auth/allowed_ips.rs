@@ -0,0 +1,207 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use super::client_keys::ClientKeysStore;
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+
+/// An IPv4 or IPv6 network in CIDR notation. A bare address is treated as a
+/// single-host network (`/32` or `/128`). Host bits are zeroed on parse, so
+/// `10.1.2.3/8` and `10.0.0.0/8` are the same network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpCidr {
+    /// Whether `ip` falls inside this network. IPv4-mapped IPv6 addresses
+    /// (`::ffff:a.b.c.d`) are matched against IPv4 networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                u32::from(ip) & v4_mask(self.prefix) == u32::from(net)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                u128::from(ip) & v6_mask(self.prefix) == u128::from(net)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn v4_mask(prefix: u8) -> u32 {
+    u32::MAX
+        .checked_shl(32u32.saturating_sub(u32::from(prefix)))
+        .unwrap_or(0)
+}
+
+fn v6_mask(prefix: u8) -> u128 {
+    u128::MAX
+        .checked_shl(128u32.saturating_sub(u32::from(prefix)))
+        .unwrap_or(0)
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|e| format!("Invalid IP address in '{s}': {e}"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|&p| p <= max)
+                .ok_or_else(|| format!("Invalid prefix length in '{s}' (0-{max})"))?,
+            None => max,
+        };
+        let network = match addr {
+            IpAddr::V4(a) => IpAddr::V4(Ipv4Addr::from(u32::from(a) & v4_mask(prefix))),
+            IpAddr::V6(a) => IpAddr::V6(Ipv6Addr::from(u128::from(a) & v6_mask(prefix))),
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+// ============================================================================
+// Per-key IP allow-list (key_allowed_ips table)
+// ============================================================================
+
+impl ClientKeysStore {
+    /// Get the CIDR allow-list for a key. Empty vec means "any IP".
+    pub async fn get_allowed_ips(&self, key_id: &str) -> Result<Vec<String>, ProxyError> {
+        let conn = db::get_conn().await?;
+        let rows = sqlx::query!(
+            "SELECT cidr FROM key_allowed_ips WHERE key_id = $1 ORDER BY cidr",
+            key_id
+        )
+        .fetch_all(&conn)
+        .await
+        .db_context("Failed to get allowed IPs")?;
+        Ok(rows.into_iter().map(|row| row.cidr).collect())
+    }
+
+    /// Replace the CIDR allow-list for a key. Empty vec = allow any IP.
+    pub async fn set_allowed_ips(&self, key_id: &str, cidrs: &[IpCidr]) -> Result<(), ProxyError> {
+        let conn = db::get_conn().await?;
+        let mut tx = conn
+            .begin()
+            .await
+            .db_context("Failed to start allowed IPs transaction")?;
+
+        sqlx::query!("DELETE FROM key_allowed_ips WHERE key_id = $1", key_id)
+            .execute(&mut *tx)
+            .await
+            .db_context("Failed to clear allowed IPs")?;
+
+        for cidr in cidrs {
+            sqlx::query!(
+                "INSERT INTO key_allowed_ips (key_id, cidr) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                key_id,
+                cidr.to_string(),
+            )
+            .execute(&mut *tx)
+            .await
+            .db_context("Failed to insert allowed IP")?;
+        }
+
+        tx.commit()
+            .await
+            .db_context("Failed to commit allowed IPs")?;
+        Ok(())
+    }
+
+    /// Check whether `ip` may use a key. Keys without an allow-list accept
+    /// any IP. Stored entries that no longer parse are ignored.
+    pub async fn is_ip_allowed(&self, key_id: &str, ip: IpAddr) -> Result<bool, ProxyError> {
+        let cidrs = self.get_allowed_ips(key_id).await?;
+        if cidrs.is_empty() {
+            return Ok(true);
+        }
+        Ok(cidrs
+            .iter()
+            .filter_map(|c| c.parse::<IpCidr>().ok())
+            .any(|c| c.contains(ip)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> IpCidr {
+        s.parse().unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ipv4_cidr_range() {
+        let net = cidr("192.168.1.0/24");
+        assert!(net.contains(ip("192.168.1.0")));
+        assert!(net.contains(ip("192.168.1.255")));
+        assert!(!net.contains(ip("192.168.2.1")));
+        assert!(!net.contains(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn test_bare_address_is_single_host() {
+        let net = cidr("203.0.113.7");
+        assert_eq!(net.to_string(), "203.0.113.7/32");
+        assert!(net.contains(ip("203.0.113.7")));
+        assert!(!net.contains(ip("203.0.113.8")));
+    }
+
+    #[test]
+    fn test_host_bits_are_normalized() {
+        assert_eq!(cidr("10.1.2.3/8"), cidr("10.0.0.0/8"));
+        assert_eq!(cidr("10.1.2.3/8").to_string(), "10.0.0.0/8");
+    }
+
+    #[test]
+    fn test_zero_prefix_matches_everything_in_family() {
+        let any_v4 = cidr("0.0.0.0/0");
+        assert!(any_v4.contains(ip("8.8.8.8")));
+        assert!(!any_v4.contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn test_ipv6_cidr_range() {
+        let net = cidr("2001:db8::/32");
+        assert!(net.contains(ip("2001:db8:ffff::1")));
+        assert!(!net.contains(ip("2001:db9::1")));
+        assert_eq!(cidr("::1").to_string(), "::1/128");
+    }
+
+    #[test]
+    fn test_ipv4_mapped_ipv6_client() {
+        let net = cidr("192.168.1.0/24");
+        assert!(net.contains(ip("::ffff:192.168.1.20")));
+        assert!(!net.contains(ip("::ffff:192.168.2.20")));
+    }
+
+    #[test]
+    fn test_invalid_cidrs() {
+        assert_eq!("not-an-ip".parse::<IpCidr>().ok(), None);
+        assert_eq!("10.0.0.0/33".parse::<IpCidr>().ok(), None);
+        assert_eq!("2001:db8::/129".parse::<IpCidr>().ok(), None);
+        assert_eq!("10.0.0.0/abc".parse::<IpCidr>().ok(), None);
+    }
+}
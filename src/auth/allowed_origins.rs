@@ -0,0 +1,166 @@
+use url::Url;
+
+use super::client_keys::ClientKeysStore;
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+
+/// Canonical form of a browser origin: `scheme://host[:port]`, lowercased,
+/// with the scheme's default port dropped. Paths, queries and credentials
+/// are rejected since an `Origin` header never carries them.
+pub fn normalize_origin(origin: &str) -> Result<String, String> {
+    let trimmed = origin.trim().trim_end_matches('/');
+    let url = Url::parse(trimmed).map_err(|e| format!("Invalid origin '{origin}': {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Invalid origin '{origin}': scheme must be http or https"
+        ));
+    }
+    if url.path() != "/"
+        || url.query().is_some()
+        || url.fragment().is_some()
+        || !url.username().is_empty()
+    {
+        return Err(format!(
+            "Invalid origin '{origin}': expected scheme://host[:port] only"
+        ));
+    }
+    let Some(host) = url.host_str() else {
+        return Err(format!("Invalid origin '{origin}': missing host"));
+    };
+    Ok(match url.port() {
+        Some(port) => format!("{}://{host}:{port}", url.scheme()),
+        None => format!("{}://{host}", url.scheme()),
+    })
+}
+
+/// Whether a request from `origin` may use a key allowing `allowed`. An
+/// empty list allows any origin. Requests without an `Origin` header don't
+/// come from a browser page and aren't restricted; a header that doesn't
+/// parse (including `null`) only passes an empty list.
+pub fn origin_allowed(allowed: &[String], origin: Option<&str>) -> bool {
+    if allowed.is_empty() {
+        return true;
+    }
+    let Some(origin) = origin else {
+        return true;
+    };
+    normalize_origin(origin).is_ok_and(|origin| allowed.contains(&origin))
+}
+
+// ============================================================================
+// Per-key origin allow-list (key_allowed_origins table)
+// ============================================================================
+
+impl ClientKeysStore {
+    /// Get the origin allow-list for a key. Empty vec means "any origin".
+    pub async fn get_allowed_origins(&self, key_id: &str) -> Result<Vec<String>, ProxyError> {
+        let conn = db::get_conn().await?;
+        let rows = sqlx::query!(
+            "SELECT origin FROM key_allowed_origins WHERE key_id = $1 ORDER BY origin",
+            key_id
+        )
+        .fetch_all(&conn)
+        .await
+        .db_context("Failed to get allowed origins")?;
+        Ok(rows.into_iter().map(|row| row.origin).collect())
+    }
+
+    /// Replace the origin allow-list for a key with already normalized
+    /// origins. Empty vec = allow any origin.
+    pub async fn set_allowed_origins(
+        &self,
+        key_id: &str,
+        origins: &[String],
+    ) -> Result<(), ProxyError> {
+        let conn = db::get_conn().await?;
+        let mut tx = conn
+            .begin()
+            .await
+            .db_context("Failed to start allowed origins transaction")?;
+
+        sqlx::query!("DELETE FROM key_allowed_origins WHERE key_id = $1", key_id)
+            .execute(&mut *tx)
+            .await
+            .db_context("Failed to clear allowed origins")?;
+
+        for origin in origins {
+            sqlx::query!(
+                "INSERT INTO key_allowed_origins (key_id, origin) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                key_id,
+                origin,
+            )
+            .execute(&mut *tx)
+            .await
+            .db_context("Failed to insert allowed origin")?;
+        }
+
+        tx.commit()
+            .await
+            .db_context("Failed to commit allowed origins")?;
+        Ok(())
+    }
+
+    /// Check whether a request with `origin` may use a key, per
+    /// [`origin_allowed`].
+    pub async fn is_origin_allowed(
+        &self,
+        key_id: &str,
+        origin: Option<&str>,
+    ) -> Result<bool, ProxyError> {
+        let allowed = self.get_allowed_origins(key_id).await?;
+        Ok(origin_allowed(&allowed, origin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(origins: &[&str]) -> Vec<String> {
+        origins
+            .iter()
+            .map(|o| normalize_origin(o).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize_origin() {
+        assert_eq!(
+            normalize_origin("HTTPS://App.Example.com/").unwrap(),
+            "https://app.example.com"
+        );
+        assert_eq!(
+            normalize_origin("https://app.example.com:443").unwrap(),
+            "https://app.example.com"
+        );
+        assert_eq!(
+            normalize_origin("http://localhost:5173").unwrap(),
+            "http://localhost:5173"
+        );
+        assert!(
+            normalize_origin("https://app.example.com/chat").is_err_and(|e| e.contains("only"))
+        );
+        assert!(normalize_origin("ftp://files.example.com").is_err_and(|e| e.contains("scheme")));
+        assert!(normalize_origin("app.example.com").is_err_and(|e| e.contains("Invalid origin")));
+    }
+
+    #[test]
+    fn test_allowed_and_disallowed_origins() {
+        let list = allowed(&["https://app.example.com", "http://localhost:5173"]);
+        assert!(origin_allowed(&list, Some("https://app.example.com")));
+        assert!(origin_allowed(&list, Some("http://localhost:5173")));
+        assert!(!origin_allowed(&list, Some("https://evil.example.com")));
+        // Same host, different scheme or port is a different origin
+        assert!(!origin_allowed(&list, Some("http://app.example.com")));
+        assert!(!origin_allowed(&list, Some("http://localhost:3000")));
+        assert!(!origin_allowed(&list, Some("null")));
+    }
+
+    #[test]
+    fn test_empty_list_and_missing_header_allowed() {
+        assert!(origin_allowed(&[], Some("https://anything.example")));
+        assert!(origin_allowed(&[], None));
+        let list = allowed(&["https://app.example.com"]);
+        assert!(origin_allowed(&list, None));
+    }
+}
@@ -6,6 +6,7 @@ use subtle::ConstantTimeEq;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use super::key_settings::KeySettings;
 use crate::db;
 use crate::error::{DbResultExt, ProxyError};
 use crate::subscription::timestamp_millis;
@@ -29,6 +30,31 @@ pub struct TokenLimits {
     pub total_limit: Option<u64>,
 }
 
+/// Length of a key's rate-limit windows (ms). The defaults match Claude's
+/// subscription windows, and only default-length windows follow the
+/// subscription's reset times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowDurations {
+    /// Length of the "5-hour" window
+    pub five_hour_ms: u64,
+    pub weekly_ms: u64,
+}
+
+impl WindowDurations {
+    pub const DEFAULT_FIVE_HOUR_MS: u64 = 5 * 60 * 60 * 1000;
+    pub const DEFAULT_WEEKLY_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+}
+
+impl Default for WindowDurations {
+    fn default() -> Self {
+        Self {
+            five_hour_ms: Self::DEFAULT_FIVE_HOUR_MS,
+            weekly_ms: Self::DEFAULT_WEEKLY_MS,
+        }
+    }
+}
+
 /// Current token usage for a client key (derived from per-model aggregation)
 #[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
 #[serde(default, rename_all = "camelCase")]
@@ -45,6 +71,42 @@ pub struct TokenUsage {
     pub weekly_reset_at: u64,
     /// Total cost (lifetime, microdollars)
     pub total_tokens: u64,
+    /// Share of each window's limit already spent
+    pub utilization_pct: UtilizationPct,
+}
+
+impl TokenUsage {
+    /// Fill in `utilization_pct` from the current costs and `limits`.
+    pub fn with_utilization(mut self, limits: &TokenLimits) -> Self {
+        self.utilization_pct = UtilizationPct {
+            five_hour: utilization_pct(self.five_hour_tokens, limits.five_hour_limit),
+            weekly: utilization_pct(self.weekly_tokens, limits.weekly_limit),
+            total: utilization_pct(self.total_tokens, limits.total_limit),
+        };
+        self
+    }
+}
+
+/// Used/limit per window in percent, so the UI can flag keys near their
+/// limits. `null` for windows without a limit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UtilizationPct {
+    pub five_hour: Option<f64>,
+    pub weekly: Option<f64>,
+    pub total: Option<f64>,
+}
+
+/// `used / limit` in percent. A zero limit blocks every request, so it
+/// reads as fully used.
+fn utilization_pct(used: u64, limit: Option<u64>) -> Option<f64> {
+    limit.map(|limit| {
+        if limit == 0 {
+            100.0
+        } else {
+            used as f64 / limit as f64 * 100.0
+        }
+    })
 }
 
 /// Which usage counter to reset
@@ -70,6 +132,16 @@ pub struct ClientKey {
     pub limits: TokenLimits,
     #[serde(default)]
     pub usage: TokenUsage,
+    #[serde(default)]
+    pub settings: KeySettings,
+    #[serde(default)]
+    pub windows: WindowDurations,
+    /// Free-form admin note (owner, purpose)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Model every request is sent to, whatever the client asks for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_model: Option<String>,
 }
 
 pub struct ClientKeysStore;
@@ -87,7 +159,12 @@ struct ClientKeyRow {
     total_limit: Option<i64>,
     five_hour_reset_at: i64,
     weekly_reset_at: i64,
+    five_hour_window_ms: i64,
+    weekly_window_ms: i64,
     allow_extra_usage: bool,
+    settings: String,
+    note: Option<String>,
+    force_model: Option<String>,
 }
 
 pub(crate) fn opt_i64_to_u64(value: Option<i64>) -> Option<u64> {
@@ -98,7 +175,16 @@ pub(crate) fn i64_to_u64(value: i64) -> u64 {
     u64::try_from(value).unwrap_or_default()
 }
 
+/// Generate a fresh `sk-proxy-*` secret from 32 random bytes.
+fn generate_key() -> String {
+    let mut rng = rand::rng();
+    let mut bytes = [0u8; 32];
+    rng.fill(&mut bytes);
+    format!("sk-proxy-{}", URL_SAFE_NO_PAD.encode(bytes))
+}
+
 fn row_to_client_key(row: ClientKeyRow) -> ClientKey {
+    let settings = KeySettings::from_column(&row.id, &row.settings);
     ClientKey {
         id: row.id,
         key: row.key,
@@ -114,12 +200,17 @@ fn row_to_client_key(row: ClientKeyRow) -> ClientKey {
         },
         // Usage is derived via aggregation — zero here, populated separately
         usage: TokenUsage {
-            five_hour_tokens: 0,
             five_hour_reset_at: i64_to_u64(row.five_hour_reset_at),
-            weekly_tokens: 0,
             weekly_reset_at: i64_to_u64(row.weekly_reset_at),
-            total_tokens: 0,
+            ..TokenUsage::default()
+        },
+        settings,
+        windows: WindowDurations {
+            five_hour_ms: i64_to_u64(row.five_hour_window_ms),
+            weekly_ms: i64_to_u64(row.weekly_window_ms),
         },
+        note: row.note,
+        force_model: row.force_model,
     }
 }
 
@@ -132,7 +223,7 @@ impl ClientKeysStore {
         let conn = db::get_conn().await?;
         let rows = sqlx::query_as!(
             ClientKeyRow,
-            "SELECT id, key, name, enabled, created_at, last_used_at, five_hour_limit, weekly_limit, total_limit, five_hour_reset_at, weekly_reset_at, allow_extra_usage FROM client_keys"
+            "SELECT id, key, name, enabled, created_at, last_used_at, five_hour_limit, weekly_limit, total_limit, five_hour_reset_at, weekly_reset_at, five_hour_window_ms, weekly_window_ms, allow_extra_usage, settings, note, force_model FROM client_keys"
         )
             .fetch_all(&conn)
             .await
@@ -145,14 +236,18 @@ impl ClientKeysStore {
         Ok(keys)
     }
 
+    /// Number of keys, enabled or not
+    pub async fn count(&self) -> Result<u64, ProxyError> {
+        let conn = db::get_conn().await?;
+        let count = sqlx::query_scalar!("SELECT COUNT(*) FROM client_keys")
+            .fetch_one(&conn)
+            .await
+            .db_context("Failed to count keys")?;
+        Ok(i64_to_u64(count.unwrap_or(0)))
+    }
+
     pub async fn create(&self, name: String) -> Result<ClientKey, ProxyError> {
-        let key_suffix = {
-            let mut rng = rand::rng();
-            let mut bytes = [0u8; 32];
-            rng.fill(&mut bytes);
-            URL_SAFE_NO_PAD.encode(bytes)
-        };
-        let key = format!("sk-proxy-{}", key_suffix);
+        let key = generate_key();
         let id = Uuid::new_v4().to_string();
         let now = timestamp_millis();
 
@@ -178,35 +273,127 @@ impl ClientKeysStore {
             allow_extra_usage: false,
             limits: TokenLimits::default(),
             usage: TokenUsage::default(),
+            settings: KeySettings::default(),
+            windows: WindowDurations::default(),
+            note: None,
+            force_model: None,
         })
     }
 
-    pub async fn set_enabled(&self, id: &str, enabled: bool) -> Result<bool, ProxyError> {
+    /// Create a new key with the configuration of `source_id`: limits,
+    /// `allow_extra_usage`, settings, window durations, allowed models, per-model limits and the
+    /// IP and origin allow-lists. The new key gets its own id and secret and starts with
+    /// zero usage.
+    /// Returns `None` if the source key does not exist.
+    pub async fn clone_key(
+        &self,
+        source_id: &str,
+        name: String,
+    ) -> Result<Option<ClientKey>, ProxyError> {
+        let key = generate_key();
+        let id = Uuid::new_v4().to_string();
+        let now = timestamp_millis();
+
         let conn = db::get_conn().await?;
-        let affected = sqlx::query!(
-            "UPDATE client_keys SET enabled = $1 WHERE id = $2",
+        let mut tx = conn
+            .begin()
+            .await
+            .db_context("Failed to start key clone transaction")?;
+
+        let inserted = sqlx::query!(
+            "INSERT INTO client_keys (id, key, name, enabled, created_at, five_hour_limit, weekly_limit, total_limit, allow_extra_usage, settings, five_hour_window_ms, weekly_window_ms, force_model) \
+             SELECT $1, $2, $3, TRUE, $4, five_hour_limit, weekly_limit, total_limit, allow_extra_usage, settings, five_hour_window_ms, weekly_window_ms, force_model \
+             FROM client_keys WHERE id = $5",
+            id,
+            key,
+            name,
+            now as i64,
+            source_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .db_context("Failed to clone key")?
+        .rows_affected();
+        if inserted == 0 {
+            return Ok(None);
+        }
+
+        sqlx::query!(
+            "INSERT INTO key_allowed_models (key_id, model) \
+             SELECT $1, model FROM key_allowed_models WHERE key_id = $2",
+            id,
+            source_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .db_context("Failed to clone allowed models")?;
+
+        sqlx::query!(
+            "INSERT INTO key_model_limits (key_id, model, five_hour_limit, weekly_limit, total_limit, count_from) \
+             SELECT $1, model, five_hour_limit, weekly_limit, total_limit, 0 \
+             FROM key_model_limits WHERE key_id = $2",
+            id,
+            source_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .db_context("Failed to clone model limits")?;
+
+        sqlx::query!(
+            "INSERT INTO key_allowed_ips (key_id, cidr) \
+             SELECT $1, cidr FROM key_allowed_ips WHERE key_id = $2",
+            id,
+            source_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .db_context("Failed to clone allowed IPs")?;
+
+        sqlx::query!(
+            "INSERT INTO key_allowed_origins (key_id, origin) \
+             SELECT $1, origin FROM key_allowed_origins WHERE key_id = $2",
+            id,
+            source_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .db_context("Failed to clone allowed origins")?;
+
+        tx.commit().await.db_context("Failed to commit key clone")?;
+
+        self.get(&id).await
+    }
+
+    /// Enable or disable a key. Returns the stored value, `None` if the key
+    /// does not exist.
+    pub async fn set_enabled(&self, id: &str, enabled: bool) -> Result<Option<bool>, ProxyError> {
+        let conn = db::get_conn().await?;
+        sqlx::query_scalar!(
+            "UPDATE client_keys SET enabled = $1 WHERE id = $2 RETURNING enabled",
             enabled,
             id
         )
-        .execute(&conn)
+        .fetch_optional(&conn)
         .await
-        .db_context("Failed to update key")?
-        .rows_affected();
-        Ok(affected > 0)
+        .db_context("Failed to update key")
     }
 
-    pub async fn set_allow_extra_usage(&self, id: &str, allow: bool) -> Result<bool, ProxyError> {
+    /// Set whether a key may run on extra usage. Returns the stored value,
+    /// `None` if the key does not exist.
+    pub async fn set_allow_extra_usage(
+        &self,
+        id: &str,
+        allow: bool,
+    ) -> Result<Option<bool>, ProxyError> {
         let conn = db::get_conn().await?;
-        let affected = sqlx::query!(
-            "UPDATE client_keys SET allow_extra_usage = $1 WHERE id = $2",
+        sqlx::query_scalar!(
+            "UPDATE client_keys SET allow_extra_usage = $1 WHERE id = $2 RETURNING allow_extra_usage",
             allow,
             id
         )
-        .execute(&conn)
+        .fetch_optional(&conn)
         .await
-        .db_context("Failed to update key")?
-        .rows_affected();
-        Ok(affected > 0)
+        .db_context("Failed to update key")
     }
 
     pub async fn delete(&self, id: &str) -> Result<bool, ProxyError> {
@@ -225,7 +412,7 @@ impl ClientKeysStore {
         let conn = db::get_conn().await?;
         let rows = sqlx::query_as!(
             ClientKeyRow,
-            "SELECT id, key, name, enabled, created_at, last_used_at, five_hour_limit, weekly_limit, total_limit, five_hour_reset_at, weekly_reset_at, allow_extra_usage FROM client_keys WHERE enabled = TRUE"
+            "SELECT id, key, name, enabled, created_at, last_used_at, five_hour_limit, weekly_limit, total_limit, five_hour_reset_at, weekly_reset_at, five_hour_window_ms, weekly_window_ms, allow_extra_usage, settings, note, force_model FROM client_keys WHERE enabled = TRUE"
         )
             .fetch_all(&conn)
             .await
@@ -260,7 +447,7 @@ impl ClientKeysStore {
         let conn = db::get_conn().await?;
         let row = sqlx::query_as!(
             ClientKeyRow,
-            "SELECT id, key, name, enabled, created_at, last_used_at, five_hour_limit, weekly_limit, total_limit, five_hour_reset_at, weekly_reset_at, allow_extra_usage FROM client_keys WHERE id = $1",
+            "SELECT id, key, name, enabled, created_at, last_used_at, five_hour_limit, weekly_limit, total_limit, five_hour_reset_at, weekly_reset_at, five_hour_window_ms, weekly_window_ms, allow_extra_usage, settings, note, force_model FROM client_keys WHERE id = $1",
             id
         )
             .fetch_optional(&conn)
@@ -272,6 +459,34 @@ impl ClientKeysStore {
         Ok(Some(row_to_client_key(row)))
     }
 
+    /// Set or clear (`None`) a key's note. Returns `false` if the key does
+    /// not exist.
+    pub async fn set_note(&self, id: &str, note: Option<&str>) -> Result<bool, ProxyError> {
+        let conn = db::get_conn().await?;
+        let affected = sqlx::query!("UPDATE client_keys SET note = $1 WHERE id = $2", note, id)
+            .execute(&conn)
+            .await
+            .db_context("Failed to set key note")?
+            .rows_affected();
+        Ok(affected > 0)
+    }
+
+    /// Pin a key to one model, or unpin it (`None`). Returns `false` if the
+    /// key does not exist.
+    pub async fn set_force_model(&self, id: &str, model: Option<&str>) -> Result<bool, ProxyError> {
+        let conn = db::get_conn().await?;
+        let affected = sqlx::query!(
+            "UPDATE client_keys SET force_model = $1 WHERE id = $2",
+            model,
+            id
+        )
+        .execute(&conn)
+        .await
+        .db_context("Failed to set key forced model")?
+        .rows_affected();
+        Ok(affected > 0)
+    }
+
     /// Update limits for a key
     pub async fn set_limits(&self, id: &str, limits: TokenLimits) -> Result<bool, ProxyError> {
         let conn = db::get_conn().await?;
@@ -280,18 +495,184 @@ impl ClientKeysStore {
         let w = limits.weekly_limit.map(|v| v as i64);
         let t = limits.total_limit.map(|v| v as i64);
 
-        let affected = sqlx::query!(
-            "UPDATE client_keys SET five_hour_limit = $1, weekly_limit = $2, total_limit = $3 WHERE id = $4",
-            h,
-            w,
-            t,
-            id,
-        )
-        .execute(&conn)
+        let affected = db::retry_on_lock(|| async {
+            sqlx::query!(
+                "UPDATE client_keys SET five_hour_limit = $1, weekly_limit = $2, total_limit = $3 WHERE id = $4",
+                h,
+                w,
+                t,
+                id,
+            )
+            .execute(&conn)
             .await
-            .db_context("Failed to set limits")?
-            .rows_affected();
+            .db_context("Failed to set limits")
+        })
+        .await?
+        .rows_affected();
 
         Ok(affected > 0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::RequestTags;
+    use crate::auth::allowed_ips::IpCidr;
+    use crate::auth::usage::usage_from_json;
+    use crate::subscription::SubscriptionState;
+    use crate::test_support::with_db;
+    use serde_json::json;
+
+    fn usage(five_hour: u64, weekly: u64, total: u64) -> TokenUsage {
+        TokenUsage {
+            five_hour_tokens: five_hour,
+            weekly_tokens: weekly,
+            total_tokens: total,
+            ..TokenUsage::default()
+        }
+    }
+
+    async fn record(keys: &ClientKeysStore, key_id: &str, input: u64) {
+        let usage = usage_from_json(&json!({ "input_tokens": input, "output_tokens": 1 }));
+        keys.record_model_usage(
+            key_id,
+            "claude-sonnet-4-5",
+            &usage,
+            &SubscriptionState::default(),
+            timestamp_millis(),
+            &RequestTags::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn total_input(keys: &ClientKeysStore, key_id: &str) -> u64 {
+        keys.get_model_usage(key_id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| entry.total.input)
+            .sum()
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_clone_copies_config_not_usage() {
+        with_db(async {
+            let keys = ClientKeysStore::new();
+            let source = keys.create("clone-source".into()).await.unwrap();
+            let limits = TokenLimits {
+                five_hour_limit: Some(1_000),
+                weekly_limit: Some(5_000),
+                total_limit: None,
+            };
+            keys.set_limits(&source.id, limits.clone()).await.unwrap();
+            keys.set_allow_extra_usage(&source.id, true).await.unwrap();
+            let settings = KeySettings {
+                force_cloak: true,
+                debug_logging: true,
+                ..KeySettings::default()
+            };
+            keys.set_settings(&source.id, &settings).await.unwrap();
+            keys.set_force_model(&source.id, Some("claude-haiku-4-5"))
+                .await
+                .unwrap();
+            keys.set_allowed_models(&source.id, vec!["claude-sonnet-4-5".into()])
+                .await
+                .unwrap();
+            keys.set_model_limits(&source.id, "claude-sonnet-4-5", limits)
+                .await
+                .unwrap();
+            let cidrs: Vec<IpCidr> = vec!["10.0.0.0/8".parse().unwrap()];
+            keys.set_allowed_ips(&source.id, &cidrs).await.unwrap();
+            keys.set_allowed_origins(&source.id, &["https://app.example".into()])
+                .await
+                .unwrap();
+            record(&keys, &source.id, 100).await;
+
+            let clone = keys
+                .clone_key(&source.id, "clone-copy".into())
+                .await
+                .unwrap()
+                .expect("source exists");
+            let source = keys.get(&source.id).await.unwrap().unwrap();
+            assert_ne!(clone.id, source.id);
+            assert_ne!(clone.key, source.key);
+            assert_eq!(clone.name, "clone-copy");
+            assert_eq!(clone.limits.five_hour_limit, Some(1_000));
+            assert_eq!(clone.limits.weekly_limit, Some(5_000));
+            assert_eq!(clone.limits.total_limit, None);
+            assert!(clone.allow_extra_usage);
+            assert_eq!(clone.settings, source.settings);
+            assert_eq!(clone.windows, source.windows);
+            assert_eq!(clone.force_model.as_deref(), Some("claude-haiku-4-5"));
+            assert_eq!(
+                keys.get_allowed_models(&clone.id).await.unwrap(),
+                keys.get_allowed_models(&source.id).await.unwrap()
+            );
+            assert_eq!(
+                keys.get_allowed_ips(&clone.id).await.unwrap(),
+                keys.get_allowed_ips(&source.id).await.unwrap()
+            );
+            assert_eq!(
+                keys.get_allowed_origins(&clone.id).await.unwrap(),
+                keys.get_allowed_origins(&source.id).await.unwrap()
+            );
+            let clone_limits = keys.get_model_usage(&clone.id).await.unwrap();
+            let sonnet = clone_limits
+                .iter()
+                .find(|entry| entry.model == "claude-sonnet-4-5")
+                .expect("per-model limit copied");
+            assert_eq!(sonnet.limits.five_hour_limit, Some(1_000));
+            assert_eq!(sonnet.limits.weekly_limit, Some(5_000));
+
+            // Usage starts at zero and is counted per key from then on
+            assert_eq!(total_input(&keys, &clone.id).await, 0);
+            record(&keys, &clone.id, 40).await;
+            assert_eq!(total_input(&keys, &clone.id).await, 40);
+            assert_eq!(total_input(&keys, &source.id).await, 100);
+        });
+    }
+
+    #[test]
+    fn test_utilization_pct_per_window() {
+        let limits = TokenLimits {
+            five_hour_limit: Some(2_000_000),
+            weekly_limit: Some(10_000_000),
+            total_limit: Some(1_000_000),
+        };
+        let pct = usage(500_000, 10_000_000, 1_500_000)
+            .with_utilization(&limits)
+            .utilization_pct;
+        assert_eq!(pct.five_hour, Some(25.0));
+        assert_eq!(pct.weekly, Some(100.0));
+        // Overspend (e.g. a long stream finishing past the limit) shows above 100
+        assert_eq!(pct.total, Some(150.0));
+    }
+
+    #[test]
+    fn test_utilization_pct_unlimited_is_null() {
+        let pct = usage(500_000, 0, 0)
+            .with_utilization(&TokenLimits::default())
+            .utilization_pct;
+        assert_eq!(pct, UtilizationPct::default());
+
+        let json = serde_json::to_value(&pct).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "fiveHour": null, "weekly": null, "total": null })
+        );
+    }
+
+    #[test]
+    fn test_utilization_pct_zero_limit_is_full() {
+        let limits = TokenLimits {
+            weekly_limit: Some(0),
+            ..TokenLimits::default()
+        };
+        let pct = usage(0, 0, 0).with_utilization(&limits).utilization_pct;
+        assert_eq!(pct.weekly, Some(100.0));
+        assert_eq!(pct.five_hour, None);
+    }
+}
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use super::client_keys::ClientKeysStore;
+use crate::constants::OPUS_4_6_MAX_OUTPUT;
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+
+/// Longest accepted system prefix override, in characters.
+const MAX_SYSTEM_PREFIX_CHARS: usize = 8192;
+
+/// Scheduling priority when upstream concurrency is saturated. Ordered so
+/// that `High` compares greatest.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Anthropic `service_tier` request value: `auto` lets requests use
+/// priority capacity when available, `standard_only` never does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceTier {
+    Auto,
+    StandardOnly,
+}
+
+impl ServiceTier {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::StandardOnly => "standard_only",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(Self::Auto),
+            "standard_only" => Some(Self::StandardOnly),
+            _ => None,
+        }
+    }
+}
+
+/// Per-key overrides of request defaults, stored as JSON on the key row.
+/// Absent fields fall back to the global behaviour.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct KeySettings {
+    /// Replaces the Claude Code system prefix injected when cloaking
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prefix_override: Option<String>,
+    /// `max_tokens` for OpenAI requests that don't set one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_max_tokens: Option<u32>,
+    /// Cloak this key's requests even when the global cloak mode would not
+    pub force_cloak: bool,
+    /// Queue position relative to other keys when all upstream slots are busy
+    pub priority: Priority,
+    /// Log this key's request and response bodies (redacted, truncated)
+    pub debug_logging: bool,
+    /// `service_tier` for requests that don't set one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<ServiceTier>,
+    /// `anthropic-version` header (`YYYY-MM-DD`) for this key's upstream requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anthropic_version: Option<String>,
+}
+
+impl KeySettings {
+    /// Check values that deserialize fine but make no sense.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(prefix) = &self.system_prefix_override {
+            if prefix.trim().is_empty() {
+                return Err("systemPrefixOverride must not be empty".into());
+            }
+            if prefix.chars().count() > MAX_SYSTEM_PREFIX_CHARS {
+                return Err(format!(
+                    "systemPrefixOverride must be at most {MAX_SYSTEM_PREFIX_CHARS} characters"
+                ));
+            }
+        }
+        if let Some(max_tokens) = self.default_max_tokens
+            && !(1..=OPUS_4_6_MAX_OUTPUT).contains(&max_tokens)
+        {
+            return Err(format!(
+                "defaultMaxTokens must be between 1 and {OPUS_4_6_MAX_OUTPUT}"
+            ));
+        }
+        if let Some(version) = &self.anthropic_version
+            && !is_api_version(version)
+        {
+            return Err(format!(
+                "anthropicVersion must be a date like 2023-06-01, got `{version}`"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parse the stored column. A corrupt value falls back to defaults rather
+    /// than locking the key out.
+    pub(crate) fn from_column(key_id: &str, raw: &str) -> Self {
+        serde_json::from_str(raw).unwrap_or_else(|e| {
+            warn!("Ignoring malformed settings for key {key_id}: {e}");
+            Self::default()
+        })
+    }
+}
+
+/// Anthropic API versions are release dates: `YYYY-MM-DD`.
+fn is_api_version(value: &str) -> bool {
+    value.len() == 10 && chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+}
+
+impl ClientKeysStore {
+    /// Replace a key's settings. Returns `false` if the key does not exist.
+    pub async fn set_settings(&self, id: &str, settings: &KeySettings) -> Result<bool, ProxyError> {
+        let raw = serde_json::to_string(settings)
+            .map_err(|e| ProxyError::ParseError(format!("Failed to encode key settings: {e}")))?;
+        let conn = db::get_conn().await?;
+        let affected = sqlx::query!(
+            "UPDATE client_keys SET settings = $1 WHERE id = $2",
+            raw,
+            id,
+        )
+        .execute(&conn)
+        .await
+        .db_context("Failed to set key settings")?
+        .rows_affected();
+        Ok(affected > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_object_is_default() {
+        assert_eq!(KeySettings::from_column("k", "{}"), KeySettings::default());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let settings = KeySettings {
+            system_prefix_override: Some("You are a support bot.".into()),
+            default_max_tokens: Some(4096),
+            force_cloak: true,
+            priority: Priority::High,
+            debug_logging: true,
+            service_tier: Some(ServiceTier::StandardOnly),
+            anthropic_version: Some("2023-06-01".into()),
+        };
+        let raw = serde_json::to_string(&settings).unwrap();
+        assert_eq!(KeySettings::from_column("k", &raw), settings);
+    }
+
+    #[test]
+    fn test_unknown_field_rejected() {
+        let parsed = serde_json::from_str::<KeySettings>(r#"{"forceCloack": true}"#);
+        assert_eq!(parsed.ok(), None);
+    }
+
+    #[test]
+    fn test_priority_defaults_to_normal() {
+        let settings: KeySettings = serde_json::from_str(r#"{"forceCloak": true}"#).unwrap();
+        assert_eq!(settings.priority, Priority::Normal);
+        let settings: KeySettings = serde_json::from_str(r#"{"priority": "low"}"#).unwrap();
+        assert_eq!(settings.priority, Priority::Low);
+    }
+
+    #[test]
+    fn test_service_tier_values() {
+        let settings: KeySettings =
+            serde_json::from_str(r#"{"serviceTier": "standard_only"}"#).unwrap();
+        assert_eq!(settings.service_tier, Some(ServiceTier::StandardOnly));
+        let parsed = serde_json::from_str::<KeySettings>(r#"{"serviceTier": "priority"}"#);
+        assert_eq!(parsed.ok(), None);
+        assert_eq!(ServiceTier::parse("auto"), Some(ServiceTier::Auto));
+        assert_eq!(ServiceTier::parse("default"), None);
+    }
+
+    #[test]
+    fn test_malformed_column_falls_back() {
+        assert_eq!(
+            KeySettings::from_column("k", "not json"),
+            KeySettings::default()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(KeySettings::default().validate(), Ok(()));
+
+        let empty_prefix = KeySettings {
+            system_prefix_override: Some("  ".into()),
+            ..KeySettings::default()
+        };
+        assert!(empty_prefix.validate().is_err_and(|e| e.contains("empty")));
+
+        let zero_tokens = KeySettings {
+            default_max_tokens: Some(0),
+            ..KeySettings::default()
+        };
+        assert!(
+            zero_tokens
+                .validate()
+                .is_err_and(|e| e.contains("defaultMaxTokens"))
+        );
+
+        let too_many = KeySettings {
+            default_max_tokens: Some(OPUS_4_6_MAX_OUTPUT + 1),
+            ..KeySettings::default()
+        };
+        assert!(
+            too_many
+                .validate()
+                .is_err_and(|e| e.contains("defaultMaxTokens"))
+        );
+
+        for version in ["2023-06-01", "2024-10-22"] {
+            let settings = KeySettings {
+                anthropic_version: Some(version.into()),
+                ..KeySettings::default()
+            };
+            assert_eq!(settings.validate(), Ok(()));
+        }
+        for version in ["2023-6-1", "2023-13-01", "latest", ""] {
+            let settings = KeySettings {
+                anthropic_version: Some(version.into()),
+                ..KeySettings::default()
+            };
+            assert!(
+                settings
+                    .validate()
+                    .is_err_and(|e| e.contains("anthropicVersion")),
+                "{version}"
+            );
+        }
+    }
+}
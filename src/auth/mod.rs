@@ -1,12 +1,27 @@
+pub mod allowed_ips;
+pub mod allowed_origins;
 pub mod client_keys;
+pub mod key_settings;
 pub mod models;
 pub mod oauth;
 pub mod rate_limits;
 pub mod storage;
+pub mod token_refresh;
 pub mod usage;
+pub mod usage_forecast;
 
-pub use client_keys::{ClientKey, ClientKeysStore, TokenLimits, TokenUsage, UsageResetType};
-pub use models::{Model, ModelsStore};
-pub use oauth::OAuthManager;
-pub use rate_limits::ModelUsageEntry;
+pub use allowed_ips::IpCidr;
+pub use allowed_origins::normalize_origin;
+pub use client_keys::{
+    ClientKey, ClientKeysStore, TokenLimits, TokenUsage, UsageResetType, WindowDurations,
+};
+pub use key_settings::{KeySettings, Priority, ServiceTier};
+pub use models::{
+    Model, ModelAlias, ModelCapabilities, ModelPricing, ModelSort, ModelsStore, effective_model_ids,
+};
+pub use oauth::{OAuthManager, RefreshError};
+pub use rate_limits::{
+    CacheStats, EffectiveModelLimits, EffectiveWindow, LimitSource, ModelUsageEntry, RequestTags,
+    WindowOverrides,
+};
 pub use storage::AuthStore;
@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
+use tracing::info;
 use utoipa::ToSchema;
 
+use super::client_keys::i64_to_u64;
+use crate::config::UnknownModelPolicy;
 use crate::db;
 use crate::error::{DbResultExt, ProxyError};
+use crate::subscription::timestamp_millis;
 
 /// Model pricing for cost calculation during requests
 #[derive(Debug, Clone)]
@@ -24,6 +28,152 @@ pub struct Model {
     pub output_price: f64,
     pub cache_read_price: f64,
     pub cache_write_price: f64,
+    /// Epoch-ms before which the model is treated as disabled. `None` = no lower bound.
+    pub enabled_from: Option<i64>,
+    /// Epoch-ms from which the model is treated as disabled. `None` = no upper bound.
+    pub enabled_until: Option<i64>,
+    /// Accepts image content blocks
+    pub supports_vision: bool,
+    /// Accepts `tools` / `tool_choice`
+    pub supports_tools: bool,
+    /// Accepts a `thinking` config
+    pub supports_thinking: bool,
+    /// Effort used for OpenAI requests that don't specify one
+    pub default_effort: Option<String>,
+}
+
+/// What a model accepts. Features it lacks are stripped from requests before
+/// they are forwarded (see `prepare_anthropic_request`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    pub vision: bool,
+    pub tools: bool,
+    pub thinking: bool,
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            vision: true,
+            tools: true,
+            thinking: true,
+        }
+    }
+}
+
+impl Model {
+    /// Whether the model is enabled and `now` (epoch ms) falls inside its schedule.
+    pub fn is_active_at(&self, now: i64) -> bool {
+        self.enabled && schedule_contains(self.enabled_from, self.enabled_until, now)
+    }
+}
+
+/// Resolve which models a key can actually use at `now`: models that are
+/// enabled and inside their schedule, intersected with the key's allow-list
+/// (empty = all). Keeps the catalog's sort order.
+pub fn effective_model_ids(models: &[Model], allowed: &[String], now: i64) -> Vec<String> {
+    models
+        .iter()
+        .filter(|m| m.is_active_at(now))
+        .filter(|m| allowed.is_empty() || allowed.contains(&m.id))
+        .map(|m| m.id.clone())
+        .collect()
+}
+
+/// Order of the admin model list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelSort {
+    /// Catalog order
+    #[default]
+    SortOrder,
+    Id,
+    /// Input price, then output price, cheapest first
+    Price,
+}
+
+/// Keep the models whose `enabled` flag matches (all when `None`) and order
+/// them by `sort`. Ties keep the order of `models`.
+pub fn filter_and_sort_models(
+    mut models: Vec<Model>,
+    enabled: Option<bool>,
+    sort: ModelSort,
+) -> Vec<Model> {
+    models.retain(|m| enabled.is_none_or(|e| m.enabled == e));
+    match sort {
+        ModelSort::SortOrder => models.sort_by_key(|m| m.sort_order),
+        ModelSort::Id => models.sort_by(|a, b| a.id.cmp(&b.id)),
+        ModelSort::Price => models.sort_by(|a, b| {
+            a.input_price
+                .total_cmp(&b.input_price)
+                .then(a.output_price.total_cmp(&b.output_price))
+        }),
+    }
+    models
+}
+
+/// Check `now` against an optional `[from, until)` window. Mirrors the SQL
+/// filter in `ModelsStore`.
+fn schedule_contains(from: Option<i64>, until: Option<i64>, now: i64) -> bool {
+    from.is_none_or(|f| now >= f) && until.is_none_or(|u| now < u)
+}
+
+/// Maps a client-facing model name (e.g. `gpt-4`) to a configured model id
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelAlias {
+    pub alias: String,
+    pub target: String,
+}
+
+/// Apply an alias target to a requested model name, keeping any thinking
+/// suffix: `gpt-4(high)` with target `claude-opus-4-6` becomes
+/// `claude-opus-4-6(high)`.
+pub(crate) fn apply_alias(requested: &str, target: Option<&str>) -> String {
+    let Some(target) = target else {
+        return requested.to_string();
+    };
+    match requested.find('(') {
+        Some(idx) => format!("{target}{}", requested.get(idx..).unwrap_or_default()),
+        None => target.to_string(),
+    }
+}
+
+/// The model name to resolve: the requested one, or `default_model` when the
+/// client sent none (or an empty string).
+fn requested_or_default<'a>(requested: Option<&'a str>, default_model: &'a str) -> &'a str {
+    requested
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .unwrap_or(default_model)
+}
+
+/// Apply the unknown-model policy to a resolved model name. `available` is
+/// the enabled, in-schedule model ids. A fallback to `default_model` keeps
+/// the requested thinking suffix; if the default is unavailable as well the
+/// request is rejected either way.
+pub(crate) fn apply_unknown_model_policy(
+    resolved: String,
+    default_model: &str,
+    available: &[String],
+    policy: UnknownModelPolicy,
+) -> Result<String, ProxyError> {
+    let (base, suffix) = resolved.find('(').map_or((resolved.as_str(), ""), |idx| {
+        (
+            resolved.get(..idx).unwrap_or_default(),
+            resolved.get(idx..).unwrap_or_default(),
+        )
+    });
+    if available.iter().any(|id| id == base) {
+        return Ok(resolved);
+    }
+    if policy == UnknownModelPolicy::Fallback && available.iter().any(|id| id == default_model) {
+        return Ok(format!("{default_model}{suffix}"));
+    }
+    Err(ProxyError::ModelNotFound {
+        model: base.to_string(),
+        available: available.to_vec(),
+    })
 }
 
 pub struct ModelsStore;
@@ -36,6 +186,12 @@ struct ModelRow {
     output_price: f64,
     cache_read_price: f64,
     cache_write_price: f64,
+    enabled_from: Option<i64>,
+    enabled_until: Option<i64>,
+    supports_vision: bool,
+    supports_tools: bool,
+    supports_thinking: bool,
+    default_effort: Option<String>,
 }
 
 fn row_to_model(row: ModelRow) -> Model {
@@ -47,6 +203,12 @@ fn row_to_model(row: ModelRow) -> Model {
         output_price: row.output_price,
         cache_read_price: row.cache_read_price,
         cache_write_price: row.cache_write_price,
+        enabled_from: row.enabled_from,
+        enabled_until: row.enabled_until,
+        supports_vision: row.supports_vision,
+        supports_tools: row.supports_tools,
+        supports_thinking: row.supports_thinking,
+        default_effort: row.default_effort,
     }
 }
 
@@ -60,7 +222,7 @@ impl ModelsStore {
         let conn = db::get_conn().await?;
         let rows = sqlx::query_as!(
             ModelRow,
-            "SELECT id, sort_order, enabled, input_price, output_price, cache_read_price, cache_write_price FROM models ORDER BY sort_order",
+            "SELECT id, sort_order, enabled, input_price, output_price, cache_read_price, cache_write_price, enabled_from, enabled_until, supports_vision, supports_tools, supports_thinking, default_effort FROM models ORDER BY sort_order",
         )
         .fetch_all(&conn)
         .await
@@ -69,12 +231,37 @@ impl ModelsStore {
         Ok(rows.into_iter().map(row_to_model).collect())
     }
 
-    /// List only enabled models (for API endpoints)
+    /// Number of models in the catalog, enabled or not
+    pub async fn count(&self) -> Result<u64, ProxyError> {
+        let conn = db::get_conn().await?;
+        let count = sqlx::query_scalar!("SELECT COUNT(*) FROM models")
+            .fetch_one(&conn)
+            .await
+            .db_context("Failed to count models")?;
+        Ok(i64_to_u64(count.unwrap_or(0)))
+    }
+
+    /// List models filtered by `enabled` (all when `None`) in `sort` order
+    pub async fn list_filtered(
+        &self,
+        enabled: Option<bool>,
+        sort: ModelSort,
+    ) -> Result<Vec<Model>, ProxyError> {
+        Ok(filter_and_sort_models(self.list().await?, enabled, sort))
+    }
+
+    /// List only enabled models inside their schedule (for API endpoints)
     pub async fn list_enabled(&self) -> Result<Vec<Model>, ProxyError> {
         let conn = db::get_conn().await?;
+        let now = timestamp_millis() as i64;
         let rows = sqlx::query_as!(
             ModelRow,
-            "SELECT id, sort_order, enabled, input_price, output_price, cache_read_price, cache_write_price FROM models WHERE enabled = TRUE ORDER BY sort_order",
+            "SELECT id, sort_order, enabled, input_price, output_price, cache_read_price, cache_write_price, enabled_from, enabled_until, supports_vision, supports_tools, supports_thinking, default_effort FROM models \
+             WHERE enabled = TRUE \
+             AND (enabled_from IS NULL OR enabled_from <= $1) \
+             AND (enabled_until IS NULL OR enabled_until > $1) \
+             ORDER BY sort_order",
+            now,
         )
         .fetch_all(&conn)
         .await
@@ -83,13 +270,21 @@ impl ModelsStore {
         Ok(rows.into_iter().map(row_to_model).collect())
     }
 
-    /// List only enabled model IDs (for /v1/models endpoint)
+    /// List only enabled model IDs inside their schedule (for /v1/models endpoint)
     pub async fn list_enabled_ids(&self) -> Result<Vec<String>, ProxyError> {
         let conn = db::get_conn().await?;
-        let rows = sqlx::query!("SELECT id FROM models WHERE enabled = TRUE ORDER BY sort_order")
-            .fetch_all(&conn)
-            .await
-            .db_context("Failed to list model IDs")?;
+        let now = timestamp_millis() as i64;
+        let rows = sqlx::query!(
+            "SELECT id FROM models \
+             WHERE enabled = TRUE \
+             AND (enabled_from IS NULL OR enabled_from <= $1) \
+             AND (enabled_until IS NULL OR enabled_until > $1) \
+             ORDER BY sort_order",
+            now,
+        )
+        .fetch_all(&conn)
+        .await
+        .db_context("Failed to list model IDs")?;
 
         Ok(rows.into_iter().map(|row| row.id).collect())
     }
@@ -120,6 +315,7 @@ impl ModelsStore {
         output_price: f64,
         cache_read_price: f64,
         cache_write_price: f64,
+        capabilities: ModelCapabilities,
     ) -> Result<(), ProxyError> {
         let conn = db::get_conn().await?;
         // Set sort_order to max + 1
@@ -130,13 +326,16 @@ impl ModelsStore {
                 .db_context("Failed to get max sort_order")?;
 
         sqlx::query!(
-            "INSERT INTO models (id, sort_order, enabled, input_price, output_price, cache_read_price, cache_write_price) VALUES ($1, $2, TRUE, $3, $4, $5, $6)",
+            "INSERT INTO models (id, sort_order, enabled, input_price, output_price, cache_read_price, cache_write_price, supports_vision, supports_tools, supports_thinking) VALUES ($1, $2, TRUE, $3, $4, $5, $6, $7, $8, $9)",
             id,
             next_order,
             input_price,
             output_price,
             cache_read_price,
             cache_write_price,
+            capabilities.vision,
+            capabilities.tools,
+            capabilities.thinking,
         )
         .execute(&conn)
         .await
@@ -216,12 +415,201 @@ impl ModelsStore {
         Ok(affected > 0)
     }
 
-    /// Check if a model exists and is enabled
+    /// Update capability flags; `None` leaves a flag unchanged
+    pub async fn set_capabilities(
+        &self,
+        id: &str,
+        vision: Option<bool>,
+        tools: Option<bool>,
+        thinking: Option<bool>,
+    ) -> Result<bool, ProxyError> {
+        let conn = db::get_conn().await?;
+        let affected = sqlx::query!(
+            "UPDATE models SET \
+             supports_vision = COALESCE($1, supports_vision), \
+             supports_tools = COALESCE($2, supports_tools), \
+             supports_thinking = COALESCE($3, supports_thinking) \
+             WHERE id = $4",
+            vision,
+            tools,
+            thinking,
+            id,
+        )
+        .execute(&conn)
+        .await
+        .db_context("Failed to update model capabilities")?
+        .rows_affected();
+        Ok(affected > 0)
+    }
+
+    /// Capabilities of a model. Unknown models are treated as fully capable
+    /// and left for upstream to judge.
+    pub async fn capabilities(&self, model_id: &str) -> Result<ModelCapabilities, ProxyError> {
+        let conn = db::get_conn().await?;
+        let row = sqlx::query!(
+            "SELECT supports_vision, supports_tools, supports_thinking FROM models WHERE id = $1",
+            model_id,
+        )
+        .fetch_optional(&conn)
+        .await
+        .db_context("Failed to get model capabilities")?;
+        Ok(
+            row.map_or_else(ModelCapabilities::default, |row| ModelCapabilities {
+                vision: row.supports_vision,
+                tools: row.supports_tools,
+                thinking: row.supports_thinking,
+            }),
+        )
+    }
+
+    /// Default thinking effort of a model, if it has one.
+    pub async fn default_effort(&self, model_id: &str) -> Result<Option<String>, ProxyError> {
+        let conn = db::get_conn().await?;
+        let effort =
+            sqlx::query_scalar!("SELECT default_effort FROM models WHERE id = $1", model_id)
+                .fetch_optional(&conn)
+                .await
+                .db_context("Failed to get model default effort")?;
+        Ok(effort.flatten())
+    }
+
+    /// Set or clear a model's default thinking effort
+    pub async fn set_default_effort(
+        &self,
+        id: &str,
+        effort: Option<&str>,
+    ) -> Result<bool, ProxyError> {
+        let conn = db::get_conn().await?;
+        let affected = sqlx::query!(
+            "UPDATE models SET default_effort = $1 WHERE id = $2",
+            effort,
+            id,
+        )
+        .execute(&conn)
+        .await
+        .db_context("Failed to set model default effort")?
+        .rows_affected();
+        Ok(affected > 0)
+    }
+
+    /// Set or clear a model's enable schedule (epoch ms, `None` = unbounded)
+    pub async fn set_schedule(
+        &self,
+        id: &str,
+        enabled_from: Option<i64>,
+        enabled_until: Option<i64>,
+    ) -> Result<bool, ProxyError> {
+        let conn = db::get_conn().await?;
+        let affected = sqlx::query!(
+            "UPDATE models SET enabled_from = $1, enabled_until = $2 WHERE id = $3",
+            enabled_from,
+            enabled_until,
+            id,
+        )
+        .execute(&conn)
+        .await
+        .db_context("Failed to set model schedule")?
+        .rows_affected();
+        Ok(affected > 0)
+    }
+
+    /// List all model aliases
+    pub async fn list_aliases(&self) -> Result<Vec<ModelAlias>, ProxyError> {
+        let conn = db::get_conn().await?;
+        let rows = sqlx::query_as!(
+            ModelAlias,
+            "SELECT alias, target FROM model_aliases ORDER BY alias"
+        )
+        .fetch_all(&conn)
+        .await
+        .db_context("Failed to list model aliases")?;
+        Ok(rows)
+    }
+
+    /// Create or replace an alias
+    pub async fn set_alias(&self, alias: &str, target: &str) -> Result<(), ProxyError> {
+        let conn = db::get_conn().await?;
+        sqlx::query!(
+            "INSERT INTO model_aliases (alias, target) VALUES ($1, $2) \
+             ON CONFLICT (alias) DO UPDATE SET target = EXCLUDED.target",
+            alias,
+            target,
+        )
+        .execute(&conn)
+        .await
+        .db_context("Failed to set model alias")?;
+        Ok(())
+    }
+
+    /// Remove an alias
+    pub async fn remove_alias(&self, alias: &str) -> Result<bool, ProxyError> {
+        let conn = db::get_conn().await?;
+        let affected = sqlx::query!("DELETE FROM model_aliases WHERE alias = $1", alias)
+            .execute(&conn)
+            .await
+            .db_context("Failed to remove model alias")?
+            .rows_affected();
+        Ok(affected > 0)
+    }
+
+    /// Resolve the model a client asked for into a configured model id:
+    /// falls back to `default_model` when none was sent, then applies the
+    /// alias table (single hop). A thinking suffix like `(high)` is kept.
+    pub async fn resolve_model(
+        &self,
+        requested: Option<&str>,
+        default_model: &str,
+    ) -> Result<String, ProxyError> {
+        let requested = requested_or_default(requested, default_model);
+        let base = requested
+            .split_once('(')
+            .map_or(requested, |(base, _)| base);
+
+        let conn = db::get_conn().await?;
+        let target = sqlx::query_scalar!("SELECT target FROM model_aliases WHERE alias = $1", base)
+            .fetch_optional(&conn)
+            .await
+            .db_context("Failed to resolve model alias")?;
+
+        Ok(apply_alias(requested, target.as_deref()))
+    }
+
+    /// Resolve the requested model like [`Self::resolve_model`], then apply
+    /// `policy` if the result is unknown, disabled or outside its schedule.
+    pub async fn resolve_available_model(
+        &self,
+        requested: Option<&str>,
+        default_model: &str,
+        policy: UnknownModelPolicy,
+    ) -> Result<String, ProxyError> {
+        let resolved = self.resolve_model(requested, default_model).await?;
+        let base = resolved
+            .split_once('(')
+            .map_or(resolved.as_str(), |(base, _)| base);
+        if self.is_valid(base).await? {
+            return Ok(resolved);
+        }
+        let available = self.list_enabled_ids().await?;
+        let model = apply_unknown_model_policy(resolved, default_model, &available, policy)?;
+        info!(
+            requested = requested.unwrap_or_default(),
+            %model,
+            "requested model unavailable; using the default model"
+        );
+        Ok(model)
+    }
+
+    /// Check if a model exists, is enabled, and is inside its schedule
     pub async fn is_valid(&self, model_id: &str) -> Result<bool, ProxyError> {
         let conn = db::get_conn().await?;
+        let now = timestamp_millis() as i64;
         let count = sqlx::query_scalar!(
-            "SELECT COUNT(*) FROM models WHERE id = $1 AND enabled = TRUE",
-            model_id
+            "SELECT COUNT(*) FROM models \
+             WHERE id = $1 AND enabled = TRUE \
+             AND (enabled_from IS NULL OR enabled_from <= $2) \
+             AND (enabled_until IS NULL OR enabled_until > $2)",
+            model_id,
+            now,
         )
         .fetch_one(&conn)
         .await
@@ -229,3 +617,273 @@ impl ModelsStore {
         Ok(count.unwrap_or(0) > 0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(enabled_from: Option<i64>, enabled_until: Option<i64>) -> Model {
+        named_model("claude-opus-4-6", enabled_from, enabled_until)
+    }
+
+    fn named_model(id: &str, enabled_from: Option<i64>, enabled_until: Option<i64>) -> Model {
+        Model {
+            id: id.into(),
+            sort_order: 0,
+            enabled: true,
+            input_price: 5.0,
+            output_price: 25.0,
+            cache_read_price: 0.5,
+            cache_write_price: 6.25,
+            enabled_from,
+            enabled_until,
+            supports_vision: true,
+            supports_tools: true,
+            supports_thinking: true,
+            default_effort: None,
+        }
+    }
+
+    fn priced_model(id: &str, sort_order: i64, enabled: bool, input: f64, output: f64) -> Model {
+        Model {
+            sort_order,
+            enabled,
+            input_price: input,
+            output_price: output,
+            ..named_model(id, None, None)
+        }
+    }
+
+    fn ids(models: &[Model]) -> Vec<&str> {
+        models.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_filter_and_sort_models() {
+        let catalog = vec![
+            priced_model("claude-opus-4-6", 0, true, 5.0, 25.0),
+            priced_model("claude-haiku-4-5", 1, false, 1.0, 5.0),
+            priced_model("claude-sonnet-4-5", 2, true, 3.0, 15.0),
+            priced_model("claude-sonnet-4-6", 3, true, 3.0, 12.0),
+        ];
+
+        let all = filter_and_sort_models(catalog.clone(), None, ModelSort::default());
+        assert_eq!(
+            ids(&all),
+            [
+                "claude-opus-4-6",
+                "claude-haiku-4-5",
+                "claude-sonnet-4-5",
+                "claude-sonnet-4-6"
+            ]
+        );
+
+        let enabled_by_price =
+            filter_and_sort_models(catalog.clone(), Some(true), ModelSort::Price);
+        assert_eq!(
+            ids(&enabled_by_price),
+            ["claude-sonnet-4-6", "claude-sonnet-4-5", "claude-opus-4-6"]
+        );
+
+        let disabled = filter_and_sort_models(catalog.clone(), Some(false), ModelSort::Id);
+        assert_eq!(ids(&disabled), ["claude-haiku-4-5"]);
+
+        let by_id = filter_and_sort_models(catalog, None, ModelSort::Id);
+        assert_eq!(
+            ids(&by_id),
+            [
+                "claude-haiku-4-5",
+                "claude-opus-4-6",
+                "claude-sonnet-4-5",
+                "claude-sonnet-4-6"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unscheduled_model_is_active() {
+        assert!(model(None, None).is_active_at(0));
+        assert!(model(None, None).is_active_at(i64::MAX));
+    }
+
+    #[test]
+    fn test_model_inside_schedule_is_active() {
+        let m = model(Some(1_000), Some(2_000));
+        assert!(m.is_active_at(1_000));
+        assert!(m.is_active_at(1_999));
+    }
+
+    #[test]
+    fn test_model_outside_schedule_is_inactive() {
+        let m = model(Some(1_000), Some(2_000));
+        assert!(!m.is_active_at(999));
+        assert!(!m.is_active_at(2_000));
+        assert!(!model(Some(1_000), None).is_active_at(500));
+        assert!(!model(None, Some(1_000)).is_active_at(1_500));
+    }
+
+    #[test]
+    fn test_disabled_model_ignores_schedule() {
+        let mut m = model(None, None);
+        m.enabled = false;
+        assert!(!m.is_active_at(0));
+    }
+
+    #[test]
+    fn test_effective_models_without_allow_list() {
+        let mut disabled = named_model("claude-haiku-4-5", None, None);
+        disabled.enabled = false;
+        let models = vec![named_model("claude-opus-4-6", None, None), disabled];
+        assert_eq!(
+            effective_model_ids(&models, &[], 0),
+            vec!["claude-opus-4-6".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_effective_models_exclude_disabled_allow_list_entry() {
+        let mut disabled = named_model("claude-haiku-4-5", None, None);
+        disabled.enabled = false;
+        let models = vec![
+            named_model("claude-opus-4-6", None, None),
+            named_model("claude-sonnet-4-5", None, None),
+            disabled,
+        ];
+        let allowed = vec![
+            "claude-opus-4-6".to_string(),
+            "claude-haiku-4-5".to_string(),
+        ];
+        assert_eq!(
+            effective_model_ids(&models, &allowed, 0),
+            vec!["claude-opus-4-6".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_effective_models_respect_schedule() {
+        let models = vec![
+            named_model("claude-opus-4-6", None, Some(1_000)),
+            named_model("claude-sonnet-4-5", None, None),
+        ];
+        let allowed = vec![
+            "claude-opus-4-6".to_string(),
+            "claude-sonnet-4-5".to_string(),
+        ];
+        assert_eq!(effective_model_ids(&models, &allowed, 500).len(), 2);
+        assert_eq!(
+            effective_model_ids(&models, &allowed, 1_000),
+            vec!["claude-sonnet-4-5".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_effective_models_ignore_unknown_allow_list_entry() {
+        let models = vec![named_model("claude-opus-4-6", None, None)];
+        let allowed = vec!["claude-removed-1".to_string()];
+        assert!(effective_model_ids(&models, &allowed, 0).is_empty());
+    }
+
+    #[test]
+    fn test_alias_replaces_model() {
+        assert_eq!(
+            apply_alias("gpt-4", Some("claude-opus-4-6")),
+            "claude-opus-4-6"
+        );
+        assert_eq!(apply_alias("claude-opus-4-6", None), "claude-opus-4-6");
+    }
+
+    #[test]
+    fn test_alias_keeps_thinking_suffix() {
+        assert_eq!(
+            apply_alias("gpt-4(high)", Some("claude-opus-4-6")),
+            "claude-opus-4-6(high)"
+        );
+        assert_eq!(apply_alias("gpt-4(high)", None), "gpt-4(high)");
+    }
+
+    #[test]
+    fn test_default_model_fallback() {
+        assert_eq!(
+            requested_or_default(None, "claude-sonnet-4-5"),
+            "claude-sonnet-4-5"
+        );
+        assert_eq!(
+            requested_or_default(Some("  "), "claude-sonnet-4-5"),
+            "claude-sonnet-4-5"
+        );
+        assert_eq!(
+            requested_or_default(Some("gpt-4"), "claude-sonnet-4-5"),
+            "gpt-4"
+        );
+    }
+
+    fn available() -> Vec<String> {
+        vec!["claude-opus-4-6".into(), "claude-sonnet-4-5".into()]
+    }
+
+    #[test]
+    fn test_available_model_passes_under_either_policy() {
+        for policy in [UnknownModelPolicy::Reject, UnknownModelPolicy::Fallback] {
+            let model = apply_unknown_model_policy(
+                "claude-opus-4-6(high)".into(),
+                "claude-sonnet-4-5",
+                &available(),
+                policy,
+            );
+            assert_eq!(model.ok().as_deref(), Some("claude-opus-4-6(high)"));
+        }
+    }
+
+    #[test]
+    fn test_unknown_model_rejected_with_available_list() {
+        let result = apply_unknown_model_policy(
+            "claude-instant-1".into(),
+            "claude-sonnet-4-5",
+            &available(),
+            UnknownModelPolicy::Reject,
+        );
+        let Err(ProxyError::ModelNotFound { model, available }) = result else {
+            panic!("expected ModelNotFound, got {result:?}");
+        };
+        assert_eq!(model, "claude-instant-1");
+        assert_eq!(available, self::available());
+        let message = ProxyError::ModelNotFound { model, available }.to_string();
+        assert!(
+            message.contains("claude-opus-4-6, claude-sonnet-4-5"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_unknown_model_falls_back_to_default() {
+        let model = apply_unknown_model_policy(
+            "claude-instant-1(medium)".into(),
+            "claude-sonnet-4-5",
+            &available(),
+            UnknownModelPolicy::Fallback,
+        );
+        assert_eq!(model.ok().as_deref(), Some("claude-sonnet-4-5(medium)"));
+
+        // An unavailable default can't be fallen back to
+        let result = apply_unknown_model_policy(
+            "claude-instant-1".into(),
+            "claude-2",
+            &available(),
+            UnknownModelPolicy::Fallback,
+        );
+        assert!(matches!(result, Err(ProxyError::ModelNotFound { .. })));
+    }
+
+    #[test]
+    fn test_unknown_model_policy_parse() {
+        assert_eq!(
+            UnknownModelPolicy::parse(" Fallback "),
+            Some(UnknownModelPolicy::Fallback)
+        );
+        assert_eq!(
+            UnknownModelPolicy::parse("reject"),
+            Some(UnknownModelPolicy::Reject)
+        );
+        assert_eq!(UnknownModelPolicy::parse("ignore"), None);
+    }
+}
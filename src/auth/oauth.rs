@@ -26,14 +26,77 @@ const AUTHORIZE_SCOPES: &str = "org:create_api_key user:profile user:inference u
 const REFRESH_SCOPES: &str =
     "user:profile user:inference user:sessions:claude_code user:mcp_servers user:file_upload";
 
+/// Why a token refresh failed, so callers can tell a blip from a dead login.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RefreshError {
+    /// Network failure, 429/5xx or an unreadable answer from the token
+    /// endpoint, or the new tokens couldn't be saved. Retrying may succeed.
+    #[error("{0}")]
+    Transient(String),
+    /// Anthropic rejected the refresh token (revoked, rotated or otherwise
+    /// invalid). Only reconnecting OAuth in the admin UI helps.
+    #[error("OAuth login is no longer valid, reconnect OAuth in the admin UI ({0})")]
+    Revoked(String),
+}
+
+/// How long before expiry the request path refreshes a token itself.
+const REQUEST_REFRESH_LEAD_MS: u64 = 300_000;
+
+/// Classify a non-success answer from the token endpoint.
+fn refresh_failure(status: reqwest::StatusCode, body: &str) -> RefreshError {
+    let message = format!("Token refresh failed ({status}): {body}");
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        RefreshError::Transient(message)
+    } else {
+        RefreshError::Revoked(message)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-struct TokenResponse {
-    access_token: String,
-    refresh_token: String,
-    expires_in: u64,
+pub(super) struct TokenResponse {
+    pub(super) access_token: String,
+    pub(super) refresh_token: String,
+    pub(super) expires_in: u64,
     token_type: String,
 }
 
+/// Trade `refresh` for new tokens at `token_url`. An `invalid_grant` answer
+/// (rotated or revoked refresh token) is [`RefreshError::Revoked`].
+pub(super) async fn request_refresh(
+    client: &Client,
+    token_url: &str,
+    refresh: &str,
+) -> Result<TokenResponse, RefreshError> {
+    let body = json!({
+        "grant_type": "refresh_token",
+        "refresh_token": refresh,
+        "client_id": CLIENT_ID,
+        "scope": REFRESH_SCOPES,
+    });
+
+    let response = client
+        .post(token_url)
+        .header("Content-Type", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| RefreshError::Transient(format!("Failed to refresh token: {e}")))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        if text.contains("invalid_grant") {
+            return Err(RefreshError::Revoked("refresh token rejected".into()));
+        }
+        return Err(refresh_failure(status, &text));
+    }
+
+    response
+        .json::<TokenResponse>()
+        .await
+        .map_err(|e| RefreshError::Transient(format!("Failed to parse refresh response: {e}")))
+}
+
 pub struct OAuthManager {
     client: Client,
     verifier: RwLock<Option<String>>,
@@ -145,45 +208,21 @@ impl OAuthManager {
         Ok(())
     }
 
-    async fn do_refresh(&self, refresh: String) -> Result<Option<String>, String> {
-        let body = json!({
-            "grant_type": "refresh_token",
-            "refresh_token": refresh,
-            "client_id": CLIENT_ID,
-            "scope": REFRESH_SCOPES,
-        });
-
-        let response = self
-            .client
-            .post(TOKEN_URL)
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to refresh token: {}", e))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-
+    async fn do_refresh(&self, refresh: String) -> Result<Option<String>, RefreshError> {
+        let token = match request_refresh(&self.client, TOKEN_URL, &refresh).await {
+            Ok(token) => token,
             // If the refresh token is invalid (e.g. rotated or revoked),
             // clear the stale credentials so the UI shows "Connect" instead
             // of endlessly failing.
-            if text.contains("invalid_grant") {
+            Err(revoked @ RefreshError::Revoked(_)) => {
                 warn!("OAuth refresh token is invalid, clearing stale credentials");
                 if let Err(e) = self.auth_store.remove("anthropic").await {
                     warn!("Failed to clear stale OAuth credentials: {e}");
                 }
-                return Ok(None);
+                return Err(revoked);
             }
-
-            return Err(format!("Token refresh failed ({}): {}", status, text));
-        }
-
-        let token = response
-            .json::<TokenResponse>()
-            .await
-            .map_err(|e| format!("Failed to parse refresh response: {}", e))?;
+            Err(e) => return Err(e),
+        };
 
         let new_expires = now_millis() + (token.expires_in * 1000);
 
@@ -195,12 +234,19 @@ impl OAuthManager {
                 new_expires,
             )
             .await
-            .map_err(|e| format!("Failed to save refreshed auth: {}", e))?;
+            .map_err(|e| RefreshError::Transient(format!("Failed to save refreshed auth: {e}")))?;
 
         Ok(Some(token.access_token))
     }
 
-    pub async fn refresh_if_needed(&self) -> Result<Option<String>, String> {
+    /// Current access token, refreshed first if it expires within 5 minutes.
+    /// `Ok(None)` when no credentials are stored.
+    pub async fn refresh_if_needed(&self) -> Result<Option<String>, RefreshError> {
+        self.refresh_within(REQUEST_REFRESH_LEAD_MS).await
+    }
+
+    /// Current access token, refreshed first if it expires within `lead_ms`.
+    pub async fn refresh_within(&self, lead_ms: u64) -> Result<Option<String>, RefreshError> {
         // Fast path: check without the lock first.
         {
             let auth = match self.auth_store.get("anthropic").await {
@@ -214,7 +260,7 @@ impl OAuthManager {
                 Auth::OAuth {
                     access, expires, ..
                 } => {
-                    if now_millis() + 300_000 < expires {
+                    if now_millis() + lead_ms < expires {
                         return Ok(Some(access));
                     }
                 }
@@ -240,7 +286,7 @@ impl OAuthManager {
                 ..
             } => (access, refresh, expires),
         };
-        if now_millis() + 300_000 < expires {
+        if now_millis() + lead_ms < expires {
             return Ok(Some(access));
         }
 
@@ -249,7 +295,7 @@ impl OAuthManager {
 
     /// Force a token refresh regardless of expiry. Used when Anthropic returns 401
     /// to recover from server-side token revocation without waiting for local expiry.
-    pub async fn force_refresh(&self) -> Result<Option<String>, String> {
+    pub async fn force_refresh(&self) -> Result<Option<String>, RefreshError> {
         let _guard = self.refresh_lock.lock().await;
 
         let auth = match self.auth_store.get("anthropic").await {
@@ -272,15 +318,60 @@ impl OAuthManager {
         self.auth_store.remove("anthropic").await
     }
 
+    /// Remove the stored OAuth credentials if their token still expires at
+    /// `expires`, so credentials reconnected in the meantime are kept.
+    pub async fn forget_revoked(&self, expires: u64) -> Result<(), ProxyError> {
+        let _guard = self.refresh_lock.lock().await;
+        if self.oauth_expires_at().await != Some(expires) {
+            return Ok(());
+        }
+        self.auth_store.remove("anthropic").await
+    }
+
     pub async fn is_authenticated(&self) -> bool {
         self.auth_store.has("anthropic").await.unwrap_or(false)
     }
+
+    /// Expiry (epoch ms) of the stored OAuth access token; `None` without
+    /// OAuth credentials.
+    pub async fn oauth_expires_at(&self) -> Option<u64> {
+        match self.auth_store.get("anthropic").await? {
+            Auth::OAuth { expires, .. } => Some(expires),
+            _ => None,
+        }
+    }
 }
 
-fn now_millis() -> u64 {
+pub(super) fn now_millis() -> u64 {
     let millis = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
         .as_millis();
     u64::try_from(millis).unwrap_or(u64::MAX)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_refresh_failure_classification() {
+        for status in [
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ] {
+            assert!(
+                matches!(refresh_failure(status, "busy"), RefreshError::Transient(_)),
+                "{status}"
+            );
+        }
+        for status in [StatusCode::BAD_REQUEST, StatusCode::UNAUTHORIZED] {
+            assert!(
+                matches!(refresh_failure(status, "nope"), RefreshError::Revoked(_)),
+                "{status}"
+            );
+        }
+    }
+}
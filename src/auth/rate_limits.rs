@@ -5,7 +5,8 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use super::client_keys::{
-    ClientKeysStore, TokenLimits, TokenUsage, UsageResetType, i64_to_u64, opt_i64_to_u64,
+    ClientKey, ClientKeysStore, TokenLimits, TokenUsage, UsageResetType, WindowDurations,
+    i64_to_u64, opt_i64_to_u64,
 };
 use crate::db;
 use crate::error::{DbResultExt, ProxyError};
@@ -16,12 +17,31 @@ mod cost;
 mod windows;
 
 use cost::{aggregate_usage_costs, compute_cost, query_model_cost};
-use windows::{WindowState, maybe_reset_expired_windows};
+use windows::{WindowState, initial_reset_at, maybe_reset_expired_windows};
+
+/// Shortest and longest accepted rate-limit window: a minute to 31 days
+const MIN_WINDOW_MS: u64 = 60 * 1000;
+const MAX_WINDOW_MS: u64 = 31 * 24 * 60 * 60 * 1000;
+
+/// Milliseconds between request start and `now`; 0 if the clock went
+/// backwards in between.
+fn request_duration_ms(started_at: u64, now: u64) -> i64 {
+    i64::try_from(now.saturating_sub(started_at)).unwrap_or(i64::MAX)
+}
 
 // ============================================================================
 // Structs
 // ============================================================================
 
+/// What is recorded about a request on its `request_log` row besides usage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestTags {
+    /// Tier the request was sent with, if any
+    pub service_tier: Option<String>,
+    /// End user the client attributed the request to (OpenAI `user`)
+    pub end_user: Option<String>,
+}
+
 /// 4-type token breakdown for display
 #[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -47,6 +67,266 @@ pub struct ModelUsageEntry {
     pub weekly_reset_at: u64,
 }
 
+/// Which level's limit binds a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitSource {
+    Key,
+    Model,
+}
+
+/// The ceiling a key will hit first in one window, with the spend counted
+/// against it (microdollars)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveWindow {
+    /// Binding limit (None = unlimited at both levels)
+    pub limit: Option<u64>,
+    /// Spend counted against `limit`; key-level spend when unlimited
+    pub used: u64,
+    /// `limit - used`, floored at 0 (None = unlimited)
+    pub remaining: Option<u64>,
+    /// Whether the key-level or the per-model limit binds
+    pub source: Option<LimitSource>,
+}
+
+/// Binding limits for one model a key can use
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveModelLimits {
+    pub model: String,
+    pub five_hour: EffectiveWindow,
+    pub weekly: EffectiveWindow,
+    pub total: EffectiveWindow,
+}
+
+/// Window boundaries (epoch ms) and lengths an admin sets by hand. Absent
+/// fields are left as they are. A reset time of 0 means "not started": the
+/// next request aligns it with the subscription window again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowOverrides {
+    pub five_hour_reset_at: Option<u64>,
+    pub weekly_reset_at: Option<u64>,
+    /// Requests logged at or after this time count toward the 5-hour window
+    pub five_hour_count_from: Option<u64>,
+    pub weekly_count_from: Option<u64>,
+    pub total_count_from: Option<u64>,
+    /// Length of the 5-hour window (ms). Anything but the default stops it
+    /// following the subscription's reset times.
+    pub five_hour_window_ms: Option<u64>,
+    pub weekly_window_ms: Option<u64>,
+}
+
+impl WindowOverrides {
+    /// Reset times must be 0 or in the future; counting can't start in the
+    /// future.
+    pub fn validate(&self, now: u64) -> Result<(), String> {
+        for (name, reset_at) in [
+            ("fiveHourResetAt", self.five_hour_reset_at),
+            ("weeklyResetAt", self.weekly_reset_at),
+        ] {
+            if let Some(t) = reset_at
+                && t != 0
+                && t <= now
+            {
+                return Err(format!("{name} must be 0 or in the future"));
+            }
+        }
+        for (name, count_from) in [
+            ("fiveHourCountFrom", self.five_hour_count_from),
+            ("weeklyCountFrom", self.weekly_count_from),
+            ("totalCountFrom", self.total_count_from),
+        ] {
+            if count_from.is_some_and(|t| t > now) {
+                return Err(format!("{name} must not be in the future"));
+            }
+        }
+        for (name, window_ms) in [
+            ("fiveHourWindowMs", self.five_hour_window_ms),
+            ("weeklyWindowMs", self.weekly_window_ms),
+        ] {
+            if window_ms.is_some_and(|ms| !(MIN_WINDOW_MS..=MAX_WINDOW_MS).contains(&ms)) {
+                return Err(format!(
+                    "{name} must be between {MIN_WINDOW_MS} and {MAX_WINDOW_MS} ms"
+                ));
+            }
+        }
+        if self == &Self::default() {
+            return Err("No window values given".into());
+        }
+        Ok(())
+    }
+}
+
+impl UsageResetType {
+    /// Boundaries that restart the reset windows at `now`. Reset times go
+    /// to 0, so the next request aligns them with the subscription again.
+    fn overrides(self, now: u64) -> WindowOverrides {
+        let (five_hour, weekly, total) = match self {
+            Self::FiveHour => (true, false, false),
+            Self::Weekly => (false, true, false),
+            Self::Total => (false, false, true),
+            Self::All => (true, true, true),
+        };
+        WindowOverrides {
+            five_hour_reset_at: five_hour.then_some(0),
+            weekly_reset_at: weekly.then_some(0),
+            five_hour_count_from: five_hour.then_some(now),
+            weekly_count_from: weekly.then_some(now),
+            total_count_from: total.then_some(now),
+            five_hour_window_ms: None,
+            weekly_window_ms: None,
+        }
+    }
+}
+
+/// Apply `windows` to one key, or to every key when `id` is `None`.
+/// Returns the number of keys updated.
+async fn update_windows(id: Option<&str>, windows: &WindowOverrides) -> Result<u64, ProxyError> {
+    let conn = db::get_conn().await?;
+    let to_i64 = |t: Option<u64>| t.map(|t| i64::try_from(t).unwrap_or(i64::MAX));
+    let affected = sqlx::query!(
+        "UPDATE client_keys SET \
+         five_hour_reset_at = COALESCE($1, five_hour_reset_at), \
+         weekly_reset_at = COALESCE($2, weekly_reset_at), \
+         five_hour_count_from = COALESCE($3, five_hour_count_from), \
+         weekly_count_from = COALESCE($4, weekly_count_from), \
+         total_count_from = COALESCE($5, total_count_from), \
+         five_hour_window_ms = COALESCE($6, five_hour_window_ms), \
+         weekly_window_ms = COALESCE($7, weekly_window_ms) \
+         WHERE $8::TEXT IS NULL OR id = $8",
+        to_i64(windows.five_hour_reset_at),
+        to_i64(windows.weekly_reset_at),
+        to_i64(windows.five_hour_count_from),
+        to_i64(windows.weekly_count_from),
+        to_i64(windows.total_count_from),
+        to_i64(windows.five_hour_window_ms),
+        to_i64(windows.weekly_window_ms),
+        id,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to update key windows")?
+    .rows_affected();
+    Ok(affected)
+}
+
+/// The read-only usage view of a key's windows at `now`, as shown by
+/// [`ClientKeysStore::get_usage`]: a window whose reset time has passed
+/// shows no spend and no reset time until the next request rolls it over.
+fn usage_view(
+    limits: &TokenLimits,
+    five_hour_reset_at: u64,
+    weekly_reset_at: u64,
+    (five_hour, weekly, total): (u64, u64, u64),
+    now: u64,
+) -> TokenUsage {
+    let five_hour_expired = five_hour_reset_at > 0 && now >= five_hour_reset_at;
+    let weekly_expired = weekly_reset_at > 0 && now >= weekly_reset_at;
+    TokenUsage {
+        five_hour_tokens: if five_hour_expired { 0 } else { five_hour },
+        five_hour_reset_at: if five_hour_expired {
+            0
+        } else {
+            five_hour_reset_at
+        },
+        weekly_tokens: if weekly_expired { 0 } else { weekly },
+        weekly_reset_at: if weekly_expired { 0 } else { weekly_reset_at },
+        total_tokens: total,
+        ..TokenUsage::default()
+    }
+    .with_utilization(limits)
+}
+
+/// Pick whichever of the key-level and per-model `(limit, used)` pairs has
+/// less headroom. A tie goes to the key-level limit.
+fn binding_window(key: (Option<u64>, u64), model: (Option<u64>, u64)) -> EffectiveWindow {
+    let candidates = [(LimitSource::Key, key), (LimitSource::Model, model)];
+    candidates
+        .into_iter()
+        .filter_map(|(source, (limit, used))| limit.map(|limit| (source, limit, used)))
+        .min_by_key(|&(_, limit, used)| limit.saturating_sub(used))
+        .map_or(
+            EffectiveWindow {
+                used: key.1,
+                ..EffectiveWindow::default()
+            },
+            |(source, limit, used)| EffectiveWindow {
+                limit: Some(limit),
+                used,
+                remaining: Some(limit.saturating_sub(used)),
+                source: Some(source),
+            },
+        )
+}
+
+/// Per-model `(limit, used)` for one window. Spend is only queried when the
+/// model has a limit there.
+async fn model_window(
+    conn: &db::Connection,
+    key_id: &str,
+    model: &str,
+    limit: Option<u64>,
+    from: u64,
+) -> Result<(Option<u64>, u64), ProxyError> {
+    let used = match limit {
+        Some(_) => query_model_cost(conn, key_id, model, from).await?,
+        None => 0,
+    };
+    Ok((limit, used))
+}
+
+/// Prompt-cache effectiveness for a key over a period
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub input_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    /// `cache_read / (input + cache_read)`, 0 when there was no input
+    pub cache_read_ratio: f64,
+    /// What cache reads saved versus paying the full input price, minus the
+    /// cache write premium (microdollars, can be negative)
+    pub estimated_savings_microdollars: i64,
+}
+
+/// Per-model token sums and prices feeding [`summarize_cache_usage`].
+/// Prices are `None` for models no longer in the models table.
+#[derive(Debug, Clone, Default)]
+struct ModelCacheUsage {
+    input_tokens: u64,
+    cache_read_tokens: u64,
+    cache_write_tokens: u64,
+    input_price: Option<f64>,
+    cache_read_price: Option<f64>,
+    cache_write_price: Option<f64>,
+}
+
+/// Combine per-model cache usage into totals, pricing each model separately.
+/// Models without pricing count towards the ratio but not the savings.
+fn summarize_cache_usage(models: &[ModelCacheUsage]) -> CacheStats {
+    let mut stats = CacheStats::default();
+    let mut savings = 0.0;
+    for m in models {
+        stats.input_tokens += m.input_tokens;
+        stats.cache_read_tokens += m.cache_read_tokens;
+        stats.cache_write_tokens += m.cache_write_tokens;
+        if let (Some(input), Some(read), Some(write)) =
+            (m.input_price, m.cache_read_price, m.cache_write_price)
+        {
+            savings += m.cache_read_tokens as f64 * (input - read)
+                - m.cache_write_tokens as f64 * (write - input);
+        }
+    }
+    let denominator = stats.input_tokens + stats.cache_read_tokens;
+    if denominator > 0 {
+        stats.cache_read_ratio = stats.cache_read_tokens as f64 / denominator as f64;
+    }
+    stats.estimated_savings_microdollars = savings.round() as i64;
+    stats
+}
+
 // ============================================================================
 // Rate limiting, usage tracking, and model access methods on ClientKeysStore
 // ============================================================================
@@ -123,12 +403,33 @@ impl ClientKeysStore {
 
     /// Record usage by inserting into request_log.
     /// Window boundaries are updated via maybe_reset_expired_windows.
+    /// The row is dated `started_at` (when the request arrived, ms), not when
+    /// the response finished, so long streams land in the right time bucket.
+    /// `tags` are stored alongside (see [`RequestTags`]). Retried on lock
+    /// conflicts: the row is inserted last, so a failed try inserts nothing.
     pub async fn record_model_usage(
         &self,
         key_id: &str,
         model: &str,
         report: &Usage,
         window_resets: &SubscriptionState,
+        started_at: u64,
+        tags: &RequestTags,
+    ) -> Result<(), ProxyError> {
+        db::retry_on_lock(|| {
+            self.try_record_model_usage(key_id, model, report, window_resets, started_at, tags)
+        })
+        .await
+    }
+
+    async fn try_record_model_usage(
+        &self,
+        key_id: &str,
+        model: &str,
+        report: &Usage,
+        window_resets: &SubscriptionState,
+        started_at: u64,
+        tags: &RequestTags,
     ) -> Result<(), ProxyError> {
         let now = timestamp_millis();
         let conn = db::get_conn().await?;
@@ -138,7 +439,7 @@ impl ClientKeysStore {
 
         // Initialize reset timestamps if not yet set
         let row = sqlx::query!(
-            "SELECT five_hour_reset_at, weekly_reset_at FROM client_keys WHERE id = $1",
+            "SELECT five_hour_reset_at, weekly_reset_at, five_hour_window_ms, weekly_window_ms FROM client_keys WHERE id = $1",
             key_id,
         )
         .fetch_optional(&conn)
@@ -152,19 +453,23 @@ impl ClientKeysStore {
             let mut needs_init = false;
             let new_five_hour = if five_hour_reset_at == 0 {
                 needs_init = true;
-                window_resets
-                    .five_hour_reset_at
-                    .filter(|&t| t > now)
-                    .unwrap_or(0)
+                initial_reset_at(
+                    i64_to_u64(row.five_hour_window_ms),
+                    WindowDurations::DEFAULT_FIVE_HOUR_MS,
+                    window_resets.five_hour_reset_at,
+                    now,
+                )
             } else {
                 five_hour_reset_at
             };
             let new_weekly = if weekly_reset_at == 0 {
                 needs_init = true;
-                window_resets
-                    .seven_day_reset_at
-                    .filter(|&t| t > now)
-                    .unwrap_or(0)
+                initial_reset_at(
+                    i64_to_u64(row.weekly_window_ms),
+                    WindowDurations::DEFAULT_WEEKLY_MS,
+                    window_resets.seven_day_reset_at,
+                    now,
+                )
             } else {
                 weekly_reset_at
             };
@@ -187,7 +492,7 @@ impl ClientKeysStore {
 
         // Single INSERT into request_log
         sqlx::query!(
-            "INSERT INTO request_log (key_id, model, input_tokens, output_tokens, cache_read_tokens, cache_write_tokens, cost_microdollars, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            "INSERT INTO request_log (key_id, model, input_tokens, output_tokens, cache_read_tokens, cache_write_tokens, cost_microdollars, created_at, duration_ms, service_tier, end_user) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
             key_id,
             model,
             report.input_tokens as i64,
@@ -195,7 +500,10 @@ impl ClientKeysStore {
             report.cache_read_input_tokens.unwrap_or(0) as i64,
             report.cache_creation_input_tokens.unwrap_or(0) as i64,
             cost as i64,
-            now as i64,
+            started_at as i64,
+            request_duration_ms(started_at, now),
+            tags.service_tier.as_deref(),
+            tags.end_user.as_deref(),
         )
         .execute(&conn)
         .await
@@ -230,39 +538,79 @@ impl ClientKeysStore {
         let weekly_count_from = i64_to_u64(count_from_row.weekly_count_from);
         let total_count_from = i64_to_u64(count_from_row.total_count_from);
 
-        let mut five_hour_reset_at = key.usage.five_hour_reset_at;
-        let mut weekly_reset_at = key.usage.weekly_reset_at;
-
-        // Show 0 if windows have expired (read-only view)
-        let five_hour_expired = five_hour_reset_at > 0 && now >= five_hour_reset_at;
-        let weekly_expired = weekly_reset_at > 0 && now >= weekly_reset_at;
-
-        if five_hour_expired {
-            five_hour_reset_at = 0;
-        }
-        if weekly_expired {
-            weekly_reset_at = 0;
-        }
-
         let ws = WindowState {
             five_hour_count_from,
             weekly_count_from,
             total_count_from,
         };
 
-        let (five_hour_cost, weekly_cost, total_cost) =
-            aggregate_usage_costs(&conn, id, &ws).await?;
-
-        Ok(Some((
-            key.limits,
-            TokenUsage {
-                five_hour_tokens: if five_hour_expired { 0 } else { five_hour_cost },
-                five_hour_reset_at,
-                weekly_tokens: if weekly_expired { 0 } else { weekly_cost },
-                weekly_reset_at,
-                total_tokens: total_cost,
-            },
-        )))
+        let costs = aggregate_usage_costs(&conn, id, &ws).await?;
+
+        // Show 0 if windows have expired (read-only view)
+        let usage = usage_view(
+            &key.limits,
+            key.usage.five_hour_reset_at,
+            key.usage.weekly_reset_at,
+            costs,
+            now,
+        );
+        Ok(Some((key.limits, usage)))
+    }
+
+    /// Fill in current costs and utilization for every key in `keys` with one
+    /// aggregation over request_log. Same read-only view as
+    /// [`Self::get_usage`]: expired windows show 0.
+    pub async fn fill_usage(&self, keys: &mut [ClientKey]) -> Result<(), ProxyError> {
+        let now = timestamp_millis();
+        let conn = db::get_conn().await?;
+        let rows = sqlx::query!(
+            "SELECT c.id, \
+                 COALESCE(SUM(CASE WHEN r.created_at >= c.five_hour_count_from THEN r.cost_microdollars ELSE 0 END), 0)::BIGINT AS \"five_hour!\", \
+                 COALESCE(SUM(CASE WHEN r.created_at >= c.weekly_count_from THEN r.cost_microdollars ELSE 0 END), 0)::BIGINT AS \"weekly!\", \
+                 COALESCE(SUM(CASE WHEN r.created_at >= c.total_count_from THEN r.cost_microdollars ELSE 0 END), 0)::BIGINT AS \"total!\" \
+                 FROM client_keys c \
+                 LEFT JOIN request_log r ON r.key_id = c.id \
+                     AND r.created_at >= LEAST(c.five_hour_count_from, c.weekly_count_from, c.total_count_from) \
+                 GROUP BY c.id"
+        )
+        .fetch_all(&conn)
+        .await
+        .db_context("Failed to aggregate key usage")?;
+        let costs: HashMap<String, (u64, u64, u64)> = rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.id,
+                    (
+                        i64_to_u64(row.five_hour),
+                        i64_to_u64(row.weekly),
+                        i64_to_u64(row.total),
+                    ),
+                )
+            })
+            .collect();
+
+        for key in keys {
+            key.usage = usage_view(
+                &key.limits,
+                key.usage.five_hour_reset_at,
+                key.usage.weekly_reset_at,
+                costs.get(&key.id).copied().unwrap_or_default(),
+                now,
+            );
+        }
+        Ok(())
+    }
+
+    /// Overwrite a key's window boundaries (admin override). Returns `false`
+    /// if the key does not exist. Validate with [`WindowOverrides::validate`]
+    /// first.
+    pub async fn set_windows(
+        &self,
+        id: &str,
+        windows: &WindowOverrides,
+    ) -> Result<bool, ProxyError> {
+        Ok(update_windows(Some(id), windows).await? > 0)
     }
 
     /// Reset usage for a key by advancing count_from timestamps.
@@ -272,51 +620,15 @@ impl ClientKeysStore {
         id: &str,
         reset_type: UsageResetType,
     ) -> Result<bool, ProxyError> {
-        let now = timestamp_millis();
-        let conn = db::get_conn().await?;
-
-        let affected = match reset_type {
-            UsageResetType::FiveHour => sqlx::query!(
-                "UPDATE client_keys SET five_hour_count_from = $1, five_hour_reset_at = 0 WHERE id = $2",
-                now as i64,
-                id,
-            )
-            .execute(&conn)
-            .await
-            .db_context("Failed to reset usage")?
-            .rows_affected(),
-            UsageResetType::Weekly => sqlx::query!(
-                "UPDATE client_keys SET weekly_count_from = $1, weekly_reset_at = 0 WHERE id = $2",
-                now as i64,
-                id,
-            )
-            .execute(&conn)
-            .await
-            .db_context("Failed to reset usage")?
-            .rows_affected(),
-            UsageResetType::Total => sqlx::query!(
-                "UPDATE client_keys SET total_count_from = $1 WHERE id = $2",
-                now as i64,
-                id,
-            )
-            .execute(&conn)
-            .await
-            .db_context("Failed to reset usage")?
-            .rows_affected(),
-            UsageResetType::All => sqlx::query!(
-                "UPDATE client_keys SET five_hour_count_from = $1, weekly_count_from = $2, total_count_from = $3, five_hour_reset_at = 0, weekly_reset_at = 0 WHERE id = $4",
-                now as i64,
-                now as i64,
-                now as i64,
-                id,
-            )
-                .execute(&conn)
-                .await
-                .db_context("Failed to reset usage")?
-                .rows_affected(),
-        };
+        let windows = reset_type.overrides(timestamp_millis());
+        Ok(update_windows(Some(id), &windows).await? > 0)
+    }
 
-        Ok(affected > 0)
+    /// [`Self::reset_usage`] for every key at once, in one statement so no
+    /// key is left behind if it fails. Returns how many keys were reset.
+    pub async fn reset_all_usage(&self, reset_type: UsageResetType) -> Result<u64, ProxyError> {
+        let windows = reset_type.overrides(timestamp_millis());
+        update_windows(None, &windows).await
     }
 
     // ========================================================================
@@ -631,6 +943,110 @@ impl ClientKeysStore {
         Ok(entries)
     }
 
+    /// Binding limit and spend per window for each of `models`, combining the
+    /// key's own limits with its per-model limits. Read-only like
+    /// [`Self::get_usage`]: expired windows count as already reset. `None` if
+    /// the key does not exist.
+    pub async fn get_effective_limits(
+        &self,
+        key_id: &str,
+        models: &[String],
+    ) -> Result<Option<Vec<EffectiveModelLimits>>, ProxyError> {
+        let Some((key_limits, key_usage)) = self.get_usage(key_id).await? else {
+            return Ok(None);
+        };
+        let now = timestamp_millis();
+        let conn = db::get_conn().await?;
+
+        let ts_row = sqlx::query!(
+            "SELECT five_hour_reset_at, weekly_reset_at, five_hour_count_from, weekly_count_from, total_count_from FROM client_keys WHERE id = $1",
+            key_id,
+        )
+        .fetch_optional(&conn)
+        .await
+        .db_context("Failed to read window state")?;
+        let Some(ts_row) = ts_row else {
+            return Ok(None);
+        };
+        let five_hour_reset_at = i64_to_u64(ts_row.five_hour_reset_at);
+        let weekly_reset_at = i64_to_u64(ts_row.weekly_reset_at);
+        let five_hour_from = if five_hour_reset_at > 0 && now >= five_hour_reset_at {
+            now
+        } else {
+            i64_to_u64(ts_row.five_hour_count_from)
+        };
+        let weekly_from = if weekly_reset_at > 0 && now >= weekly_reset_at {
+            now
+        } else {
+            i64_to_u64(ts_row.weekly_count_from)
+        };
+        let total_from = i64_to_u64(ts_row.total_count_from);
+
+        let limit_rows = sqlx::query!(
+            "SELECT model, five_hour_limit, weekly_limit, total_limit, count_from FROM key_model_limits WHERE key_id = $1",
+            key_id,
+        )
+        .fetch_all(&conn)
+        .await
+        .db_context("Failed to read model limits")?;
+        let model_limits: HashMap<String, (TokenLimits, u64)> = limit_rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.model,
+                    (
+                        TokenLimits {
+                            five_hour_limit: opt_i64_to_u64(row.five_hour_limit),
+                            weekly_limit: opt_i64_to_u64(row.weekly_limit),
+                            total_limit: opt_i64_to_u64(row.total_limit),
+                        },
+                        i64_to_u64(row.count_from),
+                    ),
+                )
+            })
+            .collect();
+
+        let mut entries = Vec::with_capacity(models.len());
+        for model in models {
+            let (limits, count_from) = model_limits.get(model).cloned().unwrap_or_default();
+            let five_hour = model_window(
+                &conn,
+                key_id,
+                model,
+                limits.five_hour_limit,
+                five_hour_from.max(count_from),
+            )
+            .await?;
+            let weekly = model_window(
+                &conn,
+                key_id,
+                model,
+                limits.weekly_limit,
+                weekly_from.max(count_from),
+            )
+            .await?;
+            let total = model_window(
+                &conn,
+                key_id,
+                model,
+                limits.total_limit,
+                total_from.max(count_from),
+            )
+            .await?;
+
+            entries.push(EffectiveModelLimits {
+                model: model.clone(),
+                five_hour: binding_window(
+                    (key_limits.five_hour_limit, key_usage.five_hour_tokens),
+                    five_hour,
+                ),
+                weekly: binding_window((key_limits.weekly_limit, key_usage.weekly_tokens), weekly),
+                total: binding_window((key_limits.total_limit, key_usage.total_tokens), total),
+            });
+        }
+        Ok(Some(entries))
+    }
+
     /// Set per-model limits for a key (UPSERT into key_model_limits)
     pub async fn set_model_limits(
         &self,
@@ -644,22 +1060,25 @@ impl ClientKeysStore {
         let w = limits.weekly_limit.map(|v| v as i64);
         let t = limits.total_limit.map(|v| v as i64);
 
-        sqlx::query!(
-            "INSERT INTO key_model_limits (key_id, model, five_hour_limit, weekly_limit, total_limit) \
-             VALUES ($1, $2, $3, $4, $5) \
-             ON CONFLICT (key_id, model) DO UPDATE SET \
-                 five_hour_limit = EXCLUDED.five_hour_limit, \
-                 weekly_limit = EXCLUDED.weekly_limit, \
-                 total_limit = EXCLUDED.total_limit",
-            key_id,
-            model,
-            h,
-            w,
-            t,
-        )
-        .execute(&conn)
-        .await
-        .db_context("Failed to upsert model limits")?;
+        db::retry_on_lock(|| async {
+            sqlx::query!(
+                "INSERT INTO key_model_limits (key_id, model, five_hour_limit, weekly_limit, total_limit) \
+                 VALUES ($1, $2, $3, $4, $5) \
+                 ON CONFLICT (key_id, model) DO UPDATE SET \
+                     five_hour_limit = EXCLUDED.five_hour_limit, \
+                     weekly_limit = EXCLUDED.weekly_limit, \
+                     total_limit = EXCLUDED.total_limit",
+                key_id,
+                model,
+                h,
+                w,
+                t,
+            )
+            .execute(&conn)
+            .await
+            .db_context("Failed to upsert model limits")
+        })
+        .await?;
         Ok(())
     }
 
@@ -702,4 +1121,349 @@ impl ClientKeysStore {
         .rows_affected();
         Ok(affected > 0)
     }
+
+    /// Prompt-cache hit ratio and estimated savings for a key over requests
+    /// logged since `from` (epoch ms), priced at current model prices.
+    pub async fn get_cache_stats(&self, key_id: &str, from: u64) -> Result<CacheStats, ProxyError> {
+        let conn = db::get_conn().await?;
+        let rows = sqlx::query!(
+            "SELECT r.model, \
+                 COALESCE(SUM(r.input_tokens), 0)::BIGINT AS \"input!\", \
+                 COALESCE(SUM(r.cache_read_tokens), 0)::BIGINT AS \"cache_read!\", \
+                 COALESCE(SUM(r.cache_write_tokens), 0)::BIGINT AS \"cache_write!\", \
+                 m.input_price AS \"input_price?\", \
+                 m.cache_read_price AS \"cache_read_price?\", \
+                 m.cache_write_price AS \"cache_write_price?\" \
+                 FROM request_log r LEFT JOIN models m ON m.id = r.model \
+                 WHERE r.key_id = $1 AND r.created_at >= $2 \
+                 GROUP BY r.model, m.input_price, m.cache_read_price, m.cache_write_price",
+            key_id,
+            from as i64,
+        )
+        .fetch_all(&conn)
+        .await
+        .db_context("Failed to aggregate cache usage")?;
+
+        let models: Vec<ModelCacheUsage> = rows
+            .into_iter()
+            .map(|row| ModelCacheUsage {
+                input_tokens: i64_to_u64(row.input),
+                cache_read_tokens: i64_to_u64(row.cache_read),
+                cache_write_tokens: i64_to_u64(row.cache_write),
+                input_price: row.input_price,
+                cache_read_price: row.cache_read_price,
+                cache_write_price: row.cache_write_price,
+            })
+            .collect();
+        Ok(summarize_cache_usage(&models))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{SeedRequest, seed_request, with_db};
+
+    #[test]
+    fn test_request_duration_ms() {
+        assert_eq!(request_duration_ms(1_000, 3_500), 2_500);
+        assert_eq!(request_duration_ms(5_000, 5_000), 0);
+        // Clock stepped backwards mid-request
+        assert_eq!(request_duration_ms(5_000, 4_000), 0);
+    }
+
+    const NOW: u64 = 1_760_000_000_000;
+
+    #[test]
+    fn test_window_overrides_validation() {
+        let future = WindowOverrides {
+            five_hour_reset_at: Some(NOW + 1),
+            weekly_reset_at: Some(0),
+            total_count_from: Some(NOW),
+            ..WindowOverrides::default()
+        };
+        assert_eq!(future.validate(NOW), Ok(()));
+
+        let past_reset = WindowOverrides {
+            weekly_reset_at: Some(NOW - 1),
+            ..WindowOverrides::default()
+        };
+        assert!(
+            past_reset
+                .validate(NOW)
+                .is_err_and(|e| e.contains("weeklyResetAt"))
+        );
+
+        let future_count = WindowOverrides {
+            five_hour_count_from: Some(NOW + 1),
+            ..WindowOverrides::default()
+        };
+        assert!(
+            future_count
+                .validate(NOW)
+                .is_err_and(|e| e.contains("fiveHourCountFrom"))
+        );
+        assert!(
+            WindowOverrides::default()
+                .validate(NOW)
+                .is_err_and(|e| e.contains("No window values"))
+        );
+
+        let one_hour = WindowOverrides {
+            five_hour_window_ms: Some(60 * 60 * 1000),
+            ..WindowOverrides::default()
+        };
+        assert_eq!(one_hour.validate(NOW), Ok(()));
+        let too_short = WindowOverrides {
+            weekly_window_ms: Some(1_000),
+            ..WindowOverrides::default()
+        };
+        assert!(
+            too_short
+                .validate(NOW)
+                .is_err_and(|e| e.contains("weeklyWindowMs"))
+        );
+        let three_days = WindowOverrides {
+            weekly_window_ms: Some(3 * 24 * 60 * 60 * 1000),
+            ..WindowOverrides::default()
+        };
+        assert_eq!(three_days.validate(NOW), Ok(()));
+        let too_long = WindowOverrides {
+            weekly_window_ms: Some(MAX_WINDOW_MS + 1),
+            ..WindowOverrides::default()
+        };
+        assert!(
+            too_long
+                .validate(NOW)
+                .is_err_and(|e| e.contains("weeklyWindowMs"))
+        );
+    }
+
+    #[test]
+    fn test_set_windows_reflected_in_usage() {
+        let limits = TokenLimits {
+            five_hour_limit: Some(2_000_000),
+            ..TokenLimits::default()
+        };
+        let five_hour_reset_at = NOW + 3_600_000;
+        let weekly_reset_at = NOW + 86_400_000;
+        let usage = usage_view(
+            &limits,
+            five_hour_reset_at,
+            weekly_reset_at,
+            (500_000, 700_000, 900_000),
+            NOW,
+        );
+        assert_eq!(usage.five_hour_reset_at, five_hour_reset_at);
+        assert_eq!(usage.weekly_reset_at, weekly_reset_at);
+        assert_eq!(usage.five_hour_tokens, 500_000);
+        assert_eq!(usage.utilization_pct.five_hour, Some(25.0));
+
+        // Cleared (0) and expired windows
+        let usage = usage_view(&limits, 0, NOW, (500_000, 700_000, 900_000), NOW);
+        assert_eq!(usage.five_hour_reset_at, 0);
+        assert_eq!(usage.five_hour_tokens, 500_000);
+        assert_eq!(usage.weekly_reset_at, 0);
+        assert_eq!(usage.weekly_tokens, 0);
+        assert_eq!(usage.total_tokens, 900_000);
+    }
+
+    #[test]
+    fn test_reset_overrides_cover_requested_windows() {
+        let total = UsageResetType::Total.overrides(NOW);
+        assert_eq!(
+            total,
+            WindowOverrides {
+                total_count_from: Some(NOW),
+                ..WindowOverrides::default()
+            }
+        );
+        let five_hour = UsageResetType::FiveHour.overrides(NOW);
+        assert_eq!(five_hour.five_hour_count_from, Some(NOW));
+        assert_eq!(five_hour.five_hour_reset_at, Some(0));
+        assert_eq!(five_hour.weekly_count_from, None);
+        // Whatever was reset is still a valid admin override
+        for reset_type in [
+            UsageResetType::FiveHour,
+            UsageResetType::Weekly,
+            UsageResetType::Total,
+            UsageResetType::All,
+        ] {
+            assert_eq!(reset_type.overrides(NOW).validate(NOW), Ok(()));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_reset_all_zeroes_every_key_window() {
+        with_db(async {
+            let keys = ClientKeysStore::new();
+            let now = timestamp_millis();
+            // Spend logged by each key before the reset, as (age ms, cost)
+            let spend: [&[(u64, i64)]; 3] = [
+                &[(60_000, 400_000), (1_000, 100_000)],
+                &[(3 * 86_400_000, 2_000_000)],
+                &[(30 * 86_400_000, 700_000), (10_000, 50_000)],
+            ];
+            let mut ids = Vec::new();
+            for (i, log) in spend.into_iter().enumerate() {
+                let key = keys.create(format!("reset-all-{i}")).await.unwrap();
+                for &(age, cost) in log {
+                    seed_request(SeedRequest {
+                        key_id: &key.id,
+                        model: "claude-sonnet-4-5",
+                        cost_microdollars: cost,
+                        created_at: now - age,
+                        ..SeedRequest::default()
+                    })
+                    .await;
+                }
+                let (_, usage) = keys.get_usage(&key.id).await.unwrap().unwrap();
+                assert!(usage.total_tokens > 0);
+                ids.push(key.id);
+            }
+
+            let reset = keys.reset_all_usage(UsageResetType::All).await.unwrap();
+            assert!(reset >= 3, "{reset}");
+            for id in &ids {
+                let (_, usage) = keys.get_usage(id).await.unwrap().unwrap();
+                assert_eq!(usage.five_hour_tokens, 0, "{id}");
+                assert_eq!(usage.weekly_tokens, 0, "{id}");
+                assert_eq!(usage.total_tokens, 0, "{id}");
+            }
+        });
+    }
+
+    #[test]
+    fn test_binding_window_picks_least_headroom() {
+        // Key: $10 limit, $9 used ($1 left); model: $5 limit, $1 used ($4 left)
+        let window = binding_window((Some(10_000_000), 9_000_000), (Some(5_000_000), 1_000_000));
+        assert_eq!(window.source, Some(LimitSource::Key));
+        assert_eq!(window.remaining, Some(1_000_000));
+
+        let window = binding_window((Some(10_000_000), 0), (Some(5_000_000), 4_500_000));
+        assert_eq!(window.source, Some(LimitSource::Model));
+        assert_eq!(window.limit, Some(5_000_000));
+        assert_eq!(window.used, 4_500_000);
+        assert_eq!(window.remaining, Some(500_000));
+    }
+
+    #[test]
+    fn test_binding_window_single_level_and_unlimited() {
+        let window = binding_window((None, 700), (Some(1_000), 1_200));
+        assert_eq!(window.source, Some(LimitSource::Model));
+        assert_eq!(window.remaining, Some(0));
+
+        let window = binding_window((None, 700), (None, 0));
+        assert_eq!(
+            window,
+            EffectiveWindow {
+                used: 700,
+                ..EffectiveWindow::default()
+            }
+        );
+    }
+
+    fn sonnet(input: u64, cache_read: u64, cache_write: u64) -> ModelCacheUsage {
+        ModelCacheUsage {
+            input_tokens: input,
+            cache_read_tokens: cache_read,
+            cache_write_tokens: cache_write,
+            input_price: Some(3.0),
+            cache_read_price: Some(0.30),
+            cache_write_price: Some(3.75),
+        }
+    }
+
+    #[test]
+    fn test_cache_stats_empty() {
+        let stats = summarize_cache_usage(&[]);
+        assert_eq!(stats, CacheStats::default());
+    }
+
+    #[test]
+    fn test_cache_read_ratio_and_savings() {
+        // 1M cache-read tokens at $3.00 input vs $0.30 read saves $2.70;
+        // no writes, so no premium to subtract.
+        let stats = summarize_cache_usage(&[sonnet(1_000_000, 1_000_000, 0)]);
+        assert_eq!(stats.input_tokens, 1_000_000);
+        assert_eq!(stats.cache_read_tokens, 1_000_000);
+        assert!((stats.cache_read_ratio - 0.5).abs() < 1e-9);
+        assert_eq!(stats.estimated_savings_microdollars, 2_700_000);
+    }
+
+    #[test]
+    fn test_cache_write_premium_is_subtracted() {
+        // Writing 100k tokens costs $0.075 more than plain input; reading
+        // 100k saves $0.27.
+        let stats = summarize_cache_usage(&[sonnet(0, 100_000, 100_000)]);
+        assert!((stats.cache_read_ratio - 1.0).abs() < 1e-9);
+        assert_eq!(stats.estimated_savings_microdollars, 270_000 - 75_000);
+    }
+
+    #[test]
+    fn test_unpriced_model_counts_for_ratio_only() {
+        let unpriced = ModelCacheUsage {
+            input_tokens: 500_000,
+            cache_read_tokens: 500_000,
+            ..ModelCacheUsage::default()
+        };
+        let stats = summarize_cache_usage(&[sonnet(500_000, 0, 0), unpriced]);
+        assert_eq!(stats.input_tokens, 1_000_000);
+        assert!((stats.cache_read_ratio - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.estimated_savings_microdollars, 0);
+    }
+
+    fn row<'a>(
+        key_id: &'a str,
+        model: &'a str,
+        input: i64,
+        cache_read: i64,
+        cache_write: i64,
+        created_at: u64,
+    ) -> SeedRequest<'a> {
+        SeedRequest {
+            key_id,
+            model,
+            input,
+            cache_read,
+            cache_write,
+            created_at,
+            ..SeedRequest::default()
+        }
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_cache_stats_from_request_log() {
+        with_db(async {
+            let keys = ClientKeysStore::new();
+            let key = keys.create("cache-stats".into()).await.unwrap();
+            let other = keys.create("cache-stats-other".into()).await.unwrap();
+            let now = timestamp_millis();
+            let from = now - 86_400_000;
+            for seed in [
+                row(&key.id, "claude-sonnet-4-5", 200_000, 600_000, 40_000, now),
+                row(&key.id, "claude-sonnet-4-5", 100_000, 200_000, 0, now),
+                // Not in the models table: ratio only
+                row(&key.id, "cache-stats-unpriced", 100_000, 100_000, 0, now),
+                // Before `from`, and another key's request
+                row(&key.id, "claude-sonnet-4-5", 0, 9_000_000, 0, from - 1),
+                row(&other.id, "claude-sonnet-4-5", 0, 1_000_000, 0, now),
+            ] {
+                seed_request(seed).await;
+            }
+
+            let stats = keys.get_cache_stats(&key.id, from).await.unwrap();
+            assert_eq!(stats.input_tokens, 400_000);
+            assert_eq!(stats.cache_read_tokens, 900_000);
+            assert_eq!(stats.cache_write_tokens, 40_000);
+            assert!((stats.cache_read_ratio - 9.0 / 13.0).abs() < 1e-9);
+            // Sonnet reads save 800k * ($3.00 - $0.30); writes cost 40k * $0.75 extra
+            assert_eq!(stats.estimated_savings_microdollars, 2_160_000 - 30_000);
+
+            let stats = keys.get_cache_stats(&key.id, now + 1).await.unwrap();
+            assert_eq!(stats, CacheStats::default());
+        });
+    }
 }
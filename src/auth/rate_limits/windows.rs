@@ -1,4 +1,4 @@
-use crate::auth::client_keys::i64_to_u64;
+use crate::auth::client_keys::{WindowDurations, i64_to_u64};
 use crate::db::Connection;
 use crate::error::{DbResultExt, ProxyError};
 use crate::usage::SubscriptionState;
@@ -10,6 +10,72 @@ pub(super) struct WindowState {
     pub(super) total_count_from: u64,
 }
 
+/// One rolling window's stored boundaries (epoch ms). `reset_at` 0 means the
+/// window hasn't started yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Window {
+    pub(super) reset_at: u64,
+    pub(super) count_from: u64,
+}
+
+/// The subscription reset time a window of `window_ms` follows: only
+/// default-length windows line up with Claude's own.
+pub(super) fn subscription_reset(
+    window_ms: u64,
+    default_ms: u64,
+    subscription_reset_at: Option<u64>,
+) -> Option<u64> {
+    (window_ms == default_ms)
+        .then_some(subscription_reset_at)
+        .flatten()
+}
+
+/// Roll `window` forward at `now`. An expired window starts counting from
+/// its old reset time and resets again at the subscription's next reset or,
+/// without one, `window_ms` from now. A running window moves its reset
+/// earlier if the subscription resets sooner.
+pub(super) fn advance_window(
+    window: Window,
+    window_ms: u64,
+    subscription_reset_at: Option<u64>,
+    now: u64,
+) -> Window {
+    if window.reset_at == 0 {
+        return window;
+    }
+    let upcoming = subscription_reset_at.filter(|&t| t > now);
+    if now >= window.reset_at {
+        Window {
+            reset_at: upcoming.unwrap_or(now.saturating_add(window_ms)),
+            count_from: window.reset_at,
+        }
+    } else {
+        Window {
+            reset_at: upcoming
+                .filter(|&t| t < window.reset_at)
+                .unwrap_or(window.reset_at),
+            count_from: window.count_from,
+        }
+    }
+}
+
+/// Reset time for a window that hasn't started, as of the first recorded
+/// request: the subscription's next reset, or `window_ms` from now for
+/// custom-length windows. 0 (still unstarted) when a default-length window
+/// has no subscription data yet.
+pub(super) fn initial_reset_at(
+    window_ms: u64,
+    default_ms: u64,
+    subscription_reset_at: Option<u64>,
+    now: u64,
+) -> u64 {
+    if window_ms == default_ms {
+        subscription_reset_at.filter(|&t| t > now).unwrap_or(0)
+    } else {
+        now.saturating_add(window_ms)
+    }
+}
+
 /// Check and update window boundaries. When a window has expired, advances
 /// the count_from timestamp and updates the reset_at from subscription state.
 /// No counter zeroing: request_log queries use count_from as the lower bound.
@@ -19,11 +85,8 @@ pub(super) async fn maybe_reset_expired_windows(
     now: u64,
     window_resets: &SubscriptionState,
 ) -> Result<WindowState, ProxyError> {
-    let five_hour_ms: u64 = 5 * 60 * 60 * 1000;
-    let one_week_ms: u64 = 7 * 24 * 60 * 60 * 1000;
-
     let row = sqlx::query!(
-        "SELECT five_hour_reset_at, weekly_reset_at, five_hour_count_from, weekly_count_from, total_count_from FROM client_keys WHERE id = $1",
+        "SELECT five_hour_reset_at, weekly_reset_at, five_hour_count_from, weekly_count_from, total_count_from, five_hour_window_ms, weekly_window_ms FROM client_keys WHERE id = $1",
         key_id,
     )
     .fetch_optional(conn)
@@ -38,74 +101,195 @@ pub(super) async fn maybe_reset_expired_windows(
         });
     };
 
-    let mut five_hour_reset_at = i64_to_u64(row.five_hour_reset_at);
-    let mut weekly_reset_at = i64_to_u64(row.weekly_reset_at);
-    let mut five_hour_count_from = i64_to_u64(row.five_hour_count_from);
-    let mut weekly_count_from = i64_to_u64(row.weekly_count_from);
+    let five_hour_ms = i64_to_u64(row.five_hour_window_ms);
+    let weekly_ms = i64_to_u64(row.weekly_window_ms);
+    let five_hour = Window {
+        reset_at: i64_to_u64(row.five_hour_reset_at),
+        count_from: i64_to_u64(row.five_hour_count_from),
+    };
+    let weekly = Window {
+        reset_at: i64_to_u64(row.weekly_reset_at),
+        count_from: i64_to_u64(row.weekly_count_from),
+    };
     let total_count_from = i64_to_u64(row.total_count_from);
 
-    let reset_five_hour = five_hour_reset_at > 0 && now >= five_hour_reset_at;
-    let reset_weekly = weekly_reset_at > 0 && now >= weekly_reset_at;
-
-    if !reset_five_hour && !reset_weekly {
-        let new_five_hour = window_resets
-            .five_hour_reset_at
-            .filter(|&t| t > now && t < five_hour_reset_at)
-            .unwrap_or(five_hour_reset_at);
-        let new_weekly = window_resets
-            .seven_day_reset_at
-            .filter(|&t| t > now && t < weekly_reset_at)
-            .unwrap_or(weekly_reset_at);
-
-        if new_five_hour != five_hour_reset_at || new_weekly != weekly_reset_at {
-            sqlx::query!(
-                "UPDATE client_keys SET five_hour_reset_at = $1, weekly_reset_at = $2 WHERE id = $3",
-                new_five_hour as i64,
-                new_weekly as i64,
-                key_id,
-            )
-            .execute(conn)
-            .await
-            .db_context("Failed to update window state")?;
-        }
+    let new_five_hour = advance_window(
+        five_hour,
+        five_hour_ms,
+        subscription_reset(
+            five_hour_ms,
+            WindowDurations::DEFAULT_FIVE_HOUR_MS,
+            window_resets.five_hour_reset_at,
+        ),
+        now,
+    );
+    let new_weekly = advance_window(
+        weekly,
+        weekly_ms,
+        subscription_reset(
+            weekly_ms,
+            WindowDurations::DEFAULT_WEEKLY_MS,
+            window_resets.seven_day_reset_at,
+        ),
+        now,
+    );
 
-        return Ok(WindowState {
-            five_hour_count_from,
-            weekly_count_from,
-            total_count_from,
-        });
+    if new_five_hour != five_hour || new_weekly != weekly {
+        sqlx::query!(
+            "UPDATE client_keys SET five_hour_reset_at = $1, weekly_reset_at = $2, five_hour_count_from = $3, weekly_count_from = $4 WHERE id = $5",
+            new_five_hour.reset_at as i64,
+            new_weekly.reset_at as i64,
+            new_five_hour.count_from as i64,
+            new_weekly.count_from as i64,
+            key_id,
+        )
+        .execute(conn)
+        .await
+        .db_context("Failed to update window state")?;
+    }
+
+    Ok(WindowState {
+        five_hour_count_from: new_five_hour.count_from,
+        weekly_count_from: new_weekly.count_from,
+        total_count_from,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_760_000_000_000;
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+
+    #[test]
+    fn test_custom_one_hour_window_resets() {
+        let started = Window {
+            reset_at: NOW + HOUR_MS,
+            count_from: NOW,
+        };
+        // A 1-hour window ignores the subscription's 5-hour reset
+        let sub = subscription_reset(
+            HOUR_MS,
+            WindowDurations::DEFAULT_FIVE_HOUR_MS,
+            Some(NOW + 30 * 60 * 1000),
+        );
+        assert_eq!(sub, None);
+
+        // Still running half an hour in
+        assert_eq!(
+            advance_window(started, HOUR_MS, sub, NOW + HOUR_MS / 2),
+            started
+        );
+
+        // Past the reset: counting restarts there, next reset an hour later
+        let later = NOW + HOUR_MS + 5_000;
+        assert_eq!(
+            advance_window(started, HOUR_MS, sub, later),
+            Window {
+                reset_at: later + HOUR_MS,
+                count_from: NOW + HOUR_MS,
+            }
+        );
     }
 
-    if reset_five_hour {
-        five_hour_count_from = five_hour_reset_at;
-        five_hour_reset_at = window_resets
-            .five_hour_reset_at
-            .filter(|&t| t > now)
-            .unwrap_or(now + five_hour_ms);
+    #[test]
+    fn test_custom_three_day_weekly_window_rolls_over() {
+        let three_days = 72 * HOUR_MS;
+        // The subscription's 7-day reset doesn't apply to a 3-day window
+        let sub = subscription_reset(
+            three_days,
+            WindowDurations::DEFAULT_WEEKLY_MS,
+            Some(NOW + 5 * 24 * HOUR_MS),
+        );
+        assert_eq!(sub, None);
+
+        let first = Window {
+            reset_at: initial_reset_at(three_days, WindowDurations::DEFAULT_WEEKLY_MS, sub, NOW),
+            count_from: 0,
+        };
+        assert_eq!(first.reset_at, NOW + three_days);
+
+        // Day 4: rolled over once, counting from the first reset
+        let day_four = NOW + 96 * HOUR_MS;
+        let second = advance_window(first, three_days, sub, day_four);
+        assert_eq!(
+            second,
+            Window {
+                reset_at: day_four + three_days,
+                count_from: NOW + three_days,
+            }
+        );
+
+        // Nothing changes until the next reset, then it rolls again
+        assert_eq!(
+            advance_window(second, three_days, sub, day_four + HOUR_MS),
+            second
+        );
+        let day_eight = day_four + three_days + 1;
+        assert_eq!(
+            advance_window(second, three_days, sub, day_eight),
+            Window {
+                reset_at: day_eight + three_days,
+                count_from: day_four + three_days,
+            }
+        );
     }
-    if reset_weekly {
-        weekly_count_from = weekly_reset_at;
-        weekly_reset_at = window_resets
-            .seven_day_reset_at
-            .filter(|&t| t > now)
-            .unwrap_or(now + one_week_ms);
+
+    #[test]
+    fn test_default_window_follows_subscription() {
+        let five_hours = WindowDurations::DEFAULT_FIVE_HOUR_MS;
+        let sub_reset = NOW + 2 * HOUR_MS;
+        let sub = subscription_reset(five_hours, five_hours, Some(sub_reset));
+        assert_eq!(sub, Some(sub_reset));
+
+        // Pulled earlier to the subscription's reset
+        let running = Window {
+            reset_at: NOW + 4 * HOUR_MS,
+            count_from: NOW - HOUR_MS,
+        };
+        assert_eq!(
+            advance_window(running, five_hours, sub, NOW).reset_at,
+            sub_reset
+        );
+
+        // Expired: next reset is the subscription's
+        let expired = Window {
+            reset_at: NOW - 1,
+            count_from: NOW - five_hours,
+        };
+        assert_eq!(
+            advance_window(expired, five_hours, sub, NOW),
+            Window {
+                reset_at: sub_reset,
+                count_from: NOW - 1,
+            }
+        );
     }
 
-    sqlx::query!(
-        "UPDATE client_keys SET five_hour_reset_at = $1, weekly_reset_at = $2, five_hour_count_from = $3, weekly_count_from = $4 WHERE id = $5",
-        five_hour_reset_at as i64,
-        weekly_reset_at as i64,
-        five_hour_count_from as i64,
-        weekly_count_from as i64,
-        key_id,
-    )
-    .execute(conn)
-    .await
-    .db_context("Failed to update window state")?;
+    #[test]
+    fn test_unstarted_window_is_left_alone() {
+        let unstarted = Window {
+            reset_at: 0,
+            count_from: 0,
+        };
+        assert_eq!(
+            advance_window(unstarted, HOUR_MS, Some(NOW + 1), NOW),
+            unstarted
+        );
+    }
 
-    Ok(WindowState {
-        five_hour_count_from,
-        weekly_count_from,
-        total_count_from,
-    })
+    #[test]
+    fn test_initial_reset_at() {
+        let five_hours = WindowDurations::DEFAULT_FIVE_HOUR_MS;
+        assert_eq!(
+            initial_reset_at(HOUR_MS, five_hours, Some(NOW + 10), NOW),
+            NOW + HOUR_MS
+        );
+        assert_eq!(
+            initial_reset_at(five_hours, five_hours, Some(NOW + 10), NOW),
+            NOW + 10
+        );
+        assert_eq!(initial_reset_at(five_hours, five_hours, None, NOW), 0);
+    }
 }
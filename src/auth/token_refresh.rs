@@ -0,0 +1,295 @@
+//! Background OAuth token refresh.
+//!
+//! Refreshes the stored access token a configurable lead time before it
+//! expires, so requests rarely pay for a refresh (or see one fail). The
+//! request path still refreshes on its own as a fallback. A rejected
+//! refresh token is not retried: the credentials are dropped and refreshing
+//! stops until OAuth is reconnected.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use super::oauth::{OAuthManager, RefreshError, now_millis};
+
+/// How often to look again when there is nothing to refresh yet. Also caps
+/// the sleep before a refresh, so replaced credentials are picked up.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Wait after the first failed refresh; doubles per failure in a row
+const BASE_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+
+/// The stored OAuth token. [`OAuthManager`] in production.
+pub trait TokenSource: Send + Sync + 'static {
+    /// Expiry (epoch ms) of the access token; `None` without OAuth credentials.
+    fn expires_at(&self) -> impl Future<Output = Option<u64>> + Send;
+    /// Refresh the token if it expires within `lead_ms`.
+    fn refresh_within(
+        &self,
+        lead_ms: u64,
+    ) -> impl Future<Output = Result<Option<String>, RefreshError>> + Send;
+    /// Drop the credentials whose token expires at `expires` after their
+    /// refresh token was rejected. Newer credentials are kept.
+    fn forget(&self, expires: u64) -> impl Future<Output = ()> + Send;
+}
+
+impl TokenSource for OAuthManager {
+    async fn expires_at(&self) -> Option<u64> {
+        self.oauth_expires_at().await
+    }
+
+    async fn refresh_within(&self, lead_ms: u64) -> Result<Option<String>, RefreshError> {
+        OAuthManager::refresh_within(self, lead_ms).await
+    }
+
+    async fn forget(&self, expires: u64) {
+        if let Err(e) = self.forget_revoked(expires).await {
+            warn!("Failed to clear revoked OAuth credentials: {e}");
+        }
+    }
+}
+
+/// Where the refresh loop stands between ticks.
+#[derive(Debug, Default)]
+struct RefreshState {
+    /// Failed refreshes in a row
+    failures: u32,
+    /// Expiry of the credentials whose refresh token was rejected; they are
+    /// not refreshed again
+    revoked: Option<u64>,
+}
+
+/// Keep `source`'s token refreshed `lead` ahead of expiry, forever.
+pub fn spawn<S: TokenSource>(source: Arc<S>, lead: Duration) {
+    tokio::spawn(async move {
+        let mut state = RefreshState::default();
+        loop {
+            let delay = tick(source.as_ref(), lead, &mut state).await;
+            tokio::time::sleep(delay).await;
+        }
+    });
+}
+
+/// Refresh the token if it's due and return how long to sleep. Failures
+/// are logged and retried with exponential backoff, except a rejected
+/// refresh token, which stops refreshing until the credentials change.
+async fn tick<S: TokenSource>(source: &S, lead: Duration, state: &mut RefreshState) -> Duration {
+    let Some(expires) = source.expires_at().await else {
+        state.failures = 0;
+        return POLL_INTERVAL;
+    };
+    if state.revoked == Some(expires) {
+        return POLL_INTERVAL;
+    }
+    state.revoked = None;
+    let lead_ms = u64::try_from(lead.as_millis()).unwrap_or(u64::MAX);
+    let due = expires.saturating_sub(lead_ms);
+    let now = now_millis();
+    if now < due {
+        return Duration::from_millis(due - now).min(POLL_INTERVAL);
+    }
+
+    match source.refresh_within(lead_ms).await {
+        Ok(_) => {
+            if state.failures > 0 {
+                info!(
+                    "Background OAuth refresh recovered after {} failures",
+                    state.failures
+                );
+            }
+            state.failures = 0;
+            POLL_INTERVAL
+        }
+        Err(e @ RefreshError::Revoked(_)) => {
+            error!("Background OAuth refresh stopped until OAuth is reconnected: {e}");
+            source.forget(expires).await;
+            state.failures = 0;
+            state.revoked = Some(expires);
+            POLL_INTERVAL
+        }
+        Err(e @ RefreshError::Transient(_)) => {
+            state.failures = state.failures.saturating_add(1);
+            let backoff = backoff(state.failures);
+            warn!(
+                "Background OAuth refresh failed ({} in a row), retrying in {}s: {e}",
+                state.failures,
+                backoff.as_secs()
+            );
+            backoff
+        }
+    }
+}
+
+fn backoff(failures: u32) -> Duration {
+    BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::oauth::request_refresh;
+    use crate::test_support::spawn_mock;
+    use axum::{Json, Router, http::StatusCode, routing::post};
+    use serde_json::json;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const LEAD: Duration = Duration::from_secs(600);
+
+    /// In-memory token refreshed against a mock token endpoint
+    struct MockSource {
+        client: reqwest::Client,
+        token_url: String,
+        expires: Mutex<Option<u64>>,
+    }
+
+    impl TokenSource for MockSource {
+        async fn expires_at(&self) -> Option<u64> {
+            *self.expires.lock().unwrap()
+        }
+
+        async fn refresh_within(&self, lead_ms: u64) -> Result<Option<String>, RefreshError> {
+            let expires = self.expires.lock().unwrap().unwrap();
+            if now_millis() + lead_ms < expires {
+                return Ok(None);
+            }
+            let token = request_refresh(&self.client, &self.token_url, "refresh-1").await?;
+            *self.expires.lock().unwrap() = Some(now_millis() + token.expires_in * 1000);
+            Ok(Some(token.access_token))
+        }
+
+        async fn forget(&self, expires: u64) {
+            let mut stored = self.expires.lock().unwrap();
+            if *stored == Some(expires) {
+                *stored = None;
+            }
+        }
+    }
+
+    fn tokens() -> serde_json::Value {
+        json!({
+            "access_token": "access-2",
+            "refresh_token": "refresh-2",
+            "expires_in": 28_800,
+            "token_type": "Bearer"
+        })
+    }
+
+    /// Token endpoint answering `status` and `body`; counts the calls.
+    async fn token_endpoint(
+        status: StatusCode,
+        body: serde_json::Value,
+    ) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/v1/oauth/token",
+            post(move || async move {
+                counter.fetch_add(1, Ordering::Relaxed);
+                (status, Json(body))
+            }),
+        );
+        let addr = spawn_mock(app).await;
+        (format!("http://{addr}/v1/oauth/token"), calls)
+    }
+
+    fn source(token_url: String, expires_in: Duration) -> MockSource {
+        MockSource {
+            client: reqwest::Client::new(),
+            token_url,
+            expires: Mutex::new(Some(
+                now_millis() + u64::try_from(expires_in.as_millis()).unwrap(),
+            )),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refreshes_before_expiry() {
+        let (url, calls) = token_endpoint(StatusCode::OK, tokens()).await;
+        // Still valid for 5 minutes, inside the 10-minute lead
+        let source = source(url, Duration::from_secs(300));
+        let mut state = RefreshState::default();
+
+        let delay = tick(&source, LEAD, &mut state).await;
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(delay, POLL_INTERVAL);
+        let expires = source.expires_at().await.unwrap();
+        assert!(expires > now_millis() + 8 * 3600 * 1000 - 60_000);
+
+        // Fresh token: nothing to do on the next pass
+        tick(&source, LEAD, &mut state).await;
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_waits_until_due() {
+        let (url, calls) = token_endpoint(StatusCode::OK, tokens()).await;
+        let source = source(url, Duration::from_secs(3600));
+        let delay = tick(&source, LEAD, &mut RefreshState::default()).await;
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+        assert_eq!(delay, POLL_INTERVAL);
+    }
+
+    #[tokio::test]
+    async fn test_failures_back_off() {
+        let (url, calls) = token_endpoint(StatusCode::SERVICE_UNAVAILABLE, tokens()).await;
+        let source = source(url, Duration::from_secs(60));
+        let mut state = RefreshState::default();
+
+        assert_eq!(tick(&source, LEAD, &mut state).await, BASE_BACKOFF);
+        assert_eq!(tick(&source, LEAD, &mut state).await, BASE_BACKOFF * 2);
+        assert_eq!(state.failures, 2);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(backoff(20), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_login_stops_refreshing() {
+        let rejected =
+            json!({ "error": "invalid_grant", "error_description": "Refresh token revoked" });
+        let (url, calls) = token_endpoint(StatusCode::BAD_REQUEST, rejected).await;
+        let source = source(url, Duration::from_secs(60));
+        let expires = source.expires_at().await.unwrap();
+        let mut state = RefreshState {
+            failures: 2,
+            ..RefreshState::default()
+        };
+
+        // No backoff: the credentials are dropped and the loop goes idle
+        assert_eq!(tick(&source, LEAD, &mut state).await, POLL_INTERVAL);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(state.failures, 0);
+        assert_eq!(source.expires_at().await, None);
+        tick(&source, LEAD, &mut state).await;
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // The same credentials, had they survived, are not retried either
+        *source.expires.lock().unwrap() = Some(expires);
+        assert_eq!(tick(&source, LEAD, &mut state).await, POLL_INTERVAL);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // Reconnected credentials are refreshed again
+        *source.expires.lock().unwrap() = Some(expires + 1);
+        tick(&source, LEAD, &mut state).await;
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_idle_without_credentials() {
+        let source = MockSource {
+            client: reqwest::Client::new(),
+            token_url: String::new(),
+            expires: Mutex::new(None),
+        };
+        let mut state = RefreshState {
+            failures: 3,
+            ..RefreshState::default()
+        };
+        assert_eq!(tick(&source, LEAD, &mut state).await, POLL_INTERVAL);
+        assert_eq!(state.failures, 0);
+    }
+}
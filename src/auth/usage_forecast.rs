@@ -0,0 +1,215 @@
+//! When a key will hit its window limits at its recent spend rate.
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::client_keys::{ClientKey, TokenUsage, i64_to_u64};
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+
+/// How far back the spend rate is measured.
+pub const BURN_RATE_LOOKBACK_MS: u64 = 60 * 60 * 1000;
+
+/// Cost (microdollars) of `key_id`'s requests since `since` (epoch ms).
+async fn cost_since(key_id: &str, since: u64) -> Result<u64, ProxyError> {
+    let conn = db::get_conn().await?;
+    let cost = sqlx::query_scalar!(
+        "SELECT COALESCE(SUM(cost_microdollars), 0)::BIGINT AS \"cost!\" FROM request_log WHERE key_id = $1 AND created_at >= $2",
+        key_id,
+        since as i64,
+    )
+    .fetch_one(&conn)
+    .await
+    .db_context("Failed to read recent spend")?;
+    Ok(i64_to_u64(cost))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct UsageForecast {
+    /// Spend over the last hour (microdollars), the rate projected forward
+    pub burn_rate_per_hour: u64,
+    /// Epoch ms when the 5-hour limit is reached at that rate; `null` without
+    /// a limit, without recent spend, or if the window resets first
+    pub five_hour_exhausts_at: Option<u64>,
+    /// Same for the weekly limit
+    pub weekly_exhausts_at: Option<u64>,
+}
+
+/// Project `key`'s window usage forward at its spend over the last
+/// [`BURN_RATE_LOOKBACK_MS`].
+pub async fn forecast(
+    key: &ClientKey,
+    usage: &TokenUsage,
+    now: u64,
+) -> Result<UsageForecast, ProxyError> {
+    let burn = cost_since(&key.id, now.saturating_sub(BURN_RATE_LOOKBACK_MS)).await?;
+    Ok(UsageForecast {
+        burn_rate_per_hour: burn,
+        five_hour_exhausts_at: exhausts_at(
+            key.limits.five_hour_limit,
+            usage.five_hour_tokens,
+            window_end(usage.five_hour_reset_at, key.windows.five_hour_ms, now),
+            burn,
+            now,
+        ),
+        weekly_exhausts_at: exhausts_at(
+            key.limits.weekly_limit,
+            usage.weekly_tokens,
+            window_end(usage.weekly_reset_at, key.windows.weekly_ms, now),
+            burn,
+            now,
+        ),
+    })
+}
+
+/// When the current window ends. A window that hasn't started or has
+/// already expired restarts with the next request, so it runs a full
+/// `window_ms` from now.
+fn window_end(reset_at: u64, window_ms: u64, now: u64) -> u64 {
+    if reset_at > now {
+        reset_at
+    } else {
+        now.saturating_add(window_ms)
+    }
+}
+
+/// When spending `burn_per_hour` exhausts `limit` from `used`, if that's
+/// before `window_end`. Already over the limit means now.
+fn exhausts_at(
+    limit: Option<u64>,
+    used: u64,
+    window_end: u64,
+    burn_per_hour: u64,
+    now: u64,
+) -> Option<u64> {
+    let remaining = limit?.checked_sub(used).filter(|&r| r > 0);
+    let Some(remaining) = remaining else {
+        return Some(now);
+    };
+    if burn_per_hour == 0 {
+        return None;
+    }
+    let ms = u128::from(remaining) * u128::from(BURN_RATE_LOOKBACK_MS) / u128::from(burn_per_hour);
+    let at = now.saturating_add(u64::try_from(ms).unwrap_or(u64::MAX));
+    (at < window_end).then_some(at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{ClientKeysStore, TokenLimits};
+    use crate::subscription::timestamp_millis;
+    use crate::test_support::{SeedRequest, seed_request, with_db};
+
+    const NOW: u64 = 1_760_000_000_000;
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+
+    /// A new key with these limits and `(age ms, cost)` requests logged
+    /// before `now`.
+    async fn key_with_spend(
+        five_hour_limit: Option<u64>,
+        weekly_limit: Option<u64>,
+        spend: &[(u64, i64)],
+        now: u64,
+    ) -> ClientKey {
+        let key = ClientKeysStore::new()
+            .create("forecast".into())
+            .await
+            .unwrap();
+        for &(age, cost) in spend {
+            seed_request(SeedRequest {
+                key_id: &key.id,
+                model: "claude-sonnet-4-5",
+                cost_microdollars: cost,
+                created_at: now - age,
+                ..SeedRequest::default()
+            })
+            .await;
+        }
+        ClientKey {
+            limits: TokenLimits {
+                five_hour_limit,
+                weekly_limit,
+                total_limit: None,
+            },
+            ..key
+        }
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_forecast_from_seeded_log() {
+        with_db(async {
+            let now = timestamp_millis();
+            let key = key_with_spend(
+                Some(5_000_000),
+                Some(100_000_000),
+                &[
+                    // Last hour: $1 in total
+                    (50 * 60 * 1000, 600_000),
+                    (10 * 60 * 1000, 400_000),
+                    // Too old to count
+                    (2 * HOUR_MS, 5_000_000),
+                ],
+                now,
+            )
+            .await;
+            // Another key's spend
+            key_with_spend(None, None, &[(60_000, 9_000_000)], now).await;
+            let usage = TokenUsage {
+                five_hour_tokens: 3_000_000,
+                five_hour_reset_at: now + 4 * HOUR_MS,
+                weekly_tokens: 10_000_000,
+                weekly_reset_at: now + 100 * HOUR_MS,
+                ..TokenUsage::default()
+            };
+            // $2 left in the 5-hour window: two hours at $1/h, before the reset.
+            // $90 left for the week: 90 hours, also before its reset.
+            let forecast = forecast(&key, &usage, now).await.unwrap();
+            assert_eq!(
+                forecast,
+                UsageForecast {
+                    burn_rate_per_hour: 1_000_000,
+                    five_hour_exhausts_at: Some(now + 2 * HOUR_MS),
+                    weekly_exhausts_at: Some(now + 90 * HOUR_MS),
+                }
+            );
+        });
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_no_exhaustion_before_reset_or_without_spend() {
+        with_db(async {
+            let now = timestamp_millis();
+            let usage = TokenUsage {
+                five_hour_tokens: 0,
+                five_hour_reset_at: now + HOUR_MS,
+                ..TokenUsage::default()
+            };
+            // $5 at $1/h takes 5 hours, but the window resets in one
+            let key = key_with_spend(Some(5_000_000), None, &[(60_000, 1_000_000)], now).await;
+            let result = forecast(&key, &usage, now).await.unwrap();
+            assert_eq!(result.five_hour_exhausts_at, None);
+            assert_eq!(result.weekly_exhausts_at, None);
+
+            let idle = key_with_spend(Some(5_000_000), None, &[], now).await;
+            let idle = forecast(&idle, &usage, now).await.unwrap();
+            assert_eq!(idle.burn_rate_per_hour, 0);
+            assert_eq!(idle.five_hour_exhausts_at, None);
+        });
+    }
+
+    #[test]
+    fn test_exhausted_or_unstarted_windows() {
+        // Already at the limit
+        assert_eq!(
+            exhausts_at(Some(100), 100, NOW + HOUR_MS, 0, NOW),
+            Some(NOW)
+        );
+        // An unstarted window runs a full length from now
+        assert_eq!(window_end(0, 5 * HOUR_MS, NOW), NOW + 5 * HOUR_MS);
+        assert_eq!(window_end(NOW - 1, HOUR_MS, NOW), NOW + HOUR_MS);
+        assert_eq!(window_end(NOW + 10, HOUR_MS, NOW), NOW + 10);
+    }
+}
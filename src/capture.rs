@@ -207,11 +207,12 @@ fn reqwest_headers_to_json(headers: &ReqwestHeaderMap) -> Value {
     )
 }
 
-fn sanitize_header(name: &str, value: &str) -> String {
+/// Mask credential-bearing headers.
+pub(crate) fn sanitize_header(name: &str, value: &str) -> String {
     let name = name.to_ascii_lowercase();
     if matches!(
         name.as_str(),
-        "authorization" | "x-api-key" | "cookie" | "set-cookie" | "proxy-authorization"
+        "authorization" | "x-api-key" | "api-key" | "cookie" | "set-cookie" | "proxy-authorization"
     ) {
         "<redacted>".to_string()
     } else {
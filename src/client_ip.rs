@@ -0,0 +1,86 @@
+//! Client IP resolution.
+//!
+//! The socket peer address is the only value a client cannot forge. Behind a
+//! reverse proxy the peer is the proxy itself, so the real client has to be
+//! read from `X-Forwarded-For` — but only as many hops as the operator has
+//! declared trusted (`CLAUDE_PROXY_TRUSTED_PROXY_HOPS`). With zero trusted
+//! hops the header is ignored entirely, so it can't be spoofed.
+
+use axum::http::HeaderMap;
+use std::net::IpAddr;
+
+/// Resolve the client IP for a request.
+///
+/// Each trusted proxy appends the address it received the request from to
+/// `X-Forwarded-For`, so with `trusted_hops = N` the client is the N-th entry
+/// from the right. Anything further left was supplied by the client and is
+/// never trusted. Falls back to `peer` when the header is missing, too short,
+/// or the selected entry doesn't parse.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted_hops: usize) -> IpAddr {
+    let Some(index) = trusted_hops.checked_sub(1) else {
+        return peer;
+    };
+
+    let entries: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    entries
+        .iter()
+        .rev()
+        .nth(index)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(peer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> IpAddr {
+        "10.0.0.1".parse().unwrap()
+    }
+
+    fn xff(value: &str) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        h.insert("x-forwarded-for", value.parse().unwrap());
+        h
+    }
+
+    #[test]
+    fn test_untrusted_ignores_forwarded_header() {
+        let ip = resolve_client_ip(peer(), &xff("203.0.113.9"), 0);
+        assert_eq!(ip, peer());
+    }
+
+    #[test]
+    fn test_single_trusted_hop_uses_rightmost_entry() {
+        // The client prepended a fake address; only the proxy-appended one counts.
+        let ip = resolve_client_ip(peer(), &xff("1.2.3.4, 203.0.113.9"), 1);
+        assert_eq!(ip, "203.0.113.9".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_two_trusted_hops() {
+        let ip = resolve_client_ip(peer(), &xff("1.2.3.4, 203.0.113.9, 172.16.0.2"), 2);
+        assert_eq!(ip, "203.0.113.9".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_missing_or_short_header_falls_back_to_peer() {
+        assert_eq!(resolve_client_ip(peer(), &HeaderMap::new(), 1), peer());
+        assert_eq!(resolve_client_ip(peer(), &xff("203.0.113.9"), 2), peer());
+        assert_eq!(resolve_client_ip(peer(), &xff("garbage"), 1), peer());
+    }
+
+    #[test]
+    fn test_ipv6_entry() {
+        let ip = resolve_client_ip(peer(), &xff("2001:db8::1"), 1);
+        assert_eq!(ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+    }
+}
@@ -1,5 +1,18 @@
 use dotenvy::dotenv;
 use std::env;
+use std::time::Duration;
+
+use crate::admin_session::CookieSettings;
+use crate::constants::{ANTHROPIC_BASE_URL, SEED_MODELS};
+use crate::outbound_proxy;
+use crate::routes::admin::is_valid_banner_color;
+use crate::spend_alerts;
+use crate::transforms::cache_breakpoints::DEFAULT_CACHE_MIN_TOKENS;
+use crate::transforms::validate_reasoning_effort;
+use crate::upstream_urls::UpstreamUrls;
+
+/// Shortest export link secret `--check-config` accepts
+const MIN_EXPORT_SECRET_LEN: usize = 32;
 
 /// Cloaking mode — controls when Claude Code identity spoofing is applied
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,6 +25,27 @@ pub enum CloakMode {
     Auto,
 }
 
+/// What to do with a request for a model that is unknown, disabled or
+/// outside its schedule (after alias resolution)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownModelPolicy {
+    /// Fail with 400, listing the available models (default)
+    #[default]
+    Reject,
+    /// Serve the request with the configured default model instead
+    Fallback,
+}
+
+impl UnknownModelPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "reject" => Some(Self::Reject),
+            "fallback" => Some(Self::Fallback),
+            _ => None,
+        }
+    }
+}
+
 /// CORS configuration mode
 #[derive(Debug, Clone)]
 pub enum CorsMode {
@@ -27,11 +61,102 @@ pub struct Config {
     pub host: String,
     pub port: u16,
     pub database_url: String,
+    /// Schema to keep all tables in (created if missing). `None` = the
+    /// connection's default `search_path`.
+    pub database_schema: Option<String>,
     pub admin_username: String,
     pub admin_password: String,
+    /// Optional read-only admin login; both must be set to enable it
+    pub readonly_username: Option<String>,
+    pub readonly_password: Option<String>,
     pub cors_mode: CorsMode,
+    /// Session cookie `Path` / `SameSite` overrides, validated at startup
+    pub cookie_path: Option<String>,
+    pub cookie_same_site: Option<String>,
     pub disable_auth: bool,
     pub cloak_mode: CloakMode,
+    /// Subscription utilization (percent) at which keys without
+    /// `allow_extra_usage` are turned away. Defaults to 100.
+    pub subscription_gate_pct: f64,
+    /// Maximum total duration of a streamed response. `None` = unlimited.
+    pub max_stream_duration: Option<Duration>,
+    /// How long a stream may go without its first real upstream event
+    /// (pings don't count). `None` = wait indefinitely.
+    pub first_event_timeout: Option<Duration>,
+    /// Answer `/v1` with 503 until OAuth is connected (see `routes::readiness`)
+    pub wait_for_oauth: bool,
+    /// Smallest estimated prompt prefix (tokens) that gets an auto-injected
+    /// cache breakpoint. 0 = always inject.
+    pub cache_min_tokens: usize,
+    /// Failed admin logins from one IP before it is locked out
+    pub login_max_failures: usize,
+    /// Sliding window over which failed logins are counted
+    pub login_window: Duration,
+    /// How long an IP stays locked out after too many failures
+    pub login_lockout: Duration,
+    /// Number of reverse proxies in front of this server whose
+    /// `X-Forwarded-For` entries are trusted. 0 = use the socket peer only.
+    pub trusted_proxy_hops: usize,
+    /// Reject OpenAI requests that set parameters with no Anthropic
+    /// equivalent (400) instead of silently dropping them.
+    pub reject_unsupported_params: bool,
+    /// Maximum request body size on the `/v1` API, in bytes
+    pub max_body_bytes: usize,
+    /// gzip/deflate response compression (SSE is never compressed)
+    pub compression: bool,
+    /// Model used when a request doesn't name one
+    pub default_model: String,
+    /// Model whose prices convert key cost limits into token estimates in
+    /// the admin API (defaults to `default_model`)
+    pub limit_reference_model: String,
+    /// Thinking effort for OpenAI requests that set none, when the model has
+    /// no default of its own. Validated at startup.
+    pub default_thinking_effort: Option<String>,
+    /// Attempts for non-streaming requests hitting 429/503/529 (1 = no retry)
+    pub retry_max_attempts: u32,
+    /// Backoff before the first retry; doubles per retry, with jitter
+    pub retry_base_backoff: Duration,
+    /// Concurrent upstream inference requests before new ones queue by key
+    /// priority. `None` = unlimited.
+    pub max_concurrent_requests: Option<usize>,
+    /// How long a low-priority request may wait in the queue before 503
+    pub low_priority_max_wait: Duration,
+    /// Interval of the background `ANALYZE` pass. `None` = disabled.
+    pub db_maintenance_interval: Option<Duration>,
+    /// How long before expiry a background task refreshes the OAuth token.
+    /// `None` = disabled (requests refresh on demand).
+    pub oauth_refresh_lead: Option<Duration>,
+    /// Forward proxy for upstream requests (`CLAUDE_PROXY_OUTBOUND_PROXY`,
+    /// else `HTTPS_PROXY` / `ALL_PROXY`). Validated at startup.
+    pub outbound_proxy: Option<String>,
+    /// Base URL of the Anthropic API (or a compatible gateway). Validated at
+    /// startup.
+    pub anthropic_base_url: String,
+    /// List `model(low)`-style thinking variants in `/v1/models`
+    pub list_thinking_variants: bool,
+    /// `CLAUDE_PROXY_UNKNOWN_MODEL`: `reject` (default) or `fallback`
+    pub unknown_model_policy: UnknownModelPolicy,
+    /// Secret signing usage export links; random per process when unset
+    pub export_secret: Option<String>,
+    /// Environment banner for the admin UI (e.g. "PRODUCTION")
+    pub ui_banner: Option<String>,
+    /// CSS color of the banner. Validated at startup.
+    pub ui_banner_color: Option<String>,
+    /// JSON keys whose values are redacted from tool inputs in `/v1/messages`
+    /// responses. Empty = no redaction.
+    pub redact_tool_input_keys: Vec<String>,
+    /// How long `count_tokens` results are reused for identical requests.
+    /// `None` = no caching.
+    pub count_tokens_cache_ttl: Option<Duration>,
+    /// Total spend levels (comma-separated dollars) that trigger an alert.
+    /// Validated at startup.
+    pub spend_alert_thresholds: Option<String>,
+    /// Rolling window the spend alerts sum over
+    pub spend_alert_window: Duration,
+    /// URL receiving a JSON POST for each spend alert
+    pub spend_alert_webhook: Option<String>,
+    /// Model ids the admin API refuses to delete. Defaults to the seed models.
+    pub protected_models: Vec<String>,
 }
 
 impl Config {
@@ -47,6 +172,10 @@ impl Config {
         let database_url = env::var("CLAUDE_PROXY_DATABASE_URL")
             .or_else(|_| env::var("DATABASE_URL"))
             .expect("CLAUDE_PROXY_DATABASE_URL or DATABASE_URL must be set");
+        let database_schema = env::var("CLAUDE_PROXY_DATABASE_SCHEMA")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
 
         let disable_auth = env::var("CLAUDE_PROXY_DISABLE_AUTH")
             .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
@@ -65,6 +194,13 @@ impl Config {
                 .expect("CLAUDE_PROXY_ADMIN_PASSWORD must be set")
         };
 
+        let readonly_username = env::var("CLAUDE_PROXY_READONLY_USERNAME")
+            .ok()
+            .filter(|v| !v.is_empty());
+        let readonly_password = env::var("CLAUDE_PROXY_READONLY_PASSWORD")
+            .ok()
+            .filter(|v| !v.is_empty());
+
         let cloak_mode = match env::var("CLAUDE_PROXY_CLOAK_MODE")
             .as_deref()
             .map(str::to_lowercase)
@@ -84,15 +220,454 @@ impl Config {
             _ => CorsMode::LocalhostOnly,
         };
 
+        let cookie_path = env::var("CLAUDE_PROXY_COOKIE_PATH")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        let cookie_same_site = env::var("CLAUDE_PROXY_COOKIE_SAMESITE")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let subscription_gate_pct = env::var("CLAUDE_PROXY_SUBSCRIPTION_GATE_PCT")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| *v > 0.0 && *v <= 100.0)
+            .unwrap_or(100.0);
+
+        let max_stream_duration = env::var("CLAUDE_PROXY_MAX_STREAM_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+
+        let first_event_timeout = Some(
+            env::var("CLAUDE_PROXY_FIRST_EVENT_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(60),
+        )
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs);
+
+        let cache_min_tokens = env::var("CLAUDE_PROXY_CACHE_MIN_TOKENS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CACHE_MIN_TOKENS);
+
+        let wait_for_oauth = env::var("CLAUDE_PROXY_WAIT_FOR_OAUTH")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        let login_max_failures = env::var("CLAUDE_PROXY_LOGIN_MAX_FAILURES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(5);
+        let login_window = Duration::from_secs(
+            env::var("CLAUDE_PROXY_LOGIN_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+        );
+        let login_lockout = Duration::from_secs(
+            env::var("CLAUDE_PROXY_LOGIN_LOCKOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(900),
+        );
+
+        let trusted_proxy_hops = env::var("CLAUDE_PROXY_TRUSTED_PROXY_HOPS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let reject_unsupported_params = env::var("CLAUDE_PROXY_REJECT_UNSUPPORTED_PARAMS")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        // Anthropic rejects requests over 32 MB, so anything larger can only
+        // waste memory here. Still roomy enough for base64 image payloads.
+        let max_body_bytes = env::var("CLAUDE_PROXY_MAX_BODY_MB")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&mb| mb > 0)
+            .unwrap_or(32)
+            .saturating_mul(1024 * 1024);
+
+        let compression = env::var("CLAUDE_PROXY_COMPRESSION")
+            .map(|v| !(v == "0" || v.eq_ignore_ascii_case("false")))
+            .unwrap_or(true);
+
+        let default_model = env::var("CLAUDE_PROXY_DEFAULT_MODEL")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "claude-sonnet-4-5".to_string());
+
+        let limit_reference_model = env::var("CLAUDE_PROXY_LIMIT_REFERENCE_MODEL")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| default_model.clone());
+
+        let default_thinking_effort = env::var("CLAUDE_PROXY_DEFAULT_THINKING_EFFORT")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let retry_max_attempts = env::var("CLAUDE_PROXY_RETRY_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(3)
+            .max(1);
+
+        let retry_base_backoff = Duration::from_millis(
+            env::var("CLAUDE_PROXY_RETRY_BASE_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(500),
+        );
+
+        let max_concurrent_requests = env::var("CLAUDE_PROXY_MAX_CONCURRENT_REQUESTS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&n| n > 0);
+        let low_priority_max_wait = Duration::from_secs(
+            env::var("CLAUDE_PROXY_LOW_PRIORITY_MAX_WAIT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+        );
+
+        let db_maintenance_interval = Some(
+            env::var("CLAUDE_PROXY_DB_MAINTENANCE_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(3600),
+        )
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs);
+
+        // Capped well below the token lifetime (hours), or every pass would
+        // find the fresh token already due again
+        let oauth_refresh_lead = Some(
+            env::var("CLAUDE_PROXY_OAUTH_REFRESH_LEAD_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(600)
+                .min(3600),
+        )
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs);
+
+        let outbound_proxy = outbound_proxy::resolve(
+            env::var("CLAUDE_PROXY_OUTBOUND_PROXY").ok().as_deref(),
+            |name| env::var(name).ok(),
+        );
+
+        let anthropic_base_url = env::var("CLAUDE_PROXY_ANTHROPIC_BASE_URL")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| ANTHROPIC_BASE_URL.to_string());
+
+        let list_thinking_variants = env::var("CLAUDE_PROXY_LIST_THINKING_VARIANTS")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        let unknown_model_policy = env::var("CLAUDE_PROXY_UNKNOWN_MODEL")
+            .ok()
+            .and_then(|v| UnknownModelPolicy::parse(&v))
+            .unwrap_or_default();
+
+        let export_secret = env::var("CLAUDE_PROXY_EXPORT_SECRET")
+            .ok()
+            .filter(|v| !v.is_empty());
+
+        let ui_banner = env::var("CLAUDE_PROXY_UI_BANNER")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        let ui_banner_color = env::var("CLAUDE_PROXY_UI_BANNER_COLOR")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let redact_tool_input_keys = env::var("CLAUDE_PROXY_REDACT_TOOL_INPUT_KEYS")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let count_tokens_cache_ttl = env::var("CLAUDE_PROXY_COUNT_TOKENS_CACHE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+
+        let spend_alert_thresholds = env::var("CLAUDE_PROXY_SPEND_ALERT_THRESHOLDS")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        let spend_alert_window = Duration::from_secs(
+            env::var("CLAUDE_PROXY_SPEND_ALERT_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|&secs| secs > 0)
+                .unwrap_or(86_400),
+        );
+        let spend_alert_webhook = env::var("CLAUDE_PROXY_SPEND_ALERT_WEBHOOK")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        // Set but empty protects nothing
+        let protected_models = match env::var("CLAUDE_PROXY_PROTECTED_MODELS") {
+            Ok(v) => v
+                .split(',')
+                .map(str::trim)
+                .filter(|m| !m.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(_) => SEED_MODELS.iter().map(|(id, ..)| id.to_string()).collect(),
+        };
+
         Self {
             host,
             port,
             database_url,
+            database_schema,
             admin_username,
             admin_password,
+            readonly_username,
+            readonly_password,
             cors_mode,
+            cookie_path,
+            cookie_same_site,
             disable_auth,
             cloak_mode,
+            subscription_gate_pct,
+            max_stream_duration,
+            first_event_timeout,
+            wait_for_oauth,
+            cache_min_tokens,
+            login_max_failures,
+            login_window,
+            login_lockout,
+            trusted_proxy_hops,
+            reject_unsupported_params,
+            max_body_bytes,
+            compression,
+            default_model,
+            limit_reference_model,
+            default_thinking_effort,
+            retry_max_attempts,
+            retry_base_backoff,
+            max_concurrent_requests,
+            low_priority_max_wait,
+            db_maintenance_interval,
+            oauth_refresh_lead,
+            outbound_proxy,
+            anthropic_base_url,
+            list_thinking_variants,
+            unknown_model_policy,
+            export_secret,
+            ui_banner,
+            ui_banner_color,
+            redact_tool_input_keys,
+            count_tokens_cache_ttl,
+            spend_alert_thresholds,
+            spend_alert_window,
+            spend_alert_webhook,
+            protected_models,
         }
     }
 }
+
+/// Problems with the environment that would stop the server from starting or
+/// make it misbehave, for `--check-config`. Unlike [`Config::from_env`] this
+/// never panics, so every problem is reported at once.
+pub fn check_env() -> Vec<String> {
+    drop(dotenv());
+    check(|name| env::var(name).ok())
+}
+
+fn check(env: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let var = |name: &str| env(name).filter(|v| !v.trim().is_empty());
+    let mut problems = Vec::new();
+
+    if var("CLAUDE_PROXY_DATABASE_URL")
+        .or_else(|| var("DATABASE_URL"))
+        .is_none()
+    {
+        problems.push("CLAUDE_PROXY_DATABASE_URL or DATABASE_URL must be set".to_string());
+    }
+
+    let disable_auth = var("CLAUDE_PROXY_DISABLE_AUTH")
+        .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    if !disable_auth {
+        for name in ["CLAUDE_PROXY_ADMIN_USERNAME", "CLAUDE_PROXY_ADMIN_PASSWORD"] {
+            if var(name).is_none() {
+                problems.push(format!(
+                    "{name} must be set (or CLAUDE_PROXY_DISABLE_AUTH=1)"
+                ));
+            }
+        }
+    }
+
+    if var("CLAUDE_PROXY_READONLY_USERNAME").is_some()
+        != var("CLAUDE_PROXY_READONLY_PASSWORD").is_some()
+    {
+        problems.push(
+            "CLAUDE_PROXY_READONLY_USERNAME and CLAUDE_PROXY_READONLY_PASSWORD must be set together"
+                .to_string(),
+        );
+    }
+
+    if let Some(origins) = var("CLAUDE_PROXY_CORS_ORIGINS").filter(|v| v != "*") {
+        for origin in origins.split(',').map(str::trim) {
+            if url::Url::parse(origin)
+                .ok()
+                .is_none_or(|url| url.host_str().is_none())
+            {
+                problems.push(format!(
+                    "CLAUDE_PROXY_CORS_ORIGINS: `{origin}` is not an origin like https://app.example.com"
+                ));
+            }
+        }
+    }
+
+    if let Err(e) = CookieSettings::new(
+        var("CLAUDE_PROXY_COOKIE_PATH").as_deref().map(str::trim),
+        var("CLAUDE_PROXY_COOKIE_SAMESITE")
+            .as_deref()
+            .map(str::trim),
+        true,
+    ) {
+        problems.push(e);
+    }
+
+    if let Some(effort) = var("CLAUDE_PROXY_DEFAULT_THINKING_EFFORT")
+        && let Err(e) = validate_reasoning_effort(effort.trim())
+    {
+        problems.push(format!("CLAUDE_PROXY_DEFAULT_THINKING_EFFORT: {e}"));
+    }
+
+    if let Some(base) = var("CLAUDE_PROXY_ANTHROPIC_BASE_URL")
+        && let Err(e) = UpstreamUrls::new(&base)
+    {
+        problems.push(format!("CLAUDE_PROXY_ANTHROPIC_BASE_URL: {e}"));
+    }
+
+    if let Some(policy) = var("CLAUDE_PROXY_UNKNOWN_MODEL")
+        && UnknownModelPolicy::parse(&policy).is_none()
+    {
+        problems.push(format!(
+            "CLAUDE_PROXY_UNKNOWN_MODEL: `{policy}` is not one of reject/fallback"
+        ));
+    }
+
+    if let Some(secret) = var("CLAUDE_PROXY_EXPORT_SECRET")
+        && secret.len() < MIN_EXPORT_SECRET_LEN
+    {
+        problems.push(format!(
+            "CLAUDE_PROXY_EXPORT_SECRET must be at least {MIN_EXPORT_SECRET_LEN} characters"
+        ));
+    }
+
+    if let Some(color) = var("CLAUDE_PROXY_UI_BANNER_COLOR")
+        && !is_valid_banner_color(color.trim())
+    {
+        problems.push(format!(
+            "CLAUDE_PROXY_UI_BANNER_COLOR: `{color}` is not a #rgb/#rrggbb color or color name"
+        ));
+    }
+
+    if let Some(thresholds) = var("CLAUDE_PROXY_SPEND_ALERT_THRESHOLDS")
+        && let Err(e) = spend_alerts::parse_thresholds(&thresholds)
+    {
+        problems.push(format!("CLAUDE_PROXY_SPEND_ALERT_THRESHOLDS: {e}"));
+    }
+
+    if let Some(webhook) = var("CLAUDE_PROXY_SPEND_ALERT_WEBHOOK")
+        && !url::Url::parse(webhook.trim())
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+    {
+        problems.push(format!(
+            "CLAUDE_PROXY_SPEND_ALERT_WEBHOOK: `{webhook}` is not an http(s) URL"
+        ));
+    }
+
+    let proxy = outbound_proxy::resolve(var("CLAUDE_PROXY_OUTBOUND_PROXY").as_deref(), &env);
+    if let Some(proxy) = proxy
+        && let Err(e) = outbound_proxy::parse(&proxy)
+    {
+        problems.push(format!(
+            "Outbound proxy {}: {e}",
+            outbound_proxy::display(&proxy)
+        ));
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn check_with(vars: &[(&str, &str)]) -> Vec<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        check(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_check_reports_missing_required_config() {
+        let problems = check_with(&[]);
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("DATABASE_URL"));
+        assert!(problems[1].contains("CLAUDE_PROXY_ADMIN_USERNAME"));
+        assert!(problems[2].contains("CLAUDE_PROXY_ADMIN_PASSWORD"));
+
+        // Auth disabled: admin credentials are optional
+        let problems = check_with(&[("CLAUDE_PROXY_DISABLE_AUTH", "1")]);
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn test_check_accepts_complete_config() {
+        let problems = check_with(&[
+            ("DATABASE_URL", "postgres://localhost/proxy"),
+            ("CLAUDE_PROXY_ADMIN_USERNAME", "admin"),
+            ("CLAUDE_PROXY_ADMIN_PASSWORD", "secret"),
+            (
+                "CLAUDE_PROXY_CORS_ORIGINS",
+                "https://a.example.com, http://localhost:5173",
+            ),
+        ]);
+        assert_eq!(problems, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_check_reports_invalid_values() {
+        let problems = check_with(&[
+            ("DATABASE_URL", "postgres://localhost/proxy"),
+            ("CLAUDE_PROXY_DISABLE_AUTH", "true"),
+            ("CLAUDE_PROXY_CORS_ORIGINS", "app.example.com"),
+            ("CLAUDE_PROXY_DEFAULT_THINKING_EFFORT", "extreme"),
+            ("HTTPS_PROXY", "ftp://proxy:21"),
+        ]);
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("CLAUDE_PROXY_CORS_ORIGINS"));
+        assert!(problems[1].contains("CLAUDE_PROXY_DEFAULT_THINKING_EFFORT"));
+        assert!(problems[2].contains("Outbound proxy"));
+    }
+}
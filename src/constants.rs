@@ -1,15 +1,22 @@
-/// Anthropic API URL for messages endpoint (with beta features)
-pub const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1/messages?beta=true";
+/// Default Anthropic API base URL, overridable with
+/// `CLAUDE_PROXY_ANTHROPIC_BASE_URL`. Endpoint paths below are relative to it.
+pub const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com";
 
-/// Anthropic API URL for token counting (with beta features)
-pub const ANTHROPIC_COUNT_TOKENS_URL: &str =
-    "https://api.anthropic.com/v1/messages/count_tokens?beta=true";
+/// Messages endpoint (with beta features)
+pub const ANTHROPIC_MESSAGES_PATH: &str = "/v1/messages?beta=true";
 
-/// Anthropic API URL for subscription usage (OAuth)
-pub const ANTHROPIC_USAGE_URL: &str = "https://api.anthropic.com/api/oauth/usage";
+/// Token counting endpoint (with beta features)
+pub const ANTHROPIC_COUNT_TOKENS_PATH: &str = "/v1/messages/count_tokens?beta=true";
 
-/// Anthropic API URL for OAuth profile (plan detection)
-pub const ANTHROPIC_PROFILE_URL: &str = "https://api.anthropic.com/api/oauth/profile";
+/// Message batches endpoint; a batch's status and results live under
+/// `/{id}` and `/{id}/results`
+pub const ANTHROPIC_BATCHES_PATH: &str = "/v1/messages/batches";
+
+/// Subscription usage endpoint (OAuth)
+pub const ANTHROPIC_USAGE_PATH: &str = "/api/oauth/usage";
+
+/// OAuth profile endpoint (plan detection)
+pub const ANTHROPIC_PROFILE_PATH: &str = "/api/oauth/profile";
 
 /// Anthropic API version header value
 pub const ANTHROPIC_VERSION: &str = "2023-06-01";
@@ -43,7 +50,8 @@ pub const USER_AGENT: &str = "claude-code/2.1.178";
 pub const SYSTEM_PREFIX: &str = "You are Claude Code, Anthropic's official CLI for Claude.";
 
 /// Seed models for initial database population.
-/// Used only on first startup when the models table is empty.
+/// Used on first startup when the models table is empty, and as the
+/// defaults restored by the admin "reset pricing" action.
 /// After that, models are managed via the admin UI.
 /// Format: (id, input_price, output_price, cache_read_price, cache_write_price) — all $/MTok
 pub static SEED_MODELS: &[(&str, f64, f64, f64, f64)] = &[
@@ -0,0 +1,117 @@
+//! Cache of `count_tokens` results.
+//!
+//! Anthropic's token count is a pure function of the request, so a repeated
+//! count of the same prepared body (with the same beta flags and API
+//! version) is answered from memory instead of upstream. Entries only expire
+//! by TTL: there is nothing that could make a stored count stale. Enabled
+//! with `CLAUDE_PROXY_COUNT_TOKENS_CACHE_SECS`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Upper bound on cached counts. When exceeded, expired entries are pruned,
+/// and if that frees nothing the cache starts over.
+const MAX_ENTRIES: usize = 10_000;
+
+/// Content hash of everything that goes upstream for one count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    pub fn new(body: &Value, betas: &[String], anthropic_version: &str) -> Self {
+        let mut hasher = Sha256::new();
+        // Object keys serialize sorted, so equal bodies hash equally
+        hasher.update(body.to_string().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(betas.join(",").as_bytes());
+        hasher.update(b"\n");
+        hasher.update(anthropic_version.as_bytes());
+        Self(hasher.finalize().into())
+    }
+}
+
+pub struct CountTokensCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<CacheKey, (Instant, Value)>>,
+}
+
+impl CountTokensCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The stored response for `key`, unless it has expired.
+    pub fn get(&self, key: &CacheKey, now: Instant) -> Option<Value> {
+        let entries = self.lock();
+        entries
+            .get(key)
+            .filter(|(stored_at, _)| now.saturating_duration_since(*stored_at) < self.ttl)
+            .map(|(_, response)| response.clone())
+    }
+
+    /// Store a successful upstream response for `key`.
+    pub fn insert(&self, key: CacheKey, response: Value, now: Instant) {
+        let mut entries = self.lock();
+        if entries.len() >= MAX_ENTRIES {
+            let ttl = self.ttl;
+            entries.retain(|_, (stored_at, _)| now.saturating_duration_since(*stored_at) < ttl);
+            if entries.len() >= MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+        entries.insert(key, (now, response));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<CacheKey, (Instant, Value)>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn body(text: &str) -> Value {
+        json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{ "role": "user", "content": text }]
+        })
+    }
+
+    #[test]
+    fn test_key_covers_body_betas_and_version() {
+        let key = CacheKey::new(&body("hi"), &[], "2023-06-01");
+        assert_eq!(key, CacheKey::new(&body("hi"), &[], "2023-06-01"));
+        assert_ne!(key, CacheKey::new(&body("hello"), &[], "2023-06-01"));
+        assert_ne!(
+            key,
+            CacheKey::new(&body("hi"), &["context-1m-2025-08-07".into()], "2023-06-01")
+        );
+        assert_ne!(key, CacheKey::new(&body("hi"), &[], "2024-10-22"));
+    }
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let cache = CountTokensCache::new(Duration::from_secs(60));
+        let key = CacheKey::new(&body("hi"), &[], "2023-06-01");
+        let now = Instant::now();
+        assert_eq!(cache.get(&key, now), None);
+
+        cache.insert(key, json!({ "input_tokens": 9 }), now);
+        assert_eq!(
+            cache.get(&key, now + Duration::from_secs(59)),
+            Some(json!({ "input_tokens": 9 }))
+        );
+        assert_eq!(cache.get(&key, now + Duration::from_secs(60)), None);
+    }
+}
@@ -1,7 +1,13 @@
-use sqlx::PgPool;
-use sqlx::postgres::PgPoolOptions;
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{AssertSqlSafe, PgPool};
+use std::future::Future;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 use tokio::sync::OnceCell;
-use tracing::info;
+use tracing::{info, warn};
+use utoipa::ToSchema;
 
 use crate::constants::SEED_MODELS;
 use crate::error::{DbResultExt, ProxyError};
@@ -9,17 +15,85 @@ use crate::error::{DbResultExt, ProxyError};
 /// Global database pool.
 static DATABASE: OnceCell<PgPool> = OnceCell::const_new();
 
+/// Migrations embedded in this build.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
 pub type Connection = PgPool;
 
+/// Tries of a write that keeps hitting lock conflicts before giving up.
+const LOCK_RETRY_ATTEMPTS: u32 = 4;
+/// Wait before the first retry; doubles on each further one.
+const LOCK_RETRY_BACKOFF: Duration = Duration::from_millis(25);
+
+/// Longest PostgreSQL identifier (NAMEDATALEN - 1).
+const MAX_SCHEMA_NAME_LEN: usize = 63;
+
+/// Schema names are interpolated into DDL, so only plain lowercase
+/// identifiers are accepted.
+fn is_valid_schema_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    name.len() <= MAX_SCHEMA_NAME_LEN
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn connect_options(
+    database_url: &str,
+    schema: Option<&str>,
+) -> Result<PgConnectOptions, ProxyError> {
+    let options = PgConnectOptions::from_str(database_url).db_context("Invalid PostgreSQL URL")?;
+    match schema {
+        Some(schema) if !is_valid_schema_name(schema) => Err(ProxyError::DatabaseState(
+            "Database schema must be a lowercase identifier (a-z, 0-9, _)",
+        )),
+        Some(schema) => Ok(options.options([("search_path", schema)])),
+        None => Ok(options),
+    }
+}
+
+/// Check that the database is reachable with these settings, without
+/// creating the schema or running migrations. Used by `--check-config`.
+pub async fn check_connection(database_url: &str, schema: Option<&str>) -> Result<(), ProxyError> {
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(Duration::from_secs(10))
+        .connect_with(connect_options(database_url, schema)?)
+        .await
+        .db_context("Failed to connect to PostgreSQL")?;
+    sqlx::raw_sql("SELECT 1")
+        .execute(&pool)
+        .await
+        .db_context("Failed to query PostgreSQL")?;
+    pool.close().await;
+    Ok(())
+}
+
 /// Initialize the PostgreSQL database and apply schema migrations.
-pub async fn init_db(database_url: &str) -> Result<(), ProxyError> {
+///
+/// With `schema` set, every connection uses it as its `search_path` and the
+/// schema is created if missing, so tests and throwaway instances can share
+/// a server without touching each other's tables. Dropping the schema
+/// (`DROP SCHEMA <name> CASCADE`) discards the instance.
+pub async fn init_db(database_url: &str, schema: Option<&str>) -> Result<(), ProxyError> {
     let pool = PgPoolOptions::new()
         .max_connections(10)
-        .connect(database_url)
+        .connect_with(connect_options(database_url, schema)?)
         .await
         .db_context("Failed to connect to PostgreSQL")?;
 
-    sqlx::migrate!("./migrations")
+    if let Some(schema) = schema {
+        sqlx::raw_sql(AssertSqlSafe(format!(
+            "CREATE SCHEMA IF NOT EXISTS {schema}"
+        )))
+        .execute(&pool)
+        .await
+        .db_context("Failed to create database schema")?;
+        info!("Using database schema {schema}");
+    }
+
+    MIGRATOR
         .run(&pool)
         .await
         .db_context("Failed to run migrations")?;
@@ -41,6 +115,160 @@ pub async fn get_conn() -> Result<Connection, ProxyError> {
         .ok_or(ProxyError::DatabaseState("Database not initialized"))
 }
 
+/// Run `op`, retrying with backoff while it fails on a transient lock
+/// conflict (deadlock, serialization failure, lock timeout) so concurrent
+/// writers don't drop usage records. Other errors return at once. `op` runs
+/// again from the start, so it must be safe to repeat.
+pub async fn retry_on_lock<T, F, Fut>(mut op: F) -> Result<T, ProxyError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProxyError>>,
+{
+    let mut backoff = LOCK_RETRY_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(e) if e.is_transient_lock() && attempt < LOCK_RETRY_ATTEMPTS => {
+                warn!("{e}; retrying in {backoff:?} ({attempt}/{LOCK_RETRY_ATTEMPTS})");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Round-trip a trivial query to check the database is reachable.
+pub async fn ping() -> Result<(), ProxyError> {
+    let conn = get_conn().await?;
+    sqlx::query_scalar!("SELECT 1")
+        .fetch_one(&conn)
+        .await
+        .db_context("Database ping failed")?;
+    Ok(())
+}
+
+/// Version of the newest migration applied, `None` before the first one.
+pub async fn schema_version() -> Result<Option<i64>, ProxyError> {
+    let conn = get_conn().await?;
+    sqlx::query_scalar!("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+        .fetch_one(&conn)
+        .await
+        .db_context("Failed to read schema version")
+}
+
+/// One migration known to this build.
+#[derive(Debug, PartialEq, Eq, Serialize, ToSchema)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Embedded migrations and whether each has been applied successfully,
+/// plus applied versions this build doesn't know (e.g. after a downgrade).
+pub async fn migration_status() -> Result<(Vec<MigrationStatus>, Vec<i64>), ProxyError> {
+    let conn = get_conn().await?;
+    let applied =
+        sqlx::query_scalar!("SELECT version FROM _sqlx_migrations WHERE success ORDER BY version")
+            .fetch_all(&conn)
+            .await
+            .db_context("Failed to read applied migrations")?;
+    let known = MIGRATOR.iter().map(|m| (m.version, m.description.as_ref()));
+    Ok(match_migrations(known, &applied))
+}
+
+/// Migrations this build would apply, plus applied versions it doesn't
+/// know, read without migrating or creating anything. Used by
+/// `--check-migrations`.
+pub async fn pending_migrations(
+    database_url: &str,
+    schema: Option<&str>,
+) -> Result<(Vec<MigrationStatus>, Vec<i64>), ProxyError> {
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(Duration::from_secs(10))
+        .connect_with(connect_options(database_url, schema)?)
+        .await
+        .db_context("Failed to connect to PostgreSQL")?;
+    // A database that was never migrated has no bookkeeping table yet
+    let tracked =
+        sqlx::query_scalar!("SELECT to_regclass('_sqlx_migrations') IS NOT NULL AS \"tracked!\"")
+            .fetch_one(&pool)
+            .await
+            .db_context("Failed to look for the migrations table")?;
+    let applied = if tracked {
+        sqlx::query_scalar!("SELECT version FROM _sqlx_migrations WHERE success ORDER BY version")
+            .fetch_all(&pool)
+            .await
+            .db_context("Failed to read applied migrations")?
+    } else {
+        Vec::new()
+    };
+    pool.close().await;
+    let known = MIGRATOR.iter().map(|m| (m.version, m.description.as_ref()));
+    let (migrations, unknown) = match_migrations(known, &applied);
+    Ok((unapplied(migrations), unknown))
+}
+
+fn unapplied(migrations: Vec<MigrationStatus>) -> Vec<MigrationStatus> {
+    migrations.into_iter().filter(|m| !m.applied).collect()
+}
+
+fn match_migrations<'a>(
+    known: impl Iterator<Item = (i64, &'a str)>,
+    applied: &[i64],
+) -> (Vec<MigrationStatus>, Vec<i64>) {
+    let migrations: Vec<MigrationStatus> = known
+        .map(|(version, description)| MigrationStatus {
+            version,
+            description: description.to_string(),
+            applied: applied.contains(&version),
+        })
+        .collect();
+    let unknown = applied
+        .iter()
+        .copied()
+        .filter(|v| !migrations.iter().any(|m| m.version == *v))
+        .collect();
+    (migrations, unknown)
+}
+
+/// `ANALYZE` of the tables whose statistics drift fastest: `request_log` is
+/// append-only and the usage counters are rewritten on every request.
+const ANALYZE_HOT_TABLES: &str = "ANALYZE request_log, client_keys, key_model_limits";
+
+/// Refresh planner statistics on the hot tables.
+///
+/// WAL size and dead tuples are PostgreSQL's job (checkpointer and
+/// autovacuum); autoanalyze, however, only kicks in after a large fraction
+/// of a table changes, which lags behind on a busy `request_log`.
+pub async fn optimize() -> Result<(), ProxyError> {
+    let conn = get_conn().await?;
+    sqlx::raw_sql(ANALYZE_HOT_TABLES)
+        .execute(&conn)
+        .await
+        .db_context("Failed to analyze tables")?;
+    Ok(())
+}
+
+/// Run [`optimize`] every `every` in the background. Failures are logged
+/// and retried on the next tick.
+pub fn spawn_maintenance(every: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+        loop {
+            ticker.tick().await;
+            let started = Instant::now();
+            match optimize().await {
+                Ok(()) => info!("Database maintenance done in {:?}", started.elapsed()),
+                Err(e) => warn!("Database maintenance failed: {e}"),
+            }
+        }
+    });
+}
+
 async fn seed_models_if_empty(conn: &Connection) -> Result<(), ProxyError> {
     let model_count = sqlx::query_scalar!("SELECT COUNT(*) FROM models")
         .fetch_one(conn)
@@ -72,3 +300,103 @@ async fn seed_models_if_empty(conn: &Connection) -> Result<(), ProxyError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn locked() -> ProxyError {
+        ProxyError::DatabaseLocked {
+            context: "Failed to insert request log",
+            source: sqlx::Error::PoolTimedOut,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transient_lock_is_retried() {
+        let calls = AtomicU32::new(0);
+        let result = retry_on_lock(|| async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(locked())
+            } else {
+                Ok("recorded")
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "recorded");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_lock_retries_are_bounded() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = retry_on_lock(|| async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(locked())
+        })
+        .await;
+        assert!(result.is_err_and(|e| e.is_transient_lock()));
+        assert_eq!(calls.load(Ordering::SeqCst), LOCK_RETRY_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn test_other_errors_are_not_retried() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = retry_on_lock(|| async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(ProxyError::DatabaseState("Database not initialized"))
+        })
+        .await;
+        assert!(result.is_err_and(|e| !e.is_transient_lock()));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_match_migrations() {
+        let known = [(1, "initial"), (2, "request log"), (3, "key settings")];
+        let (migrations, unknown) = match_migrations(known.into_iter(), &[1, 2, 99]);
+        assert_eq!(
+            migrations
+                .iter()
+                .map(|m| (m.version, m.applied))
+                .collect::<Vec<_>>(),
+            [(1, true), (2, true), (3, false)]
+        );
+        assert_eq!(migrations[1].description, "request log");
+        assert_eq!(unknown, [99]);
+
+        let pending = unapplied(migrations);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].description, "key settings");
+
+        // Never migrated: everything is pending
+        let (fresh, _) = match_migrations(known.into_iter(), &[]);
+        assert_eq!(unapplied(fresh).len(), known.len());
+    }
+
+    #[test]
+    fn test_embedded_migrations_are_ordered() {
+        let versions: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+        assert!(!versions.is_empty());
+        assert!(versions.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_schema_name_validation() {
+        for name in ["proxy_test", "_scratch", "ci_run_42"] {
+            assert!(is_valid_schema_name(name), "{name}");
+        }
+        let too_long = "a".repeat(MAX_SCHEMA_NAME_LEN + 1);
+        for name in [
+            "",
+            "42_runs",
+            "Proxy",
+            "a-b",
+            "x; DROP TABLE auth",
+            too_long.as_str(),
+        ] {
+            assert!(!is_valid_schema_name(name), "{name}");
+        }
+    }
+}
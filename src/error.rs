@@ -1,10 +1,21 @@
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
-use serde_json::json;
+use serde_json::{Value, json};
 use std::io::Error as StdIoError;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::auth::RefreshError;
+
+/// `Retry-After` for transient OAuth refresh failures
+const OAUTH_RETRY_AFTER_SECS: u64 = 10;
+
+/// SQLSTATEs of transient lock conflicts: serialization_failure,
+/// deadlock_detected and lock_not_available (lock_timeout).
+const LOCK_SQLSTATES: [&str; 3] = ["40001", "40P01", "55P03"];
 
 #[derive(Debug, thiserror::Error)]
 pub enum ProxyError {
@@ -17,14 +28,20 @@ pub enum ProxyError {
     #[error("Rate limit exceeded: {0}")]
     RateLimitExceeded(String),
 
+    #[error("Rate limit exceeded: {message}")]
+    SubscriptionLimitReached {
+        message: String,
+        retry_after_secs: Option<u64>,
+    },
+
     #[error("Anthropic API error: {0}")]
     AnthropicApiError(String),
 
     #[error("Failed to parse response: {0}")]
     ParseError(String),
 
-    #[error("OAuth error: {0}")]
-    OAuthError(String),
+    #[error("OAuth token refresh failed: {0}")]
+    OAuthRefresh(#[from] RefreshError),
 
     #[error("Network error: {0}")]
     NetworkError(#[from] reqwest::Error),
@@ -42,6 +59,15 @@ pub enum ProxyError {
         source: sqlx::Error,
     },
 
+    /// A lock conflict that is expected to clear on retry, see
+    /// [`crate::db::retry_on_lock`]
+    #[error("Database busy while {context}: {source}")]
+    DatabaseLocked {
+        context: &'static str,
+        #[source]
+        source: sqlx::Error,
+    },
+
     #[error("Database migration error while {context}: {source}")]
     DatabaseMigration {
         context: &'static str,
@@ -57,29 +83,121 @@ pub enum ProxyError {
 
     #[error("Invalid model: {0}")]
     InvalidModel(String),
+
+    #[error("Model not found: {model}. Available models: {}", available.join(", "))]
+    ModelNotFound {
+        model: String,
+        available: Vec<String>,
+    },
+
+    #[error("Client IP not allowed for this key: {0}")]
+    IpNotAllowed(IpAddr),
+
+    #[error("Origin not allowed for this key: {0}")]
+    OriginNotAllowed(String),
+
+    #[error("Proxy is at capacity: request queued longer than {0:?}")]
+    QueueTimeout(Duration),
+
+    #[error("Idempotency-Key must be 1-255 visible ASCII characters")]
+    InvalidIdempotencyKey,
+
+    #[error("A request with this Idempotency-Key is still in progress")]
+    IdempotencyKeyInUse,
+
+    #[error("Idempotency-Key was already used for a different request")]
+    IdempotencyKeyReused,
+
+    #[error("Ambiguous tool names: {0}")]
+    ToolNameCollision(String),
 }
 
 impl ProxyError {
+    /// Whether this is a transient lock conflict worth retrying.
+    pub fn is_transient_lock(&self) -> bool {
+        matches!(self, ProxyError::DatabaseLocked { .. })
+    }
+
     /// Convert error to OpenAI-compatible error response
     pub fn to_openai_response(&self) -> Response {
-        let (status, message) = match self {
-            ProxyError::InvalidApiKey
-            | ProxyError::MissingHeader(_)
-            | ProxyError::NoAuthConfigured => (StatusCode::UNAUTHORIZED, self.to_string()),
-            ProxyError::RateLimitExceeded(_) => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
-            ProxyError::ModelNotAllowed(_) => (StatusCode::FORBIDDEN, self.to_string()),
-            ProxyError::InvalidModel(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            ProxyError::OAuthError(_)
-            | ProxyError::IoError(_)
+        let (status, error_type, code) = match self {
+            ProxyError::InvalidApiKey | ProxyError::MissingHeader(_) => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_request_error",
+                Some("invalid_api_key"),
+            ),
+            ProxyError::NoAuthConfigured => {
+                (StatusCode::UNAUTHORIZED, "authentication_error", None)
+            }
+            ProxyError::OAuthRefresh(RefreshError::Revoked(_)) => (
+                StatusCode::UNAUTHORIZED,
+                "authentication_error",
+                Some("oauth_reconnect_required"),
+            ),
+            ProxyError::OAuthRefresh(RefreshError::Transient(_)) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server_error",
+                Some("oauth_refresh_failed"),
+            ),
+            ProxyError::RateLimitExceeded(_) | ProxyError::SubscriptionLimitReached { .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "requests",
+                Some("rate_limit_exceeded"),
+            ),
+            ProxyError::ModelNotAllowed(_) => (
+                StatusCode::FORBIDDEN,
+                "permission_error",
+                Some("model_not_allowed"),
+            ),
+            ProxyError::IpNotAllowed(_) => (
+                StatusCode::FORBIDDEN,
+                "permission_error",
+                Some("ip_not_allowed"),
+            ),
+            ProxyError::OriginNotAllowed(_) => (
+                StatusCode::FORBIDDEN,
+                "permission_error",
+                Some("origin_not_allowed"),
+            ),
+            ProxyError::InvalidModel(_) | ProxyError::ModelNotFound { .. } => (
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                Some("model_not_found"),
+            ),
+            ProxyError::InvalidIdempotencyKey | ProxyError::ToolNameCollision(_) => {
+                (StatusCode::BAD_REQUEST, "invalid_request_error", None)
+            }
+            ProxyError::IdempotencyKeyInUse => (
+                StatusCode::CONFLICT,
+                "invalid_request_error",
+                Some("idempotency_key_in_use"),
+            ),
+            ProxyError::IdempotencyKeyReused => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_request_error",
+                Some("idempotency_key_reused"),
+            ),
+            ProxyError::QueueTimeout(_) | ProxyError::DatabaseLocked { .. } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server_error",
+                Some("overloaded"),
+            ),
+            ProxyError::IoError(_)
             | ProxyError::Database { .. }
             | ProxyError::DatabaseMigration { .. }
-            | ProxyError::DatabaseState(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            | ProxyError::DatabaseState(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "server_error", None)
+            }
             ProxyError::NetworkError(_)
             | ProxyError::AnthropicApiError(_)
-            | ProxyError::ParseError(_) => (StatusCode::BAD_GATEWAY, self.to_string()),
+            | ProxyError::ParseError(_) => (
+                StatusCode::BAD_GATEWAY,
+                "server_error",
+                Some("upstream_error"),
+            ),
         };
 
-        (status, Json(json!({ "error": message }))).into_response()
+        self.with_retry_after(openai_error(status, error_type, &self.to_string(), code))
     }
 
     /// Convert error to Anthropic-compatible error response
@@ -87,26 +205,51 @@ impl ProxyError {
         let (status, error_type, message) = match self {
             ProxyError::InvalidApiKey
             | ProxyError::MissingHeader(_)
-            | ProxyError::NoAuthConfigured => (
+            | ProxyError::NoAuthConfigured
+            | ProxyError::OAuthRefresh(RefreshError::Revoked(_)) => (
                 StatusCode::UNAUTHORIZED,
                 "authentication_error",
                 self.to_string(),
             ),
-            ProxyError::RateLimitExceeded(_) => (
+            ProxyError::RateLimitExceeded(_) | ProxyError::SubscriptionLimitReached { .. } => (
                 StatusCode::TOO_MANY_REQUESTS,
                 "rate_limit_error",
                 self.to_string(),
             ),
-            ProxyError::ModelNotAllowed(_) => {
+            ProxyError::ModelNotAllowed(_)
+            | ProxyError::IpNotAllowed(_)
+            | ProxyError::OriginNotAllowed(_) => {
                 (StatusCode::FORBIDDEN, "permission_error", self.to_string())
             }
-            ProxyError::InvalidModel(_) => (
+            ProxyError::InvalidModel(_)
+            | ProxyError::ModelNotFound { .. }
+            | ProxyError::InvalidIdempotencyKey
+            | ProxyError::ToolNameCollision(_) => (
                 StatusCode::BAD_REQUEST,
                 "invalid_request_error",
                 self.to_string(),
             ),
-            ProxyError::OAuthError(_)
-            | ProxyError::IoError(_)
+            ProxyError::IdempotencyKeyInUse => (
+                StatusCode::CONFLICT,
+                "invalid_request_error",
+                self.to_string(),
+            ),
+            ProxyError::IdempotencyKeyReused => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_request_error",
+                self.to_string(),
+            ),
+            ProxyError::QueueTimeout(_) | ProxyError::DatabaseLocked { .. } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "overloaded_error",
+                self.to_string(),
+            ),
+            ProxyError::OAuthRefresh(RefreshError::Transient(_)) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "api_error",
+                self.to_string(),
+            ),
+            ProxyError::IoError(_)
             | ProxyError::Database { .. }
             | ProxyError::DatabaseMigration { .. }
             | ProxyError::DatabaseState(_) => (
@@ -119,18 +262,96 @@ impl ProxyError {
             | ProxyError::ParseError(_) => (StatusCode::BAD_GATEWAY, "api_error", self.to_string()),
         };
 
-        (
-            status,
-            Json(json!({
-                "type": "error",
-                "error": {
-                    "type": error_type,
-                    "message": message
-                }
-            })),
+        self.with_retry_after(
+            (
+                status,
+                Json(json!({
+                    "type": "error",
+                    "error": {
+                        "type": error_type,
+                        "message": message
+                    }
+                })),
+            )
+                .into_response(),
         )
-            .into_response()
     }
+
+    /// Attach a `Retry-After` header when the error carries a known wait time.
+    fn with_retry_after(&self, mut response: Response) -> Response {
+        let secs = match self {
+            ProxyError::SubscriptionLimitReached {
+                retry_after_secs: Some(secs),
+                ..
+            } => *secs,
+            ProxyError::OAuthRefresh(RefreshError::Transient(_)) => OAUTH_RETRY_AFTER_SECS,
+            _ => return response,
+        };
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        response
+    }
+}
+
+/// OpenAI's error body: `{"error": {"message", "type", "param", "code"}}`.
+/// OpenAI SDKs read `message` from it and pick the exception class from the
+/// status code.
+pub fn openai_error_body(
+    error_type: &str,
+    message: &str,
+    param: Option<&str>,
+    code: Option<&str>,
+) -> Value {
+    json!({
+        "error": {
+            "message": message,
+            "type": error_type,
+            "param": param,
+            "code": code
+        }
+    })
+}
+
+/// OpenAI-shaped error response with no `param`.
+pub fn openai_error(
+    status: StatusCode,
+    error_type: &str,
+    message: &str,
+    code: Option<&str>,
+) -> Response {
+    (
+        status,
+        Json(openai_error_body(error_type, message, None, code)),
+    )
+        .into_response()
+}
+
+/// Re-shape an Anthropic error response for OpenAI clients, keeping the
+/// status. Anthropic's `{"type": "error", "error": {"type", "message"}}` maps
+/// onto the closest OpenAI error type; a body that isn't Anthropic's error
+/// shape is passed on as the message.
+pub fn openai_error_from_anthropic(status: StatusCode, body: &str) -> Response {
+    let parsed = serde_json::from_str::<Value>(body).ok();
+    let error = parsed.as_ref().and_then(|v| v.get("error"));
+    let anthropic_type = error
+        .and_then(|e| e.get("type"))
+        .and_then(Value::as_str)
+        .unwrap_or("api_error");
+    let message = error
+        .and_then(|e| e.get("message"))
+        .and_then(Value::as_str)
+        .unwrap_or(body);
+    let (error_type, code) = match anthropic_type {
+        "invalid_request_error" | "request_too_large" => ("invalid_request_error", None),
+        "authentication_error" => ("authentication_error", None),
+        "permission_error" => ("permission_error", None),
+        "not_found_error" => ("invalid_request_error", Some("not_found")),
+        "rate_limit_error" => ("requests", Some("rate_limit_exceeded")),
+        "overloaded_error" => ("server_error", Some("overloaded")),
+        _ => ("server_error", Some("upstream_error")),
+    };
+    openai_error(status, error_type, message, code)
 }
 
 pub trait DbResultExt<T> {
@@ -139,10 +360,27 @@ pub trait DbResultExt<T> {
 
 impl<T> DbResultExt<T> for Result<T, sqlx::Error> {
     fn db_context(self, context: &'static str) -> Result<T, ProxyError> {
-        self.map_err(|source| ProxyError::Database { context, source })
+        self.map_err(|source| {
+            if is_lock_error(&source) {
+                ProxyError::DatabaseLocked { context, source }
+            } else {
+                ProxyError::Database { context, source }
+            }
+        })
     }
 }
 
+fn is_lock_error(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| is_lock_sqlstate(&code))
+}
+
+fn is_lock_sqlstate(code: &str) -> bool {
+    LOCK_SQLSTATES.contains(&code)
+}
+
 impl<T> DbResultExt<T> for Result<T, sqlx::migrate::MigrateError> {
     fn db_context(self, context: &'static str) -> Result<T, ProxyError> {
         self.map_err(|source| ProxyError::DatabaseMigration { context, source })
@@ -155,3 +393,125 @@ impl IntoResponse for ProxyError {
         self.to_anthropic_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+
+    async fn body_of(response: Response) -> Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    /// The fields OpenAI SDKs expect, with nothing else under `error`.
+    fn assert_openai_shape(body: &Value) {
+        let error = body["error"].as_object().unwrap();
+        assert_eq!(error.len(), 4);
+        assert!(error["message"].is_string());
+        assert!(error["type"].is_string());
+        assert!(error.contains_key("param"));
+        assert!(error.contains_key("code"));
+    }
+
+    #[tokio::test]
+    async fn test_openai_error_shape() {
+        let response = ProxyError::InvalidApiKey.to_openai_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = body_of(response).await;
+        assert_openai_shape(&body);
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["code"], "invalid_api_key");
+        assert_eq!(body["error"]["message"], "Invalid API key");
+
+        let response = ProxyError::QueueTimeout(Duration::from_secs(30)).to_openai_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = body_of(response).await;
+        assert_openai_shape(&body);
+        assert_eq!(body["error"]["type"], "server_error");
+    }
+
+    #[test]
+    fn test_lock_sqlstates_are_transient() {
+        for code in ["40001", "40P01", "55P03"] {
+            assert!(is_lock_sqlstate(code), "{code}");
+        }
+        // unique_violation and undefined_table are real errors
+        assert!(!is_lock_sqlstate("23505"));
+        assert!(!is_lock_sqlstate("42P01"));
+        assert!(!is_lock_error(&sqlx::Error::PoolTimedOut));
+    }
+
+    #[tokio::test]
+    async fn test_openai_error_keeps_retry_after() {
+        let response = ProxyError::SubscriptionLimitReached {
+            message: "5-hour window full".into(),
+            retry_after_secs: Some(120),
+        }
+        .to_openai_response();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "120");
+        let body = body_of(response).await;
+        assert_openai_shape(&body);
+        assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+    }
+
+    #[tokio::test]
+    async fn test_transient_oauth_refresh_failure_is_retryable() {
+        let error = ProxyError::OAuthRefresh(RefreshError::Transient("token endpoint 503".into()));
+        let response = error.to_anthropic_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.headers()[header::RETRY_AFTER],
+            OAUTH_RETRY_AFTER_SECS.to_string()
+        );
+
+        let response = error.to_openai_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+        let body = body_of(response).await;
+        assert_openai_shape(&body);
+        assert_eq!(body["error"]["code"], "oauth_refresh_failed");
+    }
+
+    #[tokio::test]
+    async fn test_revoked_oauth_asks_to_reconnect() {
+        let error = ProxyError::OAuthRefresh(RefreshError::Revoked("invalid_grant".into()));
+        let response = error.to_anthropic_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!response.headers().contains_key(header::RETRY_AFTER));
+        let body = body_of(response).await;
+        assert_eq!(body["error"]["type"], "authentication_error");
+        assert!(
+            body["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("reconnect OAuth")
+        );
+
+        let response = error.to_openai_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = body_of(response).await;
+        assert_eq!(body["error"]["code"], "oauth_reconnect_required");
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_error_reshaped_for_openai() {
+        let upstream =
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let response = openai_error_from_anthropic(StatusCode::from_u16(529).unwrap(), upstream);
+        assert_eq!(response.status().as_u16(), 529);
+        let body = body_of(response).await;
+        assert_openai_shape(&body);
+        assert_eq!(body["error"]["message"], "Overloaded");
+        assert_eq!(body["error"]["type"], "server_error");
+        assert_eq!(body["error"]["code"], "overloaded");
+
+        let body = body_of(openai_error_from_anthropic(
+            StatusCode::BAD_GATEWAY,
+            "upstream down",
+        ))
+        .await;
+        assert_openai_shape(&body);
+        assert_eq!(body["error"]["message"], "upstream down");
+    }
+}
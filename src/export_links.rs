@@ -0,0 +1,147 @@
+//! Short-lived signed links to a key's usage export.
+//!
+//! A token is `base64url(key_id ":" expires_ms) "." base64url(mac)`, where
+//! `mac` is HMAC-SHA256 of the encoded payload under the server secret
+//! (`CLAUDE_PROXY_EXPORT_SECRET`). Anyone holding a valid token can download
+//! that one key's usage CSV until it expires, without an admin session, so
+//! links can be handed to people who should not get admin access.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, KeyInit, Mac};
+use rand::RngExt;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a token was refused. All of them are answered with the same 404 so a
+/// holder learns nothing about other keys; the distinction is for logging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportLinkError {
+    Malformed,
+    BadSignature,
+    Expired,
+}
+
+#[derive(Clone)]
+pub struct ExportLinkSigner {
+    secret: Vec<u8>,
+}
+
+impl ExportLinkSigner {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.to_vec(),
+        }
+    }
+
+    /// Signer with a secret that lives only as long as the process, for when
+    /// none is configured: links stop working on restart.
+    pub fn ephemeral() -> Self {
+        let mut secret = [0u8; 32];
+        rand::rng().fill(&mut secret);
+        Self::new(&secret)
+    }
+
+    /// Token granting access to `key_id`'s export until `expires_at` (epoch ms).
+    pub fn mint(&self, key_id: &str, expires_at: u64) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(format!("{key_id}:{expires_at}"));
+        let mac = URL_SAFE_NO_PAD.encode(self.mac(payload.as_bytes()).finalize().into_bytes());
+        format!("{payload}.{mac}")
+    }
+
+    /// The key id a token grants access to, if it is authentic and not
+    /// expired at `now` (epoch ms).
+    pub fn verify(&self, token: &str, now: u64) -> Result<String, ExportLinkError> {
+        let (payload, mac) = token.split_once('.').ok_or(ExportLinkError::Malformed)?;
+        let mac = URL_SAFE_NO_PAD
+            .decode(mac)
+            .ok()
+            .ok_or(ExportLinkError::Malformed)?;
+        // Constant-time comparison
+        self.mac(payload.as_bytes())
+            .verify_slice(&mac)
+            .map_err(|_mismatch| ExportLinkError::BadSignature)?;
+
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(ExportLinkError::Malformed)?;
+        let (key_id, expires_at) = payload.rsplit_once(':').ok_or(ExportLinkError::Malformed)?;
+        let expires_at: u64 = expires_at.parse().ok().ok_or(ExportLinkError::Malformed)?;
+        if now >= expires_at {
+            return Err(ExportLinkError::Expired);
+        }
+        Ok(key_id.to_string())
+    }
+
+    /// HMAC-SHA256 state after feeding in `payload`
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(payload);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_760_000_000_000;
+
+    fn signer() -> ExportLinkSigner {
+        ExportLinkSigner::new(b"test-export-secret")
+    }
+
+    #[test]
+    fn test_mac_matches_rfc_4231() {
+        let mac = ExportLinkSigner::new(b"Jefe")
+            .mac(b"what do ya want for nothing?")
+            .finalize()
+            .into_bytes();
+        let hex: String = mac.iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(
+            hex,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_valid_token() {
+        let token = signer().mint("key-1", NOW + 60_000);
+        assert_eq!(signer().verify(&token, NOW), Ok("key-1".to_string()));
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let token = signer().mint("key-1", NOW);
+        assert_eq!(signer().verify(&token, NOW), Err(ExportLinkError::Expired));
+    }
+
+    #[test]
+    fn test_tampered_token() {
+        let token = signer().mint("key-1", NOW + 60_000);
+        let (_, mac) = token.split_once('.').unwrap();
+
+        // Another key id (or a later expiry) under the original signature
+        let forged = URL_SAFE_NO_PAD.encode(format!("key-2:{}", NOW + 60_000));
+        assert_eq!(
+            signer().verify(&format!("{forged}.{mac}"), NOW),
+            Err(ExportLinkError::BadSignature)
+        );
+
+        // A different secret
+        let other = ExportLinkSigner::new(b"another-secret");
+        assert_eq!(
+            other.verify(&token, NOW),
+            Err(ExportLinkError::BadSignature)
+        );
+
+        assert_eq!(
+            signer().verify("not-a-token", NOW),
+            Err(ExportLinkError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,389 @@
+//! `Idempotency-Key` support for non-streaming inference requests.
+//!
+//! A client that retries a request after a timeout would otherwise pay for
+//! the same completion twice. When a request carries an `Idempotency-Key`
+//! header, its successful response is stored per `(key_id, idempotency_key)`
+//! for [`IDEMPOTENCY_TTL_MS`]; a retry with the same key and body gets the
+//! stored response back (marked with `Idempotent-Replayed: true`) without
+//! calling Anthropic or recording usage again.
+//!
+//! Reusing a key with a different body is rejected with 422, and a retry
+//! that arrives while the first request is still running gets 409. Errors
+//! are not stored, so a retry after a failure goes upstream as usual.
+//! Streaming requests ignore the header: a stream can't be replayed.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+use crate::subscription::timestamp_millis;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set to `true` on responses served from the idempotency store
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long a stored response can be replayed (1 hour)
+pub const IDEMPOTENCY_TTL_MS: u64 = 60 * 60 * 1000;
+
+/// Longest accepted `Idempotency-Key`
+const MAX_KEY_LEN: usize = 255;
+
+/// A successful response stored for replay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    /// Hash of the endpoint and request body the response belongs to
+    pub request_hash: String,
+    /// Response body as sent to the client
+    pub body: String,
+}
+
+/// The response stored for this key at or after `since` (epoch ms).
+async fn load(
+    key_id: &str,
+    idempotency_key: &str,
+    since: u64,
+) -> Result<Option<StoredResponse>, ProxyError> {
+    let conn = db::get_conn().await?;
+    let row = sqlx::query!(
+        "SELECT request_hash, body FROM idempotent_responses \
+         WHERE key_id = $1 AND idempotency_key = $2 AND created_at >= $3",
+        key_id,
+        idempotency_key,
+        since as i64,
+    )
+    .fetch_optional(&conn)
+    .await
+    .db_context("Failed to load idempotent response")?;
+    Ok(row.map(|row| StoredResponse {
+        request_hash: row.request_hash,
+        body: row.body,
+    }))
+}
+
+/// Store a response, replacing any expired one with the same key. Expired
+/// rows are pruned on each save.
+async fn save(
+    key_id: &str,
+    idempotency_key: &str,
+    response: &StoredResponse,
+    now: u64,
+) -> Result<(), ProxyError> {
+    let conn = db::get_conn().await?;
+    sqlx::query!(
+        "INSERT INTO idempotent_responses (key_id, idempotency_key, request_hash, body, created_at) \
+         VALUES ($1, $2, $3, $4, $5) \
+         ON CONFLICT (key_id, idempotency_key) DO UPDATE SET \
+         request_hash = EXCLUDED.request_hash, body = EXCLUDED.body, created_at = EXCLUDED.created_at",
+        key_id,
+        idempotency_key,
+        response.request_hash,
+        response.body,
+        now as i64,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to store idempotent response")?;
+
+    let cutoff = now.saturating_sub(IDEMPOTENCY_TTL_MS);
+    sqlx::query!(
+        "DELETE FROM idempotent_responses WHERE created_at < $1",
+        cutoff as i64,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to prune idempotent responses")?;
+    Ok(())
+}
+
+/// Outcome of [`Idempotency::begin`].
+pub enum Idempotent {
+    /// No key, or a streaming request: handle the request as usual
+    Skip,
+    /// The stored response for this key; return it as is
+    Replay(Response),
+    /// First request with this key; pass the response to [`Claim::complete`]
+    Claimed(Claim),
+}
+
+/// Tracks keys whose first request is still in flight, so a concurrent retry
+/// is refused instead of reaching Anthropic a second time.
+#[derive(Clone, Default)]
+pub struct Idempotency {
+    in_flight: Arc<Mutex<HashSet<(String, String)>>>,
+}
+
+impl Idempotency {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up the request's `Idempotency-Key`, if any, and either replay
+    /// the stored response or claim the key for this request.
+    pub async fn begin(
+        &self,
+        key_id: &str,
+        endpoint: &str,
+        headers: &HeaderMap,
+        body: &Value,
+        stream: bool,
+    ) -> Result<Idempotent, ProxyError> {
+        let Some(raw) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+            return Ok(Idempotent::Skip);
+        };
+        if stream {
+            return Ok(Idempotent::Skip);
+        }
+        let idempotency_key = raw
+            .to_str()
+            .ok()
+            .map(str::trim)
+            .filter(|k| !k.is_empty() && k.len() <= MAX_KEY_LEN)
+            .ok_or(ProxyError::InvalidIdempotencyKey)?
+            .to_string();
+        let request_hash = request_hash(endpoint, body);
+
+        // Claim before looking up: the first request stores its response
+        // before releasing the key, so a retry either finds the key in
+        // flight or finds the stored response.
+        let entry = (key_id.to_string(), idempotency_key);
+        if !self.lock().insert(entry.clone()) {
+            return Err(ProxyError::IdempotencyKeyInUse);
+        }
+        let claim = Claim {
+            in_flight: self.in_flight.clone(),
+            entry,
+            request_hash,
+        };
+
+        let (key_id, idempotency_key) = &claim.entry;
+        let since = timestamp_millis().saturating_sub(IDEMPOTENCY_TTL_MS);
+        match load(key_id, idempotency_key, since).await? {
+            Some(stored) if stored.request_hash != claim.request_hash => {
+                Err(ProxyError::IdempotencyKeyReused)
+            }
+            Some(stored) => Ok(Idempotent::Replay(replay(stored.body))),
+            None => Ok(Idempotent::Claimed(claim)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashSet<(String, String)>> {
+        lock(&self.in_flight)
+    }
+}
+
+fn lock(set: &Mutex<HashSet<(String, String)>>) -> MutexGuard<'_, HashSet<(String, String)>> {
+    // A poisoned lock only means another thread panicked mid-update; the set
+    // is still usable.
+    set.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// An `Idempotency-Key` held by the request that will produce its response.
+/// Dropping the claim without completing it (e.g. on an upstream error)
+/// releases the key so the client can retry.
+pub struct Claim {
+    in_flight: Arc<Mutex<HashSet<(String, String)>>>,
+    entry: (String, String),
+    request_hash: String,
+}
+
+impl Claim {
+    /// Store the successful response body for replay. Failing to store it is
+    /// logged and otherwise ignored: the client still gets its response.
+    pub async fn complete(self, body: &Value) {
+        let (key_id, idempotency_key) = &self.entry;
+        let response = StoredResponse {
+            request_hash: self.request_hash.clone(),
+            body: body.to_string(),
+        };
+        if let Err(e) = save(key_id, idempotency_key, &response, timestamp_millis()).await {
+            warn!("Failed to store idempotent response for key {key_id}: {e}");
+        }
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        lock(&self.in_flight).remove(&self.entry);
+    }
+}
+
+/// Hash of the endpoint and body. Object keys serialize in sorted order, so
+/// the same JSON with reordered keys hashes the same.
+fn request_hash(endpoint: &str, body: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(endpoint.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body.to_string().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn replay(body: String) -> Response {
+    (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            ),
+            (
+                header::HeaderName::from_static(REPLAYED_HEADER),
+                HeaderValue::from_static("true"),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{create_test_key, test_state, with_db};
+    use axum::Json;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn headers(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_str(key).unwrap());
+        headers
+    }
+
+    fn body() -> Value {
+        json!({"model": "claude-sonnet-4-5", "messages": [{"role": "user", "content": "Hi"}]})
+    }
+
+    /// A handler that counts its upstream calls.
+    async fn handle(
+        idempotency: &Idempotency,
+        key_id: &str,
+        upstream_calls: &AtomicUsize,
+        headers: &HeaderMap,
+        body: &Value,
+    ) -> Response {
+        match idempotency
+            .begin(key_id, "/v1/messages", headers, body, false)
+            .await
+        {
+            Ok(Idempotent::Skip) => {
+                upstream_calls.fetch_add(1, Ordering::SeqCst);
+                Json(json!({"id": "msg_1"})).into_response()
+            }
+            Ok(Idempotent::Replay(response)) => response,
+            Ok(Idempotent::Claimed(claim)) => {
+                upstream_calls.fetch_add(1, Ordering::SeqCst);
+                let response = json!({"id": "msg_1"});
+                claim.complete(&response).await;
+                Json(response).into_response()
+            }
+            Err(e) => e.to_anthropic_response(),
+        }
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_retry_with_same_key_calls_upstream_once() {
+        with_db(async {
+            let key = create_test_key(&test_state(), "idempotent-retry").await;
+            let calls = AtomicUsize::new(0);
+            let headers = headers("retry-1");
+
+            let first = handle(&Idempotency::new(), &key.id, &calls, &headers, &body()).await;
+            // A fresh tracker, as after a restart: the replay comes from the table
+            let second = handle(&Idempotency::new(), &key.id, &calls, &headers, &body()).await;
+
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+            assert_eq!(first.status(), StatusCode::OK);
+            assert_eq!(first.headers().get(REPLAYED_HEADER), None);
+            assert_eq!(second.status(), StatusCode::OK);
+            assert_eq!(second.headers()[REPLAYED_HEADER], "true");
+            let replayed = axum::body::to_bytes(second.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(
+                serde_json::from_slice::<Value>(&replayed).unwrap(),
+                json!({"id": "msg_1"})
+            );
+        });
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_key_are_not_deduplicated() {
+        let idempotency = Idempotency::new();
+        let calls = AtomicUsize::new(0);
+
+        handle(&idempotency, "key-1", &calls, &HeaderMap::new(), &body()).await;
+        handle(&idempotency, "key-1", &calls, &HeaderMap::new(), &body()).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_key_reused_with_different_body_rejected() {
+        with_db(async {
+            let key = create_test_key(&test_state(), "idempotent-reuse").await;
+            let idempotency = Idempotency::new();
+            let calls = AtomicUsize::new(0);
+            let headers = headers("retry-2");
+
+            handle(&idempotency, &key.id, &calls, &headers, &body()).await;
+            let other = json!({"model": "claude-sonnet-4-5", "messages": []});
+            let response = handle(&idempotency, &key.id, &calls, &headers, &other).await;
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+        });
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_key_in_flight_conflicts_until_released() {
+        with_db(async {
+            let key = create_test_key(&test_state(), "idempotent-in-flight").await;
+            let idempotency = Idempotency::new();
+            let headers = headers("retry-3");
+            let begin = || idempotency.begin(&key.id, "/v1/messages", &headers, &body(), false);
+
+            let claim = begin().await.unwrap();
+            assert!(matches!(claim, Idempotent::Claimed(_)));
+            assert!(matches!(
+                begin().await,
+                Err(ProxyError::IdempotencyKeyInUse)
+            ));
+
+            // An upstream error drops the claim without storing anything
+            drop(claim);
+            assert!(matches!(begin().await, Ok(Idempotent::Claimed(_))));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_streaming_requests_skip() {
+        let result = Idempotency::new()
+            .begin("key-1", "/v1/messages", &headers("s"), &body(), true)
+            .await;
+        assert!(matches!(result, Ok(Idempotent::Skip)));
+    }
+
+    #[test]
+    fn test_request_hash_ignores_key_order() {
+        let a = json!({"model": "m", "max_tokens": 10});
+        let b = json!({"max_tokens": 10, "model": "m"});
+        assert_eq!(
+            request_hash("/v1/messages", &a),
+            request_hash("/v1/messages", &b)
+        );
+        assert_ne!(
+            request_hash("/v1/messages", &a),
+            request_hash("/v1/chat/completions", &a)
+        );
+    }
+}
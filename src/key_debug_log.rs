@@ -0,0 +1,189 @@
+//! Per-key request/response logging for diagnosing a single client.
+//!
+//! Keys with `debugLogging` set in their settings get their inbound request
+//! (headers and body) and the upstream response body written to the tracing
+//! log at debug level under the `key_debug` target; every other key stays
+//! quiet. The default `info` filter hides them, so enable the target with
+//! `RUST_LOG=info,key_debug=debug`. Secrets are redacted and bodies are
+//! truncated to [`MAX_LOGGED_BODY_BYTES`].
+//!
+//! Streaming responses are logged by keeping a bounded copy of the upstream
+//! SSE bytes until the stream ends, so the memory cost is only paid by keys
+//! that opted in.
+
+use std::pin::pin;
+
+use async_stream::stream;
+use axum::http::HeaderMap;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::auth::ClientKey;
+use crate::capture::sanitize_header;
+
+/// Longest body logged per request or response; the rest is cut off.
+const MAX_LOGGED_BODY_BYTES: usize = 64 * 1024;
+
+const REDACTED: &str = "<redacted>";
+
+pub struct KeyDebugLog {
+    key_id: String,
+    /// The client's own API key, scrubbed from anything logged
+    secret: String,
+}
+
+impl KeyDebugLog {
+    /// Logger for `key`, or `None` if the key hasn't opted in.
+    pub fn for_key(key: &ClientKey) -> Option<Self> {
+        key.settings.debug_logging.then(|| Self {
+            key_id: key.id.clone(),
+            secret: key.key.clone(),
+        })
+    }
+
+    pub fn request(&self, endpoint: &str, headers: &HeaderMap, body: &Value) {
+        let headers: Vec<String> = headers
+            .iter()
+            .map(|(name, value)| {
+                let value = sanitize_header(name.as_str(), value.to_str().unwrap_or("<binary>"));
+                format!("{name}: {}", self.redact(&value))
+            })
+            .collect();
+        debug!(
+            target: "key_debug",
+            key_id = %self.key_id,
+            endpoint,
+            "Request headers={headers:?} body={}",
+            self.redact(&truncate(&body.to_string()))
+        );
+    }
+
+    pub fn response(&self, status: u16, body: &str) {
+        debug!(
+            target: "key_debug",
+            key_id = %self.key_id,
+            status,
+            "Response body={}",
+            self.redact(&truncate(body))
+        );
+    }
+
+    fn redact(&self, text: &str) -> String {
+        if self.secret.is_empty() {
+            text.to_string()
+        } else {
+            text.replace(&self.secret, REDACTED)
+        }
+    }
+}
+
+/// Cut `text` to at most [`MAX_LOGGED_BODY_BYTES`] on a char boundary.
+fn truncate(text: &str) -> String {
+    if text.len() <= MAX_LOGGED_BODY_BYTES {
+        return text.to_string();
+    }
+    let mut end = MAX_LOGGED_BODY_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}... [truncated, {} bytes total]",
+        text.get(..end).unwrap_or_default(),
+        text.len()
+    )
+}
+
+/// Pass `body` through unchanged, logging a bounded copy of it along with the
+/// upstream `status` once it ends when `log` is set.
+pub fn tee_stream<S, E>(
+    log: Option<KeyDebugLog>,
+    status: u16,
+    body: S,
+) -> impl Stream<Item = Result<Bytes, E>> + Send
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Send + 'static,
+{
+    stream! {
+        let mut body = pin!(body);
+        let mut copy = Vec::new();
+        let mut total = 0usize;
+
+        while let Some(item) = body.next().await {
+            if log.is_some()
+                && let Ok(bytes) = &item
+            {
+                total += bytes.len();
+                let room = MAX_LOGGED_BODY_BYTES.saturating_sub(copy.len());
+                copy.extend_from_slice(bytes.get(..room.min(bytes.len())).unwrap_or_default());
+            }
+            yield item;
+        }
+
+        if let Some(log) = &log {
+            let mut text = String::from_utf8_lossy(&copy).into_owned();
+            if total > copy.len() {
+                text.push_str(&format!("... [truncated, {total} bytes total]"));
+            }
+            log.response(status, &text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use futures_util::stream;
+
+    fn log() -> KeyDebugLog {
+        KeyDebugLog {
+            key_id: "key-1".into(),
+            secret: "sk-proxy-secret".into(),
+        }
+    }
+
+    #[test]
+    fn test_redacts_client_key() {
+        assert_eq!(
+            log().redact("Bearer sk-proxy-secret and again sk-proxy-secret"),
+            "Bearer <redacted> and again <redacted>"
+        );
+    }
+
+    #[test]
+    fn test_authorization_header_is_redacted() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer other"));
+        let (name, value) = headers.iter().next().unwrap();
+        assert_eq!(
+            sanitize_header(name.as_str(), value.to_str().unwrap()),
+            REDACTED
+        );
+    }
+
+    #[test]
+    fn test_truncate_respects_char_boundaries() {
+        let short = "héllo";
+        assert_eq!(truncate(short), short);
+
+        let long = "é".repeat(MAX_LOGGED_BODY_BYTES);
+        let cut = truncate(&long);
+        assert!(cut.contains("[truncated"));
+        assert!(cut.len() < long.len());
+    }
+
+    #[tokio::test]
+    async fn test_tee_stream_passes_bytes_through() {
+        let chunks: Vec<Result<Bytes, ()>> = vec![
+            Ok(Bytes::from("data: a\n\n")),
+            Ok(Bytes::from("data: b\n\n")),
+        ];
+        let out: Vec<_> = tee_stream(Some(log()), 200, stream::iter(chunks.clone()))
+            .collect()
+            .await;
+        assert_eq!(out, chunks);
+    }
+}
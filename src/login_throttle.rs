@@ -0,0 +1,170 @@
+//! Brute-force protection for the admin login endpoint and Basic Auth.
+//!
+//! Failed logins are counted per client IP over a sliding window. Once an IP
+//! reaches `max_failures` inside the window it is locked out for `lockout`,
+//! during which every login attempt is rejected with 429 before credentials
+//! are even compared. A successful login clears the IP's history.
+//!
+//! State is in-memory only: a restart forgets all counters, which is an
+//! acceptable trade-off for a single-process admin endpoint.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Upper bound on tracked IPs. When exceeded, entries with no recent failures
+/// and no active lockout are pruned so a spray of source addresses can't grow
+/// the map without limit.
+const MAX_TRACKED_IPS: usize = 10_000;
+
+#[derive(Default)]
+struct Entry {
+    failures: VecDeque<Instant>,
+    locked_until: Option<Instant>,
+}
+
+pub struct LoginThrottle {
+    max_failures: usize,
+    window: Duration,
+    lockout: Duration,
+    entries: Mutex<HashMap<IpAddr, Entry>>,
+}
+
+impl LoginThrottle {
+    pub fn new(max_failures: usize, window: Duration, lockout: Duration) -> Self {
+        Self {
+            max_failures,
+            window,
+            lockout,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check whether `ip` may attempt a login. Returns the remaining lockout
+    /// time when it may not.
+    pub fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let entries = self.lock();
+        match entries.get(&ip).and_then(|e| e.locked_until) {
+            Some(until) if until > now => Err(until.saturating_duration_since(now)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a failed attempt. Returns the lockout duration if this failure
+    /// tripped the lockout.
+    pub fn record_failure(&self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let mut entries = self.lock();
+        if entries.len() >= MAX_TRACKED_IPS {
+            let window = self.window;
+            entries.retain(|_, e| {
+                e.locked_until.is_some_and(|until| until > now)
+                    || e.failures
+                        .back()
+                        .is_some_and(|&t| now.saturating_duration_since(t) < window)
+            });
+        }
+
+        let entry = entries.entry(ip).or_default();
+        while entry
+            .failures
+            .front()
+            .is_some_and(|&t| now.saturating_duration_since(t) >= self.window)
+        {
+            entry.failures.pop_front();
+        }
+        entry.failures.push_back(now);
+
+        if entry.failures.len() >= self.max_failures {
+            entry.failures.clear();
+            entry.locked_until = Some(now + self.lockout);
+            return Some(self.lockout);
+        }
+        None
+    }
+
+    /// Forget all failures for `ip` after a successful login.
+    pub fn record_success(&self, ip: IpAddr) {
+        self.lock().remove(&ip);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<IpAddr, Entry>> {
+        // A poisoned lock only means another thread panicked mid-update; the
+        // counters are still usable.
+        self.entries
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle::new(3, Duration::from_secs(60), Duration::from_secs(300))
+    }
+
+    #[test]
+    fn test_locks_out_after_max_failures() {
+        let t = throttle();
+        let now = Instant::now();
+        let a = ip("203.0.113.1");
+
+        assert_eq!(t.record_failure(a, now), None);
+        assert_eq!(t.record_failure(a, now), None);
+        assert_eq!(t.check(a, now), Ok(()));
+        assert_eq!(t.record_failure(a, now), Some(Duration::from_secs(300)));
+
+        assert_eq!(
+            t.check(a, now + Duration::from_secs(10)),
+            Err(Duration::from_secs(290))
+        );
+        // Other IPs are unaffected
+        assert_eq!(t.check(ip("203.0.113.2"), now), Ok(()));
+    }
+
+    #[test]
+    fn test_lockout_expires() {
+        let t = throttle();
+        let now = Instant::now();
+        let a = ip("203.0.113.1");
+        for _ in 0..3 {
+            t.record_failure(a, now);
+        }
+        assert_eq!(
+            t.check(a, now + Duration::from_secs(299)),
+            Err(Duration::from_secs(1))
+        );
+        assert_eq!(t.check(a, now + Duration::from_secs(300)), Ok(()));
+    }
+
+    #[test]
+    fn test_failures_outside_window_do_not_count() {
+        let t = throttle();
+        let now = Instant::now();
+        let a = ip("203.0.113.1");
+        t.record_failure(a, now);
+        t.record_failure(a, now);
+        // The first two slide out of the 60s window
+        assert_eq!(t.record_failure(a, now + Duration::from_secs(61)), None);
+        assert_eq!(t.check(a, now + Duration::from_secs(61)), Ok(()));
+    }
+
+    #[test]
+    fn test_success_resets_counter() {
+        let t = throttle();
+        let now = Instant::now();
+        let a = ip("203.0.113.1");
+        t.record_failure(a, now);
+        t.record_failure(a, now);
+        t.record_success(a);
+        assert_eq!(t.record_failure(a, now), None);
+        assert_eq!(t.record_failure(a, now), None);
+        assert_eq!(t.check(a, now), Ok(()));
+    }
+}
@@ -1,16 +1,32 @@
 mod admin_session;
+mod admin_tokens;
+mod audit_log;
 mod auth;
 mod capture;
+mod client_ip;
 mod config;
 mod constants;
+mod count_tokens_cache;
 mod db;
 mod error;
+mod export_links;
+mod idempotency;
+mod key_debug_log;
+mod login_throttle;
+mod outbound_proxy;
+mod request_queue;
 mod routes;
+mod settings;
+mod spend_alerts;
 mod subscription;
+mod telemetry;
+#[cfg(test)]
+mod test_support;
 mod transforms;
+mod upstream_urls;
 mod usage;
 
-use admin_session::{AdminCredentials, admin_auth_middleware};
+use admin_session::{AdminCredentials, CookieSettings, admin_auth_middleware};
 use anyhow::{Context, Result};
 use auth::{AuthStore, ClientKeysStore, ModelsStore, OAuthManager};
 use axum::ServiceExt;
@@ -24,7 +40,12 @@ use axum::{
 };
 use capture::CaptureConfig;
 use clap::Parser;
-use config::{CloakMode, Config, CorsMode};
+use config::{CloakMode, Config, CorsMode, UnknownModelPolicy};
+use count_tokens_cache::CountTokensCache;
+use export_links::ExportLinkSigner;
+use idempotency::Idempotency;
+use login_throttle::LoginThrottle;
+use request_queue::RequestQueue;
 use reqwest::Client;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -32,8 +53,10 @@ use std::time::Duration;
 use tokio::net::TcpListener;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::normalize_path::NormalizePath;
+use tower_http::trace::TraceLayer;
 use tracing::{info, warn};
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+use upstream_urls::UpstreamUrls;
 use url::Url;
 use usage::UsageCache;
 use utoipa::openapi::{InfoBuilder, OpenApi, OpenApiBuilder};
@@ -44,17 +67,22 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const GIT_HASH: &str = env!("GIT_HASH");
 pub const BUILD_TIME: &str = env!("BUILD_TIME");
 
-use crate::routes::{admin, anthropic, health, openai, user_usage};
+use crate::routes::retry::RetryPolicy;
+use crate::routes::{
+    admin, anthropic, body_limit, compression, count_tokens_batch, estimate, health,
+    message_batches, openai, proxy_headers, readiness, usage_export, user_usage,
+};
+use crate::transforms::validate_reasoning_effort;
 
 pub struct AppState {
     pub auth_store: Arc<AuthStore>,
     pub client_keys: Arc<ClientKeysStore>,
     pub models: Arc<ModelsStore>,
-    pub oauth: OAuthManager,
+    pub oauth: Arc<OAuthManager>,
     pub http_client: Client,
     pub admin_credentials: AdminCredentials,
-    /// Whether to set Secure flag on cookies (true when not binding to localhost)
-    pub secure_cookies: bool,
+    /// Admin session cookie attributes; `Secure` unless binding to localhost
+    pub session_cookie: CookieSettings,
     /// When true, admin auth middleware is bypassed (for local development)
     pub disable_auth: bool,
     /// Cloaking mode (always / never / auto)
@@ -68,6 +96,53 @@ pub struct AppState {
     pub session_id: String,
     /// Optional request/response capture sink for debugging client compatibility.
     pub capture: CaptureConfig,
+    /// Subscription utilization (percent) at which keys without extra-usage
+    /// permission are rejected with 429.
+    pub subscription_gate_pct: f64,
+    /// Cap on total SSE stream duration, independent of the keep-alive
+    /// interval. `None` = unlimited.
+    pub max_stream_duration: Option<Duration>,
+    /// Close OpenAI streams whose upstream sends no real event in time.
+    /// `None` = wait indefinitely.
+    pub first_event_timeout: Option<Duration>,
+    /// Startup gate holding `/v1` traffic until OAuth is connected.
+    pub readiness: readiness::Readiness,
+    /// Threshold for auto-injected cache breakpoints (see `PrepareOptions`).
+    pub cache_min_tokens: usize,
+    /// Per-IP failed-login counter for the admin login endpoint.
+    pub login_throttle: LoginThrottle,
+    /// Trusted reverse-proxy hops for `X-Forwarded-For` (0 = ignore header).
+    pub trusted_proxy_hops: usize,
+    /// Return 400 for unsupported OpenAI parameters instead of dropping them.
+    pub reject_unsupported_params: bool,
+    /// Model used when a request omits `model` (before alias resolution).
+    pub default_model: String,
+    /// Model whose prices back the token estimates for key limits.
+    pub limit_reference_model: String,
+    /// Fallback thinking effort for OpenAI requests (after the model's own).
+    pub default_thinking_effort: Option<String>,
+    /// Retry policy for transient upstream errors on non-streaming requests.
+    pub retry_policy: RetryPolicy,
+    /// Priority-ordered admission of inference requests to Anthropic.
+    pub request_queue: Arc<RequestQueue>,
+    /// Anthropic endpoint URLs (`CLAUDE_PROXY_ANTHROPIC_BASE_URL`).
+    pub upstream: UpstreamUrls,
+    /// `Idempotency-Key` requests whose first attempt is still running.
+    pub idempotency: Idempotency,
+    /// List suffixed thinking variants of models in `/v1/models`.
+    pub list_thinking_variants: bool,
+    /// Reject or fall back for requests naming an unavailable model.
+    pub unknown_model_policy: UnknownModelPolicy,
+    /// Signs and checks usage export links.
+    pub export_links: ExportLinkSigner,
+    /// Banner and other settings served to the admin SPA.
+    pub ui_config: admin::UiConfig,
+    /// Keys redacted from tool inputs in native responses (empty = off).
+    pub redact_tool_input_keys: Arc<[String]>,
+    /// Repeated `count_tokens` results by request content. `None` = disabled.
+    pub count_tokens_cache: Option<CountTokensCache>,
+    /// Model ids `DELETE /admin/models/{id}` refuses to remove.
+    pub protected_models: Arc<[String]>,
 }
 
 impl AppState {
@@ -99,10 +174,21 @@ struct Args {
     /// Dump OpenAPI spec as JSON and exit (no config/DB needed)
     #[arg(long)]
     openapi: bool,
+
+    /// Validate configuration and database access, then exit without serving
+    #[arg(long)]
+    check_config: bool,
+
+    /// List migrations not yet applied to the database, then exit without
+    /// applying them. Exits non-zero if any are pending
+    #[arg(long)]
+    check_migrations: bool,
 }
 
 fn full_openapi_router() -> OpenApiRouter<Arc<AppState>> {
-    admin_openapi_router().merge(user_usage::user_usage_router())
+    admin_openapi_router()
+        .merge(user_usage::user_usage_router())
+        .merge(admin::ui_config_router())
 }
 
 fn admin_openapi_router() -> OpenApiRouter<Arc<AppState>> {
@@ -117,35 +203,65 @@ fn admin_openapi_router() -> OpenApiRouter<Arc<AppState>> {
             )
             .build(),
     )
+    .routes(routes!(admin::get_admin_status))
+    .routes(routes!(admin::get_migrations))
     // OAuth
     .routes(routes!(admin::get_oauth_status))
     .routes(routes!(admin::start_oauth_flow))
     .routes(routes!(admin::exchange_oauth_code))
     .routes(routes!(admin::delete_oauth))
+    .routes(routes!(admin::test_oauth_connection))
+    .routes(routes!(admin::refresh_oauth_cache))
     .routes(routes!(admin::get_subscription_usage))
     .routes(routes!(
         admin::get_web_session_status,
         admin::save_web_session,
         admin::delete_web_session
     ))
+    // Admin API tokens
+    .routes(routes!(admin::create_admin_token, admin::list_admin_tokens))
+    .routes(routes!(admin::revoke_admin_token))
     // Keys
     .routes(routes!(admin::create_key))
+    .routes(routes!(admin::clone_key))
     .routes(routes!(admin::list_keys))
     .routes(routes!(admin::delete_key))
     .routes(routes!(admin::set_key_enabled))
     .routes(routes!(admin::set_allow_extra_usage))
+    .routes(routes!(admin::get_key_note, admin::set_key_note))
+    .routes(routes!(
+        admin::get_key_force_model,
+        admin::set_key_force_model
+    ))
     .routes(routes!(admin::get_key_usage))
+    .routes(routes!(admin::get_key_usage_forecast))
     .routes(routes!(admin::update_key_limits))
     .routes(routes!(admin::reset_key_usage))
+    .routes(routes!(admin::reset_all_key_usage))
+    .routes(routes!(admin::set_key_windows))
     // Models
     .routes(routes!(admin::list_models_admin))
     .routes(routes!(admin::add_model))
     .routes(routes!(admin::delete_model, admin::update_model))
+    .routes(routes!(admin::get_model_usage_rollup))
+    .routes(routes!(admin::reset_model_pricing))
     .routes(routes!(admin::reorder_models))
+    .routes(routes!(admin::set_model_schedule))
+    .routes(routes!(admin::set_model_default_effort))
+    .routes(routes!(admin::list_model_aliases))
+    .routes(routes!(admin::set_model_alias, admin::delete_model_alias))
     // Per-key model access
     .routes(routes!(admin::get_key_models, admin::set_key_models))
+    .routes(routes!(admin::get_key_effective_models))
+    // Per-key IP allow-list
+    .routes(routes!(admin::get_key_ips, admin::set_key_ips))
+    .routes(routes!(admin::get_key_origins, admin::set_key_origins))
+    .routes(routes!(admin::set_key_settings))
     // Per-key per-model usage
     .routes(routes!(admin::get_key_model_usage))
+    .routes(routes!(admin::get_key_effective_limits))
+    .routes(routes!(admin::get_key_cache_stats))
+    .routes(routes!(admin::create_export_link))
     .routes(routes!(
         admin::set_key_model_limits,
         admin::remove_key_model_limits
@@ -155,7 +271,10 @@ fn admin_openapi_router() -> OpenApiRouter<Arc<AppState>> {
     .routes(routes!(admin::get_usage_history_timeseries))
     .routes(routes!(admin::get_usage_history_by_model))
     .routes(routes!(admin::get_usage_history_by_key))
+    .routes(routes!(admin::get_subscription_samples))
     .routes(routes!(admin::delete_usage_history))
+    .routes(routes!(admin::get_request_log))
+    .routes(routes!(admin::get_audit_log))
 }
 
 fn build_openapi() -> OpenApi {
@@ -163,6 +282,50 @@ fn build_openapi() -> OpenApi {
     openapi
 }
 
+/// Report configuration problems and whether the database is reachable.
+/// Exits non-zero if anything is wrong.
+async fn check_config() -> Result<()> {
+    let problems = config::check_env();
+    for problem in &problems {
+        println!("error: {problem}");
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("configuration check failed ({} problem(s))", problems.len());
+    }
+    println!("ok: environment");
+
+    let config = Config::from_env();
+    db::check_connection(&config.database_url, config.database_schema.as_deref())
+        .await
+        .context("configuration check failed")?;
+    println!("ok: database");
+    Ok(())
+}
+
+/// Report migrations this build would apply on startup, without applying
+/// them. Exits non-zero if any are pending.
+async fn check_migrations() -> Result<()> {
+    let config = Config::from_env();
+    let (pending, unknown) =
+        db::pending_migrations(&config.database_url, config.database_schema.as_deref())
+            .await
+            .context("migration check failed")?;
+    for version in &unknown {
+        println!("warning: applied migration {version} is unknown to this build");
+    }
+    if pending.is_empty() {
+        println!("ok: no pending migrations");
+        return Ok(());
+    }
+    for migration in &pending {
+        println!(
+            "pending: {:04} {}",
+            migration.version, migration.description
+        );
+    }
+    anyhow::bail!("{} pending migration(s)", pending.len());
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
@@ -179,16 +342,35 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    if args.check_config {
+        return check_config().await;
+    }
+    if args.check_migrations {
+        return check_migrations().await;
+    }
+
+    let (otel_layer, _telemetry_guard) = telemetry::otel_layer();
+    let otel_enabled = otel_layer.is_some();
     tracing_subscriber::registry()
+        .with(otel_layer)
         .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
         .with(fmt::layer())
         .init();
+    if otel_enabled {
+        info!("OpenTelemetry trace export enabled");
+    } else if telemetry::otlp_endpoint_configured() && cfg!(not(feature = "otel")) {
+        warn!("OTEL_EXPORTER_OTLP_ENDPOINT is set but this build lacks the `otel` feature");
+    }
     let config = Config::from_env();
+    health::mark_started();
 
     // Initialize database (before moving fields out of config)
-    db::init_db(&config.database_url)
+    db::init_db(&config.database_url, config.database_schema.as_deref())
         .await
         .context("Failed to initialize database")?;
+    if let Some(every) = config.db_maintenance_interval {
+        db::spawn_maintenance(every);
+    }
 
     let host = args.host.unwrap_or(config.host);
     let port = args.port.unwrap_or(config.port);
@@ -198,21 +380,87 @@ async fn main() -> Result<()> {
     let models = Arc::new(ModelsStore::new());
 
     // Shared HTTP client with connection pooling
-    let http_client = Client::builder()
-        .timeout(Duration::from_secs(300)) // 5 min timeout for long requests
-        .pool_max_idle_per_host(10)
-        .build()
-        .context("Failed to create HTTP client")?;
+    let http_client = outbound_proxy::build_http_client(config.outbound_proxy.as_deref())?;
+    if let Some(proxy) = &config.outbound_proxy {
+        info!(
+            "Upstream requests go through proxy {}",
+            outbound_proxy::display(proxy)
+        );
+    }
+
+    let upstream = UpstreamUrls::new(&config.anthropic_base_url)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| {
+            format!(
+                "Invalid CLAUDE_PROXY_ANTHROPIC_BASE_URL {}",
+                config.anthropic_base_url
+            )
+        })?;
+    if config.anthropic_base_url != constants::ANTHROPIC_BASE_URL {
+        info!("Upstream Anthropic API: {}", config.anthropic_base_url);
+    }
 
-    let oauth = OAuthManager::new(http_client.clone(), auth_store.clone());
+    let export_links = match &config.export_secret {
+        Some(secret) => ExportLinkSigner::new(secret.as_bytes()),
+        None => {
+            info!("CLAUDE_PROXY_EXPORT_SECRET not set; usage export links stop working on restart");
+            ExportLinkSigner::ephemeral()
+        }
+    };
+
+    let usage_cache = UsageCache::restore(subscription::timestamp_millis()).await;
+
+    let oauth = Arc::new(OAuthManager::new(http_client.clone(), auth_store.clone()));
+    if let Some(lead) = config.oauth_refresh_lead {
+        auth::token_refresh::spawn(oauth.clone(), lead);
+    }
 
+    if let Some(raw) = &config.spend_alert_thresholds {
+        let thresholds = spend_alerts::parse_thresholds(raw)
+            .map_err(anyhow::Error::msg)
+            .context("Invalid CLAUDE_PROXY_SPEND_ALERT_THRESHOLDS")?;
+        info!(
+            "Spend alerts enabled for {} threshold(s) over {}s",
+            thresholds.len(),
+            config.spend_alert_window.as_secs()
+        );
+        spend_alerts::spawn(spend_alerts::SpendAlerts::new(
+            thresholds,
+            config.spend_alert_window,
+            config.spend_alert_webhook.clone(),
+            http_client.clone(),
+        ));
+    }
+
+    let read_only = match (config.readonly_username, config.readonly_password) {
+        (Some(username), Some(password)) => {
+            info!("Read-only admin login enabled for {username}");
+            Some((username, password))
+        }
+        (None, None) => None,
+        _ => {
+            warn!(
+                "Ignoring read-only admin login: set both CLAUDE_PROXY_READONLY_USERNAME and CLAUDE_PROXY_READONLY_PASSWORD"
+            );
+            None
+        }
+    };
     let admin_credentials = AdminCredentials {
         username: config.admin_username,
         password: config.admin_password,
+        read_only,
     };
 
     let is_localhost = matches!(host.as_str(), "127.0.0.1" | "localhost" | "::1");
-    let secure_cookies = !is_localhost;
+    let session_cookie = CookieSettings::new(
+        config.cookie_path.as_deref(),
+        config.cookie_same_site.as_deref(),
+        !is_localhost,
+    )
+    .map_err(anyhow::Error::msg)?;
+    if session_cookie.secure && is_localhost {
+        info!("Session cookie is Secure because SameSite=None requires it");
+    }
 
     let disable_auth = config.disable_auth;
     if disable_auth {
@@ -221,11 +469,41 @@ async fn main() -> Result<()> {
 
     let cloak_mode = config.cloak_mode;
     info!("Cloaking mode: {:?}", cloak_mode);
+    if config.subscription_gate_pct < 100.0 {
+        info!(
+            "Subscription gate: rejecting non-extra-usage keys at {}% utilization",
+            config.subscription_gate_pct
+        );
+    }
+    if config.trusted_proxy_hops > 0 {
+        info!(
+            "Trusting {} proxy hop(s) in X-Forwarded-For",
+            config.trusted_proxy_hops
+        );
+    }
+    let default_thinking_effort =
+        config
+            .default_thinking_effort
+            .filter(|effort| match validate_reasoning_effort(effort) {
+                Ok(()) => {
+                    info!("Default thinking effort: {effort}");
+                    true
+                }
+                Err(e) => {
+                    warn!("Ignoring CLAUDE_PROXY_DEFAULT_THINKING_EFFORT: {e}");
+                    false
+                }
+            });
     let capture = CaptureConfig::from_env();
     if capture.is_enabled() {
         info!("Request capture is enabled");
     }
 
+    if config.wait_for_oauth {
+        info!("/v1 answers 503 until OAuth is connected (CLAUDE_PROXY_WAIT_FOR_OAUTH)");
+    }
+    let readiness = readiness::Readiness::new(config.wait_for_oauth, auth_store.clone());
+
     let state = Arc::new(AppState {
         auth_store,
         client_keys,
@@ -233,12 +511,44 @@ async fn main() -> Result<()> {
         oauth,
         http_client,
         admin_credentials,
-        secure_cookies,
+        session_cookie,
         disable_auth,
         cloak_mode,
-        usage_cache: UsageCache::new(),
+        usage_cache,
         session_id: Uuid::new_v4().to_string(),
         capture,
+        subscription_gate_pct: config.subscription_gate_pct,
+        max_stream_duration: config.max_stream_duration,
+        first_event_timeout: config.first_event_timeout,
+        readiness,
+        cache_min_tokens: config.cache_min_tokens,
+        login_throttle: LoginThrottle::new(
+            config.login_max_failures,
+            config.login_window,
+            config.login_lockout,
+        ),
+        trusted_proxy_hops: config.trusted_proxy_hops,
+        reject_unsupported_params: config.reject_unsupported_params,
+        default_model: config.default_model.clone(),
+        limit_reference_model: config.limit_reference_model.clone(),
+        default_thinking_effort,
+        retry_policy: RetryPolicy {
+            max_attempts: config.retry_max_attempts,
+            base_backoff: config.retry_base_backoff,
+        },
+        request_queue: Arc::new(RequestQueue::new(
+            config.max_concurrent_requests,
+            config.low_priority_max_wait,
+        )),
+        upstream,
+        idempotency: Idempotency::new(),
+        list_thinking_variants: config.list_thinking_variants,
+        unknown_model_policy: config.unknown_model_policy,
+        export_links,
+        ui_config: admin::UiConfig::new(config.ui_banner.clone(), config.ui_banner_color.clone()),
+        redact_tool_input_keys: config.redact_tool_input_keys.clone().into(),
+        count_tokens_cache: config.count_tokens_cache_ttl.map(CountTokensCache::new),
+        protected_models: config.protected_models.clone().into(),
     });
 
     // CORS configuration based on environment
@@ -274,7 +584,14 @@ async fn main() -> Result<()> {
             header::CONTENT_TYPE,
             header::AUTHORIZATION,
             HeaderName::from_static("x-api-key"),
+            HeaderName::from_static("api-key"),
             HeaderName::from_static("anthropic-version"),
+            HeaderName::from_static(idempotency::IDEMPOTENCY_KEY_HEADER),
+        ])
+        .expose_headers([
+            proxy_headers::MODEL_HEADER,
+            proxy_headers::LATENCY_HEADER,
+            proxy_headers::MODEL_FORCED_HEADER,
         ])
         .allow_credentials(true);
 
@@ -290,6 +607,9 @@ async fn main() -> Result<()> {
     // User-facing usage routes (unprotected — Bearer key auth handled in handlers)
     let (user_router, _) = user_usage::user_usage_router().split_for_parts();
 
+    // Banner and other UI settings (unprotected — shown on the login page)
+    let (ui_config_router, _) = admin::ui_config_router().split_for_parts();
+
     // Auth endpoints (accessible without authentication)
     let auth_routes = Router::new()
         .route("/auth/login", post(admin::login))
@@ -297,36 +617,85 @@ async fn main() -> Result<()> {
         .route("/auth/check", get(admin::auth_check))
         .with_state(state.clone());
 
-    // Protected admin routes (session cookie or Basic Auth)
-    let protected_routes = api_router.layer(middleware::from_fn_with_state(
-        state.clone(),
-        admin_auth_middleware,
-    ));
+    // Protected admin routes (session cookie or Basic Auth); changes are
+    // audited once the admin is known
+    let protected_routes = api_router
+        .layer(middleware::from_fn(audit_log::audit_middleware))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin_auth_middleware,
+        ));
 
-    // Combine: auth routes (unprotected) + user usage (unprotected) + protected API + static SPA
+    // Combine: auth routes, user usage and UI config (unprotected) + protected API + static SPA
     let admin_routes = Router::new()
         .merge(auth_routes)
         .merge(user_router)
+        .merge(ui_config_router)
         .merge(protected_routes)
         .merge(admin::static_routes());
 
+    if !config.compression {
+        info!("Response compression disabled (CLAUDE_PROXY_COMPRESSION=0)");
+    }
+
+    // Listings and batch results: plain JSON, never streamed as SSE
+    let listing_routes = Router::new()
+        .route("/models", get(openai::list_models))
+        .route("/models/{id}", get(openai::get_model))
+        .route("/messages/batches/{id}", get(message_batches::get_batch))
+        .route(
+            "/messages/batches/{id}/results",
+            get(message_batches::get_batch_results),
+        );
+
     // API routes
     let api_routes = Router::new()
         .route("/chat/completions", post(openai::chat_completions))
-        .route("/models", get(openai::list_models))
         .route("/messages", post(anthropic::messages))
-        .route("/messages/count_tokens", post(anthropic::count_tokens));
-
-    let app = NormalizePath::trim_trailing_slash(
-        Router::new()
-            .route("/health", get(health::health))
-            .route("/version", get(health::version))
-            .nest("/admin", admin_routes)
-            .nest("/v1", api_routes)
-            .layer(cors)
-            .layer(DefaultBodyLimit::max(100 * 1024 * 1024)) // 100 MB
-            .with_state(state),
-    );
+        .route("/messages/count_tokens", post(anthropic::count_tokens))
+        .route(
+            "/messages/count_tokens/batch",
+            post(count_tokens_batch::count_tokens_batch),
+        )
+        .route("/messages/estimate", post(estimate::estimate))
+        .route("/messages/batches", post(message_batches::create_batch))
+        .merge(compression::compressed(listing_routes, config.compression))
+        .layer(middleware::from_fn(proxy_headers::annotate))
+        .layer(middleware::from_fn_with_state(
+            state.readiness.clone(),
+            readiness::gate::<AuthStore>,
+        ))
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            config.max_body_bytes,
+            body_limit::json_payload_too_large,
+        ))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|req: &Request| telemetry::request_span(req))
+                .on_request(())
+                .on_response(())
+                .on_failure(()),
+        );
+
+    let export_routes =
+        Router::new().route("/export/{token}", get(usage_export::download_usage_export));
+
+    // Inference routes stay uncompressed: they may answer with SSE
+    let router = Router::new()
+        .route("/health", get(health::health))
+        .route("/health/ready", get(readiness::ready::<AuthStore>))
+        .route("/version", get(health::version))
+        .merge(compression::compressed(export_routes, config.compression))
+        .nest(
+            "/admin",
+            compression::compressed(admin_routes, config.compression),
+        )
+        .nest("/v1", api_routes)
+        .layer(cors)
+        .layer(DefaultBodyLimit::max(100 * 1024 * 1024)) // 100 MB
+        .with_state(state);
+    let app = NormalizePath::trim_trailing_slash(router);
 
     let bind_addr = format!("{}:{}", host, port);
     let addr: SocketAddr = bind_addr
@@ -342,9 +711,12 @@ async fn main() -> Result<()> {
     let listener = TcpListener::bind(addr)
         .await
         .with_context(|| format!("Failed to bind {addr}"))?;
-    serve(listener, ServiceExt::<Request>::into_make_service(app))
-        .await
-        .context("HTTP server failed")?;
+    serve(
+        listener,
+        ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app),
+    )
+    .await
+    .context("HTTP server failed")?;
 
     Ok(())
 }
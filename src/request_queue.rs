@@ -0,0 +1,234 @@
+//! Priority-aware admission in front of upstream forwarding.
+//!
+//! At most `max_in_flight` inference requests talk to Anthropic at once.
+//! When all slots are taken, new requests wait in a queue ordered by their
+//! key's [`Priority`] (FIFO within a priority), so interactive keys overtake
+//! batch ones. Low-priority requests give up with 503 after `low_max_wait`;
+//! the others wait as long as it takes.
+//!
+//! A slot is held by a [`QueuePermit`] and freed when the permit is dropped.
+//! Streaming handlers attach the permit to the response stream so the slot
+//! stays taken until the stream ends. Freed slots are handed directly to the
+//! next waiter, so a new arrival can't jump the queue.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use futures_util::{Stream, StreamExt};
+use tokio::sync::oneshot;
+
+use crate::auth::Priority;
+use crate::error::ProxyError;
+
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    wake: oneshot::Sender<QueuePermit>,
+}
+
+impl Ord for Waiter {
+    // Max-heap: highest priority first, then earliest arrival.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+#[derive(Default)]
+struct QueueState {
+    in_flight: usize,
+    next_seq: u64,
+    waiters: BinaryHeap<Waiter>,
+}
+
+pub struct RequestQueue {
+    /// `None` = unlimited, every request is admitted immediately
+    max_in_flight: Option<usize>,
+    low_max_wait: Duration,
+    state: Mutex<QueueState>,
+}
+
+/// A slot in the queue. Dropping it admits the next waiter.
+pub struct QueuePermit {
+    queue: Option<Arc<RequestQueue>>,
+}
+
+impl RequestQueue {
+    pub fn new(max_in_flight: Option<usize>, low_max_wait: Duration) -> Self {
+        Self {
+            max_in_flight,
+            low_max_wait,
+            state: Mutex::new(QueueState::default()),
+        }
+    }
+
+    /// Wait for a free slot. Fails with [`ProxyError::QueueTimeout`] when a
+    /// low-priority request waits longer than the configured limit.
+    pub async fn acquire(self: &Arc<Self>, priority: Priority) -> Result<QueuePermit, ProxyError> {
+        let Some(max_in_flight) = self.max_in_flight else {
+            return Ok(QueuePermit { queue: None });
+        };
+
+        let mut rx = {
+            let mut state = self.lock();
+            if state.in_flight < max_in_flight {
+                state.in_flight += 1;
+                return Ok(QueuePermit {
+                    queue: Some(self.clone()),
+                });
+            }
+            let (wake, rx) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiters.push(Waiter {
+                priority,
+                seq,
+                wake,
+            });
+            rx
+        };
+
+        let woken = if priority == Priority::Low {
+            tokio::time::timeout(self.low_max_wait, &mut rx).await
+        } else {
+            Ok((&mut rx).await)
+        };
+        match woken {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => {
+                // Timed out, but a slot may have been handed over just as the
+                // wait expired; take it rather than leak it.
+                rx.close();
+                rx.try_recv()
+                    .ok()
+                    .ok_or(ProxyError::QueueTimeout(self.low_max_wait))
+            }
+        }
+    }
+
+    /// Hand the freed slot to the highest-priority live waiter, or return it
+    /// to the pool if nobody is waiting.
+    fn release(self: Arc<Self>) {
+        let mut handoff = QueuePermit {
+            queue: Some(self.clone()),
+        };
+        let mut state = self.lock();
+        while let Some(waiter) = state.waiters.pop() {
+            // A waiter whose receiver is gone (timed out, client left) hands
+            // the permit straight back.
+            match waiter.wake.send(handoff) {
+                Ok(()) => return,
+                Err(returned) => handoff = returned,
+            }
+        }
+        state.in_flight = state.in_flight.saturating_sub(1);
+        handoff.queue = None;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        // A poisoned lock only means another thread panicked mid-update; the
+        // counters are still usable.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl QueuePermit {
+    /// Keep the slot until `stream` is finished or dropped.
+    pub fn hold_for<S: Stream>(self, stream: S) -> impl Stream<Item = S::Item> {
+        stream.map(move |item| {
+            let _held = &self;
+            item
+        })
+    }
+}
+
+impl Drop for QueuePermit {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(max_in_flight: usize) -> Arc<RequestQueue> {
+        Arc::new(RequestQueue::new(
+            Some(max_in_flight),
+            Duration::from_millis(50),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_overtakes_queued_low() {
+        let queue = queue(1);
+        let running = queue.acquire(Priority::Normal).await.unwrap();
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        let spawn_waiter = |priority: Priority| {
+            let queue = queue.clone();
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let permit = queue.acquire(priority).await;
+                order_tx.send(priority).unwrap();
+                drop(permit);
+            })
+        };
+
+        let low = spawn_waiter(Priority::Low);
+        tokio::task::yield_now().await;
+        let high = spawn_waiter(Priority::High);
+        tokio::task::yield_now().await;
+
+        drop(running);
+        high.await.unwrap();
+        low.await.unwrap();
+        assert_eq!(order_rx.recv().await, Some(Priority::High));
+        assert_eq!(order_rx.recv().await, Some(Priority::Low));
+    }
+
+    #[tokio::test]
+    async fn test_low_priority_times_out() {
+        let queue = queue(1);
+        let _running = queue.acquire(Priority::High).await.unwrap();
+        let result = queue.acquire(Priority::Low).await;
+        assert!(matches!(result, Err(ProxyError::QueueTimeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_waiter_does_not_leak_slot() {
+        let queue = queue(1);
+        let running = queue.acquire(Priority::Normal).await.unwrap();
+        let result = queue.acquire(Priority::Low).await;
+        assert!(matches!(result, Err(ProxyError::QueueTimeout(_))));
+        drop(running);
+        let _next = queue.acquire(Priority::Low).await.unwrap();
+        assert_eq!(queue.lock().in_flight, 1);
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_admits_immediately() {
+        let queue = Arc::new(RequestQueue::new(None, Duration::ZERO));
+        let _a = queue.acquire(Priority::Low).await.unwrap();
+        let _b = queue.acquire(Priority::Low).await.unwrap();
+    }
+}
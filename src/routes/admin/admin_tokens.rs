@@ -0,0 +1,152 @@
+use axum::{Json, extract::Path, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{ErrorResponse, SuccessResponse};
+use crate::admin_session::AdminRole;
+use crate::admin_tokens::{self, AdminToken};
+use crate::error::ProxyError;
+
+const MAX_TOKEN_NAME_LENGTH: usize = 100;
+
+// --- Types ---
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAdminTokenRequest {
+    name: String,
+    /// Limit the token to GET requests, like the read-only admin login
+    #[serde(default)]
+    read_only: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateAdminTokenResponse {
+    pub id: String,
+    /// `sk-admin-*` secret; shown only in this response
+    pub token: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub read_only: bool,
+    pub created_at: u64,
+    pub revoked_at: Option<u64>,
+}
+
+impl From<AdminToken> for AdminTokenInfo {
+    fn from(token: AdminToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            read_only: token.role == AdminRole::ReadOnly,
+            created_at: token.created_at,
+            revoked_at: token.revoked_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ListAdminTokensResponse {
+    pub tokens: Vec<AdminTokenInfo>,
+}
+
+fn internal(e: ProxyError) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+// --- Handlers ---
+
+/// Mint a bearer token for scripting the admin API
+#[utoipa::path(
+    post,
+    path = "/tokens",
+    tag = "tokens",
+    request_body = CreateAdminTokenRequest,
+    responses(
+        (status = 200, body = CreateAdminTokenResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn create_admin_token(
+    Json(body): Json<CreateAdminTokenRequest>,
+) -> Result<Json<CreateAdminTokenResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let name = body.name.trim();
+    if name.is_empty()
+        || name.chars().count() > MAX_TOKEN_NAME_LENGTH
+        || name.chars().any(char::is_control)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!(
+                    "Token name must be 1-{MAX_TOKEN_NAME_LENGTH} characters without control characters"
+                ),
+            }),
+        ));
+    }
+    let role = if body.read_only {
+        AdminRole::ReadOnly
+    } else {
+        AdminRole::Full
+    };
+    let (token, secret) = admin_tokens::mint(name, role).await.map_err(internal)?;
+    Ok(Json(CreateAdminTokenResponse {
+        id: token.id,
+        token: secret,
+    }))
+}
+
+/// List admin tokens, revoked ones included
+#[utoipa::path(
+    get,
+    path = "/tokens",
+    tag = "tokens",
+    responses(
+        (status = 200, body = ListAdminTokensResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn list_admin_tokens()
+-> Result<Json<ListAdminTokensResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let tokens = admin_tokens::list().await.map_err(internal)?;
+    Ok(Json(ListAdminTokensResponse {
+        tokens: tokens.into_iter().map(AdminTokenInfo::from).collect(),
+    }))
+}
+
+/// Revoke an admin token; it stops working on the next request
+#[utoipa::path(
+    delete,
+    path = "/tokens/{id}",
+    tag = "tokens",
+    params(("id" = String, Path, description = "Token ID")),
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn revoke_admin_token(
+    Path(id): Path<String>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match admin_tokens::revoke(&id).await {
+        Ok(true) => Ok(Json(SuccessResponse { success: true })),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Token not found or already revoked".into(),
+            }),
+        )),
+        Err(e) => Err(internal(e)),
+    }
+}
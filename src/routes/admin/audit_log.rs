@@ -0,0 +1,148 @@
+use axum::{Json, extract::Query, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::ErrorResponse;
+use super::request_log::{Cursor, page_size};
+use crate::auth::client_keys::i64_to_u64;
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+
+// --- Types ---
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogQuery {
+    pub username: Option<String>,
+    /// Only entries recorded at or after this time (epoch ms)
+    pub from: Option<i64>,
+    /// Only entries recorded before this time (epoch ms)
+    pub to: Option<i64>,
+    /// `nextCursor` from the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogEntry {
+    pub id: i64,
+    pub created_at: u64,
+    /// Admin login; `anonymous` when admin auth is disabled
+    pub username: String,
+    pub method: String,
+    pub path: String,
+    /// HTTP status the request was answered with
+    pub status: u16,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogPage {
+    /// Newest first
+    pub entries: Vec<AuditLogEntry>,
+    /// Pass as `cursor` to get the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Cursor for the page after `entries`, or `None` if this page wasn't full.
+fn next_cursor(entries: &[AuditLogEntry], page_size: u32) -> Option<String> {
+    if entries.len() < page_size as usize {
+        return None;
+    }
+    entries.last().map(|entry| {
+        Cursor {
+            created_at: entry.created_at as i64,
+            id: entry.id,
+        }
+        .encode()
+    })
+}
+
+async fn query_audit_log(
+    query: &AuditLogQuery,
+    cursor: Option<Cursor>,
+    limit: u32,
+) -> Result<Vec<AuditLogEntry>, ProxyError> {
+    let conn = db::get_conn().await?;
+    let rows = sqlx::query!(
+        "SELECT id, created_at, username, method, path, status \
+         FROM admin_audit_log \
+         WHERE ($1::TEXT IS NULL OR username = $1) \
+         AND ($2::BIGINT IS NULL OR created_at >= $2) \
+         AND ($3::BIGINT IS NULL OR created_at < $3) \
+         AND ($4::BIGINT IS NULL OR (created_at, id) < ($4, $5)) \
+         ORDER BY created_at DESC, id DESC \
+         LIMIT $6",
+        query.username.as_deref(),
+        query.from,
+        query.to,
+        cursor.map(|c| c.created_at),
+        cursor.map_or(0, |c| c.id),
+        i64::from(limit),
+    )
+    .fetch_all(&conn)
+    .await
+    .db_context("Failed to read audit log")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| AuditLogEntry {
+            id: row.id,
+            created_at: i64_to_u64(row.created_at),
+            username: row.username,
+            method: row.method,
+            path: row.path,
+            status: u16::try_from(row.status).unwrap_or_default(),
+        })
+        .collect())
+}
+
+// --- Handlers ---
+
+/// Browse recorded admin changes, newest first
+#[utoipa::path(
+    get,
+    path = "/audit-log",
+    params(
+        ("username" = Option<String>, Query, description = "Only this admin's actions"),
+        ("from" = Option<i64>, Query, description = "Recorded at or after (epoch ms)"),
+        ("to" = Option<i64>, Query, description = "Recorded before (epoch ms)"),
+        ("cursor" = Option<String>, Query, description = "nextCursor of the previous page"),
+        ("limit" = Option<u32>, Query, description = "Page size (default 100, max 1000)"),
+    ),
+    responses(
+        (status = 200, body = AuditLogPage),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn get_audit_log(
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<AuditLogPage>, (StatusCode, Json<ErrorResponse>)> {
+    let cursor = match query.cursor.as_deref() {
+        Some(raw) => Some(Cursor::parse(raw).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Invalid cursor".into(),
+                }),
+            )
+        })?),
+        None => None,
+    };
+    let limit = page_size(query.limit);
+    let entries = query_audit_log(&query, cursor, limit).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
+    let next_cursor = next_cursor(&entries, limit);
+    Ok(Json(AuditLogPage {
+        entries,
+        next_cursor,
+    }))
+}
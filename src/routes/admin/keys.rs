@@ -1,18 +1,35 @@
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use utoipa::ToSchema;
 
-use super::{ErrorResponse, SuccessResponse, validate_key_name};
+use super::{
+    ErrorResponse, SuccessResponse, UsageHistoryQuery, normalize_key_note, validate_key_name,
+};
 use crate::AppState;
-use crate::auth::{ClientKey, ModelUsageEntry, TokenLimits, TokenUsage, UsageResetType};
+use crate::auth::usage_forecast::{self, UsageForecast};
+use crate::auth::{
+    CacheStats, ClientKey, EffectiveModelLimits, IpCidr, KeySettings, ModelPricing,
+    ModelUsageEntry, TokenLimits, TokenUsage, UsageResetType, WindowOverrides, effective_model_ids,
+    normalize_origin,
+};
+use crate::error::ProxyError;
+use crate::subscription::timestamp_millis;
+use crate::usage::history::HistoryPeriod;
 
 // --- Types ---
 
+#[derive(Serialize, ToSchema)]
+pub struct KeyCacheStatsResponse {
+    pub period: String,
+    #[serde(flatten)]
+    pub stats: CacheStats,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct CreateKeyResponse {
     pub key: String,
@@ -35,6 +52,11 @@ pub struct CreateKeyRequest {
     name: String,
 }
 
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct CloneKeyRequest {
+    name: String,
+}
+
 #[derive(Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateLimitsRequest {
@@ -44,6 +66,60 @@ pub struct UpdateLimitsRequest {
     total_limit: Option<u64>,
 }
 
+/// Result of a limits update, with a preview of what the limits buy.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UpdateLimitsResponse {
+    pub success: bool,
+    /// Token budgets at the reference model's prices; `null` if that model
+    /// isn't in the catalog
+    pub estimate: Option<LimitsEstimate>,
+}
+
+/// Rough token budgets per window for one model's prices. A window is
+/// `null` when it has no limit.
+#[derive(Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LimitsEstimate {
+    pub model: String,
+    pub five_hour: Option<TokenBudget>,
+    pub weekly: Option<TokenBudget>,
+    pub total: Option<TokenBudget>,
+}
+
+/// Tokens a cost limit buys if spent entirely on input or entirely on
+/// output. Real traffic lands in between. `null` for a free token type.
+#[derive(Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenBudget {
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct KeyNoteResponse {
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct SetKeyNoteRequest {
+    /// Free-form note, max 1000 characters; `null` or empty clears it
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyForceModelResponse {
+    pub force_model: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SetKeyForceModelRequest {
+    /// Enabled model id every request of the key is sent to; `null` or empty
+    /// lets clients pick again
+    pub force_model: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, ToSchema)]
 pub struct SetKeyEnabledRequest {
     enabled: bool,
@@ -54,6 +130,21 @@ pub struct SetAllowExtraUsageRequest {
     allow_extra_usage: bool,
 }
 
+/// Result of toggling `enabled`; carries the stored value so clients can
+/// update without re-fetching the key.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KeyEnabledResponse {
+    pub success: bool,
+    pub enabled: bool,
+}
+
+/// Result of toggling `allow_extra_usage`, with the stored value.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AllowExtraUsageResponse {
+    pub success: bool,
+    pub allow_extra_usage: bool,
+}
+
 #[derive(Deserialize, Serialize, ToSchema)]
 pub struct ResetUsageRequest {
     /// Which counter to reset: "hourly", "weekly", "total", or "all"
@@ -61,6 +152,30 @@ pub struct ResetUsageRequest {
     reset_type: String,
 }
 
+impl ResetUsageRequest {
+    fn parse_reset_type(&self) -> Result<UsageResetType, (StatusCode, Json<ErrorResponse>)> {
+        match self.reset_type.to_lowercase().as_str() {
+            "fivehour" | "hourly" => Ok(UsageResetType::FiveHour),
+            "weekly" => Ok(UsageResetType::Weekly),
+            "total" => Ok(UsageResetType::Total),
+            "all" => Ok(UsageResetType::All),
+            _ => Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Invalid reset type. Use: fiveHour, weekly, total, or all".into(),
+                }),
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetAllUsageResponse {
+    /// Number of keys whose counters were reset
+    pub keys_reset: u64,
+}
+
 // --- Per-key model types ---
 
 #[derive(Serialize, ToSchema)]
@@ -70,16 +185,75 @@ pub struct KeyModelsResponse {
     pub models: Vec<String>,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct EffectiveModelsResponse {
+    /// Model ids the key can use right now, in catalog order
+    pub models: Vec<String>,
+}
+
 #[derive(Deserialize, Serialize, ToSchema)]
 pub struct SetKeyModelsRequest {
     pub models: Vec<String>,
 }
 
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyIpsResponse {
+    pub allow_all: bool,
+    pub cidrs: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct SetKeyIpsRequest {
+    /// CIDR ranges or bare addresses, e.g. `10.0.0.0/8` or `203.0.113.7`
+    pub cidrs: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyOriginsResponse {
+    pub allow_all: bool,
+    pub origins: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct SetKeyOriginsRequest {
+    /// Browser origins, e.g. `https://app.example.com` or `http://localhost:5173`
+    pub origins: Vec<String>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct KeyModelUsageResponse {
     pub entries: Vec<ModelUsageEntry>,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct KeyEffectiveLimitsResponse {
+    /// One entry per model the key can use, in catalog order
+    pub models: Vec<EffectiveModelLimits>,
+}
+
+/// Lifetime of an export link when the request gives none
+const DEFAULT_EXPORT_LINK_TTL_SECS: u64 = 24 * 3600;
+/// Longest accepted export link lifetime
+const MAX_EXPORT_LINK_TTL_SECS: u64 = 7 * 24 * 3600;
+
+#[derive(Default, Deserialize, ToSchema)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CreateExportLinkRequest {
+    /// Seconds until the link expires (default 1 day, max 7 days)
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportLinkResponse {
+    /// Download path, relative to the proxy's origin
+    pub path: String,
+    /// Epoch ms
+    pub expires_at: u64,
+}
+
 // --- Handlers ---
 
 /// Create a new API key
@@ -123,6 +297,56 @@ pub async fn create_key(
     }
 }
 
+/// Create a new API key with the limits and model access of an existing one
+#[utoipa::path(
+    post,
+    path = "/keys/{id}/clone",
+    tag = "keys",
+    params(("id" = String, Path, description = "Source key ID")),
+    request_body = CloneKeyRequest,
+    responses(
+        (status = 200, body = CreateKeyResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn clone_key(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<CloneKeyRequest>,
+) -> Result<Json<CreateKeyResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let name = body.name.trim().to_string();
+
+    if let Err(e) = validate_key_name(&name) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        ));
+    }
+
+    match state.client_keys.clone_key(&id, name).await {
+        Ok(Some(key)) => Ok(Json(CreateKeyResponse {
+            key: key.key,
+            id: key.id,
+        })),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
 /// List all API keys
 #[utoipa::path(
     get,
@@ -135,14 +359,20 @@ pub async fn create_key(
 pub async fn list_keys(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<ListKeysResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let keys = state.client_keys.list().await.map_err(|e| {
+    let internal = |e: ProxyError| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
                 error: e.to_string(),
             }),
         )
-    })?;
+    };
+    let mut keys = state.client_keys.list().await.map_err(internal)?;
+    state
+        .client_keys
+        .fill_usage(&mut keys)
+        .await
+        .map_err(internal)?;
     Ok(Json(ListKeysResponse { keys }))
 }
 
@@ -187,7 +417,7 @@ pub async fn delete_key(
     params(("id" = String, Path, description = "Key ID")),
     request_body = SetKeyEnabledRequest,
     responses(
-        (status = 200, body = SuccessResponse),
+        (status = 200, body = KeyEnabledResponse),
         (status = 404, body = ErrorResponse),
         (status = 500, body = ErrorResponse),
     )
@@ -196,22 +426,14 @@ pub async fn set_key_enabled(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
     Json(body): Json<SetKeyEnabledRequest>,
-) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    match state.client_keys.set_enabled(&id, body.enabled).await {
-        Ok(true) => Ok(Json(SuccessResponse { success: true })),
-        Ok(false) => Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Key not found".into(),
-            }),
-        )),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: e.to_string(),
-            }),
-        )),
-    }
+) -> Result<Json<KeyEnabledResponse>, (StatusCode, Json<ErrorResponse>)> {
+    toggle_response(
+        state.client_keys.set_enabled(&id, body.enabled).await,
+        |enabled| KeyEnabledResponse {
+            success: true,
+            enabled,
+        },
+    )
 }
 
 /// Toggle allow_extra_usage for a key
@@ -222,7 +444,7 @@ pub async fn set_key_enabled(
     params(("id" = String, Path, description = "Key ID")),
     request_body = SetAllowExtraUsageRequest,
     responses(
-        (status = 200, body = SuccessResponse),
+        (status = 200, body = AllowExtraUsageResponse),
         (status = 404, body = ErrorResponse),
         (status = 500, body = ErrorResponse),
     )
@@ -231,14 +453,27 @@ pub async fn set_allow_extra_usage(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
     Json(body): Json<SetAllowExtraUsageRequest>,
-) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    match state
-        .client_keys
-        .set_allow_extra_usage(&id, body.allow_extra_usage)
-        .await
-    {
-        Ok(true) => Ok(Json(SuccessResponse { success: true })),
-        Ok(false) => Err((
+) -> Result<Json<AllowExtraUsageResponse>, (StatusCode, Json<ErrorResponse>)> {
+    toggle_response(
+        state
+            .client_keys
+            .set_allow_extra_usage(&id, body.allow_extra_usage)
+            .await,
+        |allow_extra_usage| AllowExtraUsageResponse {
+            success: true,
+            allow_extra_usage,
+        },
+    )
+}
+
+/// Map a toggle's stored value (`None` = key not found) to its response.
+fn toggle_response<T>(
+    result: Result<Option<bool>, ProxyError>,
+    respond: impl FnOnce(bool) -> T,
+) -> Result<Json<T>, (StatusCode, Json<ErrorResponse>)> {
+    match result {
+        Ok(Some(value)) => Ok(Json(respond(value))),
+        Ok(None) => Err((
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
                 error: "Key not found".into(),
@@ -253,23 +488,23 @@ pub async fn set_allow_extra_usage(
     }
 }
 
-/// Get usage statistics for a key
+/// Get a key's note
 #[utoipa::path(
     get,
-    path = "/keys/{id}/usage",
+    path = "/keys/{id}/note",
     tag = "keys",
     params(("id" = String, Path, description = "Key ID")),
     responses(
-        (status = 200, body = KeyUsageResponse),
+        (status = 200, body = KeyNoteResponse),
         (status = 404, body = ErrorResponse),
     )
 )]
-pub async fn get_key_usage(
+pub async fn get_key_note(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<KeyUsageResponse>, (StatusCode, Json<ErrorResponse>)> {
-    match state.client_keys.get_usage(&id).await {
-        Ok(Some((limits, usage))) => Ok(Json(KeyUsageResponse { limits, usage })),
+) -> Result<Json<KeyNoteResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.client_keys.get(&id).await {
+        Ok(Some(key)) => Ok(Json(KeyNoteResponse { note: key.note })),
         Ok(None) => Err((
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
@@ -285,32 +520,36 @@ pub async fn get_key_usage(
     }
 }
 
-/// Update limits for a key
+/// Set or clear a key's note (owner, purpose, ...)
 #[utoipa::path(
     put,
-    path = "/keys/{id}/limits",
+    path = "/keys/{id}/note",
     tag = "keys",
     params(("id" = String, Path, description = "Key ID")),
-    request_body = UpdateLimitsRequest,
+    request_body = SetKeyNoteRequest,
     responses(
-        (status = 200, body = SuccessResponse),
+        (status = 200, body = KeyNoteResponse),
+        (status = 400, body = ErrorResponse),
         (status = 404, body = ErrorResponse),
         (status = 500, body = ErrorResponse),
     )
 )]
-pub async fn update_key_limits(
+pub async fn set_key_note(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-    Json(body): Json<UpdateLimitsRequest>,
-) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let limits = TokenLimits {
-        five_hour_limit: body.five_hour_limit,
-        weekly_limit: body.weekly_limit,
-        total_limit: body.total_limit,
-    };
+    Json(body): Json<SetKeyNoteRequest>,
+) -> Result<Json<KeyNoteResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let note = normalize_key_note(body.note.as_deref()).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
 
-    match state.client_keys.set_limits(&id, limits).await {
-        Ok(true) => Ok(Json(SuccessResponse { success: true })),
+    match state.client_keys.set_note(&id, note.as_deref()).await {
+        Ok(true) => Ok(Json(KeyNoteResponse { note })),
         Ok(false) => Err((
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
@@ -326,42 +565,95 @@ pub async fn update_key_limits(
     }
 }
 
-/// Reset usage counters for a key
+/// Get the model a key is pinned to
 #[utoipa::path(
-    post,
-    path = "/keys/{id}/usage/reset",
+    get,
+    path = "/keys/{id}/force-model",
     tag = "keys",
     params(("id" = String, Path, description = "Key ID")),
-    request_body = ResetUsageRequest,
     responses(
-        (status = 200, body = SuccessResponse),
+        (status = 200, body = KeyForceModelResponse),
+        (status = 404, body = ErrorResponse),
+    )
+)]
+pub async fn get_key_force_model(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<KeyForceModelResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.client_keys.get(&id).await {
+        Ok(Some(key)) => Ok(Json(KeyForceModelResponse {
+            force_model: key.force_model,
+        })),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Pin a key to one model, replacing the model in every request it makes,
+/// or unpin it
+#[utoipa::path(
+    put,
+    path = "/keys/{id}/force-model",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    request_body = SetKeyForceModelRequest,
+    responses(
+        (status = 200, body = KeyForceModelResponse),
         (status = 400, body = ErrorResponse),
         (status = 404, body = ErrorResponse),
         (status = 500, body = ErrorResponse),
     )
 )]
-pub async fn reset_key_usage(
+pub async fn set_key_force_model(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-    Json(body): Json<ResetUsageRequest>,
-) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let reset_type = match body.reset_type.to_lowercase().as_str() {
-        "fivehour" | "hourly" => UsageResetType::FiveHour,
-        "weekly" => UsageResetType::Weekly,
-        "total" => UsageResetType::Total,
-        "all" => UsageResetType::All,
-        _ => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Invalid reset type. Use: fiveHour, weekly, total, or all".into(),
-                }),
-            ));
+    Json(body): Json<SetKeyForceModelRequest>,
+) -> Result<Json<KeyForceModelResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let force_model = body
+        .force_model
+        .as_deref()
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .map(str::to_string);
+
+    if let Some(model) = &force_model {
+        match state.models.is_valid(model).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: format!("Unknown or disabled model: {model}"),
+                    }),
+                ));
+            }
+            Err(e) => {
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        error: e.to_string(),
+                    }),
+                ));
+            }
         }
-    };
+    }
 
-    match state.client_keys.reset_usage(&id, reset_type).await {
-        Ok(true) => Ok(Json(SuccessResponse { success: true })),
+    match state
+        .client_keys
+        .set_force_model(&id, force_model.as_deref())
+        .await
+    {
+        Ok(true) => Ok(Json(KeyForceModelResponse { force_model })),
         Ok(false) => Err((
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
@@ -377,59 +669,632 @@ pub async fn reset_key_usage(
     }
 }
 
-// ========================================================================
-// Per-key model access
-// ========================================================================
-
-/// Get allowed models for a key
+/// Get usage statistics for a key
 #[utoipa::path(
     get,
-    path = "/keys/{id}/models",
+    path = "/keys/{id}/usage",
     tag = "keys",
     params(("id" = String, Path, description = "Key ID")),
     responses(
-        (status = 200, body = KeyModelsResponse),
+        (status = 200, body = KeyUsageResponse),
+        (status = 404, body = ErrorResponse),
     )
 )]
-pub async fn get_key_models(
+pub async fn get_key_usage(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<KeyModelsResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let models = state
-        .client_keys
-        .get_allowed_models(&id)
-        .await
-        .map_err(|e| {
-            (
+) -> Result<Json<KeyUsageResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.client_keys.get_usage(&id).await {
+        Ok(Some((limits, usage))) => Ok(Json(KeyUsageResponse { limits, usage })),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Project when a key reaches its 5-hour and weekly limits
+///
+/// Extrapolates the key's spend over the last hour. A window's time is
+/// `null` when it has no limit or isn't on course to run out before it
+/// resets.
+#[utoipa::path(
+    get,
+    path = "/keys/{id}/usage/forecast",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    responses(
+        (status = 200, body = UsageForecast),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn get_key_usage_forecast(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<UsageForecast>, (StatusCode, Json<ErrorResponse>)> {
+    let internal = |e: ProxyError| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    };
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        )
+    };
+
+    let key = state
+        .client_keys
+        .get(&id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(not_found)?;
+    let (_, usage) = state
+        .client_keys
+        .get_usage(&id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(not_found)?;
+    usage_forecast::forecast(&key, &usage, timestamp_millis())
+        .await
+        .map(Json)
+        .map_err(internal)
+}
+
+/// Update limits for a key
+#[utoipa::path(
+    put,
+    path = "/keys/{id}/limits",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    request_body = UpdateLimitsRequest,
+    responses(
+        (status = 200, body = UpdateLimitsResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn update_key_limits(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<UpdateLimitsRequest>,
+) -> Result<Json<UpdateLimitsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let limits = TokenLimits {
+        five_hour_limit: body.five_hour_limit,
+        weekly_limit: body.weekly_limit,
+        total_limit: body.total_limit,
+    };
+
+    match state.client_keys.set_limits(&id, limits.clone()).await {
+        Ok(true) => {
+            let model = &state.limit_reference_model;
+            let estimate = state
+                .models
+                .get_pricing(model)
+                .await
+                .map(|pricing| estimate_limits(&limits, model, &pricing));
+            Ok(Json(UpdateLimitsResponse {
+                success: true,
+                estimate,
+            }))
+        }
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Token budgets for `limits` (microdollars) at `pricing`.
+fn estimate_limits(limits: &TokenLimits, model: &str, pricing: &ModelPricing) -> LimitsEstimate {
+    let budget = |limit: Option<u64>| {
+        limit.map(|microdollars| TokenBudget {
+            input_tokens: tokens_for(microdollars, pricing.input_price),
+            output_tokens: tokens_for(microdollars, pricing.output_price),
+        })
+    };
+    LimitsEstimate {
+        model: model.to_string(),
+        five_hour: budget(limits.five_hour_limit),
+        weekly: budget(limits.weekly_limit),
+        total: budget(limits.total_limit),
+    }
+}
+
+/// Whole tokens `microdollars` buys at `price` (microdollars per token).
+fn tokens_for(microdollars: u64, price: f64) -> Option<u64> {
+    (price > 0.0).then(|| {
+        #[expect(
+            clippy::cast_sign_loss,
+            reason = "microdollars and positive prices give a non-negative quotient"
+        )]
+        {
+            (microdollars as f64 / price).floor() as u64
+        }
+    })
+}
+
+/// Reset usage counters for a key
+#[utoipa::path(
+    post,
+    path = "/keys/{id}/usage/reset",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    request_body = ResetUsageRequest,
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn reset_key_usage(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<ResetUsageRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let reset_type = body.parse_reset_type()?;
+
+    match state.client_keys.reset_usage(&id, reset_type).await {
+        Ok(true) => Ok(Json(SuccessResponse { success: true })),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Reset usage counters for every key at once
+///
+/// Same as the per-key reset, applied to all keys together (e.g. `total` at
+/// the start of a billing period). Request history is kept.
+#[utoipa::path(
+    post,
+    path = "/keys/usage/reset-all",
+    tag = "keys",
+    request_body = ResetUsageRequest,
+    responses(
+        (status = 200, body = ResetAllUsageResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn reset_all_key_usage(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<ResetUsageRequest>,
+) -> Result<Json<ResetAllUsageResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let reset_type = body.parse_reset_type()?;
+
+    match state.client_keys.reset_all_usage(reset_type).await {
+        Ok(keys_reset) => Ok(Json(ResetAllUsageResponse { keys_reset })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Set or clear a key's window reset and count-from times and lengths by hand
+///
+/// Meant for testing and for aligning a key with a known subscription
+/// boundary. The values replace what request handling maintains, so
+/// careless use skews the key's usage accounting: a late `countFrom` hides
+/// spend from its limits, an early one counts old spend again. A new window
+/// length applies from the window's next rollover.
+#[utoipa::path(
+    put,
+    path = "/keys/{id}/windows",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    request_body = WindowOverrides,
+    responses(
+        (status = 200, body = KeyUsageResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn set_key_windows(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<WindowOverrides>,
+) -> Result<Json<KeyUsageResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let internal = |e: ProxyError| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    };
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        )
+    };
+
+    if let Err(error) = body.validate(timestamp_millis()) {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse { error })));
+    }
+    if !state
+        .client_keys
+        .set_windows(&id, &body)
+        .await
+        .map_err(internal)?
+    {
+        return Err(not_found());
+    }
+    match state.client_keys.get_usage(&id).await.map_err(internal)? {
+        Some((limits, usage)) => Ok(Json(KeyUsageResponse { limits, usage })),
+        None => Err(not_found()),
+    }
+}
+
+// ========================================================================
+// Per-key model access
+// ========================================================================
+
+/// Get allowed models for a key
+#[utoipa::path(
+    get,
+    path = "/keys/{id}/models",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    responses(
+        (status = 200, body = KeyModelsResponse),
+    )
+)]
+pub async fn get_key_models(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<KeyModelsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let models = state
+        .client_keys
+        .get_allowed_models(&id)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+    let allow_all = models.is_empty();
+    Ok(Json(KeyModelsResponse { allow_all, models }))
+}
+
+/// Get the models a key can actually use: enabled, in-schedule models
+/// intersected with the key's allow-list
+#[utoipa::path(
+    get,
+    path = "/keys/{id}/models/effective",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    responses(
+        (status = 200, body = EffectiveModelsResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn get_key_effective_models(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<EffectiveModelsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let internal = |e: ProxyError| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    };
+
+    if state
+        .client_keys
+        .get(&id)
+        .await
+        .map_err(internal)?
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        ));
+    }
+    let allowed = state
+        .client_keys
+        .get_allowed_models(&id)
+        .await
+        .map_err(internal)?;
+    let models = state.models.list().await.map_err(internal)?;
+
+    Ok(Json(EffectiveModelsResponse {
+        models: effective_model_ids(&models, &allowed, timestamp_millis() as i64),
+    }))
+}
+
+/// Set allowed models for a key (empty = allow all)
+#[utoipa::path(
+    put,
+    path = "/keys/{id}/models",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    request_body = SetKeyModelsRequest,
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn set_key_models(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<SetKeyModelsRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.client_keys.set_allowed_models(&id, body.models).await {
+        Ok(_) => Ok(Json(SuccessResponse { success: true })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+// ========================================================================
+// Per-key IP allow-list
+// ========================================================================
+
+/// Get the IP allow-list for a key
+#[utoipa::path(
+    get,
+    path = "/keys/{id}/ips",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    responses(
+        (status = 200, body = KeyIpsResponse),
+    )
+)]
+pub async fn get_key_ips(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<KeyIpsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let cidrs = state.client_keys.get_allowed_ips(&id).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
+    let allow_all = cidrs.is_empty();
+    Ok(Json(KeyIpsResponse { allow_all, cidrs }))
+}
+
+/// Set the IP allow-list for a key (empty = allow any IP)
+#[utoipa::path(
+    put,
+    path = "/keys/{id}/ips",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    request_body = SetKeyIpsRequest,
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn set_key_ips(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<SetKeyIpsRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let cidrs = body
+        .cidrs
+        .iter()
+        .map(|c| c.parse::<IpCidr>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })))?;
+
+    match state.client_keys.get(&id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Key not found".into(),
+                }),
+            ));
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            ));
+        }
+    }
+
+    match state.client_keys.set_allowed_ips(&id, &cidrs).await {
+        Ok(()) => Ok(Json(SuccessResponse { success: true })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+// ========================================================================
+// Per-key origin allow-list
+// ========================================================================
+
+/// Get the browser origin allow-list for a key
+#[utoipa::path(
+    get,
+    path = "/keys/{id}/origins",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    responses(
+        (status = 200, body = KeyOriginsResponse),
+    )
+)]
+pub async fn get_key_origins(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<KeyOriginsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let origins = state
+        .client_keys
+        .get_allowed_origins(&id)
+        .await
+        .map_err(|e| {
+            (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ErrorResponse {
                     error: e.to_string(),
                 }),
             )
         })?;
-    let allow_all = models.is_empty();
-    Ok(Json(KeyModelsResponse { allow_all, models }))
+    let allow_all = origins.is_empty();
+    Ok(Json(KeyOriginsResponse { allow_all, origins }))
 }
 
-/// Set allowed models for a key (empty = allow all)
+/// Set the browser origin allow-list for a key (empty = any origin).
+/// Requests carrying an `Origin` header outside the list get 403; requests
+/// without one (non-browser clients) are not affected.
 #[utoipa::path(
     put,
-    path = "/keys/{id}/models",
+    path = "/keys/{id}/origins",
     tag = "keys",
     params(("id" = String, Path, description = "Key ID")),
-    request_body = SetKeyModelsRequest,
+    request_body = SetKeyOriginsRequest,
     responses(
         (status = 200, body = SuccessResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
         (status = 500, body = ErrorResponse),
     )
 )]
-pub async fn set_key_models(
+pub async fn set_key_origins(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-    Json(body): Json<SetKeyModelsRequest>,
+    Json(body): Json<SetKeyOriginsRequest>,
 ) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    match state.client_keys.set_allowed_models(&id, body.models).await {
-        Ok(_) => Ok(Json(SuccessResponse { success: true })),
+    let origins = body
+        .origins
+        .iter()
+        .map(|o| normalize_origin(o))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })))?;
+
+    match state.client_keys.get(&id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Key not found".into(),
+                }),
+            ));
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            ));
+        }
+    }
+
+    match state.client_keys.set_allowed_origins(&id, &origins).await {
+        Ok(()) => Ok(Json(SuccessResponse { success: true })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Replace a key's settings (system prefix override, default max_tokens,
+/// forced cloaking). Omitted fields reset to the global behaviour.
+#[utoipa::path(
+    put,
+    path = "/keys/{id}/settings",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    request_body = KeySettings,
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn set_key_settings(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // Deserialize by hand so shape errors (unknown fields, wrong types) come
+    // back as a 400 with a readable message instead of axum's plain-text 422.
+    let settings = KeySettings::deserialize(&body)
+        .map_err(|e| e.to_string())
+        .and_then(|settings| settings.validate().map(|()| settings))
+        .map_err(|error| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })))?;
+
+    match state.client_keys.set_settings(&id, &settings).await {
+        Ok(true) => Ok(Json(SuccessResponse { success: true })),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        )),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
@@ -468,6 +1333,169 @@ pub async fn get_key_model_usage(
     Ok(Json(KeyModelUsageResponse { entries }))
 }
 
+/// Which limit binds first in each window, per model the key can use:
+/// the key-level limit or the per-model one, with current spend
+#[utoipa::path(
+    get,
+    path = "/keys/{id}/effective-limits",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    responses(
+        (status = 200, body = KeyEffectiveLimitsResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn get_key_effective_limits(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<KeyEffectiveLimitsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let internal = |e: ProxyError| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    };
+
+    let allowed = state
+        .client_keys
+        .get_allowed_models(&id)
+        .await
+        .map_err(internal)?;
+    let catalog = state.models.list().await.map_err(internal)?;
+    let model_ids = effective_model_ids(&catalog, &allowed, timestamp_millis() as i64);
+
+    match state
+        .client_keys
+        .get_effective_limits(&id, &model_ids)
+        .await
+        .map_err(internal)?
+    {
+        Some(models) => Ok(Json(KeyEffectiveLimitsResponse { models })),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        )),
+    }
+}
+
+/// Prompt-cache hit ratio and estimated savings for a key
+#[utoipa::path(
+    get,
+    path = "/keys/{id}/cache-stats",
+    tag = "keys",
+    params(
+        ("id" = String, Path, description = "Key ID"),
+        ("period" = Option<String>, Query, description = "Period: 24h, 7d, or 30d"),
+    ),
+    responses(
+        (status = 200, body = KeyCacheStatsResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn get_key_cache_stats(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<UsageHistoryQuery>,
+) -> Result<Json<KeyCacheStatsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let internal = |e: ProxyError| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    };
+
+    if state
+        .client_keys
+        .get(&id)
+        .await
+        .map_err(internal)?
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Key not found".into(),
+            }),
+        ));
+    }
+
+    let period = HistoryPeriod::parse(query.period.as_deref());
+    let stats = state
+        .client_keys
+        .get_cache_stats(&id, period.cutoff(timestamp_millis()))
+        .await
+        .map_err(internal)?;
+    Ok(Json(KeyCacheStatsResponse {
+        period: period.label().to_string(),
+        stats,
+    }))
+}
+
+/// Mint a signed, unauthenticated download link for a key's usage CSV
+#[utoipa::path(
+    post,
+    path = "/keys/{id}/export-link",
+    tag = "keys",
+    params(("id" = String, Path, description = "Key ID")),
+    request_body = CreateExportLinkRequest,
+    responses(
+        (status = 200, body = ExportLinkResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn create_export_link(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<CreateExportLinkRequest>,
+) -> Result<Json<ExportLinkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let ttl_secs = body.ttl_secs.unwrap_or(DEFAULT_EXPORT_LINK_TTL_SECS);
+    if !(1..=MAX_EXPORT_LINK_TTL_SECS).contains(&ttl_secs) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("ttlSecs must be between 1 and {MAX_EXPORT_LINK_TTL_SECS}"),
+            }),
+        ));
+    }
+
+    match state.client_keys.get(&id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Key not found".into(),
+                }),
+            ));
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            ));
+        }
+    }
+
+    let expires_at = timestamp_millis() + ttl_secs * 1000;
+    let token = state.export_links.mint(&id, expires_at);
+    Ok(Json(ExportLinkResponse {
+        path: format!("/export/{token}"),
+        expires_at,
+    }))
+}
+
 /// Set per-model limits for a key
 #[utoipa::path(
     put,
@@ -602,3 +1630,142 @@ pub async fn reset_key_model_usage(
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sonnet_pricing() -> ModelPricing {
+        // $3 / $15 per million tokens
+        ModelPricing {
+            input_price: 3.0,
+            output_price: 15.0,
+            cache_read_price: 0.3,
+            cache_write_price: 3.75,
+        }
+    }
+
+    #[test]
+    fn test_key_note_set_and_cleared() {
+        assert_eq!(
+            normalize_key_note(Some("  owner: ana@example.com\npurpose: CI  ")),
+            Ok(Some("owner: ana@example.com\npurpose: CI".to_string()))
+        );
+        assert_eq!(normalize_key_note(Some("   ")), Ok(None));
+        assert_eq!(normalize_key_note(None), Ok(None));
+    }
+
+    #[test]
+    fn test_key_note_rejected() {
+        assert!(normalize_key_note(Some(&"x".repeat(1001))).is_err_and(|e| e.contains("too long")));
+        let long = "é".repeat(1000);
+        assert_eq!(normalize_key_note(Some(&long)), Ok(Some(long.clone())));
+        assert!(
+            normalize_key_note(Some("bell\u{7}")).is_err_and(|e| e.contains("control characters"))
+        );
+    }
+
+    #[test]
+    fn test_key_note_listed_only_when_set() {
+        let mut key: ClientKey = serde_json::from_value(serde_json::json!({
+            "id": "k1",
+            "key": "sk-proxy-test",
+            "name": "ci",
+            "createdAt": 0,
+            "lastUsedAt": null,
+            "enabled": true,
+            "allowExtraUsage": false
+        }))
+        .unwrap();
+        assert!(serde_json::to_value(&key).unwrap().get("note").is_none());
+
+        key.note = Some("owner: ana@example.com".into());
+        assert_eq!(
+            serde_json::to_value(&key).unwrap()["note"],
+            "owner: ana@example.com"
+        );
+    }
+
+    #[test]
+    fn test_estimate_limits_at_known_prices() {
+        let limits = TokenLimits {
+            five_hour_limit: Some(1_500_000),
+            weekly_limit: Some(30_000_000),
+            total_limit: None,
+        };
+        let estimate = estimate_limits(&limits, "claude-sonnet-4-5", &sonnet_pricing());
+        assert_eq!(
+            estimate,
+            LimitsEstimate {
+                model: "claude-sonnet-4-5".into(),
+                five_hour: Some(TokenBudget {
+                    input_tokens: Some(500_000),
+                    output_tokens: Some(100_000),
+                }),
+                weekly: Some(TokenBudget {
+                    input_tokens: Some(10_000_000),
+                    output_tokens: Some(2_000_000),
+                }),
+                total: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_estimate_rounds_down_and_skips_free_tokens() {
+        assert_eq!(tokens_for(10, 3.0), Some(3));
+        assert_eq!(tokens_for(0, 3.0), Some(0));
+        assert_eq!(tokens_for(1_000_000, 0.0), None);
+    }
+
+    #[test]
+    fn test_missing_reference_model_serializes_null() {
+        let response = UpdateLimitsResponse {
+            success: true,
+            estimate: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({ "success": true, "estimate": null })
+        );
+    }
+
+    fn enabled_response(
+        result: Result<Option<bool>, ProxyError>,
+    ) -> Result<Json<KeyEnabledResponse>, (StatusCode, Json<ErrorResponse>)> {
+        toggle_response(result, |enabled| KeyEnabledResponse {
+            success: true,
+            enabled,
+        })
+    }
+
+    #[test]
+    fn test_toggle_returns_stored_state() {
+        for requested in [true, false] {
+            let Json(response) = enabled_response(Ok(Some(requested))).unwrap();
+            assert!(response.success);
+            assert_eq!(response.enabled, requested);
+        }
+    }
+
+    #[test]
+    fn test_toggle_response_keeps_success_field() {
+        let Json(response) = toggle_response(Ok(Some(true)), |allow_extra_usage| {
+            AllowExtraUsageResponse {
+                success: true,
+                allow_extra_usage,
+            }
+        })
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({ "success": true, "allow_extra_usage": true })
+        );
+    }
+
+    #[test]
+    fn test_toggle_missing_key_is_404() {
+        let (status, _) = enabled_response(Ok(None)).unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -1,15 +1,25 @@
+mod admin_tokens;
+mod audit_log;
 mod keys;
 mod models;
 mod oauth;
+mod request_log;
 mod session;
+mod status;
+mod ui_config;
 mod usage_history;
 
 // Glob re-exports so utoipa's `routes!()` macro can find the hidden `__path_*` structs
 // alongside the handler functions at the `crate::routes::admin::*` path.
+pub use admin_tokens::*;
+pub use audit_log::*;
 pub use keys::*;
 pub use models::*;
 pub use oauth::*;
+pub use request_log::*;
 pub use session::*;
+pub use status::*;
+pub use ui_config::*;
 pub use usage_history::*;
 
 use axum::Router;
@@ -50,6 +60,26 @@ pub(super) fn validate_key_name(name: &str) -> Result<(), &'static str> {
     Ok(())
 }
 
+const MAX_KEY_NOTE_LENGTH: usize = 1000;
+
+/// Trim a key note; empty clears it. Line breaks and tabs are allowed,
+/// other control characters are not.
+pub(super) fn normalize_key_note(note: Option<&str>) -> Result<Option<String>, &'static str> {
+    let Some(note) = note.map(str::trim).filter(|n| !n.is_empty()) else {
+        return Ok(None);
+    };
+    if note.chars().count() > MAX_KEY_NOTE_LENGTH {
+        return Err("Key note too long (max 1000 characters)");
+    }
+    if note
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+    {
+        return Err("Key note cannot contain control characters");
+    }
+    Ok(Some(note.to_string()))
+}
+
 const MAX_MODEL_ID_LENGTH: usize = 100;
 
 pub(super) fn validate_model_id(id: &str) -> Result<(), &'static str> {
@@ -1,18 +1,33 @@
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use utoipa::ToSchema;
 
-use super::{ErrorResponse, SuccessResponse, validate_model_id, validate_price};
+use super::{ErrorResponse, SuccessResponse, UsageHistoryQuery, validate_model_id, validate_price};
 use crate::AppState;
-use crate::auth::Model;
+use crate::auth::client_keys::i64_to_u64;
+use crate::auth::{Model, ModelAlias, ModelCapabilities, ModelSort};
+use crate::constants::SEED_MODELS;
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+use crate::subscription::timestamp_millis;
+use crate::transforms::validate_reasoning_effort;
+use crate::usage::history::HistoryPeriod;
 
 // --- Types ---
 
+#[derive(Deserialize, ToSchema)]
+pub struct ListModelsQuery {
+    /// Only enabled (`true`) or disabled (`false`) models
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub sort: ModelSort,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct ListModelsResponse {
     pub models: Vec<Model>,
@@ -30,6 +45,19 @@ pub struct AddModelRequest {
     pub cache_read_price: f64,
     #[serde(default)]
     pub cache_write_price: f64,
+    #[serde(default = "default_true")]
+    pub supports_vision: bool,
+    #[serde(default = "default_true")]
+    pub supports_tools: bool,
+    #[serde(default = "default_true")]
+    pub supports_thinking: bool,
+    /// Thinking effort for OpenAI requests that don't set one
+    #[serde(default)]
+    pub default_effort: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Deserialize, Serialize, ToSchema)]
@@ -40,6 +68,25 @@ pub struct UpdateModelRequest {
     pub output_price: Option<f64>,
     pub cache_read_price: Option<f64>,
     pub cache_write_price: Option<f64>,
+    pub supports_vision: Option<bool>,
+    pub supports_tools: Option<bool>,
+    pub supports_thinking: Option<bool>,
+}
+
+/// Enable window in epoch ms. `null` clears that bound.
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SetModelScheduleRequest {
+    pub enabled_from: Option<i64>,
+    pub enabled_until: Option<i64>,
+}
+
+/// Default thinking effort (same values as `reasoning_effort`). `null`
+/// clears it.
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SetModelDefaultEffortRequest {
+    pub default_effort: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, ToSchema)]
@@ -47,6 +94,77 @@ pub struct ReorderModelsRequest {
     pub ids: Vec<String>,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct ListModelAliasesResponse {
+    pub aliases: Vec<ModelAlias>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct SetModelAliasRequest {
+    pub target: String,
+}
+
+/// Usage of one model summed over every key
+#[derive(Debug, Default, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelUsageRollup {
+    pub model: String,
+    pub period: String,
+    pub request_count: u64,
+    /// Distinct keys that used the model in the period
+    pub key_count: u64,
+    pub cost_microdollars: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+}
+
+/// Totals for `model` over requests logged since `from` (epoch ms), all keys
+/// together.
+async fn model_usage_rollup(
+    model: String,
+    period: String,
+    from: u64,
+) -> Result<ModelUsageRollup, ProxyError> {
+    let conn = db::get_conn().await?;
+    let row = sqlx::query!(
+        "SELECT COUNT(*) AS \"request_count!\", \
+         COUNT(DISTINCT key_id) AS \"key_count!\", \
+         COALESCE(SUM(cost_microdollars), 0)::BIGINT AS \"cost_microdollars!\", \
+         COALESCE(SUM(input_tokens), 0)::BIGINT AS \"input_tokens!\", \
+         COALESCE(SUM(output_tokens), 0)::BIGINT AS \"output_tokens!\", \
+         COALESCE(SUM(cache_read_tokens), 0)::BIGINT AS \"cache_read_tokens!\", \
+         COALESCE(SUM(cache_write_tokens), 0)::BIGINT AS \"cache_write_tokens!\" \
+         FROM request_log WHERE model = $1 AND created_at >= $2 \
+         GROUP BY model",
+        model,
+        from as i64,
+    )
+    .fetch_optional(&conn)
+    .await
+    .db_context("Failed to aggregate model usage")?;
+
+    let Some(row) = row else {
+        return Ok(ModelUsageRollup {
+            model,
+            period,
+            ..ModelUsageRollup::default()
+        });
+    };
+    Ok(ModelUsageRollup {
+        model,
+        period,
+        request_count: i64_to_u64(row.request_count),
+        key_count: i64_to_u64(row.key_count),
+        cost_microdollars: i64_to_u64(row.cost_microdollars),
+        input_tokens: i64_to_u64(row.input_tokens),
+        output_tokens: i64_to_u64(row.output_tokens),
+        cache_read_tokens: i64_to_u64(row.cache_read_tokens),
+        cache_write_tokens: i64_to_u64(row.cache_write_tokens),
+    })
+}
+
 // --- Handlers ---
 
 /// List all models (admin sees enabled + disabled)
@@ -54,14 +172,59 @@ pub struct ReorderModelsRequest {
     get,
     path = "/models",
     tag = "models",
+    params(
+        ("enabled" = Option<bool>, Query, description = "Only enabled (true) or disabled (false) models"),
+        ("sort" = Option<ModelSort>, Query, description = "sort_order (default), id or price"),
+    ),
     responses(
         (status = 200, body = ListModelsResponse),
     )
 )]
 pub async fn list_models_admin(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<ListModelsQuery>,
 ) -> Result<Json<ListModelsResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let models = state.models.list().await.map_err(|e| {
+    let models = state
+        .models
+        .list_filtered(query.enabled, query.sort)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+    Ok(Json(ListModelsResponse { models }))
+}
+
+/// Usage and cost of a model across all keys
+#[utoipa::path(
+    get,
+    path = "/models/{id}/usage",
+    tag = "models",
+    params(
+        ("id" = String, Path, description = "Model ID"),
+        ("period" = Option<String>, Query, description = "Period: 24h, 7d, or 30d"),
+    ),
+    responses(
+        (status = 200, body = ModelUsageRollup),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn get_model_usage_rollup(
+    Path(id): Path<String>,
+    Query(query): Query<UsageHistoryQuery>,
+) -> Result<Json<ModelUsageRollup>, (StatusCode, Json<ErrorResponse>)> {
+    let period = HistoryPeriod::parse(query.period.as_deref());
+    let rollup = model_usage_rollup(
+        id,
+        period.label().to_string(),
+        period.cutoff(timestamp_millis()),
+    )
+    .await
+    .map_err(|e| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
@@ -69,7 +232,7 @@ pub async fn list_models_admin(
             }),
         )
     })?;
-    Ok(Json(ListModelsResponse { models }))
+    Ok(Json(rollup))
 }
 
 /// Add a new model
@@ -112,6 +275,11 @@ pub async fn add_model(
         }
     }
 
+    let default_effort = match normalize_default_effort(body.default_effort.as_deref()) {
+        Ok(e) => e,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e }))),
+    };
+
     match state
         .models
         .add(
@@ -120,10 +288,28 @@ pub async fn add_model(
             body.output_price,
             body.cache_read_price,
             body.cache_write_price,
+            ModelCapabilities {
+                vision: body.supports_vision,
+                tools: body.supports_tools,
+                thinking: body.supports_thinking,
+            },
         )
         .await
     {
-        Ok(_) => Ok(Json(SuccessResponse { success: true })),
+        Ok(()) if default_effort.is_some() => state
+            .models
+            .set_default_effort(id, default_effort)
+            .await
+            .map(|_| Json(SuccessResponse { success: true }))
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        error: e.to_string(),
+                    }),
+                )
+            }),
+        Ok(()) => Ok(Json(SuccessResponse { success: true })),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
@@ -133,7 +319,26 @@ pub async fn add_model(
     }
 }
 
-/// Delete a model
+/// Refuse to delete a protected model (`CLAUDE_PROXY_PROTECTED_MODELS`).
+fn check_deletable(
+    id: &str,
+    protected: &[String],
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if protected.iter().any(|p| p == id) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: format!(
+                    "Model {id} is protected and can't be deleted; disable it instead \
+                     (protected models are set by CLAUDE_PROXY_PROTECTED_MODELS)"
+                ),
+            }),
+        ));
+    }
+    Ok(())
+}
+
+/// Delete a model. Protected models can still be disabled or repriced.
 #[utoipa::path(
     delete,
     path = "/models/{id}",
@@ -142,6 +347,7 @@ pub async fn add_model(
     responses(
         (status = 200, body = SuccessResponse),
         (status = 404, body = ErrorResponse),
+        (status = 409, body = ErrorResponse),
         (status = 500, body = ErrorResponse),
     )
 )]
@@ -149,6 +355,7 @@ pub async fn delete_model(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    check_deletable(&id, &state.protected_models)?;
     match state.models.remove(&id).await {
         Ok(true) => Ok(Json(SuccessResponse { success: true })),
         Ok(false) => Err((
@@ -166,7 +373,7 @@ pub async fn delete_model(
     }
 }
 
-/// Update a model (prices, enabled)
+/// Update a model (prices, enabled, capabilities)
 #[utoipa::path(
     put,
     path = "/models/{id}",
@@ -202,7 +409,7 @@ pub async fn update_model(
         }
     }
 
-    match state
+    let result = match state
         .models
         .update(
             &id,
@@ -213,6 +420,139 @@ pub async fn update_model(
             body.enabled,
         )
         .await
+    {
+        Ok(true) => {
+            state
+                .models
+                .set_capabilities(
+                    &id,
+                    body.supports_vision,
+                    body.supports_tools,
+                    body.supports_thinking,
+                )
+                .await
+        }
+        other => other,
+    };
+
+    match result {
+        Ok(true) => Ok(Json(SuccessResponse { success: true })),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Model not found".into(),
+            }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Seed prices of `id` as (input, output, cache read, cache write), or
+/// `None` if it isn't a seed model.
+fn seed_prices(id: &str) -> Option<(f64, f64, f64, f64)> {
+    SEED_MODELS.iter().find(|(seed_id, ..)| *seed_id == id).map(
+        |&(_, input, output, cache_read, cache_write)| (input, output, cache_read, cache_write),
+    )
+}
+
+/// Restore a seed model's four prices to their built-in defaults
+#[utoipa::path(
+    post,
+    path = "/models/{id}/reset-pricing",
+    tag = "models",
+    params(("id" = String, Path, description = "Model ID")),
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn reset_model_pricing(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let not_found = |error: &str| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: error.into(),
+            }),
+        )
+    };
+    let (input, output, cache_read, cache_write) =
+        seed_prices(&id).ok_or_else(|| not_found("Not a seed model; no default pricing"))?;
+
+    match state
+        .models
+        .update(
+            &id,
+            Some(input),
+            Some(output),
+            Some(cache_read),
+            Some(cache_write),
+            None,
+        )
+        .await
+    {
+        Ok(true) => Ok(Json(SuccessResponse { success: true })),
+        Ok(false) => Err(not_found("Model not found")),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Set or clear a model's enable schedule. Outside the window the model is
+/// treated as disabled even when `enabled` is true.
+#[utoipa::path(
+    put,
+    path = "/models/{id}/schedule",
+    tag = "models",
+    params(("id" = String, Path, description = "Model ID")),
+    request_body = SetModelScheduleRequest,
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn set_model_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<SetModelScheduleRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if body.enabled_from.is_some_and(|t| t < 0) || body.enabled_until.is_some_and(|t| t < 0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Schedule timestamps must be non-negative".into(),
+            }),
+        ));
+    }
+    if let (Some(from), Some(until)) = (body.enabled_from, body.enabled_until)
+        && from >= until
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "enabledFrom must be before enabledUntil".into(),
+            }),
+        ));
+    }
+
+    match state
+        .models
+        .set_schedule(&id, body.enabled_from, body.enabled_until)
+        .await
     {
         Ok(true) => Ok(Json(SuccessResponse { success: true })),
         Ok(false) => Err((
@@ -230,6 +570,55 @@ pub async fn update_model(
     }
 }
 
+/// Trim a requested default effort; empty means none. Unknown values are
+/// rejected just like an invalid `reasoning_effort` on a request.
+fn normalize_default_effort(effort: Option<&str>) -> Result<Option<&str>, String> {
+    match effort.map(str::trim).filter(|e| !e.is_empty()) {
+        Some(effort) => validate_reasoning_effort(effort).map(|()| Some(effort)),
+        None => Ok(None),
+    }
+}
+
+/// Set or clear a model's default thinking effort, applied to OpenAI
+/// requests that specify neither `reasoning_effort` nor a model suffix.
+#[utoipa::path(
+    put,
+    path = "/models/{id}/default-effort",
+    tag = "models",
+    params(("id" = String, Path, description = "Model ID")),
+    request_body = SetModelDefaultEffortRequest,
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn set_model_default_effort(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<SetModelDefaultEffortRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let effort = normalize_default_effort(body.default_effort.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })))?;
+
+    match state.models.set_default_effort(&id, effort).await {
+        Ok(true) => Ok(Json(SuccessResponse { success: true })),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Model not found".into(),
+            }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
 /// Reorder models
 #[utoipa::path(
     put,
@@ -255,3 +644,235 @@ pub async fn reorder_models(
         )),
     }
 }
+
+/// List model aliases
+#[utoipa::path(
+    get,
+    path = "/model-aliases",
+    tag = "models",
+    responses(
+        (status = 200, body = ListModelAliasesResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn list_model_aliases(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ListModelAliasesResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let aliases = state.models.list_aliases().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
+    Ok(Json(ListModelAliasesResponse { aliases }))
+}
+
+/// Create or replace a model alias. The target must be a configured model and
+/// the alias must not shadow one.
+#[utoipa::path(
+    put,
+    path = "/model-aliases/{alias}",
+    tag = "models",
+    params(("alias" = String, Path, description = "Alias name")),
+    request_body = SetModelAliasRequest,
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn set_model_alias(
+    State(state): State<Arc<AppState>>,
+    Path(alias): Path<String>,
+    Json(body): Json<SetModelAliasRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+
+    let alias = alias.trim();
+    let target = body.target.trim();
+    if let Err(e) = validate_model_id(alias) {
+        return Err(bad_request(format!("Alias: {e}")));
+    }
+    if let Err(e) = validate_model_id(target) {
+        return Err(bad_request(format!("Target: {e}")));
+    }
+
+    let models = state.models.list().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
+    if models.iter().any(|m| m.id == alias) {
+        return Err(bad_request(format!(
+            "Alias '{alias}' is already a model id"
+        )));
+    }
+    if !models.iter().any(|m| m.id == target) {
+        return Err(bad_request(format!("Unknown target model '{target}'")));
+    }
+
+    match state.models.set_alias(alias, target).await {
+        Ok(()) => Ok(Json(SuccessResponse { success: true })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Delete a model alias
+#[utoipa::path(
+    delete,
+    path = "/model-aliases/{alias}",
+    tag = "models",
+    params(("alias" = String, Path, description = "Alias name")),
+    responses(
+        (status = 200, body = SuccessResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn delete_model_alias(
+    State(state): State<Arc<AppState>>,
+    Path(alias): Path<String>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.models.remove_alias(&alias).await {
+        Ok(true) => Ok(Json(SuccessResponse { success: true })),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Alias not found".into(),
+            }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{SeedRequest, seed_request, with_db};
+
+    #[test]
+    fn test_protected_model_cannot_be_deleted() {
+        let protected = vec![
+            "claude-opus-4-6".to_string(),
+            "claude-sonnet-4-5".to_string(),
+        ];
+
+        let (status, Json(body)) = check_deletable("claude-opus-4-6", &protected).unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.error.contains("protected"), "{}", body.error);
+
+        assert!(matches!(check_deletable("my-finetune", &protected), Ok(())));
+        assert!(matches!(check_deletable("claude-opus-4-6", &[]), Ok(())));
+    }
+
+    async fn rollup(model: &str, period: &str) -> ModelUsageRollup {
+        let query = UsageHistoryQuery {
+            period: Some(period.into()),
+        };
+        match get_model_usage_rollup(Path(model.to_string()), Query(query)).await {
+            Ok(Json(rollup)) => rollup,
+            Err((status, Json(body))) => panic!("{status}: {}", body.error),
+        }
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_usage_rollup_sums_keys_per_model() {
+        with_db(async {
+            let suffix = uuid::Uuid::new_v4().simple().to_string();
+            let sonnet = format!("rollup-sonnet-{suffix}");
+            let opus = format!("rollup-opus-{suffix}");
+            let (key_a, key_b) = (format!("rollup-a-{suffix}"), format!("rollup-b-{suffix}"));
+            let now = timestamp_millis();
+            for (key_id, model, input, output, cost) in [
+                (&key_a, &sonnet, 1_000, 200, 1_500),
+                (&key_a, &sonnet, 2_000, 300, 2_500),
+                (&key_b, &sonnet, 400, 50, 500),
+                (&key_b, &opus, 700, 70, 9_000),
+            ] {
+                seed_request(SeedRequest {
+                    key_id,
+                    model,
+                    input,
+                    output,
+                    cache_read: input * 2,
+                    cache_write: input / 10,
+                    cost_microdollars: cost,
+                    created_at: now,
+                })
+                .await;
+            }
+            // Before the 7d period
+            seed_request(SeedRequest {
+                key_id: &key_a,
+                model: &sonnet,
+                input: 99_999,
+                created_at: now - 8 * 86_400_000,
+                ..SeedRequest::default()
+            })
+            .await;
+
+            let total = rollup(&sonnet, "7d").await;
+            assert_eq!(
+                total,
+                ModelUsageRollup {
+                    model: sonnet.clone(),
+                    period: "7d".into(),
+                    request_count: 3,
+                    key_count: 2,
+                    cost_microdollars: 4_500,
+                    input_tokens: 3_400,
+                    output_tokens: 550,
+                    cache_read_tokens: 6_800,
+                    cache_write_tokens: 340,
+                }
+            );
+            let total = rollup(&opus, "7d").await;
+            assert_eq!((total.request_count, total.key_count), (1, 1));
+            assert_eq!(total.cost_microdollars, 9_000);
+
+            // The older row counts once the period reaches back to it
+            let total = rollup(&sonnet, "30d").await;
+            assert_eq!(total.request_count, 4);
+            assert_eq!(total.input_tokens, 103_399);
+
+            let total = rollup(&format!("rollup-unused-{suffix}"), "24h").await;
+            assert_eq!(total.key_count, 0);
+            assert_eq!(total.request_count, 0);
+            assert_eq!(total.cost_microdollars, 0);
+        });
+    }
+
+    #[test]
+    fn test_seed_prices_for_known_model() {
+        assert_eq!(
+            seed_prices("claude-sonnet-4-5"),
+            Some((3.0, 15.0, 0.30, 3.75))
+        );
+        assert_eq!(
+            seed_prices("claude-opus-4-6"),
+            Some((5.0, 25.0, 0.50, 6.25))
+        );
+    }
+
+    #[test]
+    fn test_seed_prices_rejects_custom_model() {
+        assert_eq!(seed_prices("my-finetuned-model"), None);
+        assert_eq!(seed_prices(""), None);
+    }
+}
@@ -4,14 +4,30 @@ use axum::{
     http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
 use utoipa::ToSchema;
 
 use super::{ErrorResponse, SuccessResponse};
 use crate::AppState;
 use crate::auth::storage::Auth;
+use crate::constants::ANTHROPIC_VERSION;
+use crate::routes::auth::build_anthropic_request;
 use crate::subscription::fetch_plan_name;
-use crate::usage::{SubscriptionUsageResponse, WEB_SESSION_PROVIDER};
+use crate::transforms::{PrepareOptions, prepare_count_tokens_request};
+use crate::usage::{SubscriptionState, SubscriptionUsageResponse, WEB_SESSION_PROVIDER};
+
+/// Upper bound on the connection probe, token refresh included, so a hung
+/// upstream can't stall the admin UI.
+const OAUTH_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Model probed when no model is enabled in the catalog.
+const OAUTH_PROBE_FALLBACK_MODEL: &str = "claude-sonnet-4-5";
+
+/// Longest upstream error body echoed back to the admin UI.
+const OAUTH_PROBE_MAX_ERROR_CHARS: usize = 500;
 
 // --- Types ---
 
@@ -45,6 +61,18 @@ pub struct WebSessionStatusResponse {
     pub configured: bool,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct OAuthTestResponse {
+    /// Whether Anthropic accepted the current OAuth token
+    pub ok: bool,
+    /// Wall-clock time of the probe, including any token refresh
+    pub latency_ms: u64,
+    /// Model the probe was sent for
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 /// Query parameters for `GET /oauth/usage`.
 #[derive(Debug, Default, Deserialize, utoipa::IntoParams)]
 pub struct GetUsageQuery {
@@ -148,6 +176,88 @@ pub async fn delete_oauth(
     }
 }
 
+/// Test the Anthropic connection with the current OAuth credentials.
+///
+/// Sends a one-message `count_tokens` request — it costs no quota — so
+/// expired or revoked tokens and network problems show up immediately
+/// instead of on the next user request. Always answers 200; the outcome is
+/// in `ok`/`error`.
+#[utoipa::path(
+    post,
+    path = "/oauth/test",
+    tag = "oauth",
+    responses(
+        (status = 200, body = OAuthTestResponse),
+    )
+)]
+pub async fn test_oauth_connection(State(state): State<Arc<AppState>>) -> Json<OAuthTestResponse> {
+    let model = state
+        .models
+        .list_enabled_ids()
+        .await
+        .ok()
+        .and_then(|ids| ids.into_iter().next())
+        .unwrap_or_else(|| OAUTH_PROBE_FALLBACK_MODEL.to_string());
+
+    let started = Instant::now();
+    let result = match timeout(OAUTH_PROBE_TIMEOUT, probe_anthropic(&state, &model)).await {
+        Ok(result) => result,
+        Err(_) => Err(format!(
+            "Timed out after {}s",
+            OAUTH_PROBE_TIMEOUT.as_secs()
+        )),
+    };
+    let latency_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+    Json(OAuthTestResponse {
+        ok: result.is_ok(),
+        latency_ms,
+        model,
+        error: result.err(),
+    })
+}
+
+async fn probe_anthropic(state: &AppState, model: &str) -> Result<(), String> {
+    let token = match state.oauth.refresh_if_needed().await {
+        Ok(Some(token)) => token,
+        Ok(None) => return Err("No OAuth credentials configured".into()),
+        Err(e) => return Err(format!("Token refresh failed: {e}")),
+    };
+
+    let prepared = prepare_count_tokens_request(
+        json!({
+            "model": model,
+            "messages": [{ "role": "user", "content": "ping" }]
+        }),
+        &PrepareOptions::new(state.should_cloak(None)),
+    );
+    let response = build_anthropic_request(
+        &state.http_client,
+        &state.upstream.count_tokens,
+        &token,
+        ANTHROPIC_VERSION,
+        Some(&prepared.betas),
+        &state.session_id,
+    )
+    .json(&prepared.body)
+    .send()
+    .await
+    .map_err(|e| format!("Failed to contact Anthropic: {e}"))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body: String = response
+        .text()
+        .await
+        .unwrap_or_default()
+        .chars()
+        .take(OAUTH_PROBE_MAX_ERROR_CHARS)
+        .collect();
+    Err(format!("Anthropic returned {status}: {body}"))
+}
+
 /// Get Claude subscription usage.
 ///
 /// Thin wrapper around [`UsageCache`]: reads the current cached state,
@@ -178,6 +288,31 @@ pub async fn get_subscription_usage(
     Json(cached.to_response())
 }
 
+/// Drop the cached subscription usage and fetch it again now.
+///
+/// For when the window reset times are known to be stale, e.g. after using
+/// the subscription through another client. Returns the refreshed window
+/// state that per-key rate limiting follows, or 502 if the fetch failed (the
+/// previous data is kept then).
+#[utoipa::path(
+    delete,
+    path = "/oauth/cache",
+    tag = "oauth",
+    responses(
+        (status = 200, body = SubscriptionState),
+        (status = 502, body = ErrorResponse),
+    )
+)]
+pub async fn refresh_oauth_cache(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SubscriptionState>, (StatusCode, Json<ErrorResponse>)> {
+    let cached = state.usage_cache.force_refresh(&state).await;
+    match cached.last_error {
+        Some(error) => Err((StatusCode::BAD_GATEWAY, Json(ErrorResponse { error }))),
+        None => Ok(Json(cached.window_state())),
+    }
+}
+
 /// Get web session configuration status
 #[utoipa::path(
     get,
@@ -0,0 +1,255 @@
+use axum::{Json, extract::Query, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::ErrorResponse;
+use crate::auth::client_keys::i64_to_u64;
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+
+/// Rows returned when `limit` is not given
+const DEFAULT_PAGE_SIZE: u32 = 100;
+/// Largest accepted `limit`
+const MAX_PAGE_SIZE: u32 = 1000;
+
+// --- Types ---
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestLogQuery {
+    pub key_id: Option<String>,
+    pub model: Option<String>,
+    /// Only requests attributed to this end user (OpenAI `user`)
+    pub end_user: Option<String>,
+    /// Only rows created at or after this time (epoch ms)
+    pub from: Option<i64>,
+    /// Only rows created before this time (epoch ms)
+    pub to: Option<i64>,
+    /// `nextCursor` from the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestLogRow {
+    pub id: i64,
+    pub key_id: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cost_microdollars: u64,
+    pub created_at: u64,
+    pub duration_ms: Option<u64>,
+    pub service_tier: Option<String>,
+    /// End user the client attributed the request to (OpenAI `user`)
+    pub end_user: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestLogPage {
+    /// Newest first
+    pub rows: Vec<RequestLogRow>,
+    /// Pass as `cursor` to get the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Position after the last row of a page, as `created_at:id`. Ties on
+/// `created_at` are broken by id so no row is skipped or repeated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Cursor {
+    pub(super) created_at: i64,
+    pub(super) id: i64,
+}
+
+impl Cursor {
+    pub(super) fn parse(raw: &str) -> Option<Self> {
+        let (created_at, id) = raw.split_once(':')?;
+        Some(Self {
+            created_at: created_at.parse().ok()?,
+            id: id.parse().ok()?,
+        })
+    }
+
+    pub(super) fn encode(self) -> String {
+        format!("{}:{}", self.created_at, self.id)
+    }
+}
+
+pub(super) fn page_size(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+/// Cursor for the page after `rows`, or `None` if this page wasn't full.
+fn next_cursor(rows: &[RequestLogRow], page_size: u32) -> Option<String> {
+    if rows.len() < page_size as usize {
+        return None;
+    }
+    rows.last().map(|row| {
+        Cursor {
+            created_at: row.created_at as i64,
+            id: row.id,
+        }
+        .encode()
+    })
+}
+
+async fn query_request_log(
+    query: &RequestLogQuery,
+    cursor: Option<Cursor>,
+    limit: u32,
+) -> Result<Vec<RequestLogRow>, ProxyError> {
+    let conn = db::get_conn().await?;
+    // Filters are optional; the key and model filters are served by the
+    // (key_id, created_at) and (model, created_at) indexes.
+    let rows = sqlx::query!(
+        "SELECT id, key_id, model, input_tokens, output_tokens, cache_read_tokens, \
+         cache_write_tokens, cost_microdollars, created_at, duration_ms, service_tier, end_user \
+         FROM request_log \
+         WHERE ($1::TEXT IS NULL OR key_id = $1) \
+         AND ($2::TEXT IS NULL OR model = $2) \
+         AND ($3::BIGINT IS NULL OR created_at >= $3) \
+         AND ($4::BIGINT IS NULL OR created_at < $4) \
+         AND ($5::BIGINT IS NULL OR (created_at, id) < ($5, $6)) \
+         AND ($8::TEXT IS NULL OR end_user = $8) \
+         ORDER BY created_at DESC, id DESC \
+         LIMIT $7",
+        query.key_id.as_deref(),
+        query.model.as_deref(),
+        query.from,
+        query.to,
+        cursor.map(|c| c.created_at),
+        cursor.map_or(0, |c| c.id),
+        i64::from(limit),
+        query.end_user.as_deref(),
+    )
+    .fetch_all(&conn)
+    .await
+    .db_context("Failed to read request log")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| RequestLogRow {
+            id: row.id,
+            key_id: row.key_id,
+            model: row.model,
+            input_tokens: i64_to_u64(row.input_tokens),
+            output_tokens: i64_to_u64(row.output_tokens),
+            cache_read_tokens: i64_to_u64(row.cache_read_tokens),
+            cache_write_tokens: i64_to_u64(row.cache_write_tokens),
+            cost_microdollars: i64_to_u64(row.cost_microdollars),
+            created_at: i64_to_u64(row.created_at),
+            duration_ms: row.duration_ms.map(i64_to_u64),
+            service_tier: row.service_tier,
+            end_user: row.end_user,
+        })
+        .collect())
+}
+
+// --- Handlers ---
+
+/// Browse logged requests, newest first
+#[utoipa::path(
+    get,
+    path = "/request-log",
+    params(
+        ("keyId" = Option<String>, Query, description = "Only this key's requests"),
+        ("model" = Option<String>, Query, description = "Only this model's requests"),
+        ("endUser" = Option<String>, Query, description = "Only requests attributed to this end user"),
+        ("from" = Option<i64>, Query, description = "Created at or after (epoch ms)"),
+        ("to" = Option<i64>, Query, description = "Created before (epoch ms)"),
+        ("cursor" = Option<String>, Query, description = "nextCursor of the previous page"),
+        ("limit" = Option<u32>, Query, description = "Page size (default 100, max 1000)"),
+    ),
+    responses(
+        (status = 200, body = RequestLogPage),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn get_request_log(
+    Query(query): Query<RequestLogQuery>,
+) -> Result<Json<RequestLogPage>, (StatusCode, Json<ErrorResponse>)> {
+    let cursor = match query.cursor.as_deref() {
+        Some(raw) => Some(Cursor::parse(raw).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Invalid cursor".into(),
+                }),
+            )
+        })?),
+        None => None,
+    };
+    let limit = page_size(query.limit);
+    let rows = query_request_log(&query, cursor, limit)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+    let next_cursor = next_cursor(&rows, limit);
+    Ok(Json(RequestLogPage { rows, next_cursor }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: i64, created_at: u64) -> RequestLogRow {
+        RequestLogRow {
+            id,
+            key_id: "key-1".into(),
+            model: "claude-sonnet-4-5".into(),
+            input_tokens: 10,
+            output_tokens: 5,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+            cost_microdollars: 105,
+            created_at,
+            duration_ms: Some(800),
+            service_tier: None,
+            end_user: Some("user-123".into()),
+        }
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor {
+            created_at: 1_760_000_000_000,
+            id: 42,
+        };
+        assert_eq!(Cursor::parse(&cursor.encode()), Some(cursor));
+        assert_eq!(Cursor::parse("42"), None);
+        assert_eq!(Cursor::parse("abc:1"), None);
+    }
+
+    #[test]
+    fn test_row_reports_end_user() {
+        let json = serde_json::to_value(row(1, 1_000)).unwrap();
+        assert_eq!(json["endUser"], "user-123");
+        assert_eq!(json["serviceTier"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_page_size_is_capped() {
+        assert_eq!(page_size(None), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(0)), 1);
+        assert_eq!(page_size(Some(50_000)), MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_next_cursor_only_for_full_page() {
+        let rows = vec![row(9, 2_000), row(7, 1_000)];
+        assert_eq!(next_cursor(&rows, 2).as_deref(), Some("1000:7"));
+        assert_eq!(next_cursor(&rows, 3), None);
+        assert_eq!(next_cursor(&[], 1), None);
+    }
+}
@@ -1,20 +1,23 @@
 use axum::{
     Json,
-    extract::State,
+    extract::{ConnectInfo, State},
     http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use subtle::ConstantTimeEq;
+use std::time::Instant;
+use tracing::warn;
 use utoipa::ToSchema;
 
 use super::{ErrorResponse, SuccessResponse};
 use crate::AppState;
 use crate::admin_session::{
-    clear_session_cookie, parse_cookie, remove_session, save_session, session_cookie,
-    session_expires_at, validate_session,
+    AdminRole, parse_cookie, remove_session, save_session, session_expires_at, too_many_attempts,
+    validate_session,
 };
+use crate::client_ip::resolve_client_ip;
 
 // --- Types ---
 
@@ -29,25 +32,41 @@ pub struct LoginRequest {
 pub struct AuthCheckResponse {
     pub authenticated: bool,
     pub auth_required: bool,
+    /// Logged in with the read-only admin credentials (GET-only access)
+    pub read_only: bool,
 }
 
 // --- Handlers ---
 
-/// Login with username/password, returns a session cookie
-pub async fn login(State(state): State<Arc<AppState>>, Json(body): Json<LoginRequest>) -> Response {
-    let creds = &state.admin_credentials;
+/// Login with username/password, returns a session cookie.
+///
+/// Failed attempts are counted per client IP; after too many the IP is
+/// locked out with 429 until the cooldown elapses.
+pub async fn login(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<LoginRequest>,
+) -> Response {
+    let ip = resolve_client_ip(peer.ip(), &headers, state.trusted_proxy_hops);
+    let throttle = &state.login_throttle;
 
-    let user_match = body.username.as_bytes().ct_eq(creds.username.as_bytes());
-    let pass_match = body.password.as_bytes().ct_eq(creds.password.as_bytes());
+    if let Err(remaining) = throttle.check(ip, Instant::now()) {
+        return too_many_attempts(remaining.as_secs().max(1));
+    }
 
-    if user_match.into() && pass_match.into() {
+    if let Some(role) = state
+        .admin_credentials
+        .role_for(&body.username, &body.password)
+    {
+        throttle.record_success(ip);
         let token = format!(
             "{:032x}{:032x}",
             rand::random::<u128>(),
             rand::random::<u128>()
         );
-        save_session(&token, session_expires_at()).await;
-        let cookie = session_cookie(&token, state.secure_cookies);
+        save_session(&token, session_expires_at(), role, &body.username).await;
+        let cookie = state.session_cookie.session_cookie(&token);
 
         (
             StatusCode::OK,
@@ -56,6 +75,13 @@ pub async fn login(State(state): State<Arc<AppState>>, Json(body): Json<LoginReq
         )
             .into_response()
     } else {
+        if let Some(lockout) = throttle.record_failure(ip, Instant::now()) {
+            warn!(
+                "Admin login locked out for {} after repeated failures ({}s)",
+                ip,
+                lockout.as_secs()
+            );
+        }
         (
             StatusCode::UNAUTHORIZED,
             Json(ErrorResponse {
@@ -74,7 +100,7 @@ pub async fn logout(State(state): State<Arc<AppState>>, headers: HeaderMap) -> R
         remove_session(&token).await;
     }
 
-    let clear_cookie = clear_session_cookie(state.secure_cookies);
+    let clear_cookie = state.session_cookie.clear_session_cookie();
 
     (
         StatusCode::OK,
@@ -86,17 +112,18 @@ pub async fn logout(State(state): State<Arc<AppState>>, headers: HeaderMap) -> R
 
 /// Check if the current request is authenticated
 pub async fn auth_check(headers: HeaderMap) -> Json<AuthCheckResponse> {
-    let authenticated = if let Some(cookie_header) =
+    let role = if let Some(cookie_header) =
         headers.get(header::COOKIE).and_then(|v| v.to_str().ok())
         && let Some(token) = parse_cookie(cookie_header, "admin_session")
     {
-        validate_session(&token).await
+        validate_session(&token).await.map(|(role, _)| role)
     } else {
-        false
+        None
     };
 
     Json(AuthCheckResponse {
-        authenticated,
+        authenticated: role.is_some(),
         auth_required: true,
+        read_only: role == Some(AdminRole::ReadOnly),
     })
 }
@@ -0,0 +1,185 @@
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use super::ErrorResponse;
+use crate::db::MigrationStatus;
+use crate::error::ProxyError;
+use crate::subscription::fetch_plan_name;
+use crate::{AppState, BUILD_TIME, GIT_HASH, VERSION, db};
+
+// --- Types ---
+
+/// Everything the dashboard header needs in one round-trip.
+#[derive(Serialize, ToSchema)]
+pub struct AdminStatusResponse {
+    pub version: String,
+    pub git_hash: String,
+    pub build_time: String,
+    /// Whether the database answered a trivial query
+    pub db_ok: bool,
+    pub oauth_authenticated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+    /// Models in the catalog, enabled or not; absent when the database is down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_count: Option<u64>,
+}
+
+/// Schema state, for diagnosing upgrades.
+#[derive(Serialize, ToSchema)]
+pub struct MigrationsResponse {
+    /// Newest applied migration, `None` on an empty database
+    pub schema_version: Option<i64>,
+    /// Migrations embedded in this build, oldest first
+    pub migrations: Vec<MigrationStatus>,
+    /// Applied versions this build doesn't know, e.g. after a downgrade
+    pub unknown_applied: Vec<i64>,
+}
+
+// --- Handlers ---
+
+fn internal_error(e: ProxyError) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+/// Get build info and service health in one call
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "status",
+    responses(
+        (status = 200, body = AdminStatusResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn get_admin_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<AdminStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let db_ok = match db::ping().await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Status check: {e}");
+            false
+        }
+    };
+
+    let oauth_authenticated = state.auth_store.has("anthropic").await.unwrap_or(false);
+    let plan = if oauth_authenticated {
+        fetch_plan_name(&state).await
+    } else {
+        None
+    };
+
+    // A reachable database that fails to count is an error, not a zero
+    let (model_count, key_count) = if db_ok {
+        let models = state.models.count().await.map_err(internal_error)?;
+        let keys = state.client_keys.count().await.map_err(internal_error)?;
+        (Some(models), Some(keys))
+    } else {
+        (None, None)
+    };
+
+    Ok(Json(AdminStatusResponse {
+        version: VERSION.to_string(),
+        git_hash: GIT_HASH.to_string(),
+        build_time: BUILD_TIME.to_string(),
+        db_ok,
+        oauth_authenticated,
+        plan,
+        model_count,
+        key_count,
+    }))
+}
+
+/// List database migrations and which are applied
+#[utoipa::path(
+    get,
+    path = "/db/migrations",
+    tag = "status",
+    responses(
+        (status = 200, body = MigrationsResponse),
+        (status = 500, body = ErrorResponse),
+    )
+)]
+pub async fn get_migrations() -> Result<Json<MigrationsResponse>, (StatusCode, Json<ErrorResponse>)>
+{
+    let schema_version = db::schema_version().await.map_err(internal_error)?;
+    let (migrations, unknown_applied) = db::migration_status().await.map_err(internal_error)?;
+    Ok(Json(MigrationsResponse {
+        schema_version,
+        migrations,
+        unknown_applied,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{create_test_key, spawn_mock, state_with_upstream, with_db};
+    use axum::{Router, body::Body, extract::Request, routing::get};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_status_response_shape() {
+        with_db(async {
+            // The plan lookup gets a 404 from the empty mock
+            let state = Arc::new(state_with_upstream(spawn_mock(Router::new()).await));
+            create_test_key(&state, "status-key").await;
+            let app = Router::new()
+                .route("/status", get(get_admin_status))
+                .with_state(state);
+
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/status")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let status: Value = serde_json::from_slice(&body).unwrap();
+
+            let mut fields: Vec<&str> = status
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(String::as_str)
+                .collect();
+            fields.sort_unstable();
+            assert_eq!(
+                fields,
+                [
+                    "build_time",
+                    "db_ok",
+                    "git_hash",
+                    "key_count",
+                    "model_count",
+                    "oauth_authenticated",
+                    "version"
+                ]
+            );
+            assert_eq!(status["version"], VERSION);
+            assert_eq!(status["db_ok"], true);
+            assert_eq!(status["oauth_authenticated"], true);
+            assert!(status["model_count"].as_u64().unwrap() > 0);
+            assert!(status["key_count"].as_u64().unwrap() >= 1);
+        });
+    }
+}
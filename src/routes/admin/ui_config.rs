@@ -0,0 +1,135 @@
+use axum::{
+    Json,
+    extract::{FromRef, State},
+};
+use serde::Serialize;
+use std::sync::Arc;
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::AppState;
+
+/// Longest CSS color name accepted for the banner
+const MAX_COLOR_NAME_LEN: usize = 32;
+
+// --- Types ---
+
+/// Per-deployment settings for the admin SPA, so one build of the static
+/// assets can serve every environment.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct UiConfig {
+    /// Environment banner shown on every admin page, including the login page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banner: Option<UiBanner>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UiBanner {
+    pub message: String,
+    /// CSS color (`#rgb`, `#rrggbb` or a color name); the UI picks one when absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+impl UiConfig {
+    /// From `CLAUDE_PROXY_UI_BANNER` / `CLAUDE_PROXY_UI_BANNER_COLOR`. A color
+    /// that isn't a valid [`is_valid_banner_color`] is ignored.
+    pub fn new(message: Option<String>, color: Option<String>) -> Self {
+        Self {
+            banner: message.map(|message| UiBanner {
+                message,
+                color: color.filter(|c| is_valid_banner_color(c)),
+            }),
+        }
+    }
+}
+
+impl FromRef<Arc<AppState>> for UiConfig {
+    fn from_ref(state: &Arc<AppState>) -> Self {
+        state.ui_config.clone()
+    }
+}
+
+/// `#rgb`, `#rrggbb` or a plain color name like `crimson`. Anything else
+/// could smuggle arbitrary CSS into the page's style attribute.
+pub fn is_valid_banner_color(color: &str) -> bool {
+    match color.strip_prefix('#') {
+        Some(hex) => matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => {
+            !color.is_empty()
+                && color.len() <= MAX_COLOR_NAME_LEN
+                && color.chars().all(|c| c.is_ascii_alphabetic())
+        }
+    }
+}
+
+// --- Handlers ---
+
+/// Get deployment-specific UI settings (no authentication required)
+#[utoipa::path(
+    get,
+    path = "/config/ui",
+    tag = "status",
+    responses(
+        (status = 200, body = UiConfig),
+    )
+)]
+pub async fn get_ui_config(State(config): State<UiConfig>) -> Json<UiConfig> {
+    Json(config)
+}
+
+/// Routes served without an admin session: the login page needs them too.
+pub fn ui_config_router() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new().routes(routes!(get_ui_config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, extract::Request, routing::get};
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    async fn fetch(config: UiConfig) -> Value {
+        let app = Router::new()
+            .route("/config/ui", get(get_ui_config))
+            .with_state(config);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/config/ui")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_returns_configured_banner() {
+        let config = UiConfig::new(Some("PRODUCTION".into()), Some("#c0392b".into()));
+        assert_eq!(
+            fetch(config).await,
+            json!({ "banner": { "message": "PRODUCTION", "color": "#c0392b" } })
+        );
+
+        assert_eq!(fetch(UiConfig::default()).await, json!({}));
+    }
+
+    #[test]
+    fn test_banner_color_validation() {
+        assert!(is_valid_banner_color("#fff"));
+        assert!(is_valid_banner_color("#C0392B"));
+        assert!(is_valid_banner_color("crimson"));
+        assert!(!is_valid_banner_color("#ff"));
+        assert!(!is_valid_banner_color("red; background: url(x)"));
+        assert!(!is_valid_banner_color(""));
+
+        let config = UiConfig::new(Some("staging".into()), Some("red;".into()));
+        assert_eq!(config.banner.unwrap().color, None);
+    }
+}
@@ -4,10 +4,12 @@ use utoipa::ToSchema;
 
 use super::{ErrorResponse, SuccessResponse};
 use crate::db;
+use crate::subscription::timestamp_millis;
 use crate::usage::history::{
     HistoryPeriod, KeyBreakdownResponse, ModelBreakdownResponse, TimeseriesResponse, by_key,
     by_model, timeseries,
 };
+use crate::usage::samples::{UtilizationSamplesResponse, series};
 
 // --- Types ---
 
@@ -91,6 +93,31 @@ pub async fn get_usage_history_by_key(
     )
 }
 
+/// Subscription utilization samples recorded on each full usage fetch
+#[utoipa::path(
+    get,
+    path = "/usage-history/subscription",
+    params(("period" = Option<String>, Query, description = "Period: 24h, 7d, or 30d")),
+    responses(
+        (status = 200, body = UtilizationSamplesResponse),
+    )
+)]
+pub async fn get_subscription_samples(
+    Query(query): Query<UsageHistoryQuery>,
+) -> Json<UtilizationSamplesResponse> {
+    let period = HistoryPeriod::parse(query.period.as_deref());
+    let from = period.cutoff(timestamp_millis());
+
+    let samples = match db::get_conn().await {
+        Ok(conn) => series(&conn, from).await.unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    Json(UtilizationSamplesResponse {
+        period: period.label().to_string(),
+        samples,
+    })
+}
+
 #[utoipa::path(
     delete,
     path = "/usage-history",
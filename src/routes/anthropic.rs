@@ -1,49 +1,103 @@
 use axum::{
     Json,
     body::Body,
-    extract::State,
+    extract::{ConnectInfo, Extension, State},
     http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde_json::{Value, from_str};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Instant;
 use tracing::{debug, info, warn};
 
 use crate::AppState;
 use crate::auth::usage::usage_from_json;
+use crate::auth::{ModelCapabilities, RequestTags};
 use crate::capture::{Capture, capture_byte_stream};
-use crate::constants::{ANTHROPIC_API_URL, ANTHROPIC_COUNT_TOKENS_URL};
+use crate::count_tokens_cache::CacheKey;
 use crate::error::ProxyError;
+use crate::idempotency::Idempotent;
+use crate::key_debug_log::{KeyDebugLog, tee_stream};
+use crate::subscription::timestamp_millis;
+use crate::telemetry;
+use crate::transforms::stream_events::redact_response_tool_inputs;
 use crate::transforms::{
     ToolNameMap, normalize_claude_code_tool_names, prepare_anthropic_request,
-    prepare_count_tokens_request, restore_response_tool_names,
+    prepare_count_tokens_request, prepared_service_tier, restore_response_tool_names,
     stream_restore_native_tool_names_with_usage,
 };
 
-use super::auth::{authenticate_anthropic, build_anthropic_request, extract_client_betas};
+use super::auth::{
+    authenticate, build_anthropic_request, extract_client_betas, pin_model, validate_client_key,
+};
+use super::proxy_headers::UpstreamInfo;
+use super::retry::{RetryPolicy, send_with_retry};
+
+/// Resolve the body's `model` (or the key's `forced` model) through the
+/// alias table (falling back to the configured default), apply the
+/// unknown-model policy and write the result back so upstream sees the
+/// concrete model id. Also returns whether the forced model overrode the
+/// client's.
+async fn resolve_body_model(
+    state: &AppState,
+    forced: Option<&str>,
+    body: &mut Value,
+) -> Result<(String, bool), ProxyError> {
+    let (requested, overridden) = pin_model(body.get("model").and_then(|m| m.as_str()), forced);
+    let model = state
+        .models
+        .resolve_available_model(requested, &state.default_model, state.unknown_model_policy)
+        .await?;
+    if let Some(obj) = body.as_object_mut() {
+        obj.insert("model".to_string(), Value::String(model.clone()));
+    }
+    Ok((model, overridden))
+}
 
 pub async fn messages(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     State(state): State<Arc<AppState>>,
+    Extension(upstream_info): Extension<UpstreamInfo>,
     headers: HeaderMap,
-    Json(body): Json<Value>,
+    Json(mut body): Json<Value>,
 ) -> Response {
-    let model = body
-        .get("model")
-        .and_then(|m| m.as_str())
-        .unwrap_or("claude-sonnet-4-5");
+    let started_at = timestamp_millis();
+    let client_key = match validate_client_key(&headers, &state).await {
+        Ok(k) => k,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    let forced = client_key.force_model.as_deref();
+    let (model, overridden) = match resolve_body_model(&state, forced, &mut body).await {
+        Ok(m) => m,
+        Err(e) => return e.to_anthropic_response(),
+    };
+    upstream_info.set_model(&model);
+    if overridden {
+        upstream_info.set_model_forced();
+    }
 
-    let auth = match authenticate_anthropic(&headers, &state, model).await {
+    let auth = match authenticate(client_key, &headers, &state, peer.ip(), &model).await {
         Ok(a) => a,
         Err(err) => return err.to_anthropic_response(),
     };
 
-    let cloak = state.should_cloak(headers.get("user-agent").and_then(|v| v.to_str().ok()));
-    let model = model.to_string();
+    let cloak = auth.should_cloak(&state, &headers);
 
     let stream = body
         .get("stream")
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
+    let idempotency_claim = match state
+        .idempotency
+        .begin(&auth.client_key.id, "/v1/messages", &headers, &body, stream)
+        .await
+    {
+        Ok(Idempotent::Skip) => None,
+        Ok(Idempotent::Replay(response)) => return response,
+        Ok(Idempotent::Claimed(claim)) => Some(claim),
+        Err(e) => return e.to_anthropic_response(),
+    };
     let capture = Capture::begin(
         &state.capture,
         "anthropic",
@@ -54,9 +108,24 @@ pub async fn messages(
         &body,
     )
     .await;
+    let debug_log = KeyDebugLog::for_key(&auth.client_key);
+    if let Some(log) = &debug_log {
+        log.request("/v1/messages", &headers, &body);
+    }
+
+    let capabilities = match state.models.capabilities(&model).await {
+        Ok(c) => c,
+        Err(e) => return e.to_anthropic_response(),
+    };
 
     // Apply all transformations via unified pipeline
-    let mut prepared = prepare_anthropic_request(body, cloak);
+    let options = auth
+        .prepare_options(cloak, capabilities)
+        .with_cache_min_tokens(state.cache_min_tokens);
+    let mut prepared = match prepare_anthropic_request(body, &options) {
+        Ok(p) => p,
+        Err(e) => return e.to_anthropic_response(),
+    };
     // Forward beta flags the client sent in the `anthropic-beta` header. Native
     // Claude Code carries them there (not in a body `betas` field), and dropping
     // them makes Anthropic reject newer tool types like `advisor_*` with a 400.
@@ -65,6 +134,10 @@ pub async fn messages(
             prepared.betas.push(beta);
         }
     }
+    let tags = RequestTags {
+        service_tier: prepared_service_tier(&prepared.body).map(str::to_string),
+        end_user: None,
+    };
     let tool_name_map = if cloak {
         normalize_claude_code_tool_names(&mut prepared.body)
     } else {
@@ -82,15 +155,37 @@ pub async fn messages(
         debug!(model = %model, stream = %stream, "Forwarding to Anthropic with body keys: {keys:?}");
     }
 
-    let req_builder = build_anthropic_request(
-        &state.http_client,
-        ANTHROPIC_API_URL,
-        &auth.token,
-        Some(&prepared.betas),
-        &state.session_id,
-    );
+    // Wait for an upstream slot; held until the response (or stream) is done.
+    let permit = match state
+        .request_queue
+        .acquire(auth.client_key.settings.priority)
+        .await
+    {
+        Ok(p) => p,
+        Err(e) => return e.to_anthropic_response(),
+    };
 
-    let response: reqwest::Response = match req_builder.json(&prepared.body).send().await {
+    // Transient upstream errors (429/503/529) are retried for non-streaming
+    // requests only; a stream can't be replayed once the client is reading it.
+    let retry_policy = if stream {
+        RetryPolicy::NONE
+    } else {
+        state.retry_policy
+    };
+    let send = || {
+        build_anthropic_request(
+            &state.http_client,
+            &state.upstream.messages,
+            &auth.token,
+            auth.anthropic_version(),
+            Some(&prepared.betas),
+            &state.session_id,
+        )
+        .json(&prepared.body)
+    };
+
+    let upstream_started = Instant::now();
+    let response: reqwest::Response = match send_with_retry(retry_policy, send).await {
         Ok(r) => r,
         Err(e) => {
             return ProxyError::AnthropicApiError(format!("Failed to contact Anthropic: {}", e))
@@ -108,17 +203,21 @@ pub async fn messages(
                 return ProxyError::NoAuthConfigured.to_anthropic_response();
             }
             Err(e) => {
-                return ProxyError::OAuthError(e).to_anthropic_response();
+                return ProxyError::OAuthRefresh(e).to_anthropic_response();
             }
         };
-        let retry_builder = build_anthropic_request(
-            &state.http_client,
-            ANTHROPIC_API_URL,
-            &new_token,
-            Some(&prepared.betas),
-            &state.session_id,
-        );
-        match retry_builder.json(&prepared.body).send().await {
+        let send = || {
+            build_anthropic_request(
+                &state.http_client,
+                &state.upstream.messages,
+                &new_token,
+                auth.anthropic_version(),
+                Some(&prepared.betas),
+                &state.session_id,
+            )
+            .json(&prepared.body)
+        };
+        match send_with_retry(retry_policy, send).await {
             Ok(r) => r,
             Err(e) => {
                 return ProxyError::AnthropicApiError(format!(
@@ -132,6 +231,8 @@ pub async fn messages(
         response
     };
 
+    telemetry::record_upstream_status(response.status().as_u16());
+
     if !response.status().is_success() {
         let status = response.status();
         if let Some(capture) = &capture {
@@ -140,9 +241,13 @@ pub async fn messages(
                 .await;
         }
         let text: String = response.text().await.unwrap_or_default();
+        upstream_info.set_latency(upstream_started.elapsed());
         if let Some(capture) = &capture {
             capture.write_upstream_body(&text).await;
         }
+        if let Some(log) = &debug_log {
+            log.response(status.as_u16(), &text);
+        }
         warn!(
             status = %status, model = %model,
             "Anthropic API error: {text}"
@@ -165,10 +270,17 @@ pub async fn messages(
             .await;
     }
 
+    let status = response.status();
     if stream {
-        let body_stream = capture_byte_stream(
-            response.bytes_stream(),
-            capture.as_ref().map(|c| c.upstream_stream_path()),
+        // Time-to-first-byte: the body streams from here on
+        upstream_info.set_latency(upstream_started.elapsed());
+        let body_stream = tee_stream(
+            debug_log,
+            status.as_u16(),
+            capture_byte_stream(
+                response.bytes_stream(),
+                capture.as_ref().map(|c| c.upstream_stream_path()),
+            ),
         );
         let key_id = auth.client_key.id.clone();
         // Transform stream tool names back to client-visible names and track usage.
@@ -178,6 +290,8 @@ pub async fn messages(
             key_id,
             model,
             tool_name_map,
+            started_at,
+            tags,
         );
 
         match Response::builder()
@@ -185,7 +299,7 @@ pub async fn messages(
             .header(header::CONTENT_TYPE, "text/event-stream")
             .header(header::CACHE_CONTROL, "no-cache")
             .header(header::CONNECTION, "keep-alive")
-            .body(Body::from_stream(transformed_stream))
+            .body(Body::from_stream(permit.hold_for(transformed_stream)))
         {
             Ok(response) => response,
             Err(e) => ProxyError::ParseError(format!("Failed to build stream response: {e}"))
@@ -199,9 +313,13 @@ pub async fn messages(
                     .to_anthropic_response();
             }
         };
+        upstream_info.set_latency(upstream_started.elapsed());
         if let Some(capture) = &capture {
             capture.write_upstream_body(&text).await;
         }
+        if let Some(log) = &debug_log {
+            log.response(status.as_u16(), &text);
+        }
 
         let mut json_response = match from_str::<Value>(&text) {
             Ok(r) => r,
@@ -216,9 +334,18 @@ pub async fn messages(
             let usage_report = usage_from_json(usage);
             let window_resets = state.usage_cache.snapshot().await.window_state();
 
+            telemetry::record_usage(&usage_report);
+
             if let Err(e) = state
                 .client_keys
-                .record_model_usage(&auth.client_key.id, &model, &usage_report, &window_resets)
+                .record_model_usage(
+                    &auth.client_key.id,
+                    &model,
+                    &usage_report,
+                    &window_resets,
+                    started_at,
+                    &tags,
+                )
                 .await
             {
                 warn!(
@@ -230,31 +357,46 @@ pub async fn messages(
 
         // Restore client-visible tool names in response.
         restore_response_tool_names(&mut json_response, &tool_name_map);
+        redact_response_tool_inputs(&mut json_response, &state.redact_tool_input_keys);
+        if let Some(claim) = idempotency_claim {
+            claim.complete(&json_response).await;
+        }
         Json(json_response).into_response()
     }
 }
 
 pub async fn count_tokens(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     State(state): State<Arc<AppState>>,
+    Extension(upstream_info): Extension<UpstreamInfo>,
     headers: HeaderMap,
-    Json(body): Json<Value>,
+    Json(mut body): Json<Value>,
 ) -> Response {
-    let model = body
-        .get("model")
-        .and_then(|m| m.as_str())
-        .unwrap_or("claude-sonnet-4-5");
+    let client_key = match validate_client_key(&headers, &state).await {
+        Ok(k) => k,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    let forced = client_key.force_model.as_deref();
+    let (model, overridden) = match resolve_body_model(&state, forced, &mut body).await {
+        Ok(m) => m,
+        Err(e) => return e.to_anthropic_response(),
+    };
+    upstream_info.set_model(&model);
+    if overridden {
+        upstream_info.set_model_forced();
+    }
 
-    let auth = match authenticate_anthropic(&headers, &state, model).await {
+    let auth = match authenticate(client_key, &headers, &state, peer.ip(), &model).await {
         Ok(a) => a,
         Err(err) => return err.to_anthropic_response(),
     };
 
-    let cloak = state.should_cloak(headers.get("user-agent").and_then(|v| v.to_str().ok()));
+    let cloak = auth.should_cloak(&state, &headers);
     let capture = Capture::begin(
         &state.capture,
         "anthropic",
         "/v1/messages/count_tokens",
-        model,
+        &model,
         false,
         &headers,
         &body,
@@ -262,7 +404,10 @@ pub async fn count_tokens(
     .await;
 
     // Apply lighter transformations for count_tokens (no metadata/tools support)
-    let mut prepared = prepare_count_tokens_request(body, cloak);
+    let options = auth
+        .prepare_options(cloak, ModelCapabilities::default())
+        .with_cache_min_tokens(state.cache_min_tokens);
+    let mut prepared = prepare_count_tokens_request(body, &options);
     // Forward client-supplied beta flags (see note in `messages`).
     for beta in extract_client_betas(&headers) {
         if !prepared.betas.contains(&beta) {
@@ -275,14 +420,26 @@ pub async fn count_tokens(
             .await;
     }
 
+    let cached = state.count_tokens_cache.as_ref().map(|cache| {
+        let key = CacheKey::new(&prepared.body, &prepared.betas, auth.anthropic_version());
+        (cache, key)
+    });
+    if let Some((cache, key)) = &cached
+        && let Some(counts) = cache.get(key, Instant::now())
+    {
+        return Json(counts).into_response();
+    }
+
     let req_builder = build_anthropic_request(
         &state.http_client,
-        ANTHROPIC_COUNT_TOKENS_URL,
+        &state.upstream.count_tokens,
         &auth.token,
+        auth.anthropic_version(),
         Some(&prepared.betas),
         &state.session_id,
     );
 
+    let upstream_started = Instant::now();
     let response: reqwest::Response = match req_builder.json(&prepared.body).send().await {
         Ok(r) => r,
         Err(e) => {
@@ -291,6 +448,8 @@ pub async fn count_tokens(
         }
     };
 
+    telemetry::record_upstream_status(response.status().as_u16());
+
     if !response.status().is_success() {
         let status = response.status();
         if let Some(capture) = &capture {
@@ -299,6 +458,7 @@ pub async fn count_tokens(
                 .await;
         }
         let text: String = response.text().await.unwrap_or_default();
+        upstream_info.set_latency(upstream_started.elapsed());
         if let Some(capture) = &capture {
             capture.write_upstream_body(&text).await;
         }
@@ -321,6 +481,7 @@ pub async fn count_tokens(
                 .to_anthropic_response();
         }
     };
+    upstream_info.set_latency(upstream_started.elapsed());
     if let Some(capture) = &capture {
         capture.write_upstream_body(&text).await;
     }
@@ -333,5 +494,65 @@ pub async fn count_tokens(
         }
     };
 
+    if let Some((cache, key)) = cached {
+        cache.insert(key, json_response.clone(), Instant::now());
+    }
     Json(json_response).into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::proxy_headers::{MODEL_FORCED_HEADER, MODEL_HEADER, annotate};
+    use crate::test_support::{create_test_key, spawn_mock, state_with_upstream, with_db};
+    use axum::{Router, extract::Request, middleware, routing::post};
+    use serde_json::json;
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_forced_model_replaces_client_model() {
+        with_db(async {
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let sink = seen.clone();
+            let upstream = Router::new().route(
+                "/v1/messages/count_tokens",
+                post(move |Json(body): Json<Value>| async move {
+                    sink.lock().unwrap().push(body["model"].clone());
+                    Json(json!({ "input_tokens": 12 }))
+                }),
+            );
+            let state = Arc::new(state_with_upstream(spawn_mock(upstream).await));
+            let key = create_test_key(&state, "forced-model").await;
+            state
+                .client_keys
+                .set_force_model(&key.id, Some("claude-haiku-4-5"))
+                .await
+                .unwrap();
+
+            let app = Router::new()
+                .route("/v1/messages/count_tokens", post(count_tokens))
+                .layer(middleware::from_fn(annotate))
+                .with_state(state);
+            let body = json!({
+                "model": "claude-opus-4-6",
+                "messages": [{ "role": "user", "content": "hi" }]
+            });
+            let request = Request::builder()
+                .method("POST")
+                .uri("/v1/messages/count_tokens")
+                .header("x-api-key", &key.key)
+                .header(header::CONTENT_TYPE, "application/json")
+                .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))))
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[MODEL_HEADER], "claude-haiku-4-5");
+            assert_eq!(response.headers()[MODEL_FORCED_HEADER], "true");
+            assert_eq!(*seen.lock().unwrap(), [json!("claude-haiku-4-5")]);
+        });
+    }
+}
@@ -1,13 +1,19 @@
 use axum::http::{HeaderMap, header};
 use reqwest::{Client, RequestBuilder};
 use std::collections::HashSet;
+use std::net::IpAddr;
 use std::sync::Arc;
-use tracing::warn;
+use tracing::{info, warn};
 
 use crate::AppState;
-use crate::auth::ClientKey;
+use crate::auth::{ClientKey, ModelCapabilities};
+use crate::client_ip::resolve_client_ip;
 use crate::constants::{ANTHROPIC_VERSION, INFERENCE_USER_AGENT, OAUTH_BETA_HEADER};
 use crate::error::ProxyError;
+use crate::subscription::timestamp_millis;
+use crate::telemetry;
+use crate::transforms::PrepareOptions;
+use crate::usage::{CachedUsage, SubscriptionState, UtilizationGate};
 
 /// Result of successful authentication containing the client key and OAuth token
 pub struct AuthResult {
@@ -15,23 +21,115 @@ pub struct AuthResult {
     pub token: String,
 }
 
-/// Extract API key from Authorization: Bearer header (OpenAI style)
-fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
-    headers
-        .get(header::AUTHORIZATION)
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.strip_prefix("Bearer "))
+impl AuthResult {
+    /// Whether to cloak this request: per the global cloak mode and the
+    /// client's User-Agent, or always if the key's settings force it.
+    pub fn should_cloak(&self, state: &AppState, headers: &HeaderMap) -> bool {
+        self.client_key.settings.force_cloak
+            || state.should_cloak(headers.get("user-agent").and_then(|v| v.to_str().ok()))
+    }
+
+    /// Prepare-pipeline options for this key, applying its system prefix
+    /// override.
+    pub fn prepare_options(
+        &self,
+        cloak: bool,
+        capabilities: ModelCapabilities,
+    ) -> PrepareOptions<'_> {
+        let mut options = PrepareOptions::new(cloak);
+        if let Some(prefix) = self.client_key.settings.system_prefix_override.as_deref() {
+            options.system_prefix = prefix;
+        }
+        options.capabilities = capabilities;
+        options.service_tier = self.client_key.settings.service_tier;
+        options
+    }
+
+    /// `anthropic-version` for this key's upstream requests: its override,
+    /// else [`ANTHROPIC_VERSION`].
+    pub fn anthropic_version(&self) -> &str {
+        self.client_key
+            .settings
+            .anthropic_version
+            .as_deref()
+            .unwrap_or(ANTHROPIC_VERSION)
+    }
 }
 
-/// Extract API key from either x-api-key or Authorization header (Anthropic style)
+/// Headers a client key is accepted in, for error messages.
+const API_KEY_HEADERS: &str = "x-api-key, api-key or Authorization";
+
+/// Extract the client key from `x-api-key` (Anthropic SDKs), `api-key`
+/// (Azure-style OpenAI clients) or `Authorization: Bearer` (OpenAI SDKs),
+/// in that order. Every `/v1` endpoint accepts all three.
 fn extract_api_key(headers: &HeaderMap) -> Option<&str> {
-    // Try x-api-key header first (standard Anthropic)
-    if let Some(key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
-        return Some(key);
+    let header_value = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+    header_value("x-api-key")
+        .or_else(|| header_value("api-key"))
+        .or_else(|| {
+            header_value(header::AUTHORIZATION.as_str())
+                .and_then(|s| s.strip_prefix("Bearer "))
+                .map(str::trim)
+        })
+}
+
+/// The browser `Origin` header, if any.
+fn request_origin(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::ORIGIN).and_then(|v| v.to_str().ok())
+}
+
+/// Reject browser requests from an origin outside the key's allow-list
+/// (no entries = any origin). Backs up the global CORS layer, which only
+/// stops the browser from reading the response, not the request itself.
+async fn check_origin(
+    state: &AppState,
+    client_key: &ClientKey,
+    headers: &HeaderMap,
+) -> Result<(), ProxyError> {
+    let origin = request_origin(headers);
+    if state
+        .client_keys
+        .is_origin_allowed(&client_key.id, origin)
+        .await?
+    {
+        return Ok(());
     }
+    let origin = origin.unwrap_or_default().to_string();
+    warn!(
+        key = %client_key.name,
+        %origin,
+        "auth rejected: origin not in key's allow-list"
+    );
+    Err(ProxyError::OriginNotAllowed(origin))
+}
 
-    // Try Authorization: Bearer header (alternative)
-    extract_bearer_token(headers)
+/// Reject a key used from outside its IP allow-list (no entries = any IP)
+/// or, for browser requests, its origin allow-list.
+async fn check_key_access(
+    state: &AppState,
+    client_key: &ClientKey,
+    headers: &HeaderMap,
+    client_ip: IpAddr,
+) -> Result<(), ProxyError> {
+    if !state
+        .client_keys
+        .is_ip_allowed(&client_key.id, client_ip)
+        .await?
+    {
+        warn!(
+            key = %client_key.name,
+            %client_ip,
+            "auth rejected: client IP not in key's allow-list"
+        );
+        return Err(ProxyError::IpNotAllowed(client_ip));
+    }
+    check_origin(state, client_key, headers).await
 }
 
 /// Build a non-sensitive fingerprint of a presented API key for logging.
@@ -47,32 +145,32 @@ async fn get_oauth_token(state: &AppState) -> Result<String, ProxyError> {
     match state.oauth.refresh_if_needed().await {
         Ok(Some(token)) => Ok(token),
         Ok(None) => Err(ProxyError::NoAuthConfigured),
-        Err(e) => Err(ProxyError::OAuthError(e)),
+        Err(e) => Err(ProxyError::OAuthRefresh(e)),
     }
 }
 
-/// Shared authentication logic: validate key, check limits, get OAuth token
-async fn authenticate_key(
-    key: &str,
+/// Full authentication flow for a request to `model`, shared by the
+/// OpenAI-compatible and Anthropic native endpoints: access checks, limits
+/// and the OAuth token for a key found by [`validate_client_key`]. `peer`
+/// is the socket address; the client IP is resolved from it and any
+/// trusted `X-Forwarded-For` hops.
+pub async fn authenticate(
+    client_key: ClientKey,
+    headers: &HeaderMap,
     state: &Arc<AppState>,
+    peer: IpAddr,
     model: &str,
 ) -> Result<AuthResult, ProxyError> {
-    let client_key = match state.client_keys.validate(key).await? {
-        Some(ck) => ck,
-        None => {
-            warn!(
-                key_prefix = %key_fingerprint(key),
-                "auth rejected: no enabled key matches the presented API key"
-            );
-            return Err(ProxyError::InvalidApiKey);
-        }
-    };
+    let client_ip = resolve_client_ip(peer, headers, state.trusted_proxy_hops);
+    telemetry::record_key_and_model(&client_key.id, model);
+    check_key_access(state, &client_key, headers, client_ip).await?;
 
     // Get window resets for limit checks. Pure read from the usage cache —
     // no HTTP I/O. The cache is kept fresh by `patch_from_headers` on every
     // /v1/messages response and by the opportunistic refresh triggered by
     // the admin UI poll.
-    let window_resets = state.usage_cache.snapshot().await.window_state();
+    let cached_usage = state.usage_cache.snapshot().await;
+    let window_resets = cached_usage.window_state();
 
     // Check global limits (cost-based, derived from per-model aggregation)
     if let Err(msg) = state
@@ -126,17 +224,37 @@ async fn authenticate_key(
         return Err(ProxyError::RateLimitExceeded(msg));
     }
 
-    // Block keys without extra-usage permission when subscription limits are
-    // exhausted. Reads from the usage cache (populated from /v1/messages
-    // response headers in near real time); no per-request HTTP call.
-    if !client_key.allow_extra_usage && state.usage_cache.is_over_subscription_limit().await {
-        warn!(
-            key = %client_key.name,
-            "auth rejected: subscription limits exhausted (extra usage not allowed for this key)"
-        );
-        return Err(ProxyError::RateLimitExceeded(
-            "Subscription limits exhausted (extra usage not allowed for this key)".into(),
-        ));
+    // Block keys without extra-usage permission when subscription utilization
+    // reaches the configured gate (100% by default). Normally a pure read
+    // from the usage cache (populated from /v1/messages response headers in
+    // near real time); only a stale reading just over the gate is refetched.
+    match gate_decision(
+        state,
+        client_key.allow_extra_usage,
+        &cached_usage,
+        timestamp_millis(),
+    )
+    .await
+    {
+        QuotaDecision::Included => {}
+        QuotaDecision::ExtraUsage => {
+            info!(
+                key = %client_key.name,
+                "subscription quota exhausted; request runs on extra usage"
+            );
+        }
+        QuotaDecision::Blocked(gate) => {
+            warn!(
+                key = %client_key.name,
+                threshold = state.subscription_gate_pct,
+                "auth rejected: subscription utilization gate reached (extra usage not allowed for this key)"
+            );
+            return Err(ProxyError::SubscriptionLimitReached {
+                message: "Subscription limits exhausted (extra usage not allowed for this key)"
+                    .into(),
+                retry_after_secs: gate.retry_after_secs(timestamp_millis()),
+            });
+        }
     }
 
     if let Err(e) = state.client_keys.update_last_used(&client_key.id).await {
@@ -148,26 +266,152 @@ async fn authenticate_key(
     Ok(AuthResult { client_key, token })
 }
 
-/// Full authentication flow for OpenAI-compatible endpoint
-pub async fn authenticate_openai(
+/// How a request relates to the subscription's included quota.
+///
+/// Subscription OAuth requests carry no wire-level opt-in for extra usage:
+/// Anthropic bills overage whenever extra usage is enabled on the account.
+/// Refusing keys without `allow_extra_usage` here is what keeps them from
+/// spilling into metered billing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QuotaDecision {
+    /// Inside the included quota (and below the configured gate).
+    Included,
+    /// Included quota is exhausted and the key may spill over into extra usage.
+    ExtraUsage,
+    /// Gate reached and the key is not allowed to use extra usage.
+    Blocked(UtilizationGate),
+}
+
+/// Decide whether a key may proceed given the current subscription state.
+///
+/// Keys without `allow_extra_usage` are blocked once utilization reaches
+/// `gate_pct`. Keys with it keep going past 100% as extra usage.
+fn quota_decision(
+    allow_extra_usage: bool,
+    subscription: &SubscriptionState,
+    gate_pct: f64,
+) -> QuotaDecision {
+    if allow_extra_usage {
+        return if subscription.utilization_gate(100.0).is_some() {
+            QuotaDecision::ExtraUsage
+        } else {
+            QuotaDecision::Included
+        };
+    }
+    match subscription.utilization_gate(gate_pct) {
+        Some(gate) => QuotaDecision::Blocked(gate),
+        None => QuotaDecision::Included,
+    }
+}
+
+/// [`quota_decision`] for a request at `now`, rechecked against a fresh
+/// usage fetch when the cached reading blocks the key but is
+/// [worth rechecking](CachedUsage::worth_gate_recheck), so a stale reading
+/// just over the gate doesn't answer 429 after the window has moved on.
+async fn gate_decision(
+    state: &AppState,
+    allow_extra_usage: bool,
+    cached: &CachedUsage,
+    now: u64,
+) -> QuotaDecision {
+    let gate_pct = state.subscription_gate_pct;
+    let decision = quota_decision(allow_extra_usage, &cached.window_state(), gate_pct);
+    if !matches!(decision, QuotaDecision::Blocked(_)) || !cached.worth_gate_recheck(gate_pct, now) {
+        return decision;
+    }
+    let fresh = fetch_fresh_subscription_state(state).await;
+    quota_decision(allow_extra_usage, &fresh, gate_pct)
+}
+
+/// Fetch subscription usage now, bypassing the cache's freshness windows.
+/// The result is stored in the cache as usual.
+async fn fetch_fresh_subscription_state(state: &AppState) -> SubscriptionState {
+    state.usage_cache.force_refresh(state).await.window_state()
+}
+
+/// The enabled key presented in any of the accepted headers. Endpoints look
+/// it up once, before resolving the model (the key may force one), and
+/// pass it on to [`authenticate`]. Read-only endpoints use it alone.
+pub async fn validate_client_key(
     headers: &HeaderMap,
     state: &Arc<AppState>,
-    model: &str,
-) -> Result<AuthResult, ProxyError> {
-    let key = extract_bearer_token(headers)
-        .ok_or_else(|| ProxyError::MissingHeader("Authorization".to_string()))?;
-    authenticate_key(key, state, model).await
+) -> Result<ClientKey, ProxyError> {
+    let key = extract_api_key(headers)
+        .ok_or_else(|| ProxyError::MissingHeader(API_KEY_HEADERS.to_string()))?;
+    match state.client_keys.validate(key).await? {
+        Some(ck) => Ok(ck),
+        None => {
+            warn!(
+                key_prefix = %key_fingerprint(key),
+                "auth rejected: no enabled key matches the presented API key"
+            );
+            Err(ProxyError::InvalidApiKey)
+        }
+    }
+}
+
+/// IP and origin allow-list checks for read-only endpoints, which look the
+/// key up with [`validate_client_key`] or [`optional_client_key`] but
+/// never go through [`authenticate`].
+pub async fn check_access(
+    client_key: &ClientKey,
+    headers: &HeaderMap,
+    state: &AppState,
+    peer: IpAddr,
+) -> Result<(), ProxyError> {
+    let client_ip = resolve_client_ip(peer, headers, state.trusted_proxy_hops);
+    check_key_access(state, client_key, headers, client_ip).await
 }
 
-/// Full authentication flow for Anthropic native endpoint
-pub async fn authenticate_anthropic(
+/// The client key presented in any of the accepted headers, for
+/// endpoints that also answer anonymous requests. `None` when no key was
+/// sent; an unknown or disabled key is still an error.
+pub async fn optional_client_key(
     headers: &HeaderMap,
     state: &Arc<AppState>,
-    model: &str,
+) -> Result<Option<ClientKey>, ProxyError> {
+    let Some(key) = extract_api_key(headers) else {
+        return Ok(None);
+    };
+    match state.client_keys.validate(key).await? {
+        Some(ck) => Ok(Some(ck)),
+        None => {
+            warn!(
+                key_prefix = %key_fingerprint(key),
+                "auth rejected: no enabled key matches the presented API key"
+            );
+            Err(ProxyError::InvalidApiKey)
+        }
+    }
+}
+
+/// The model to resolve for a request: a key's `forced` model replaces
+/// whatever the client asked for. The flag is set when that discarded a
+/// different model the client requested.
+pub fn pin_model<'a>(
+    requested: Option<&'a str>,
+    forced: Option<&'a str>,
+) -> (Option<&'a str>, bool) {
+    match forced {
+        Some(forced) => (Some(forced), requested.is_some_and(|r| r != forced)),
+        None => (requested, false),
+    }
+}
+
+/// Authentication for endpoints not tied to a model (batch status and
+/// results, estimates): the key's IP and origin allow-lists and the OAuth
+/// token. Limits and model access are the caller's to check, or were
+/// checked when the work was submitted.
+pub async fn authenticate_without_model(
+    client_key: ClientKey,
+    headers: &HeaderMap,
+    state: &Arc<AppState>,
+    peer: IpAddr,
 ) -> Result<AuthResult, ProxyError> {
-    let key = extract_api_key(headers)
-        .ok_or_else(|| ProxyError::MissingHeader("x-api-key or Authorization".to_string()))?;
-    authenticate_key(key, state, model).await
+    let client_ip = resolve_client_ip(peer, headers, state.trusted_proxy_hops);
+    check_key_access(state, &client_key, headers, client_ip).await?;
+    let token = get_oauth_token(state).await?;
+    Ok(AuthResult { client_key, token })
 }
 
 /// Parse client-supplied beta flags from the inbound `anthropic-beta` header.
@@ -211,14 +455,42 @@ pub fn build_anthropic_request(
     client: &Client,
     url: &str,
     token: &str,
+    anthropic_version: &str,
+    extra_betas: Option<&[String]>,
+    session_id: &str,
+) -> RequestBuilder {
+    with_anthropic_headers(
+        client.post(url),
+        token,
+        anthropic_version,
+        extra_betas,
+        session_id,
+    )
+}
+
+/// GET counterpart of [`build_anthropic_request`], for reading upstream
+/// resources such as message batches.
+pub fn build_anthropic_get_request(
+    client: &Client,
+    url: &str,
+    token: &str,
+    anthropic_version: &str,
+    session_id: &str,
+) -> RequestBuilder {
+    with_anthropic_headers(client.get(url), token, anthropic_version, None, session_id)
+}
+
+fn with_anthropic_headers(
+    builder: RequestBuilder,
+    token: &str,
+    anthropic_version: &str,
     extra_betas: Option<&[String]>,
     session_id: &str,
 ) -> RequestBuilder {
     let beta_header = build_beta_header(extra_betas.unwrap_or(&[]));
 
-    client
-        .post(url)
-        .header("anthropic-version", ANTHROPIC_VERSION)
+    builder
+        .header("anthropic-version", anthropic_version)
         .header("content-type", "application/json")
         .header("authorization", format!("Bearer {}", token))
         .header("anthropic-beta", beta_header)
@@ -241,6 +513,40 @@ pub fn build_anthropic_request(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::auth::KeySettings;
+    use crate::test_support::{create_test_key, spawn_mock, state_with_upstream, with_db};
+    use axum::{Json, Router, http::HeaderValue, routing::get};
+    use serde_json::json;
+
+    #[test]
+    fn api_key_accepted_in_each_header() {
+        let key = "sk-proxy-abc123";
+        for (name, value) in [
+            ("x-api-key", key.to_string()),
+            ("api-key", key.to_string()),
+            ("authorization", format!("Bearer {key}")),
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, value.parse().unwrap());
+            assert_eq!(extract_api_key(&headers), Some(key), "{name}");
+        }
+    }
+
+    #[test]
+    fn api_key_header_precedence_and_rejects() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer sk-proxy-bearer".parse().unwrap());
+        headers.insert("api-key", "sk-proxy-azure".parse().unwrap());
+        assert_eq!(extract_api_key(&headers), Some("sk-proxy-azure"));
+        headers.insert("x-api-key", "sk-proxy-anthropic".parse().unwrap());
+        assert_eq!(extract_api_key(&headers), Some("sk-proxy-anthropic"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Basic dXNlcjpwYXNz".parse().unwrap());
+        assert_eq!(extract_api_key(&headers), None);
+        headers.insert("x-api-key", "".parse().unwrap());
+        assert_eq!(extract_api_key(&headers), None);
+    }
 
     fn headers_with_beta(value: &str) -> HeaderMap {
         let mut h = HeaderMap::new();
@@ -248,6 +554,161 @@ mod tests {
         h
     }
 
+    fn subscription(five_hour: f64) -> SubscriptionState {
+        SubscriptionState {
+            five_hour_reset_at: Some(60_000),
+            seven_day_reset_at: None,
+            five_hour_utilization: Some(five_hour),
+            seven_day_utilization: Some(0.0),
+        }
+    }
+
+    fn auth_with_settings(settings: KeySettings) -> AuthResult {
+        AuthResult {
+            client_key: ClientKey {
+                id: "key-1".into(),
+                key: "sk-proxy-test".into(),
+                name: "test".into(),
+                created_at: 0,
+                last_used_at: None,
+                enabled: true,
+                allow_extra_usage: false,
+                limits: Default::default(),
+                usage: Default::default(),
+                settings,
+                windows: Default::default(),
+                note: None,
+                force_model: None,
+            },
+            token: "token".into(),
+        }
+    }
+
+    #[test]
+    fn prepare_options_apply_key_prefix_override_only_to_that_key() {
+        let custom = auth_with_settings(KeySettings {
+            system_prefix_override: Some("You are a support bot.".into()),
+            ..KeySettings::default()
+        });
+        let plain = auth_with_settings(KeySettings::default());
+
+        let options = custom.prepare_options(true, ModelCapabilities::default());
+        assert_eq!(options.system_prefix, "You are a support bot.");
+        let options = plain.prepare_options(true, ModelCapabilities::default());
+        assert_eq!(options.system_prefix, crate::constants::SYSTEM_PREFIX);
+    }
+
+    #[test]
+    fn anthropic_version_header_uses_key_override() {
+        let client = Client::new();
+        let version_sent = |auth: &AuthResult| {
+            let request = build_anthropic_request(
+                &client,
+                "https://api.anthropic.com/v1/messages",
+                &auth.token,
+                auth.anthropic_version(),
+                None,
+                "session",
+            )
+            .build()
+            .unwrap();
+            let values: Vec<_> = request
+                .headers()
+                .get_all("anthropic-version")
+                .iter()
+                .map(|v| v.to_str().unwrap().to_string())
+                .collect();
+            values
+        };
+
+        let pinned = auth_with_settings(KeySettings {
+            anthropic_version: Some("2024-10-22".into()),
+            ..KeySettings::default()
+        });
+        assert_eq!(version_sent(&pinned), ["2024-10-22"]);
+
+        let plain = auth_with_settings(KeySettings::default());
+        assert_eq!(version_sent(&plain), [ANTHROPIC_VERSION]);
+    }
+
+    #[test]
+    fn quota_decision_extra_usage_only_for_allowed_keys() {
+        let exhausted = subscription(100.0);
+        assert_eq!(
+            quota_decision(true, &exhausted, 100.0),
+            QuotaDecision::ExtraUsage
+        );
+        assert!(matches!(
+            quota_decision(false, &exhausted, 100.0),
+            QuotaDecision::Blocked(_)
+        ));
+    }
+
+    #[test]
+    fn quota_decision_within_quota_is_included() {
+        let fresh = subscription(40.0);
+        assert_eq!(quota_decision(true, &fresh, 100.0), QuotaDecision::Included);
+        assert_eq!(
+            quota_decision(false, &fresh, 100.0),
+            QuotaDecision::Included
+        );
+    }
+
+    #[test]
+    fn quota_decision_gate_does_not_apply_to_extra_usage_keys() {
+        let near_limit = subscription(96.0);
+        assert_eq!(
+            quota_decision(true, &near_limit, 95.0),
+            QuotaDecision::Included
+        );
+        assert!(matches!(
+            quota_decision(false, &near_limit, 95.0),
+            QuotaDecision::Blocked(_)
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn stale_reading_just_over_gate_is_refetched() {
+        with_db(async {
+            let upstream = Router::new().route(
+                "/api/oauth/usage",
+                get(|| async {
+                    Json(json!({
+                        "five_hour": { "utilization": 70.0, "resets_at": "2025-10-09T12:00:00+00:00" }
+                    }))
+                }),
+            );
+            let state = AppState {
+                subscription_gate_pct: 80.0,
+                ..state_with_upstream(spawn_mock(upstream).await)
+            };
+            create_test_key(&state, "gate-recheck").await;
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                "anthropic-ratelimit-unified-5h-utilization",
+                HeaderValue::from_static("0.81"),
+            );
+            state.usage_cache.patch_from_headers(&headers).await;
+            let cached = state.usage_cache.snapshot().await;
+            let now = timestamp_millis();
+
+            // A reading that just arrived is trusted as is
+            assert!(matches!(
+                gate_decision(&state, false, &cached, now).await,
+                QuotaDecision::Blocked(_)
+            ));
+            // A minute on it is refetched, and the usage endpoint reports
+            // the window under the gate
+            assert_eq!(
+                gate_decision(&state, false, &cached, now + 60_000).await,
+                QuotaDecision::Included
+            );
+            let refreshed = state.usage_cache.snapshot().await.window_state();
+            assert_eq!(refreshed.five_hour_utilization, Some(70.0));
+        });
+    }
+
     #[test]
     fn extract_client_betas_splits_and_trims() {
         let h = headers_with_beta("advisor-2026-03-01, fine-grained-tool-streaming-2025-05-14 ,");
@@ -289,4 +750,25 @@ mod tests {
     fn build_beta_header_no_extras_is_base() {
         assert_eq!(build_beta_header(&[]), OAUTH_BETA_HEADER);
     }
+
+    #[test]
+    fn forced_model_replaces_requested_model() {
+        let forced = Some("claude-haiku-4-5");
+        assert_eq!(
+            pin_model(Some("claude-opus-4-6"), forced),
+            (Some("claude-haiku-4-5"), true)
+        );
+        // Asking for the forced model, or for none, overrides nothing
+        assert_eq!(
+            pin_model(Some("claude-haiku-4-5"), forced),
+            (Some("claude-haiku-4-5"), false)
+        );
+        assert_eq!(pin_model(None, forced), (Some("claude-haiku-4-5"), false));
+
+        assert_eq!(
+            pin_model(Some("claude-opus-4-6"), None),
+            (Some("claude-opus-4-6"), false)
+        );
+        assert_eq!(pin_model(None, None), (None, false));
+    }
 }
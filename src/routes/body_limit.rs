@@ -0,0 +1,129 @@
+//! Request body size limit for the inference API.
+//!
+//! `DefaultBodyLimit` makes the `Json` extractor reject oversized bodies with
+//! a plain-text 413. API clients expect errors in their own wire format, so
+//! [`json_payload_too_large`] rewrites that rejection into an OpenAI- or
+//! Anthropic-shaped JSON error depending on the endpoint.
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+/// Middleware that turns axum's plain-text 413 into a JSON error. `limit` is
+/// the configured body limit in bytes, used only for the message. Upstream
+/// 413s that are already JSON pass through untouched.
+pub async fn json_payload_too_large(
+    State(limit): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let anthropic = request.uri().path().contains("/messages");
+    let response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE || is_json {
+        return response;
+    }
+
+    let message = format!(
+        "Request body exceeds the {} MB limit",
+        limit.div_ceil(1024 * 1024)
+    );
+    let body = if anthropic {
+        json!({
+            "type": "error",
+            "error": {
+                "type": "request_too_large",
+                "message": message
+            }
+        })
+    } else {
+        json!({
+            "error": {
+                "message": message,
+                "type": "invalid_request_error",
+                "param": null,
+                "code": "payload_too_large"
+            }
+        })
+    };
+    (StatusCode::PAYLOAD_TOO_LARGE, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        extract::DefaultBodyLimit,
+        middleware,
+        routing::post,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    const LIMIT: usize = 1024;
+
+    async fn echo(Json(body): Json<Value>) -> Json<Value> {
+        Json(body)
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/chat/completions", post(echo))
+            .route("/messages", post(echo))
+            .layer(DefaultBodyLimit::max(LIMIT))
+            .layer(middleware::from_fn_with_state(
+                LIMIT,
+                json_payload_too_large,
+            ))
+    }
+
+    async fn post_json(path: &str, body: String) -> (StatusCode, Value) {
+        let request = Request::post(path)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    fn oversized() -> String {
+        json!({ "content": "x".repeat(LIMIT * 2) }).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_oversized_openai_body_is_json_413() {
+        let (status, body) = post_json("/chat/completions", oversized()).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["error"]["code"], "payload_too_large");
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_anthropic_body_is_json_413() {
+        let (status, body) = post_json("/messages", oversized()).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["type"], "error");
+        assert_eq!(body["error"]["type"], "request_too_large");
+    }
+
+    #[tokio::test]
+    async fn test_body_within_limit_passes() {
+        let (status, body) = post_json("/chat/completions", json!({"a": 1}).to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["a"], 1);
+    }
+}
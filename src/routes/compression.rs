@@ -0,0 +1,119 @@
+//! gzip/deflate response compression.
+//!
+//! Compression is negotiated from the client's `Accept-Encoding`. SSE
+//! responses are never compressed: the encoder buffers output, which would
+//! hold back stream events and keep-alive pings until enough bytes pile up.
+//! Their size is unknown up front, so the size threshold alone would not
+//! exclude them.
+//!
+//! The layer is applied per router with [`compressed`], only to routes that
+//! never stream: the admin API, usage exports and `/v1` listings. Inference
+//! routes are left out entirely; the SSE predicate is a second line of
+//! defense.
+
+use axum::Router;
+use tower_http::compression::{
+    CompressionLayer, Predicate,
+    predicate::{NotForContentType, SizeAbove},
+};
+
+/// Responses smaller than this aren't worth the CPU or the framing overhead.
+const MIN_COMPRESS_BYTES: u64 = 1024;
+
+/// Compression layer for the whole router, skipping streaming and
+/// already-compressed content types.
+pub fn compression_layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new().compress_when(
+        SizeAbove::new(MIN_COMPRESS_BYTES)
+            .and(NotForContentType::SSE)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES),
+    )
+}
+
+/// `router` with [`compression_layer`] when `enabled`.
+pub fn compressed<S>(router: Router<S>, enabled: bool) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    if enabled {
+        router.layer(compression_layer())
+    } else {
+        router
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Json,
+        body::Body,
+        extract::Request,
+        http::{StatusCode, header},
+        response::{IntoResponse, Response},
+        routing::get,
+    };
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    async fn large_json() -> Json<Value> {
+        let keys: Vec<Value> = (0..200)
+            .map(|i| json!({ "id": format!("key-{i}"), "name": "example key" }))
+            .collect();
+        Json(json!({ "keys": keys }))
+    }
+
+    async fn sse() -> Response {
+        (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/event-stream")],
+            "data: {}\n\n".repeat(500),
+        )
+            .into_response()
+    }
+
+    async fn get_with_gzip(path: &str) -> Response {
+        let listings = Router::new()
+            .route("/keys", get(large_json))
+            .route("/stream", get(sse));
+        // Same payload, outside the compressed router (like inference routes)
+        let app = Router::new()
+            .route("/inference", get(large_json))
+            .merge(compressed(listings, true));
+        let request = Request::get(path)
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_large_json_is_gzipped() {
+        let response = get_with_gzip("/keys").await;
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    }
+
+    #[tokio::test]
+    async fn test_sse_is_not_compressed() {
+        let response = get_with_gzip("/stream").await;
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_routes_outside_compressed_router_untouched() {
+        let response = get_with_gzip("/inference").await;
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_compression_disabled() {
+        let app = compressed(Router::new().route("/keys", get(large_json)), false);
+        let request = Request::get("/keys")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+}
@@ -0,0 +1,393 @@
+//! `POST /v1/messages/count_tokens/batch`: token counts for many message
+//! bodies in one call.
+//!
+//! The request body is a JSON array of `count_tokens` bodies; the response is
+//! an array of the same length, each entry either `{"input_tokens": n}` or
+//! `{"error": {"type", "message"}}`. A bad item never fails the whole batch.
+//! Items are counted upstream concurrently (bounded) through the shared HTTP
+//! client.
+
+use axum::{
+    Json,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::{StreamExt, stream};
+use reqwest::Client;
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::AppState;
+use crate::auth::ModelCapabilities;
+use crate::count_tokens_cache::{CacheKey, CountTokensCache};
+use crate::error::ProxyError;
+use crate::transforms::{PrepareOptions, prepare_count_tokens_request};
+
+use super::auth::{
+    authenticate, build_anthropic_request, extract_client_betas, pin_model, validate_client_key,
+};
+
+/// Most items accepted in one batch.
+const MAX_BATCH_ITEMS: usize = 100;
+
+/// Upstream count_tokens calls in flight per batch.
+const BATCH_CONCURRENCY: usize = 4;
+
+fn item_error(error_type: &str, message: impl Into<String>) -> Value {
+    json!({ "error": { "type": error_type, "message": message.into() } })
+}
+
+/// Reject items that can't be a count_tokens body before spending an
+/// upstream call on them.
+fn check_item_shape(item: &Value) -> Result<(), Value> {
+    let Some(obj) = item.as_object() else {
+        return Err(item_error(
+            "invalid_request_error",
+            "Item must be a JSON object",
+        ));
+    };
+    if !obj.get("messages").is_some_and(Value::is_array) {
+        return Err(item_error(
+            "invalid_request_error",
+            "Item is missing a `messages` array",
+        ));
+    }
+    Ok(())
+}
+
+/// Shared upstream parameters for one batch.
+pub(super) struct Upstream<'a> {
+    pub(super) client: &'a Client,
+    pub(super) url: &'a str,
+    pub(super) token: &'a str,
+    pub(super) anthropic_version: &'a str,
+    pub(super) session_id: &'a str,
+    pub(super) betas: &'a [String],
+    pub(super) options: &'a PrepareOptions<'a>,
+    pub(super) cache: Option<&'a CountTokensCache>,
+}
+
+/// Count one prepared-for-upstream item. Upstream errors are mapped into the
+/// item's error entry, keeping Anthropic's error type when it sends one.
+pub(super) async fn count_one(upstream: &Upstream<'_>, item: Value) -> Value {
+    let mut prepared = prepare_count_tokens_request(item, upstream.options);
+    for beta in upstream.betas {
+        if !prepared.betas.contains(beta) {
+            prepared.betas.push(beta.clone());
+        }
+    }
+
+    let cached = upstream.cache.map(|cache| {
+        let key = CacheKey::new(&prepared.body, &prepared.betas, upstream.anthropic_version);
+        (cache, key)
+    });
+    if let Some((cache, key)) = &cached
+        && let Some(tokens) = cache
+            .get(key, Instant::now())
+            .and_then(|counts| counts.get("input_tokens").cloned())
+    {
+        return json!({ "input_tokens": tokens });
+    }
+
+    let response = match build_anthropic_request(
+        upstream.client,
+        upstream.url,
+        upstream.token,
+        upstream.anthropic_version,
+        Some(&prepared.betas),
+        upstream.session_id,
+    )
+    .json(&prepared.body)
+    .send()
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => return item_error("api_error", format!("Failed to contact Anthropic: {e}")),
+    };
+
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    let parsed: Option<Value> = serde_json::from_str(&text).ok();
+    if !status.is_success() {
+        let upstream_error = parsed.as_ref().and_then(|v| v.get("error"));
+        let error_type = upstream_error
+            .and_then(|e| e.get("type"))
+            .and_then(|t| t.as_str())
+            .unwrap_or("api_error");
+        let message = upstream_error
+            .and_then(|e| e.get("message"))
+            .and_then(|m| m.as_str())
+            .map_or_else(|| format!("Anthropic returned {status}"), str::to_string);
+        return item_error(error_type, message);
+    }
+
+    let Some(tokens) = parsed.as_ref().and_then(|v| v.get("input_tokens")).cloned() else {
+        return item_error("api_error", "Unexpected count_tokens response");
+    };
+    if let Some((cache, key)) = cached
+        && let Some(response) = parsed
+    {
+        cache.insert(key, response, Instant::now());
+    }
+    json!({ "input_tokens": tokens })
+}
+
+/// Count every item, preserving order. Items that already failed checks are
+/// passed through as their error entry.
+async fn count_items(upstream: &Upstream<'_>, items: Vec<Result<Value, Value>>) -> Vec<Value> {
+    stream::iter(items)
+        .map(|item| async move {
+            match item {
+                Ok(body) => count_one(upstream, body).await,
+                Err(error) => error,
+            }
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// Whether `model` is enabled and in the key's allow-list.
+async fn model_available(state: &AppState, key_id: &str, model: &str) -> Result<bool, ProxyError> {
+    Ok(state.models.is_valid(model).await?
+        && state.client_keys.is_model_allowed(key_id, model).await?)
+}
+
+/// The key is authenticated (limits, IP allow-list, OAuth token) against the
+/// first item's model; every item's model is then checked against the model
+/// catalog and the key's allow-list individually.
+pub async fn count_tokens_batch(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(items): Json<Vec<Value>>,
+) -> Response {
+    if items.len() > MAX_BATCH_ITEMS {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "type": "error",
+                "error": {
+                    "type": "invalid_request_error",
+                    "message": format!("Batch exceeds {MAX_BATCH_ITEMS} items")
+                }
+            })),
+        )
+            .into_response();
+    }
+
+    let client_key = match validate_client_key(&headers, &state).await {
+        Ok(k) => k,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    let mut checked = Vec::with_capacity(items.len());
+    for mut item in items {
+        let result = match check_item_shape(&item) {
+            Ok(()) => {
+                let (requested, _) = pin_model(
+                    item.get("model").and_then(|m| m.as_str()),
+                    client_key.force_model.as_deref(),
+                );
+                match state
+                    .models
+                    .resolve_model(requested, &state.default_model)
+                    .await
+                {
+                    Ok(model) => {
+                        if let Some(obj) = item.as_object_mut() {
+                            obj.insert("model".to_string(), Value::String(model.clone()));
+                        }
+                        Ok((model, item))
+                    }
+                    Err(e) => Err(item_error("api_error", e.to_string())),
+                }
+            }
+            Err(error) => Err(error),
+        };
+        checked.push(result);
+    }
+
+    let first_model = checked
+        .iter()
+        .find_map(|r| r.as_ref().ok().map(|(model, _)| model.clone()))
+        .unwrap_or_else(|| state.default_model.clone());
+    let auth = match authenticate(client_key, &headers, &state, peer.ip(), &first_model).await {
+        Ok(a) => a,
+        Err(err) => return err.to_anthropic_response(),
+    };
+
+    let mut items = Vec::with_capacity(checked.len());
+    for result in checked {
+        let item = match result {
+            Ok((model, body)) => match model_available(&state, &auth.client_key.id, &model).await {
+                Ok(true) => Ok(body),
+                Ok(false) => Err(item_error(
+                    "permission_error",
+                    format!("Model not available for this key: {model}"),
+                )),
+                Err(e) => Err(item_error("api_error", e.to_string())),
+            },
+            Err(error) => Err(error),
+        };
+        items.push(item);
+    }
+
+    let cloak = auth.should_cloak(&state, &headers);
+    let options = auth
+        .prepare_options(cloak, ModelCapabilities::default())
+        .with_cache_min_tokens(state.cache_min_tokens);
+    let betas = extract_client_betas(&headers);
+    let upstream = Upstream {
+        client: &state.http_client,
+        url: &state.upstream.count_tokens,
+        token: &auth.token,
+        anthropic_version: auth.anthropic_version(),
+        session_id: &state.session_id,
+        betas: &betas,
+        options: &options,
+        cache: state.count_tokens_cache.as_ref(),
+    };
+    Json(count_items(&upstream, items).await).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::ANTHROPIC_VERSION;
+    use crate::test_support::spawn_mock;
+    use axum::{Router, routing::post};
+
+    /// Mock count_tokens endpoint: one token per message, 400 for an empty
+    /// message list.
+    async fn mock_server() -> String {
+        let app = Router::new().route(
+            "/v1/messages/count_tokens",
+            post(|Json(body): Json<Value>| async move {
+                let count = body["messages"].as_array().map_or(0, Vec::len);
+                if count == 0 {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({
+                            "type": "error",
+                            "error": {
+                                "type": "invalid_request_error",
+                                "message": "messages: at least one message is required"
+                            }
+                        })),
+                    );
+                }
+                (StatusCode::OK, Json(json!({ "input_tokens": count })))
+            }),
+        );
+        let addr = spawn_mock(app).await;
+        format!("http://{addr}/v1/messages/count_tokens")
+    }
+
+    fn checked(item: Value) -> Result<Value, Value> {
+        check_item_shape(&item).map(|()| item)
+    }
+
+    #[tokio::test]
+    async fn test_mixed_batch_reports_errors_per_item() {
+        let url = mock_server().await;
+        let client = Client::new();
+        let options = PrepareOptions::new(false);
+        let upstream = Upstream {
+            client: &client,
+            url: &url,
+            token: "token",
+            anthropic_version: ANTHROPIC_VERSION,
+            session_id: "session",
+            betas: &[],
+            options: &options,
+            cache: None,
+        };
+
+        let user = json!({ "role": "user", "content": "hi" });
+        let items = vec![
+            checked(json!({ "model": "claude-sonnet-4-5", "messages": [user.clone()] })),
+            checked(json!("not an object")),
+            checked(json!({ "model": "claude-sonnet-4-5", "messages": [] })),
+            checked(json!({ "model": "claude-sonnet-4-5", "messages": [user.clone(), user] })),
+        ];
+
+        let results = count_items(&upstream, items).await;
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0], json!({ "input_tokens": 1 }));
+        assert_eq!(results[1]["error"]["type"], "invalid_request_error");
+        assert_eq!(
+            results[2]["error"]["message"],
+            "messages: at least one message is required"
+        );
+        assert_eq!(results[3], json!({ "input_tokens": 2 }));
+    }
+
+    #[tokio::test]
+    async fn test_identical_count_served_from_cache() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/v1/messages/count_tokens",
+            post(move |Json(body): Json<Value>| {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let count = body["messages"].as_array().map_or(0, Vec::len);
+                    Json(json!({ "input_tokens": count }))
+                }
+            }),
+        );
+        let url = format!("http://{}/v1/messages/count_tokens", spawn_mock(app).await);
+
+        let client = Client::new();
+        let options = PrepareOptions::new(false);
+        let cache = CountTokensCache::new(Duration::from_secs(300));
+        let upstream = Upstream {
+            client: &client,
+            url: &url,
+            token: "token",
+            anthropic_version: ANTHROPIC_VERSION,
+            session_id: "session",
+            betas: &[],
+            options: &options,
+            cache: Some(&cache),
+        };
+
+        let user = json!({ "role": "user", "content": "hi" });
+        let item = json!({ "model": "claude-sonnet-4-5", "messages": [user.clone()] });
+        assert_eq!(
+            count_one(&upstream, item.clone()).await,
+            json!({ "input_tokens": 1 })
+        );
+        assert_eq!(
+            count_one(&upstream, item).await,
+            json!({ "input_tokens": 1 })
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // A different body still goes upstream
+        let other = json!({ "model": "claude-sonnet-4-5", "messages": [user.clone(), user] });
+        assert_eq!(
+            count_one(&upstream, other).await,
+            json!({ "input_tokens": 2 })
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_item_without_messages_is_rejected() {
+        let error = check_item_shape(&json!({ "model": "claude-sonnet-4-5" })).unwrap_err();
+        assert!(
+            error["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("messages")
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! `POST /v1/messages/estimate`: what a request's input would cost, without
+//! generating anything.
+//!
+//! The body is a `count_tokens` body (a `messages` request is accepted as
+//! is). Its input is counted upstream like [`super::count_tokens_batch`]
+//! does, then priced at the resolved model's `input_price`. The key, its IP
+//! and origin allow-lists and its model allow-list are checked; cost limits
+//! are not, since an estimate spends nothing.
+
+use axum::{
+    Json,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::auth::{ModelCapabilities, ModelPricing};
+use crate::error::ProxyError;
+use crate::transforms::base_model;
+
+use super::auth::{
+    authenticate_without_model, extract_client_betas, pin_model, validate_client_key,
+};
+use super::count_tokens_batch::{Upstream, count_one};
+
+/// `messages` fields that only affect generation; count_tokens rejects them.
+const GENERATION_FIELDS: [&str; 8] = [
+    "max_tokens",
+    "stream",
+    "temperature",
+    "top_p",
+    "top_k",
+    "stop_sequences",
+    "metadata",
+    "service_tier",
+];
+
+/// Drop [`GENERATION_FIELDS`] so a `messages` body can be counted.
+pub(super) fn strip_generation_fields(body: &mut Value) {
+    if let Some(obj) = body.as_object_mut() {
+        for field in GENERATION_FIELDS {
+            obj.remove(field);
+        }
+    }
+}
+
+/// Response body of the estimate endpoint.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Estimate {
+    pub input_tokens: u64,
+    pub estimated_input_cost_microdollars: u64,
+}
+
+/// Cost of `tokens` input tokens, in microdollars (prices are per million
+/// tokens, as in `compute_cost`).
+fn input_cost_microdollars(tokens: u64, pricing: &ModelPricing) -> u64 {
+    let cost = tokens as f64 * pricing.input_price;
+    #[expect(
+        clippy::cast_sign_loss,
+        reason = "token counts and configured prices are non-negative"
+    )]
+    {
+        cost.round() as u64
+    }
+}
+
+/// Count `body` upstream and price it. Errors are Anthropic error objects
+/// (`{"type", "message"}`).
+async fn estimate_input_cost(
+    upstream: &Upstream<'_>,
+    body: Value,
+    pricing: &ModelPricing,
+) -> Result<Estimate, Value> {
+    let mut counted = count_one(upstream, body).await;
+    if let Some(error) = counted.get_mut("error") {
+        return Err(error.take());
+    }
+    let input_tokens = counted
+        .get("input_tokens")
+        .and_then(Value::as_u64)
+        .ok_or_else(
+            || json!({ "type": "api_error", "message": "Unexpected count_tokens response" }),
+        )?;
+    Ok(Estimate {
+        input_tokens,
+        estimated_input_cost_microdollars: input_cost_microdollars(input_tokens, pricing),
+    })
+}
+
+pub(super) fn error_status(error_type: &str) -> StatusCode {
+    match error_type {
+        "invalid_request_error" => StatusCode::BAD_REQUEST,
+        "authentication_error" => StatusCode::UNAUTHORIZED,
+        "permission_error" => StatusCode::FORBIDDEN,
+        "rate_limit_error" => StatusCode::TOO_MANY_REQUESTS,
+        "overloaded_error" => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+pub async fn estimate(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> Response {
+    let client_key = match validate_client_key(&headers, &state).await {
+        Ok(k) => k,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    let (requested, _) = pin_model(
+        body.get("model").and_then(Value::as_str),
+        client_key.force_model.as_deref(),
+    );
+    let model = match state
+        .models
+        .resolve_available_model(requested, &state.default_model, state.unknown_model_policy)
+        .await
+    {
+        Ok(m) => m,
+        Err(e) => return e.to_anthropic_response(),
+    };
+    if let Some(obj) = body.as_object_mut() {
+        obj.insert("model".to_string(), Value::String(model.clone()));
+    }
+    strip_generation_fields(&mut body);
+    let base = base_model(&model);
+
+    let auth = match authenticate_without_model(client_key, &headers, &state, peer.ip()).await {
+        Ok(a) => a,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    match state
+        .client_keys
+        .is_model_allowed(&auth.client_key.id, &base)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return ProxyError::ModelNotAllowed(base).to_anthropic_response(),
+        Err(e) => return e.to_anthropic_response(),
+    }
+    let Some(pricing) = state.models.get_pricing(&base).await else {
+        return ProxyError::InvalidModel(base).to_anthropic_response();
+    };
+
+    let cloak = auth.should_cloak(&state, &headers);
+    let options = auth
+        .prepare_options(cloak, ModelCapabilities::default())
+        .with_cache_min_tokens(state.cache_min_tokens);
+    let betas = extract_client_betas(&headers);
+    let upstream = Upstream {
+        client: &state.http_client,
+        url: &state.upstream.count_tokens,
+        token: &auth.token,
+        anthropic_version: auth.anthropic_version(),
+        session_id: &state.session_id,
+        betas: &betas,
+        options: &options,
+        cache: state.count_tokens_cache.as_ref(),
+    };
+    match estimate_input_cost(&upstream, body, &pricing).await {
+        Ok(estimate) => Json(estimate).into_response(),
+        Err(error) => {
+            let status = error_status(error.get("type").and_then(Value::as_str).unwrap_or(""));
+            (status, Json(json!({ "type": "error", "error": error }))).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::ANTHROPIC_VERSION;
+    use crate::test_support::spawn_mock;
+    use crate::transforms::PrepareOptions;
+    use axum::{Router, routing::post};
+    use reqwest::Client;
+
+    fn sonnet_pricing() -> ModelPricing {
+        ModelPricing {
+            input_price: 3.0,
+            output_price: 15.0,
+            cache_read_price: 0.3,
+            cache_write_price: 3.75,
+        }
+    }
+
+    /// Mock count_tokens endpoint: 1,200 tokens, 400 without messages.
+    async fn mock_server() -> String {
+        let app = Router::new().route(
+            "/v1/messages/count_tokens",
+            post(|Json(body): Json<Value>| async move {
+                if body.get("messages").is_none() {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({
+                            "type": "error",
+                            "error": {
+                                "type": "invalid_request_error",
+                                "message": "messages: Field required"
+                            }
+                        })),
+                    );
+                }
+                (StatusCode::OK, Json(json!({ "input_tokens": 1200 })))
+            }),
+        );
+        let addr = spawn_mock(app).await;
+        format!("http://{addr}/v1/messages/count_tokens")
+    }
+
+    #[tokio::test]
+    async fn test_estimate_prices_counted_input() {
+        let url = mock_server().await;
+        let client = Client::new();
+        let options = PrepareOptions::new(false);
+        let upstream = Upstream {
+            client: &client,
+            url: &url,
+            token: "token",
+            anthropic_version: ANTHROPIC_VERSION,
+            session_id: "session",
+            betas: &[],
+            options: &options,
+            cache: None,
+        };
+
+        let body = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": [{ "role": "user", "content": "hi" }]
+        });
+        assert_eq!(
+            estimate_input_cost(&upstream, body, &sonnet_pricing()).await,
+            Ok(Estimate {
+                input_tokens: 1200,
+                estimated_input_cost_microdollars: 3600,
+            })
+        );
+
+        let error = estimate_input_cost(
+            &upstream,
+            json!({ "model": "claude-sonnet-4-5" }),
+            &sonnet_pricing(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error["type"], "invalid_request_error");
+        assert_eq!(
+            error_status("invalid_request_error"),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
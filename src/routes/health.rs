@@ -1,16 +1,72 @@
-use axum::response::Json;
+use axum::{extract::State, response::Json};
 use serde_json::{Value, json};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
 
-use crate::{BUILD_TIME, GIT_HASH, VERSION};
+use crate::{AppState, BUILD_TIME, GIT_HASH, VERSION, db};
+
+/// When the process started serving, for `uptime_ms`.
+static STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Start the uptime clock. Called once at startup; uptime otherwise counts
+/// from the first `/version` request.
+pub fn mark_started() {
+    LazyLock::force(&STARTED);
+}
+
+fn uptime() -> Duration {
+    STARTED.elapsed()
+}
 
 pub async fn health() -> Json<Value> {
     Json(json!({ "status": "ok" }))
 }
 
-pub async fn version() -> Json<Value> {
-    Json(json!({
+/// Build info plus what monitoring needs to tell a ready instance: the
+/// schema version (`null` if the database can't be read), uptime and
+/// whether OAuth is connected.
+fn version_info(schema_version: Option<i64>, uptime: Duration, oauth_connected: bool) -> Value {
+    json!({
         "version": VERSION,
         "git_hash": GIT_HASH,
         "build_time": BUILD_TIME,
-    }))
+        "schema_version": schema_version,
+        "uptime_ms": u64::try_from(uptime.as_millis()).unwrap_or(u64::MAX),
+        "oauth_connected": oauth_connected,
+    })
+}
+
+pub async fn version(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let schema_version = db::schema_version().await.unwrap_or_else(|e| {
+        warn!("Version check: {e}");
+        None
+    });
+    let oauth_connected = state.auth_store.has("anthropic").await.unwrap_or(false);
+    Json(version_info(schema_version, uptime(), oauth_connected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_version_info_fields() {
+        mark_started();
+        let first = version_info(Some(17), uptime(), true);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let second = version_info(Some(17), uptime(), true);
+
+        // Existing fields stay for compatibility
+        for field in ["version", "git_hash", "build_time"] {
+            assert!(first[field].is_string(), "{field}");
+        }
+        assert_eq!(first["schema_version"], 17);
+        assert_eq!(first["oauth_connected"], true);
+        assert!(second["uptime_ms"].as_u64().unwrap() > first["uptime_ms"].as_u64().unwrap());
+
+        let no_db = version_info(None, uptime(), false);
+        assert!(no_db["schema_version"].is_null());
+        assert_eq!(no_db["oauth_connected"], false);
+    }
 }
@@ -0,0 +1,791 @@
+//! `/v1/messages/batches`: Anthropic's Message Batches API, proxied.
+//!
+//! Submitting a batch authenticates the key against the first request's
+//! model, then checks every request's model against the model catalog, the
+//! key's allow-list and its per-model limits before anything is sent. Each
+//! request's `params` goes through the same prepare pipeline as
+//! `/v1/messages`.
+//!
+//! The batch id is stored with the submitting key, so only that key can read
+//! the batch status and results. Usage isn't known until the batch has
+//! ended, so it is recorded from the results stream: every succeeded
+//! result's usage is logged against its model once the results have been
+//! read in full, and only the first full read counts.
+
+use std::collections::BTreeSet;
+use std::mem;
+use std::net::SocketAddr;
+use std::pin::pin;
+use std::sync::Arc;
+
+use async_stream::stream;
+use axum::{
+    Json,
+    body::Body,
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use llm_relay::Usage;
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use crate::AppState;
+use crate::auth::RequestTags;
+use crate::auth::usage::usage_from_json;
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+use crate::subscription::{SubscriptionState, timestamp_millis};
+use crate::telemetry;
+use crate::transforms::prepare_anthropic_request;
+
+use super::auth::{
+    authenticate, authenticate_without_model, build_anthropic_get_request, build_anthropic_request,
+    extract_client_betas, pin_model, validate_client_key,
+};
+
+fn error_response(status: StatusCode, error_type: &str, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(json!({
+            "type": "error",
+            "error": { "type": error_type, "message": message.into() }
+        })),
+    )
+        .into_response()
+}
+
+fn batch_not_found(id: &str) -> Response {
+    error_response(
+        StatusCode::NOT_FOUND,
+        "not_found_error",
+        format!("Message batch not found: {id}"),
+    )
+}
+
+/// Pass an upstream response through with its status and body.
+async fn forward_response(response: reqwest::Response) -> Response {
+    let status =
+        StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let text = response.text().await.unwrap_or_default();
+    (status, [(header::CONTENT_TYPE, "application/json")], text).into_response()
+}
+
+/// A batch model must be enabled, allowed for the key and within the key's
+/// per-model limits, just like a single `/v1/messages` request.
+async fn check_batch_model(
+    state: &AppState,
+    key_id: &str,
+    model: &str,
+    window_resets: &SubscriptionState,
+) -> Result<(), ProxyError> {
+    if !state.models.is_valid(model).await? {
+        return Err(ProxyError::InvalidModel(model.to_string()));
+    }
+    if !state.client_keys.is_model_allowed(key_id, model).await? {
+        return Err(ProxyError::ModelNotAllowed(model.to_string()));
+    }
+    state
+        .client_keys
+        .check_model_limits(key_id, model, window_resets)
+        .await
+        .map_err(ProxyError::RateLimitExceeded)
+}
+
+async fn save_batch_owner(id: &str, key_id: &str) -> Result<(), ProxyError> {
+    let conn = db::get_conn().await?;
+    sqlx::query!(
+        "INSERT INTO message_batches (id, key_id, created_at) VALUES ($1, $2, $3) \
+         ON CONFLICT (id) DO NOTHING",
+        id,
+        key_id,
+        timestamp_millis() as i64,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to save message batch")?;
+    Ok(())
+}
+
+/// Whether `key_id` submitted batch `id` through this proxy.
+async fn owns_batch(id: &str, key_id: &str) -> Result<bool, ProxyError> {
+    let conn = db::get_conn().await?;
+    let owner = sqlx::query_scalar!("SELECT key_id FROM message_batches WHERE id = $1", id)
+        .fetch_optional(&conn)
+        .await
+        .db_context("Failed to look up message batch")?;
+    Ok(owner.as_deref() == Some(key_id))
+}
+
+/// Mark a batch's usage as recorded. Returns `false` if an earlier read
+/// already did, so concurrent or repeated reads never double-count.
+async fn claim_usage_recording(id: &str) -> Result<bool, ProxyError> {
+    let conn = db::get_conn().await?;
+    let affected = sqlx::query!(
+        "UPDATE message_batches SET usage_recorded = TRUE WHERE id = $1 AND NOT usage_recorded",
+        id,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to claim message batch usage")?
+    .rows_affected();
+    Ok(affected > 0)
+}
+
+/// `POST /v1/messages/batches`
+pub async fn create_batch(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> Response {
+    let Some(requests) = body
+        .get_mut("requests")
+        .and_then(Value::as_array_mut)
+        .filter(|r| !r.is_empty())
+    else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            "Batch needs a non-empty `requests` array",
+        );
+    };
+
+    let client_key = match validate_client_key(&headers, &state).await {
+        Ok(k) => k,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    let mut models = Vec::with_capacity(requests.len());
+    for request in requests.iter_mut() {
+        let Some(params) = request.get_mut("params").and_then(Value::as_object_mut) else {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                "Every batch request needs a `params` object",
+            );
+        };
+        let (requested, _) = pin_model(
+            params.get("model").and_then(|m| m.as_str()),
+            client_key.force_model.as_deref(),
+        );
+        let model = match state
+            .models
+            .resolve_model(requested, &state.default_model)
+            .await
+        {
+            Ok(m) => m,
+            Err(e) => return e.to_anthropic_response(),
+        };
+        params.insert("model".to_string(), Value::String(model.clone()));
+        models.push(model);
+    }
+
+    let first_model = models
+        .first()
+        .map_or(state.default_model.as_str(), String::as_str);
+    let auth = match authenticate(client_key, &headers, &state, peer.ip(), first_model).await {
+        Ok(a) => a,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    let window_resets = state.usage_cache.snapshot().await.window_state();
+    let distinct: BTreeSet<&String> = models.iter().collect();
+    for model in distinct {
+        if let Err(e) = check_batch_model(&state, &auth.client_key.id, model, &window_resets).await
+        {
+            warn!(key = %auth.client_key.name, %model, "batch rejected: {e}");
+            return e.to_anthropic_response();
+        }
+    }
+
+    let cloak = auth.should_cloak(&state, &headers);
+    let mut betas = extract_client_betas(&headers);
+    for (request, model) in requests.iter_mut().zip(&models) {
+        let capabilities = match state.models.capabilities(model).await {
+            Ok(c) => c,
+            Err(e) => return e.to_anthropic_response(),
+        };
+        // Checked to be an object above
+        let Some(params) = request.get_mut("params") else {
+            continue;
+        };
+        let options = auth
+            .prepare_options(cloak, capabilities)
+            .with_cache_min_tokens(state.cache_min_tokens);
+        let prepared = match prepare_anthropic_request(mem::take(params), &options) {
+            Ok(p) => p,
+            Err(e) => return e.to_anthropic_response(),
+        };
+        for beta in prepared.betas {
+            if !betas.contains(&beta) {
+                betas.push(beta);
+            }
+        }
+        *params = prepared.body;
+    }
+
+    let response = match build_anthropic_request(
+        &state.http_client,
+        &state.upstream.batches,
+        &auth.token,
+        auth.anthropic_version(),
+        Some(&betas),
+        &state.session_id,
+    )
+    .json(&body)
+    .send()
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            return ProxyError::AnthropicApiError(format!("Failed to contact Anthropic: {e}"))
+                .to_anthropic_response();
+        }
+    };
+    telemetry::record_upstream_status(response.status().as_u16());
+    if !response.status().is_success() {
+        return forward_response(response).await;
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    let mut batch: Value = match serde_json::from_str(&text) {
+        Ok(v) => v,
+        Err(e) => {
+            return ProxyError::ParseError(format!("Failed to parse batch response: {e}"))
+                .to_anthropic_response();
+        }
+    };
+    let Some(id) = batch.get("id").and_then(|v| v.as_str()).map(str::to_string) else {
+        return ProxyError::ParseError("Batch response has no id".into()).to_anthropic_response();
+    };
+    // Without the owner row the key could never read its results, so a
+    // failure here is reported even though the batch was created upstream.
+    if let Err(e) = save_batch_owner(&id, &auth.client_key.id).await {
+        warn!("Batch {id} created upstream but not saved: {e}");
+        return e.to_anthropic_response();
+    }
+    info!(
+        key = %auth.client_key.name,
+        batch_id = %id,
+        requests = models.len(),
+        "Message batch submitted"
+    );
+    rewrite_results_url(&mut batch, &headers);
+    Json(batch).into_response()
+}
+
+/// `GET /v1/messages/batches/{id}`
+pub async fn get_batch(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let client_key = match validate_client_key(&headers, &state).await {
+        Ok(k) => k,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    let auth = match authenticate_without_model(client_key, &headers, &state, peer.ip()).await {
+        Ok(a) => a,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    match owns_batch(&id, &auth.client_key.id).await {
+        Ok(true) => {}
+        Ok(false) => return batch_not_found(&id),
+        Err(e) => return e.to_anthropic_response(),
+    }
+
+    let url = format!("{}/{id}", state.upstream.batches);
+    let response = match build_anthropic_get_request(
+        &state.http_client,
+        &url,
+        &auth.token,
+        auth.anthropic_version(),
+        &state.session_id,
+    )
+    .send()
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            return ProxyError::AnthropicApiError(format!("Failed to contact Anthropic: {e}"))
+                .to_anthropic_response();
+        }
+    };
+    if !response.status().is_success() {
+        return forward_response(response).await;
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<Value>(&text) {
+        Ok(mut batch) => {
+            rewrite_results_url(&mut batch, &headers);
+            Json(batch).into_response()
+        }
+        Err(e) => ProxyError::ParseError(format!("Failed to parse batch response: {e}"))
+            .to_anthropic_response(),
+    }
+}
+
+/// `GET /v1/messages/batches/{id}/results`: the JSONL results, streamed
+/// through unchanged while their usage is tallied.
+pub async fn get_batch_results(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let started_at = timestamp_millis();
+    let client_key = match validate_client_key(&headers, &state).await {
+        Ok(k) => k,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    let auth = match authenticate_without_model(client_key, &headers, &state, peer.ip()).await {
+        Ok(a) => a,
+        Err(err) => return err.to_anthropic_response(),
+    };
+    match owns_batch(&id, &auth.client_key.id).await {
+        Ok(true) => {}
+        Ok(false) => return batch_not_found(&id),
+        Err(e) => return e.to_anthropic_response(),
+    }
+
+    let url = format!("{}/{id}/results", state.upstream.batches);
+    let response = match build_anthropic_get_request(
+        &state.http_client,
+        &url,
+        &auth.token,
+        auth.anthropic_version(),
+        &state.session_id,
+    )
+    .send()
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            return ProxyError::AnthropicApiError(format!("Failed to contact Anthropic: {e}"))
+                .to_anthropic_response();
+        }
+    };
+    if !response.status().is_success() {
+        return forward_response(response).await;
+    }
+
+    let key_id = auth.client_key.id.clone();
+    let results = tally_results(response.bytes_stream(), move |usages| async move {
+        record_batch_usage(&state, &id, &key_id, &usages, started_at).await;
+    });
+    match Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-jsonl")
+        .body(Body::from_stream(results))
+    {
+        Ok(response) => response,
+        Err(e) => ProxyError::ParseError(format!("Failed to build results response: {e}"))
+            .to_anthropic_response(),
+    }
+}
+
+/// Log one request per succeeded result, unless an earlier read already did.
+/// Cost uses the model's regular prices; Anthropic's batch discount isn't
+/// applied, so key limits err on the safe side.
+async fn record_batch_usage(
+    state: &AppState,
+    batch_id: &str,
+    key_id: &str,
+    usages: &[(String, Usage)],
+    started_at: u64,
+) {
+    match claim_usage_recording(batch_id).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            warn!("Failed to record usage for batch {batch_id}: {e}");
+            return;
+        }
+    }
+    let window_resets = state.usage_cache.snapshot().await.window_state();
+    for (model, usage) in usages {
+        telemetry::record_usage(usage);
+        if let Err(e) = state
+            .client_keys
+            .record_model_usage(
+                key_id,
+                model,
+                usage,
+                &window_resets,
+                started_at,
+                &RequestTags::default(),
+            )
+            .await
+        {
+            warn!("Failed to record batch usage for key {key_id}/{model}: {e}");
+        }
+    }
+    info!(
+        batch_id,
+        results = usages.len(),
+        "Recorded message batch usage"
+    );
+}
+
+/// Point `results_url` at this proxy, so SDKs that follow it fetch results
+/// through the usage-recording endpoint with the client's own key.
+fn rewrite_results_url(batch: &mut Value, headers: &HeaderMap) {
+    let Some(id) = batch.get("id").and_then(|v| v.as_str()) else {
+        return;
+    };
+    if batch.get("results_url").is_none_or(Value::is_null) {
+        return;
+    }
+    let Some(host) = headers.get(header::HOST).and_then(|v| v.to_str().ok()) else {
+        return;
+    };
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+    let url = format!("{scheme}://{host}/v1/messages/batches/{id}/results");
+    if let Some(batch) = batch.as_object_mut() {
+        batch.insert("results_url".to_string(), Value::String(url));
+    }
+}
+
+/// The model and usage of one results line, if it is a succeeded result.
+fn result_usage(line: &Value) -> Option<(String, Usage)> {
+    let result = line.get("result")?;
+    if result.get("type").and_then(|t| t.as_str()) != Some("succeeded") {
+        return None;
+    }
+    let message = result.get("message")?;
+    let model = message.get("model")?.as_str()?.to_string();
+    Some((model, usage_from_json(message.get("usage")?)))
+}
+
+/// Collects per-result usage from JSONL bytes that may split lines anywhere.
+#[derive(Default)]
+struct ResultsTally {
+    pending: Vec<u8>,
+    usages: Vec<(String, Usage)>,
+}
+
+impl ResultsTally {
+    fn feed(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            self.parse_line(&line);
+        }
+    }
+
+    fn finish(mut self) -> Vec<(String, Usage)> {
+        let rest = mem::take(&mut self.pending);
+        self.parse_line(&rest);
+        self.usages
+    }
+
+    fn parse_line(&mut self, line: &[u8]) {
+        if line.trim_ascii().is_empty() {
+            return;
+        }
+        match serde_json::from_slice::<Value>(line) {
+            Ok(value) => self.usages.extend(result_usage(&value)),
+            Err(e) => warn!("Skipping unparseable batch result line: {e}"),
+        }
+    }
+}
+
+/// Pass the results stream through, calling `on_complete` with the tallied
+/// usage only if it ended cleanly. A stream the client abandons or that
+/// fails upstream records nothing, leaving the usage for a later full read.
+fn tally_results<E, F, Fut>(
+    body: impl Stream<Item = Result<Bytes, E>> + Send + 'static,
+    on_complete: F,
+) -> impl Stream<Item = Result<Bytes, E>> + Send
+where
+    E: Send + 'static,
+    F: FnOnce(Vec<(String, Usage)>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    stream! {
+        let mut body = pin!(body);
+        let mut tally = ResultsTally::default();
+        while let Some(item) = body.next().await {
+            let failed = match &item {
+                Ok(bytes) => {
+                    tally.feed(bytes);
+                    false
+                }
+                Err(_) => true,
+            };
+            yield item;
+            if failed {
+                return;
+            }
+        }
+        on_complete(tally.finish()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{create_test_key, spawn_mock, state_with_upstream, with_db};
+    use axum::extract::Request;
+    use futures_util::stream;
+    use std::io::Error as IoError;
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+
+    fn succeeded(id: &str, model: &str, input: u64, output: u64) -> String {
+        json!({
+            "custom_id": id,
+            "result": {
+                "type": "succeeded",
+                "message": {
+                    "model": model,
+                    "usage": { "input_tokens": input, "output_tokens": output }
+                }
+            }
+        })
+        .to_string()
+    }
+
+    fn results_body() -> String {
+        let errored = json!({
+            "custom_id": "b",
+            "result": { "type": "errored", "error": { "type": "invalid_request_error" } }
+        });
+        format!(
+            "{}\n{errored}\n{}",
+            succeeded("a", "claude-sonnet-4-5", 10, 5),
+            succeeded("c", "claude-opus-4-6", 7, 3)
+        )
+    }
+
+    #[test]
+    fn test_tally_counts_succeeded_results_across_chunk_splits() {
+        let body = results_body();
+        let mut tally = ResultsTally::default();
+        for chunk in body.as_bytes().chunks(17) {
+            tally.feed(chunk);
+        }
+        let usages = tally.finish();
+        assert_eq!(usages.len(), 2);
+        assert_eq!(usages[0].0, "claude-sonnet-4-5");
+        assert_eq!(usages[0].1.input_tokens, 10);
+        assert_eq!(usages[1].0, "claude-opus-4-6");
+        assert_eq!(usages[1].1.output_tokens, 3);
+    }
+
+    #[tokio::test]
+    async fn test_results_pass_through_and_record_on_completion() {
+        let body = results_body();
+        let (first, second) = body.split_at(40);
+        let chunks = vec![
+            Ok::<_, IoError>(Bytes::from(first.to_string())),
+            Ok(Bytes::from(second.to_string())),
+        ];
+        let recorded = Arc::new(Mutex::new(None));
+        let sink = recorded.clone();
+        let out: Vec<_> = tally_results(stream::iter(chunks), move |usages| {
+            *sink.lock().unwrap() = Some(usages);
+            std::future::ready(())
+        })
+        .collect()
+        .await;
+
+        let text: String = out
+            .into_iter()
+            .map(|c| String::from_utf8(c.unwrap().to_vec()).unwrap())
+            .collect();
+        assert_eq!(text, body);
+        assert_eq!(recorded.lock().unwrap().as_ref().map(Vec::len), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_failed_results_stream_records_nothing() {
+        let chunks = vec![
+            Ok(Bytes::from(format!(
+                "{}\n",
+                succeeded("a", "claude-sonnet-4-5", 1, 1)
+            ))),
+            Err(IoError::other("connection reset")),
+        ];
+        let recorded = Arc::new(Mutex::new(false));
+        let sink = recorded.clone();
+        let out: Vec<_> = tally_results(stream::iter(chunks), move |_| {
+            *sink.lock().unwrap() = true;
+            std::future::ready(())
+        })
+        .collect()
+        .await;
+        assert_eq!(out.len(), 2);
+        assert!(!*recorded.lock().unwrap());
+    }
+
+    /// Mock batches API: each submitted batch has ended by its first poll.
+    async fn mock_batches_api() -> SocketAddr {
+        use axum::{
+            Router,
+            routing::{get, post},
+        };
+        let app = Router::new()
+            .route(
+                "/v1/messages/batches",
+                post(|| async {
+                    Json(json!({
+                        "id": format!("msgbatch_{}", uuid::Uuid::new_v4().simple()),
+                        "processing_status": "in_progress",
+                        "results_url": null
+                    }))
+                }),
+            )
+            .route(
+                "/v1/messages/batches/{id}",
+                get(|Path(id): Path<String>| async move {
+                    Json(json!({
+                        "id": id,
+                        "processing_status": "ended",
+                        "results_url": format!("https://api.anthropic.com/v1/messages/batches/{id}/results")
+                    }))
+                }),
+            )
+            .route(
+                "/v1/messages/batches/{id}/results",
+                get(|| async { results_body() }),
+            );
+        spawn_mock(app).await
+    }
+
+    /// The three batch endpoints, as mounted under `/v1`.
+    fn batches_app(state: Arc<AppState>) -> axum::Router {
+        use axum::routing::{get, post};
+        axum::Router::new()
+            .route("/v1/messages/batches", post(create_batch))
+            .route("/v1/messages/batches/{id}", get(get_batch))
+            .route("/v1/messages/batches/{id}/results", get(get_batch_results))
+            .with_state(state)
+    }
+
+    fn batch_request(method: &str, uri: &str, key: &str, body: Option<Value>) -> Request<Body> {
+        let builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::HOST, "proxy.local")
+            .header("x-api-key", key)
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))));
+        match body {
+            Some(body) => builder
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => builder.body(Body::empty()),
+        }
+        .unwrap()
+    }
+
+    async fn send(app: &axum::Router, request: Request<Body>) -> (StatusCode, Bytes) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body)
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_submit_poll_and_fetch_results() {
+        with_db(async {
+            let state = Arc::new(state_with_upstream(mock_batches_api().await));
+            let owner = create_test_key(&state, "batch-owner").await;
+            let other = create_test_key(&state, "batch-other").await;
+            let app = batches_app(state.clone());
+
+            let submit = json!({
+                "requests": [{
+                    "custom_id": "a",
+                    "params": {
+                        "model": "claude-sonnet-4-5",
+                        "max_tokens": 16,
+                        "messages": [{ "role": "user", "content": "hi" }]
+                    }
+                }]
+            });
+            let (status, body) = send(
+                &app,
+                batch_request("POST", "/v1/messages/batches", &owner.key, Some(submit)),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            let submitted: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(submitted["processing_status"], "in_progress");
+            let id = submitted["id"].as_str().unwrap().to_string();
+            let batch_uri = format!("/v1/messages/batches/{id}");
+            let results_uri = format!("{batch_uri}/results");
+
+            let (status, body) =
+                send(&app, batch_request("GET", &batch_uri, &owner.key, None)).await;
+            assert_eq!(status, StatusCode::OK);
+            let polled: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(polled["processing_status"], "ended");
+            assert_eq!(
+                polled["results_url"],
+                format!("http://proxy.local{results_uri}")
+            );
+
+            // Another key can't see the batch or its results
+            for uri in [&batch_uri, &results_uri] {
+                let (status, _) = send(&app, batch_request("GET", uri, &other.key, None)).await;
+                assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+            }
+
+            // Results stream through unchanged, and a second read records nothing
+            for _ in 0..2 {
+                let (status, body) =
+                    send(&app, batch_request("GET", &results_uri, &owner.key, None)).await;
+                assert_eq!(status, StatusCode::OK);
+                assert_eq!(body, results_body());
+            }
+            let usage = state.client_keys.get_model_usage(&owner.id).await.unwrap();
+            let total = |model: &str| {
+                usage
+                    .iter()
+                    .find(|entry| entry.model == model)
+                    .map(|entry| (entry.total.input, entry.total.output))
+            };
+            assert_eq!(total("claude-sonnet-4-5"), Some((10, 5)));
+            assert_eq!(total("claude-opus-4-6"), Some((7, 3)));
+            assert!(
+                state
+                    .client_keys
+                    .get_model_usage(&other.id)
+                    .await
+                    .unwrap()
+                    .iter()
+                    .all(|entry| entry.total.input == 0)
+            );
+        });
+    }
+
+    #[test]
+    fn test_results_url_points_at_proxy() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "proxy.local:4096".parse().unwrap());
+        let mut batch = json!({
+            "id": "msgbatch_1",
+            "results_url": "https://api.anthropic.com/v1/messages/batches/msgbatch_1/results"
+        });
+        rewrite_results_url(&mut batch, &headers);
+        assert_eq!(
+            batch["results_url"],
+            "http://proxy.local:4096/v1/messages/batches/msgbatch_1/results"
+        );
+
+        // Still processing: no results yet, nothing to rewrite
+        let mut pending = json!({ "id": "msgbatch_2", "results_url": null });
+        rewrite_results_url(&mut pending, &headers);
+        assert_eq!(pending["results_url"], Value::Null);
+    }
+}
@@ -1,6 +1,15 @@
 pub mod admin;
 pub mod anthropic;
 pub mod auth;
+pub mod body_limit;
+pub mod compression;
+pub mod count_tokens_batch;
+pub mod estimate;
 pub mod health;
+pub mod message_batches;
 pub mod openai;
+pub mod proxy_headers;
+pub mod readiness;
+pub mod retry;
+pub mod usage_export;
 pub mod user_usage;
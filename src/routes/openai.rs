@@ -1,92 +1,330 @@
 use axum::{
     Json,
     body::Body,
-    extract::State,
+    extract::{ConnectInfo, Extension, Path, State, rejection::JsonRejection},
     http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
+use chrono::NaiveDate;
 use serde::Deserialize;
-use serde_json::{Value, from_str, json};
+use serde_json::{Value, json};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing::{info, warn};
+use std::time::Instant;
+use tracing::{debug, info, warn};
 
-use llm_relay::MessagesResponse;
 use llm_relay::types::openai::InboundChatRequest;
 
 use crate::AppState;
+use crate::auth::{Model, ModelCapabilities, RequestTags, effective_model_ids};
 use crate::capture::{Capture, capture_byte_stream};
-use crate::constants::ANTHROPIC_API_URL;
-use crate::error::ProxyError;
+use crate::error::{ProxyError, openai_error, openai_error_body, openai_error_from_anthropic};
+use crate::idempotency::Idempotent;
+use crate::key_debug_log::{KeyDebugLog, tee_stream};
+use crate::subscription::timestamp_millis;
+use crate::telemetry;
 use crate::transforms::{
-    prepare_anthropic_request, stream_anthropic_to_openai_with_usage, transform_openai_request,
-    transform_openai_response,
+    OpenAiStreamOptions, applied_thinking, apply_nested_reasoning_effort,
+    apply_structured_tool_results, base_model, count_only_completion, ignored_openai_params,
+    logprobs_error, openai_end_user, parse_anthropic_response, prepare_anthropic_request,
+    prepared_service_tier, set_end_user_id, stream_anthropic_to_openai_with_usage,
+    transform_openai_request, transform_openai_response, validate_reasoning_effort,
+    wants_count_only, wants_reasoning, wants_stream_usage, with_thinking_echo,
 };
 
-use super::auth::{authenticate_openai, build_anthropic_request};
+use super::auth::{
+    authenticate, build_anthropic_request, check_access, extract_client_betas, optional_client_key,
+    pin_model, validate_client_key,
+};
+use super::count_tokens_batch::{Upstream, count_one};
+use super::estimate::{error_status, strip_generation_fields};
+use super::proxy_headers::UpstreamInfo;
+use super::retry::{RetryPolicy, send_with_retry};
 
-pub async fn list_models(State(state): State<Arc<AppState>>) -> Response {
-    let model_ids = match state.models.list_enabled_ids().await {
-        Ok(ids) => ids,
-        Err(e) => {
-            return e.to_openai_response();
-        }
-    };
-    let models: Vec<Value> = model_ids
+/// Answer a `max_tokens: 0` request with the prompt's token count from
+/// count_tokens instead of a generation. Nothing is queued or billed.
+async fn count_only_response(
+    upstream: &Upstream<'_>,
+    mut body: Value,
+    model: &str,
+    stream: bool,
+) -> Response {
+    strip_generation_fields(&mut body);
+    let counted = count_one(upstream, body).await;
+    if let Some(error) = counted.get("error") {
+        let error_type = error
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("api_error");
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        return openai_error(error_status(error_type), error_type, message, None);
+    }
+    let prompt_tokens = counted
+        .get("input_tokens")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    let completion = count_only_completion(model, prompt_tokens, stream);
+    if stream {
+        return (
+            [
+                (header::CONTENT_TYPE, "text/event-stream"),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            format!("data: {completion}\n\ndata: [DONE]\n\n"),
+        )
+            .into_response();
+    }
+    Json(completion).into_response()
+}
+
+/// `created` for models whose id carries no date (2025-01-01). OpenAI
+/// clients only need a stable value here.
+const MODEL_CREATED_FALLBACK: i64 = 1_735_689_600;
+
+/// Suffixes listed as separate models for thinking-capable models when
+/// `CLAUDE_PROXY_LIST_THINKING_VARIANTS` is on.
+const THINKING_VARIANTS: &[&str] = &["low", "medium", "high"];
+
+/// `created` of a model: the date in dated ids (`claude-sonnet-4-5-20250929`),
+/// else [`MODEL_CREATED_FALLBACK`].
+fn model_created(id: &str) -> i64 {
+    base_model(id)
+        .rsplit('-')
+        .next()
+        .filter(|date| date.len() == 8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map_or(MODEL_CREATED_FALLBACK, |time| time.and_utc().timestamp())
+}
+
+/// OpenAI model object for `/v1/models` responses.
+fn model_object(id: &str) -> Value {
+    json!({
+        "id": id,
+        "object": "model",
+        "created": model_created(id),
+        "owned_by": "anthropic"
+    })
+}
+
+/// `/v1/models` entries: models active at `now` that the key may use (empty
+/// allow-list = all), each followed by its thinking variants when
+/// `thinking_variants` is set and the model supports thinking.
+fn model_list(
+    models: &[Model],
+    allowed: &[String],
+    now: i64,
+    thinking_variants: bool,
+) -> Vec<Value> {
+    let ids = effective_model_ids(models, allowed, now);
+    models
         .iter()
-        .map(|id| {
-            json!({
-                "id": id,
-                "object": "model",
-                "owned_by": "anthropic"
-            })
+        .filter(|m| ids.contains(&m.id))
+        .flat_map(|m| {
+            let variants: &[&str] = if thinking_variants && m.supports_thinking {
+                THINKING_VARIANTS
+            } else {
+                &[]
+            };
+            std::iter::once(model_object(&m.id)).chain(
+                variants
+                    .iter()
+                    .map(|variant| model_object(&format!("{}({variant})", m.id))),
+            )
         })
-        .collect();
+        .collect()
+}
+
+/// Models the caller can use. Anonymous requests see every enabled model;
+/// with an API key the list is narrowed to the key's allowed models, and
+/// the key's IP and origin allow-lists apply.
+pub async fn list_models(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    let client_key = match optional_client_key(&headers, &state).await {
+        Ok(key) => key,
+        Err(err) => return err.to_openai_response(),
+    };
+    let allowed = match &client_key {
+        Some(key) => {
+            if let Err(err) = check_access(key, &headers, &state, peer.ip()).await {
+                return err.to_openai_response();
+            }
+            match state.client_keys.get_allowed_models(&key.id).await {
+                Ok(allowed) => allowed,
+                Err(e) => return e.to_openai_response(),
+            }
+        }
+        None => Vec::new(),
+    };
+    let models = match state.models.list().await {
+        Ok(models) => models,
+        Err(e) => return e.to_openai_response(),
+    };
+    let data = model_list(
+        &models,
+        &allowed,
+        timestamp_millis() as i64,
+        state.list_thinking_variants,
+    );
 
     Json(json!({
         "object": "list",
-        "data": models
+        "data": data
     }))
     .into_response()
 }
 
+/// Whether `GET /v1/models/{id}` shows `id` to a key: its base model (ids
+/// with a thinking suffix such as `claude-opus-4-6(high)` are accepted like
+/// chat completions does) must be in the set `/v1/models` lists for the
+/// key's allow-list at `now`.
+fn model_visible(models: &[Model], allowed: &[String], id: &str, now: i64) -> bool {
+    effective_model_ids(models, allowed, now).contains(&base_model(id))
+}
+
+/// Single-model lookup. Returns 404 for unknown or disabled models and for
+/// models outside the calling key's allow-list, so keys can't probe for
+/// models they have no access to.
+pub async fn get_model(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let client_key = match validate_client_key(&headers, &state).await {
+        Ok(ck) => ck,
+        Err(err) => return err.to_openai_response(),
+    };
+    if let Err(err) = check_access(&client_key, &headers, &state, peer.ip()).await {
+        return err.to_openai_response();
+    }
+
+    let allowed = match state.client_keys.get_allowed_models(&client_key.id).await {
+        Ok(allowed) => allowed,
+        Err(e) => return e.to_openai_response(),
+    };
+    let models = match state.models.list().await {
+        Ok(models) => models,
+        Err(e) => return e.to_openai_response(),
+    };
+    if !model_visible(&models, &allowed, &id, timestamp_millis() as i64) {
+        return openai_error(
+            StatusCode::NOT_FOUND,
+            "invalid_request_error",
+            &format!("Model not found: {id}"),
+            Some("model_not_found"),
+        );
+    }
+
+    Json(model_object(&id)).into_response()
+}
+
+/// The request body, or an OpenAI-shaped error if it isn't valid JSON.
+fn json_body(payload: Result<Json<Value>, JsonRejection>) -> Result<Value, Response> {
+    match payload {
+        Ok(Json(body)) => Ok(body),
+        // Oversized bodies get their JSON 413 from the body-limit layer
+        Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+            Err(rejection.into_response())
+        }
+        Err(rejection) => Err(openai_error(
+            rejection.status(),
+            "invalid_request_error",
+            &rejection.body_text(),
+            None,
+        )),
+    }
+}
+
 pub async fn chat_completions(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     State(state): State<Arc<AppState>>,
+    Extension(upstream_info): Extension<UpstreamInfo>,
     headers: HeaderMap,
-    Json(raw_body): Json<Value>,
+    payload: Result<Json<Value>, JsonRejection>,
 ) -> Response {
+    let started_at = timestamp_millis();
+    let raw_body = match json_body(payload) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
     // Deserialize from a borrow so `raw_body` stays owned for request capture,
     // avoiding a full clone of the JSON body on every request.
-    let body: InboundChatRequest = match InboundChatRequest::deserialize(&raw_body) {
+    let mut body: InboundChatRequest = match InboundChatRequest::deserialize(&raw_body) {
         Ok(body) => body,
         Err(e) => {
-            return (
+            return openai_error(
                 StatusCode::BAD_REQUEST,
-                Json(json!({ "error": format!("Invalid request body: {e}") })),
-            )
-                .into_response();
+                "invalid_request_error",
+                &format!("Invalid request body: {e}"),
+                None,
+            );
         }
     };
 
-    // Extract model before auth so we can validate it
-    let model_name = body
-        .model
-        .as_deref()
-        .unwrap_or("claude-sonnet-4-5")
-        .to_string();
+    // Apply the key's forced model, then resolve aliases, the default model
+    // and the unknown-model policy before auth so the key's allow-list is
+    // checked against the model that will actually be used
+    let client_key = match validate_client_key(&headers, &state).await {
+        Ok(k) => k,
+        Err(err) => return err.to_openai_response(),
+    };
+    let (requested, overridden) =
+        pin_model(body.model.as_deref(), client_key.force_model.as_deref());
+    let model_name = match state
+        .models
+        .resolve_available_model(requested, &state.default_model, state.unknown_model_policy)
+        .await
+    {
+        Ok(m) => m,
+        Err(e) => return e.to_openai_response(),
+    };
+    body.model = Some(model_name.clone());
+    upstream_info.set_model(&model_name);
+    if overridden {
+        upstream_info.set_model_forced();
+    }
 
-    // Parse model suffix (e.g., "claude-sonnet-4-5(high)" -> base model)
-    let base_model = model_name
-        .split_once('(')
-        .map_or(model_name.as_str(), |(base, _)| base);
+    // Parse model suffix (e.g., "claude-sonnet-4-5(high)" -> base model).
+    // Access and limits are checked here, before the upstream call, so a
+    // rejected streaming request gets a plain JSON error and costs nothing.
+    let base_model = base_model(&model_name);
+    let base_model = base_model.as_str();
 
-    let auth = match authenticate_openai(&headers, &state, base_model).await {
+    let auth = match authenticate(client_key, &headers, &state, peer.ip(), base_model).await {
         Ok(a) => a,
         Err(err) => return err.to_openai_response(),
     };
 
-    let cloak = state.should_cloak(headers.get("user-agent").and_then(|v| v.to_str().ok()));
+    let cloak = auth.should_cloak(&state, &headers);
 
     let stream = body.stream.unwrap_or(false);
+    let stream_options = OpenAiStreamOptions {
+        include_usage: wants_stream_usage(&raw_body),
+        include_reasoning: wants_reasoning(&raw_body),
+    };
+    let idempotency_claim = match state
+        .idempotency
+        .begin(
+            &auth.client_key.id,
+            "/v1/chat/completions",
+            &headers,
+            &raw_body,
+            stream,
+        )
+        .await
+    {
+        Ok(Idempotent::Skip) => None,
+        Ok(Idempotent::Replay(response)) => return response,
+        Ok(Idempotent::Claimed(claim)) => Some(claim),
+        Err(e) => return e.to_openai_response(),
+    };
     let capture = Capture::begin(
         &state.capture,
         "openai",
@@ -97,28 +335,151 @@ pub async fn chat_completions(
         &raw_body,
     )
     .await;
-    let anthropic_value = transform_openai_request(body);
+    let debug_log = KeyDebugLog::for_key(&auth.client_key);
+    if let Some(log) = &debug_log {
+        log.request("/v1/chat/completions", &headers, &raw_body);
+    }
+    // logit_bias, frequency_penalty and presence_penalty have no Anthropic
+    // equivalent. Dropping them silently would let clients believe they took
+    // effect, so they are either rejected or at least logged.
+    let ignored = ignored_openai_params(&raw_body);
+    if !ignored.is_empty() {
+        if state.reject_unsupported_params {
+            return openai_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                &format!("Unsupported parameters: {}", ignored.join(", ")),
+                Some("unsupported_parameter"),
+            );
+        }
+        debug!(model = %base_model, "Dropping unsupported OpenAI parameters: {ignored:?}");
+    }
+    if let Some(error) = logprobs_error(&raw_body) {
+        return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+    }
+    let effort_param = if apply_nested_reasoning_effort(&mut body, &raw_body) {
+        "reasoning.effort"
+    } else {
+        "reasoning_effort"
+    };
+    if let Some(effort) = body.reasoning_effort.as_deref()
+        && let Err(message) = validate_reasoning_effort(effort)
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(openai_error_body(
+                "invalid_request_error",
+                &message,
+                Some(effort_param),
+                None,
+            )),
+        )
+            .into_response();
+    }
+    // The model's own default wins over the global one; either only applies
+    // when the client asked for nothing.
+    let default_effort = match state.models.default_effort(base_model).await {
+        Ok(e) => e.or_else(|| state.default_thinking_effort.clone()),
+        Err(e) => return e.to_openai_response(),
+    };
+    let mut anthropic_value = transform_openai_request(
+        body,
+        auth.client_key.settings.default_max_tokens,
+        default_effort.as_deref(),
+    );
+    apply_structured_tool_results(&raw_body, &mut anthropic_value);
+    // OpenAI's `service_tier` shares `auto` with Anthropic's; the prepare
+    // pipeline drops values Anthropic doesn't know.
+    if let (Some(tier), Some(obj)) = (
+        raw_body.get("service_tier"),
+        anthropic_value.as_object_mut(),
+    ) {
+        obj.insert("service_tier".to_string(), tier.clone());
+    }
+    // `user` becomes Anthropic's abuse-tracking `metadata.user_id` unless
+    // cloaking supplies its own, and is logged for per-end-user analytics.
+    let end_user = openai_end_user(&raw_body);
+    if !cloak && let Some(user) = &end_user {
+        set_end_user_id(&mut anthropic_value, &auth.client_key.id, user);
+    }
+    if wants_count_only(&raw_body) {
+        let options = auth
+            .prepare_options(cloak, ModelCapabilities::default())
+            .with_cache_min_tokens(state.cache_min_tokens);
+        let betas = extract_client_betas(&headers);
+        let upstream = Upstream {
+            client: &state.http_client,
+            url: &state.upstream.count_tokens,
+            token: &auth.token,
+            anthropic_version: auth.anthropic_version(),
+            session_id: &state.session_id,
+            betas: &betas,
+            options: &options,
+            cache: state.count_tokens_cache.as_ref(),
+        };
+        let upstream_started = Instant::now();
+        let response = count_only_response(&upstream, anthropic_value, base_model, stream).await;
+        upstream_info.set_latency(upstream_started.elapsed());
+        return response;
+    }
     let model = anthropic_value
         .get("model")
         .and_then(|m| m.as_str())
         .unwrap_or("")
         .to_string();
-    let prepared = prepare_anthropic_request(anthropic_value, cloak);
+    let capabilities = match state.models.capabilities(&model).await {
+        Ok(c) => c,
+        Err(e) => return e.to_openai_response(),
+    };
+    let options = auth
+        .prepare_options(cloak, capabilities)
+        .with_cache_min_tokens(state.cache_min_tokens);
+    let prepared = match prepare_anthropic_request(anthropic_value, &options) {
+        Ok(p) => p,
+        Err(e) => return e.to_openai_response(),
+    };
+    let thinking = applied_thinking(&prepared.body);
+    let tags = RequestTags {
+        service_tier: prepared_service_tier(&prepared.body).map(str::to_string),
+        end_user,
+    };
     if let Some(capture) = &capture {
         capture
             .write_prepared(&prepared.body, &prepared.betas, cloak)
             .await;
     }
 
-    let req_builder = build_anthropic_request(
-        &state.http_client,
-        ANTHROPIC_API_URL,
-        &auth.token,
-        Some(&prepared.betas),
-        &state.session_id,
-    );
+    // Wait for an upstream slot; held until the response (or stream) is done.
+    let permit = match state
+        .request_queue
+        .acquire(auth.client_key.settings.priority)
+        .await
+    {
+        Ok(p) => p,
+        Err(e) => return e.to_openai_response(),
+    };
+
+    // Transient upstream errors (429/503/529) are retried for non-streaming
+    // requests only; a stream can't be replayed once the client is reading it.
+    let retry_policy = if stream {
+        RetryPolicy::NONE
+    } else {
+        state.retry_policy
+    };
+    let send = || {
+        build_anthropic_request(
+            &state.http_client,
+            &state.upstream.messages,
+            &auth.token,
+            auth.anthropic_version(),
+            Some(&prepared.betas),
+            &state.session_id,
+        )
+        .json(&prepared.body)
+    };
 
-    let response: reqwest::Response = match req_builder.json(&prepared.body).send().await {
+    let upstream_started = Instant::now();
+    let response: reqwest::Response = match send_with_retry(retry_policy, send).await {
         Ok(r) => r,
         Err(e) => {
             return ProxyError::AnthropicApiError(format!("Failed to contact Anthropic: {}", e))
@@ -136,17 +497,21 @@ pub async fn chat_completions(
                 return ProxyError::NoAuthConfigured.to_openai_response();
             }
             Err(e) => {
-                return ProxyError::OAuthError(e).to_openai_response();
+                return ProxyError::OAuthRefresh(e).to_openai_response();
             }
         };
-        let retry_builder = build_anthropic_request(
-            &state.http_client,
-            ANTHROPIC_API_URL,
-            &new_token,
-            Some(&prepared.betas),
-            &state.session_id,
-        );
-        match retry_builder.json(&prepared.body).send().await {
+        let send = || {
+            build_anthropic_request(
+                &state.http_client,
+                &state.upstream.messages,
+                &new_token,
+                auth.anthropic_version(),
+                Some(&prepared.betas),
+                &state.session_id,
+            )
+            .json(&prepared.body)
+        };
+        match send_with_retry(retry_policy, send).await {
             Ok(r) => r,
             Err(e) => {
                 return ProxyError::AnthropicApiError(format!(
@@ -160,6 +525,8 @@ pub async fn chat_completions(
         response
     };
 
+    telemetry::record_upstream_status(response.status().as_u16());
+
     if !response.status().is_success() {
         let status = response.status();
         if let Some(capture) = &capture {
@@ -168,14 +535,17 @@ pub async fn chat_completions(
                 .await;
         }
         let text: String = response.text().await.unwrap_or_default();
+        upstream_info.set_latency(upstream_started.elapsed());
         if let Some(capture) = &capture {
             capture.write_upstream_body(&text).await;
         }
-        return (
+        if let Some(log) = &debug_log {
+            log.response(status.as_u16(), &text);
+        }
+        return openai_error_from_anthropic(
             StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
-            Json(json!({ "error": text })),
-        )
-            .into_response();
+            &text,
+        );
     }
 
     // Update window resets from rate-limit headers on every successful response.
@@ -189,21 +559,35 @@ pub async fn chat_completions(
             .await;
     }
 
+    let status = response.status();
     if stream {
-        let body_stream = capture_byte_stream(
-            response.bytes_stream(),
-            capture.as_ref().map(|c| c.upstream_stream_path()),
+        // Time-to-first-byte: the body streams from here on
+        upstream_info.set_latency(upstream_started.elapsed());
+        let body_stream = tee_stream(
+            debug_log,
+            status.as_u16(),
+            capture_byte_stream(
+                response.bytes_stream(),
+                capture.as_ref().map(|c| c.upstream_stream_path()),
+            ),
         );
         let key_id = auth.client_key.id.clone();
-        let sse_stream =
-            stream_anthropic_to_openai_with_usage(body_stream, model, state.clone(), key_id);
+        let sse_stream = stream_anthropic_to_openai_with_usage(
+            body_stream,
+            model,
+            stream_options,
+            state.clone(),
+            key_id,
+            started_at,
+            tags,
+        );
 
         match Response::builder()
             .status(StatusCode::OK)
             .header(header::CONTENT_TYPE, "text/event-stream")
             .header(header::CACHE_CONTROL, "no-cache")
             .header(header::CONNECTION, "keep-alive")
-            .body(Body::from_stream(sse_stream))
+            .body(Body::from_stream(permit.hold_for(sse_stream)))
         {
             Ok(response) => response,
             Err(e) => ProxyError::ParseError(format!("Failed to build stream response: {e}"))
@@ -217,25 +601,39 @@ pub async fn chat_completions(
                     .to_openai_response();
             }
         };
+        upstream_info.set_latency(upstream_started.elapsed());
         if let Some(capture) = &capture {
             capture.write_upstream_body(&text).await;
         }
+        if let Some(log) = &debug_log {
+            log.response(status.as_u16(), &text);
+        }
 
-        let anthropic_response = match from_str::<MessagesResponse>(&text) {
-            Ok(r) => r,
-            Err(e) => {
-                return ProxyError::ParseError(format!("Failed to parse response: {}", e))
-                    .to_openai_response();
-            }
-        };
+        let anthropic_response =
+            match parse_anthropic_response(&text, stream_options.include_reasoning) {
+                Ok(r) => r,
+                Err(e) => {
+                    return ProxyError::ParseError(format!("Failed to parse response: {}", e))
+                        .to_openai_response();
+                }
+            };
 
         // Record token usage (per-model; global is derived via aggregation)
         let usage_report = anthropic_response.usage.clone().unwrap_or_default();
         let window_resets = state.usage_cache.snapshot().await.window_state();
 
+        telemetry::record_usage(&usage_report);
+
         if let Err(e) = state
             .client_keys
-            .record_model_usage(&auth.client_key.id, &model, &usage_report, &window_resets)
+            .record_model_usage(
+                &auth.client_key.id,
+                &model,
+                &usage_report,
+                &window_resets,
+                started_at,
+                &tags,
+            )
             .await
         {
             warn!(
@@ -245,6 +643,235 @@ pub async fn chat_completions(
         }
 
         let openai_response = transform_openai_response(anthropic_response);
-        Json(openai_response).into_response()
+        match with_thinking_echo(&openai_response, thinking, &usage_report) {
+            Ok(body) => {
+                if let Some(claim) = idempotency_claim {
+                    claim.complete(&body).await;
+                }
+                Json(body).into_response()
+            }
+            Err(e) => ProxyError::ParseError(format!("Failed to encode response: {e}"))
+                .to_openai_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::spawn_mock;
+
+    fn model(id: &str, enabled: bool, supports_thinking: bool) -> Model {
+        Model {
+            id: id.into(),
+            sort_order: 0,
+            enabled,
+            input_price: 3.0,
+            output_price: 15.0,
+            cache_read_price: 0.3,
+            cache_write_price: 3.75,
+            enabled_from: None,
+            enabled_until: None,
+            supports_vision: true,
+            supports_tools: true,
+            supports_thinking,
+            default_effort: None,
+        }
+    }
+
+    fn ids(list: &[Value]) -> Vec<&str> {
+        list.iter().filter_map(|m| m["id"].as_str()).collect()
+    }
+
+    #[test]
+    fn test_model_object_shape() {
+        let obj = model_object("claude-opus-4-6");
+        assert_eq!(obj["id"], "claude-opus-4-6");
+        assert_eq!(obj["object"], "model");
+        assert_eq!(obj["owned_by"], "anthropic");
+        assert_eq!(obj["created"], MODEL_CREATED_FALLBACK);
+
+        // Dated ids use their date; suffixes don't change it
+        let dated = model_object("claude-sonnet-4-5-20250929(high)");
+        assert_eq!(dated["created"], 1_759_104_000);
+    }
+
+    #[test]
+    fn test_model_list_filters_disabled_and_disallowed() {
+        let models = vec![
+            model("claude-opus-4-6", true, true),
+            model("claude-sonnet-4-5", true, true),
+            model("claude-haiku-4-5", false, true),
+        ];
+        assert_eq!(
+            ids(&model_list(&models, &[], 0, false)),
+            ["claude-opus-4-6", "claude-sonnet-4-5"]
+        );
+        let allowed = vec![
+            "claude-sonnet-4-5".to_string(),
+            "claude-haiku-4-5".to_string(),
+        ];
+        assert_eq!(
+            ids(&model_list(&models, &allowed, 0, false)),
+            ["claude-sonnet-4-5"]
+        );
+    }
+
+    #[test]
+    fn test_model_list_thinking_variants() {
+        let models = vec![
+            model("claude-opus-4-6", true, true),
+            model("claude-haiku-4-5", true, false),
+        ];
+        let list = model_list(&models, &[], 0, true);
+        assert_eq!(
+            ids(&list),
+            [
+                "claude-opus-4-6",
+                "claude-opus-4-6(low)",
+                "claude-opus-4-6(medium)",
+                "claude-opus-4-6(high)",
+                "claude-haiku-4-5",
+            ]
+        );
+        for entry in &list {
+            assert_eq!(entry["owned_by"], "anthropic");
+            assert!(entry["created"].is_i64());
+        }
+    }
+
+    #[test]
+    fn test_model_visible_when_found_and_allowed() {
+        let models = vec![model("claude-opus-4-6", true, true)];
+        assert!(model_visible(&models, &[], "claude-opus-4-6", 0));
+        assert!(model_visible(&models, &[], "claude-opus-4-6(high)", 0));
+        let allowed = vec!["claude-opus-4-6".to_string()];
+        assert!(model_visible(&models, &allowed, "claude-opus-4-6", 0));
+    }
+
+    #[test]
+    fn test_model_hidden_when_not_found() {
+        let models = vec![
+            model("claude-opus-4-6", true, true),
+            model("claude-haiku-4-5", false, true),
+        ];
+        assert!(!model_visible(&models, &[], "claude-sonnet-4-5", 0));
+        // Disabled models are as good as missing
+        assert!(!model_visible(&models, &[], "claude-haiku-4-5", 0));
+    }
+
+    #[test]
+    fn test_model_hidden_when_not_allowed() {
+        let models = vec![
+            model("claude-opus-4-6", true, true),
+            model("claude-sonnet-4-5", true, true),
+        ];
+        let allowed = vec!["claude-sonnet-4-5".to_string()];
+        assert!(!model_visible(&models, &allowed, "claude-opus-4-6", 0));
+        assert!(!model_visible(&models, &allowed, "claude-opus-4-6(low)", 0));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_body_is_openai_error() {
+        use axum::{Router, body::to_bytes, http::Request, routing::post};
+        use tower::ServiceExt;
+
+        async fn handler(payload: Result<Json<Value>, JsonRejection>) -> Response {
+            match json_body(payload) {
+                Ok(body) => Json(body).into_response(),
+                Err(response) => response,
+            }
+        }
+        let app = Router::new().route("/v1/chat/completions", post(handler));
+        let request = Request::post("/v1/chat/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{not json"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert!(body["error"]["message"].is_string());
+        assert_eq!(body["error"]["code"], Value::Null);
+    }
+
+    #[test]
+    fn test_disallowed_model_is_plain_json_403() {
+        // Auth runs before the stream is opened, so even `stream: true`
+        // requests see this response rather than an SSE error event.
+        let response =
+            ProxyError::ModelNotAllowed(base_model("claude-opus-4-6(high)")).to_openai_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+
+    #[tokio::test]
+    async fn test_zero_max_tokens_returns_prompt_count() {
+        use crate::constants::ANTHROPIC_VERSION;
+        use crate::transforms::PrepareOptions;
+        use axum::{Router, body::to_bytes, routing::post};
+
+        // count_tokens rejects generation fields, as upstream does
+        let app = Router::new().route(
+            "/v1/messages/count_tokens",
+            post(|Json(body): Json<Value>| async move {
+                if body.get("max_tokens").is_some() {
+                    return (StatusCode::BAD_REQUEST, Json(json!({})));
+                }
+                (StatusCode::OK, Json(json!({ "input_tokens": 1200 })))
+            }),
+        );
+        let url = format!("http://{}/v1/messages/count_tokens", spawn_mock(app).await);
+
+        assert!(wants_count_only(&json!({ "max_tokens": 0 })));
+        assert!(wants_count_only(&json!({ "max_completion_tokens": 0 })));
+        assert!(!wants_count_only(&json!({ "max_tokens": 16 })));
+        assert!(!wants_count_only(&json!({})));
+
+        let client = reqwest::Client::new();
+        let options = PrepareOptions::new(false);
+        let upstream = Upstream {
+            client: &client,
+            url: &url,
+            token: "token",
+            anthropic_version: ANTHROPIC_VERSION,
+            session_id: "session",
+            betas: &[],
+            options: &options,
+            cache: None,
+        };
+        let body = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 0,
+            "messages": [{ "role": "user", "content": "How many tokens is this?" }]
+        });
+
+        let response =
+            count_only_response(&upstream, body.clone(), "claude-sonnet-4-5", false).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let completion: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(completion["object"], "chat.completion");
+        assert_eq!(completion["choices"][0]["message"]["content"], "");
+        assert_eq!(completion["choices"][0]["finish_reason"], "length");
+        assert_eq!(completion["usage"]["prompt_tokens"], 1200);
+        assert_eq!(completion["usage"]["completion_tokens"], 0);
+
+        let response = count_only_response(&upstream, body, "claude-sonnet-4-5", true).await;
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        let chunk = text
+            .strip_prefix("data: ")
+            .and_then(|rest| rest.strip_suffix("\n\ndata: [DONE]\n\n"))
+            .unwrap();
+        let chunk: Value = serde_json::from_str(chunk).unwrap();
+        assert_eq!(chunk["object"], "chat.completion.chunk");
+        assert_eq!(chunk["choices"][0]["delta"]["content"], "");
+        assert_eq!(chunk["usage"]["prompt_tokens"], 1200);
     }
 }
@@ -0,0 +1,210 @@
+//! `X-Proxy-*` response headers on `/v1` inference responses.
+//!
+//! `X-Proxy-Model` is the model actually sent upstream, which differs from
+//! the requested one when an alias or the unknown-model fallback rewrote it.
+//! `X-Proxy-Upstream-Latency` is the time spent waiting on Anthropic in
+//! milliseconds: until the full body for buffered responses, until the
+//! response headers (time-to-first-byte) for streams.
+//! `X-Proxy-Model-Forced: true` is set when the key's `force_model`
+//! replaced the model the client asked for.
+//!
+//! Handlers fill an [`UpstreamInfo`] slot that [`annotate`] puts in the
+//! request extensions, so every return path is covered without threading
+//! the values through each early return.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use axum::{
+    extract::Request,
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+pub const MODEL_HEADER: HeaderName = HeaderName::from_static("x-proxy-model");
+pub const LATENCY_HEADER: HeaderName = HeaderName::from_static("x-proxy-upstream-latency");
+pub const MODEL_FORCED_HEADER: HeaderName = HeaderName::from_static("x-proxy-model-forced");
+
+#[derive(Default)]
+struct Recorded {
+    model: Option<String>,
+    latency: Option<Duration>,
+    model_forced: bool,
+}
+
+/// What a handler learned about its upstream call, for [`annotate`].
+#[derive(Clone, Default)]
+pub struct UpstreamInfo(Arc<Mutex<Recorded>>);
+
+impl UpstreamInfo {
+    /// The resolved model id sent upstream.
+    pub fn set_model(&self, model: &str) {
+        self.lock().model = Some(model.to_string());
+    }
+
+    /// The key's `force_model` overrode the client's model.
+    pub fn set_model_forced(&self) {
+        self.lock().model_forced = true;
+    }
+
+    /// Time spent waiting on Anthropic.
+    pub fn set_latency(&self, latency: Duration) {
+        self.lock().latency = Some(latency);
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        let recorded = self.lock();
+        if let Some(value) = recorded
+            .model
+            .as_deref()
+            .and_then(|m| HeaderValue::from_str(m).ok())
+        {
+            headers.insert(MODEL_HEADER, value);
+        }
+        if let Some(latency) = recorded.latency {
+            let millis = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+            headers.insert(LATENCY_HEADER, HeaderValue::from(millis));
+        }
+        if recorded.model_forced {
+            headers.insert(MODEL_FORCED_HEADER, HeaderValue::from_static("true"));
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Recorded> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Middleware giving each request an [`UpstreamInfo`] and copying whatever
+/// the handler recorded into the response headers.
+pub async fn annotate(mut request: Request, next: Next) -> Response {
+    let info = UpstreamInfo::default();
+    request.extensions_mut().insert(info.clone());
+    let mut response = next.run(request).await;
+    info.apply(response.headers_mut());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppState;
+    use crate::config::UnknownModelPolicy;
+    use crate::routes::anthropic::messages;
+    use crate::test_support::{create_test_key, spawn_mock, state_with_upstream, with_db};
+    use axum::{
+        Json, Router,
+        body::Body,
+        extract::ConnectInfo,
+        http::{StatusCode, header},
+        middleware,
+        routing::post,
+    };
+    use serde_json::{Value, json};
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    /// A proxy whose upstream `/v1/messages` answers every request, with the
+    /// unknown-model fallback on. Returns the app and the models sent upstream.
+    async fn proxy() -> (Router, Arc<AppState>, Arc<Mutex<Vec<String>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let upstream = Router::new().route(
+            "/v1/messages",
+            post(move |Json(body): Json<Value>| async move {
+                let model = body["model"].as_str().unwrap().to_string();
+                sink.lock().unwrap().push(model.clone());
+                Json(json!({
+                    "id": "msg_1",
+                    "type": "message",
+                    "role": "assistant",
+                    "model": model,
+                    "content": [{ "type": "text", "text": "hi" }],
+                    "stop_reason": "end_turn",
+                    "usage": { "input_tokens": 3, "output_tokens": 1 }
+                }))
+            }),
+        );
+        let state = Arc::new(AppState {
+            unknown_model_policy: UnknownModelPolicy::Fallback,
+            ..state_with_upstream(spawn_mock(upstream).await)
+        });
+        let app = Router::new()
+            .route("/v1/messages", post(messages))
+            .layer(middleware::from_fn(annotate))
+            .with_state(state.clone());
+        (app, state, seen)
+    }
+
+    async fn send(app: &Router, api_key: &str, model: &str) -> Response {
+        let body = json!({
+            "model": model,
+            "max_tokens": 16,
+            "messages": [{ "role": "user", "content": "hi" }]
+        });
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/messages")
+            .header("x-api-key", api_key)
+            .header(header::CONTENT_TYPE, "application/json")
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_headers_reflect_rewritten_model() {
+        with_db(async {
+            let (app, state, seen) = proxy().await;
+            let key = create_test_key(&state, "proxy-headers").await;
+            let suffix = uuid::Uuid::new_v4().simple().to_string();
+            let fast = format!("fast-{suffix}");
+            let retired = format!("retired-{suffix}");
+            state
+                .models
+                .set_alias(&fast, "claude-haiku-4-5")
+                .await
+                .unwrap();
+            state
+                .models
+                .set_alias(&retired, &format!("claude-retired-{suffix}"))
+                .await
+                .unwrap();
+
+            // Alias to an enabled model
+            let response = send(&app, &key.key, &fast).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let headers = response.headers();
+            assert_eq!(headers[MODEL_HEADER], "claude-haiku-4-5");
+            let latency = headers[LATENCY_HEADER].to_str().unwrap();
+            assert!(latency.parse::<u64>().is_ok(), "latency {latency}");
+            assert!(headers.get(MODEL_FORCED_HEADER).is_none());
+
+            // Alias to a model that doesn't exist: the fallback is sent instead
+            let response = send(&app, &key.key, &retired).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[MODEL_HEADER], "claude-sonnet-4-5");
+
+            assert_eq!(
+                *seen.lock().unwrap(),
+                ["claude-haiku-4-5", "claude-sonnet-4-5"]
+            );
+        });
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_rejected_request_adds_no_headers() {
+        with_db(async {
+            let (app, _state, seen) = proxy().await;
+            let response = send(&app, "sk-proxy-unknown", "claude-sonnet-4-5").await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert!(response.headers().get(MODEL_HEADER).is_none());
+            assert!(response.headers().get(LATENCY_HEADER).is_none());
+            assert!(seen.lock().unwrap().is_empty());
+        });
+    }
+}
@@ -0,0 +1,195 @@
+//! Optional readiness gate for cold starts.
+//!
+//! With `CLAUDE_PROXY_WAIT_FOR_OAUTH` set, `/v1` answers 503 until OAuth has
+//! been connected, instead of failing each request upstream, and
+//! `/health/ready` reports not-ready so a load balancer can hold traffic
+//! back. `/admin` is not gated, so an operator can complete OAuth. Once
+//! OAuth is seen the instance stays ready: this gates startup only.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::{
+    Json,
+    extract::{FromRef, Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+use crate::AppState;
+use crate::auth::AuthStore;
+
+/// Seconds clients are asked to wait before retrying a gated request
+const RETRY_AFTER_SECS: u64 = 10;
+
+/// Whether OAuth is connected. [`AuthStore`] in production.
+pub trait OAuthStatus: Send + Sync + 'static {
+    fn connected(&self) -> impl Future<Output = bool> + Send;
+}
+
+impl OAuthStatus for AuthStore {
+    async fn connected(&self) -> bool {
+        self.has("anthropic").await.unwrap_or(false)
+    }
+}
+
+pub struct Readiness<S = AuthStore> {
+    wait_for_oauth: bool,
+    oauth: Arc<S>,
+    /// Latched once OAuth has been seen, so ready instances skip the lookup
+    ready: Arc<AtomicBool>,
+}
+
+// Not derived: that would require `S: Clone`
+impl<S> Clone for Readiness<S> {
+    fn clone(&self) -> Self {
+        Self {
+            wait_for_oauth: self.wait_for_oauth,
+            oauth: self.oauth.clone(),
+            ready: self.ready.clone(),
+        }
+    }
+}
+
+impl<S: OAuthStatus> Readiness<S> {
+    pub fn new(wait_for_oauth: bool, oauth: Arc<S>) -> Self {
+        Self {
+            wait_for_oauth,
+            oauth,
+            ready: Arc::new(AtomicBool::new(!wait_for_oauth)),
+        }
+    }
+
+    pub async fn is_ready(&self) -> bool {
+        if self.ready.load(Ordering::Relaxed) {
+            return true;
+        }
+        let connected = self.oauth.connected().await;
+        if connected {
+            self.ready.store(true, Ordering::Relaxed);
+        }
+        connected
+    }
+}
+
+impl FromRef<Arc<AppState>> for Readiness {
+    fn from_ref(state: &Arc<AppState>) -> Self {
+        state.readiness.clone()
+    }
+}
+
+/// `GET /health/ready`: 200 once the instance can serve `/v1`, else 503.
+pub async fn ready<S: OAuthStatus>(State(readiness): State<Readiness<S>>) -> Response {
+    if readiness.is_ready().await {
+        Json(json!({ "status": "ready" })).into_response()
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "not_ready", "reason": "OAuth is not connected" })),
+        )
+            .into_response()
+    }
+}
+
+/// Middleware answering 503 on `/v1` until [`Readiness::is_ready`], in the
+/// caller's API format.
+pub async fn gate<S: OAuthStatus>(
+    State(readiness): State<Readiness<S>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if readiness.is_ready().await {
+        return next.run(request).await;
+    }
+    let message = "Proxy is starting: OAuth is not connected yet";
+    let body = if request.uri().path().contains("/messages") {
+        json!({
+            "type": "error",
+            "error": { "type": "overloaded_error", "message": message }
+        })
+    } else {
+        json!({
+            "error": {
+                "message": message,
+                "type": "server_error",
+                "param": null,
+                "code": "not_ready"
+            }
+        })
+    };
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, RETRY_AFTER_SECS.to_string())],
+        Json(body),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, middleware, routing::get};
+    use tower::ServiceExt;
+
+    #[derive(Default)]
+    struct FakeOAuth(AtomicBool);
+
+    impl OAuthStatus for FakeOAuth {
+        async fn connected(&self) -> bool {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    fn app(readiness: Readiness<FakeOAuth>) -> Router {
+        let v1 = Router::new()
+            .route("/v1/models", get(|| async { "models" }))
+            .layer(middleware::from_fn_with_state(
+                readiness.clone(),
+                gate::<FakeOAuth>,
+            ));
+        Router::new()
+            .route("/health/ready", get(ready::<FakeOAuth>))
+            .route("/admin/oauth/status", get(|| async { "admin" }))
+            .with_state(readiness)
+            .merge(v1)
+    }
+
+    async fn status(app: &Router, uri: &str) -> StatusCode {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_gated_until_oauth_connects() {
+        let oauth = Arc::new(FakeOAuth::default());
+        let app = app(Readiness::new(true, oauth.clone()));
+
+        assert_eq!(
+            status(&app, "/health/ready").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status(&app, "/v1/models").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(status(&app, "/admin/oauth/status").await, StatusCode::OK);
+
+        oauth.0.store(true, Ordering::Relaxed);
+        assert_eq!(status(&app, "/health/ready").await, StatusCode::OK);
+        assert_eq!(status(&app, "/v1/models").await, StatusCode::OK);
+
+        // Startup gate only: losing OAuth later doesn't flip back
+        oauth.0.store(false, Ordering::Relaxed);
+        assert_eq!(status(&app, "/v1/models").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_ungated_is_always_ready() {
+        let app = app(Readiness::new(false, Arc::new(FakeOAuth::default())));
+        assert_eq!(status(&app, "/health/ready").await, StatusCode::OK);
+        assert_eq!(status(&app, "/v1/models").await, StatusCode::OK);
+    }
+}
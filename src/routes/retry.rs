@@ -0,0 +1,179 @@
+//! Retry of transient Anthropic errors for non-streaming requests.
+//!
+//! 429 (rate limited), 503 (unavailable) and 529 (overloaded) usually clear
+//! within seconds, so a non-streaming request is re-sent a bounded number of
+//! times before the error reaches the client. `Retry-After` is honoured when
+//! Anthropic sends it; otherwise the delay grows exponentially with jitter so
+//! concurrent requests don't retry in lockstep.
+//!
+//! Streaming requests are not retried here: the handler commits to an SSE
+//! response as soon as upstream answers, so they keep failing fast.
+
+use std::time::Duration;
+
+use axum::http::{HeaderMap, StatusCode, header};
+use rand::RngExt;
+use reqwest::{RequestBuilder, Response};
+use tracing::info;
+
+/// Longest `Retry-After` we are willing to wait out. Anything longer is
+/// returned to the client, which is better placed to decide.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// How many times to send a request and how long to wait between attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one (1 = no retries)
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubles on each further retry
+    pub base_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Send once, never retry (used for streaming requests).
+    pub const NONE: Self = Self {
+        max_attempts: 1,
+        base_backoff: Duration::ZERO,
+    };
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 503 | 529)
+}
+
+/// Delay requested by a `Retry-After` header in seconds. HTTP-date values
+/// are not used by Anthropic and are ignored.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Exponential backoff for the given retry (0-based), scaled by a random
+/// factor in `[0.5, 1.0]`.
+fn backoff(base: Duration, retry: u32) -> Duration {
+    let exp = base.saturating_mul(2u32.saturating_pow(retry));
+    exp.mul_f64(rand::rng().random_range(0.5..=1.0))
+}
+
+/// Send the request built by `build`, re-sending it on transient upstream
+/// errors according to `policy`. Returns the last response, successful or
+/// not; transport errors are returned immediately.
+pub async fn send_with_retry(
+    policy: RetryPolicy,
+    mut build: impl FnMut() -> RequestBuilder,
+) -> reqwest::Result<Response> {
+    let mut attempt = 1;
+    loop {
+        let response = build().send().await?;
+        let status = response.status();
+        if !is_transient(status) || attempt >= policy.max_attempts {
+            return Ok(response);
+        }
+
+        let delay = match retry_after(response.headers()) {
+            Some(delay) if delay > MAX_RETRY_AFTER => return Ok(response),
+            Some(delay) => delay,
+            None => backoff(policy.base_backoff, attempt - 1),
+        };
+        info!(
+            status = %status,
+            attempt,
+            max_attempts = policy.max_attempts,
+            "Transient Anthropic error, retrying in {delay:?}"
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::spawn_mock;
+    use axum::{Router, extract::State, http::HeaderValue, routing::post};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const FAST: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        base_backoff: Duration::from_millis(1),
+    };
+
+    /// Mock upstream that answers 529 for the first `failures` requests and
+    /// 200 afterwards. Returns its URL and the request counter.
+    async fn flaky_server(failures: u32) -> (String, Arc<AtomicU32>) {
+        let hits = Arc::new(AtomicU32::new(0));
+        let app = Router::new()
+            .route(
+                "/v1/messages",
+                post(move |State(hits): State<Arc<AtomicU32>>| async move {
+                    if hits.fetch_add(1, Ordering::SeqCst) < failures {
+                        (StatusCode::from_u16(529).unwrap(), "overloaded")
+                    } else {
+                        (StatusCode::OK, "ok")
+                    }
+                }),
+            )
+            .with_state(hits.clone());
+        let addr = spawn_mock(app).await;
+        (format!("http://{addr}/v1/messages"), hits)
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let (url, hits) = flaky_server(2).await;
+        let client = reqwest::Client::new();
+        let response = send_with_retry(FAST, || client.post(&url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (url, hits) = flaky_server(5).await;
+        let client = reqwest::Client::new();
+        let response = send_with_retry(FAST, || client.post(&url)).await.unwrap();
+        assert_eq!(response.status().as_u16(), 529);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_policy_sends_once() {
+        let (url, hits) = flaky_server(1).await;
+        let client = reqwest::Client::new();
+        let response = send_with_retry(RetryPolicy::NONE, || client.post(&url))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 529);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_retry_after_seconds() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(header::RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+        headers.insert(
+            header::RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_backoff_is_jittered_exponential() {
+        let base = Duration::from_millis(100);
+        for retry in 0..4 {
+            let full = base * 2u32.pow(retry);
+            let delay = backoff(base, retry);
+            assert!(delay >= full / 2 && delay <= full, "{delay:?} vs {full:?}");
+        }
+    }
+}
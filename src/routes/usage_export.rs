@@ -0,0 +1,185 @@
+//! `GET /export/{token}`: a key's request log as CSV, for holders of a
+//! signed export link (see `export_links`). No session or API key needed;
+//! the token itself is the credential. Minted by the admin endpoint
+//! `POST /admin/keys/{id}/export-link`.
+
+use std::sync::Arc;
+
+use async_stream::stream;
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat};
+use futures_util::{Stream, StreamExt};
+use serde_json::json;
+use tracing::{debug, warn};
+
+use crate::AppState;
+use crate::auth::client_keys::i64_to_u64;
+use crate::db::{self, Connection};
+use crate::error::ProxyError;
+use crate::subscription::timestamp_millis;
+
+const CSV_HEADER: &str = "created_at,model,input_tokens,output_tokens,cache_read_tokens,\
+                          cache_write_tokens,cost_usd,duration_ms,service_tier\n";
+
+/// One request-log row as exported.
+struct ExportRow {
+    created_at: u64,
+    model: String,
+    input_tokens: u64,
+    output_tokens: u64,
+    cache_read_tokens: u64,
+    cache_write_tokens: u64,
+    cost_microdollars: u64,
+    duration_ms: Option<u64>,
+    service_tier: Option<String>,
+}
+
+/// Quote a field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_line(row: &ExportRow) -> String {
+    let created_at = i64::try_from(row.created_at)
+        .ok()
+        .and_then(DateTime::from_timestamp_millis)
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_default();
+    format!(
+        "{created_at},{},{},{},{},{},{}.{:06},{},{}\n",
+        csv_field(&row.model),
+        row.input_tokens,
+        row.output_tokens,
+        row.cache_read_tokens,
+        row.cache_write_tokens,
+        row.cost_microdollars / 1_000_000,
+        row.cost_microdollars % 1_000_000,
+        row.duration_ms.map(|ms| ms.to_string()).unwrap_or_default(),
+        csv_field(row.service_tier.as_deref().unwrap_or_default()),
+    )
+}
+
+/// The key's request log, oldest first, one CSV line per chunk.
+fn csv_rows(conn: Connection, key_id: String) -> impl Stream<Item = Result<Bytes, ProxyError>> {
+    stream! {
+        yield Ok(Bytes::from_static(CSV_HEADER.as_bytes()));
+        let mut rows = sqlx::query!(
+            "SELECT created_at, model, input_tokens, output_tokens, cache_read_tokens, \
+             cache_write_tokens, cost_microdollars, duration_ms, service_tier \
+             FROM request_log WHERE key_id = $1 ORDER BY created_at, id",
+            key_id,
+        )
+        .fetch(&conn);
+        while let Some(row) = rows.next().await {
+            match row {
+                Ok(row) => {
+                    yield Ok(Bytes::from(csv_line(&ExportRow {
+                        created_at: i64_to_u64(row.created_at),
+                        model: row.model,
+                        input_tokens: i64_to_u64(row.input_tokens),
+                        output_tokens: i64_to_u64(row.output_tokens),
+                        cache_read_tokens: i64_to_u64(row.cache_read_tokens),
+                        cache_write_tokens: i64_to_u64(row.cache_write_tokens),
+                        cost_microdollars: i64_to_u64(row.cost_microdollars),
+                        duration_ms: row.duration_ms.map(i64_to_u64),
+                        service_tier: row.service_tier,
+                    })));
+                }
+                Err(source) => {
+                    warn!("Usage export for key {key_id} failed: {source}");
+                    yield Err(ProxyError::Database {
+                        context: "Failed to read request log for export",
+                        source,
+                    });
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn link_not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "Export link is invalid or has expired" })),
+    )
+        .into_response()
+}
+
+pub async fn download_usage_export(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Response {
+    let key_id = match state.export_links.verify(&token, timestamp_millis()) {
+        Ok(key_id) => key_id,
+        Err(reason) => {
+            debug!(?reason, "Refused usage export link");
+            return link_not_found();
+        }
+    };
+    // Deleting the key revokes its outstanding links
+    match state.client_keys.get(&key_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return link_not_found(),
+        Err(e) => return e.to_openai_response(),
+    }
+    let conn = match db::get_conn().await {
+        Ok(conn) => conn,
+        Err(e) => return e.to_openai_response(),
+    };
+
+    let filename = format!("attachment; filename=\"usage-{key_id}.csv\"");
+    match Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
+        .header(header::CONTENT_DISPOSITION, filename)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from_stream(csv_rows(conn, key_id)))
+    {
+        Ok(response) => response,
+        Err(e) => ProxyError::ParseError(format!("Failed to build export response: {e}"))
+            .to_openai_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_line() {
+        let row = ExportRow {
+            created_at: 1_760_000_000_000,
+            model: "claude-sonnet-4-5".into(),
+            input_tokens: 1200,
+            output_tokens: 300,
+            cache_read_tokens: 0,
+            cache_write_tokens: 50,
+            cost_microdollars: 8_604_500,
+            duration_ms: None,
+            service_tier: Some("standard".into()),
+        };
+        assert_eq!(
+            csv_line(&row),
+            "2025-10-09T08:53:20.000Z,claude-sonnet-4-5,1200,300,0,50,8.604500,,standard\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,29 @@
+//! Named values persisted in the `settings` table. Values are opaque
+//! strings (JSON by convention); callers own their encoding.
+
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+use crate::subscription::timestamp_millis;
+
+pub async fn get(name: &str) -> Result<Option<String>, ProxyError> {
+    let conn = db::get_conn().await?;
+    sqlx::query_scalar!("SELECT value FROM settings WHERE name = $1", name)
+        .fetch_optional(&conn)
+        .await
+        .db_context("Failed to read setting")
+}
+
+pub async fn set(name: &str, value: &str) -> Result<(), ProxyError> {
+    let conn = db::get_conn().await?;
+    sqlx::query!(
+        "INSERT INTO settings (name, value, updated_at) VALUES ($1, $2, $3) \
+         ON CONFLICT (name) DO UPDATE SET value = EXCLUDED.value, updated_at = EXCLUDED.updated_at",
+        name,
+        value,
+        timestamp_millis() as i64,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to write setting")?;
+    Ok(())
+}
@@ -0,0 +1,282 @@
+//! Alerts on total spend across all keys.
+//!
+//! A background task sums `cost_microdollars` from `request_log` over a
+//! rolling window every [`CHECK_INTERVAL`] and, when the total crosses one
+//! of the configured thresholds, logs a warning and POSTs a JSON
+//! [`SpendAlert`] to the optional webhook. Each threshold alerts once per
+//! crossing and is re-armed only after the total falls back below it, as
+//! old requests leave the window. Unlike key limits this never rejects
+//! requests; it only tells the operator.
+//!
+//! Armed state is in memory, so a restart while over a threshold alerts
+//! once more.
+
+use std::time::Duration;
+
+use reqwest::Client;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::auth::client_keys::i64_to_u64;
+use crate::db;
+use crate::error::{DbResultExt, ProxyError};
+use crate::subscription::timestamp_millis;
+
+/// How often total spend is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Timeout for one webhook delivery.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Parse `CLAUDE_PROXY_SPEND_ALERT_THRESHOLDS`: comma-separated dollar
+/// amounts, returned as ascending, de-duplicated microdollars.
+pub fn parse_thresholds(raw: &str) -> Result<Vec<u64>, String> {
+    let mut thresholds = Vec::new();
+    for part in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let dollars = part
+            .trim_start_matches('$')
+            .parse::<f64>()
+            .ok()
+            .filter(|d| d.is_finite() && *d > 0.0 && *d < 1e12)
+            .ok_or_else(|| format!("`{part}` is not a positive dollar amount"))?;
+        #[expect(clippy::cast_sign_loss, reason = "checked positive above")]
+        let microdollars = (dollars * 1_000_000.0).round() as u64;
+        thresholds.push(microdollars);
+    }
+    thresholds.sort_unstable();
+    thresholds.dedup();
+    Ok(thresholds)
+}
+
+/// Cost (microdollars) of all requests since `since` (epoch ms).
+async fn total_since(since: u64) -> Result<u64, ProxyError> {
+    let conn = db::get_conn().await?;
+    let total = sqlx::query_scalar!(
+        "SELECT COALESCE(SUM(cost_microdollars), 0)::BIGINT AS \"total!\" FROM request_log WHERE created_at >= $1",
+        since as i64,
+    )
+    .fetch_one(&conn)
+    .await
+    .db_context("Failed to sum recent spend")?;
+    Ok(i64_to_u64(total))
+}
+
+/// Webhook payload for a crossed threshold.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendAlert {
+    pub threshold_microdollars: u64,
+    pub total_microdollars: u64,
+    pub window_secs: u64,
+}
+
+pub struct SpendAlerts {
+    /// Ascending thresholds (microdollars)
+    thresholds: Vec<u64>,
+    window: Duration,
+    webhook: Option<String>,
+    client: Client,
+    /// Per threshold: already alerted and not yet back below it
+    crossed: Vec<bool>,
+}
+
+impl SpendAlerts {
+    pub fn new(
+        thresholds: Vec<u64>,
+        window: Duration,
+        webhook: Option<String>,
+        client: Client,
+    ) -> Self {
+        let crossed = vec![false; thresholds.len()];
+        Self {
+            thresholds,
+            window,
+            webhook,
+            client,
+            crossed,
+        }
+    }
+
+    /// Thresholds newly crossed by `total`. Thresholds `total` is below
+    /// again are re-armed.
+    fn evaluate(&mut self, total: u64) -> Vec<u64> {
+        let mut fired = Vec::new();
+        for (&threshold, crossed) in self.thresholds.iter().zip(self.crossed.iter_mut()) {
+            let above = total >= threshold;
+            if above && !*crossed {
+                fired.push(threshold);
+            }
+            *crossed = above;
+        }
+        fired
+    }
+
+    /// Check the total once and notify about new crossings.
+    async fn tick(&mut self) {
+        let window_ms = u64::try_from(self.window.as_millis()).unwrap_or(u64::MAX);
+        let since = timestamp_millis().saturating_sub(window_ms);
+        let total = match total_since(since).await {
+            Ok(total) => total,
+            Err(e) => {
+                warn!("Spend alert check failed: {e}");
+                return;
+            }
+        };
+        for threshold in self.evaluate(total) {
+            let alert = SpendAlert {
+                threshold_microdollars: threshold,
+                total_microdollars: total,
+                window_secs: self.window.as_secs(),
+            };
+            warn!(
+                "Total spend ${:.2} over the last {}s crossed the ${:.2} alert threshold",
+                total as f64 / 1_000_000.0,
+                alert.window_secs,
+                threshold as f64 / 1_000_000.0
+            );
+            self.send_webhook(&alert).await;
+        }
+    }
+
+    async fn send_webhook(&self, alert: &SpendAlert) {
+        let Some(url) = &self.webhook else {
+            return;
+        };
+        let result = self
+            .client
+            .post(url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(alert)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        match result {
+            Ok(_) => info!("Spend alert delivered to webhook"),
+            Err(e) => warn!("Spend alert webhook failed: {e}"),
+        }
+    }
+}
+
+/// Check total spend every [`CHECK_INTERVAL`], forever.
+pub fn spawn(mut alerts: SpendAlerts) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            alerts.tick().await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{SeedRequest, seed_request, spawn_mock, with_db};
+    use axum::{Json, Router, routing::post};
+    use std::sync::{Arc, Mutex};
+
+    const DOLLAR: u64 = 1_000_000;
+
+    fn alerts(thresholds: &[u64], webhook: Option<String>) -> SpendAlerts {
+        SpendAlerts::new(
+            thresholds.to_vec(),
+            Duration::from_secs(86_400),
+            webhook,
+            Client::new(),
+        )
+    }
+
+    #[test]
+    fn test_parse_thresholds() {
+        assert_eq!(
+            parse_thresholds("100, $50,0.5,50"),
+            Ok(vec![DOLLAR / 2, 50 * DOLLAR, 100 * DOLLAR])
+        );
+        assert_eq!(parse_thresholds(""), Ok(vec![]));
+        for bad in ["-5", "0", "ten", "inf"] {
+            assert!(
+                parse_thresholds(bad).is_err_and(|e| e.contains(bad)),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_alert_fires_once_per_crossing() {
+        let mut alerts = alerts(&[50 * DOLLAR, 100 * DOLLAR], None);
+        assert_eq!(alerts.evaluate(10 * DOLLAR), Vec::<u64>::new());
+        assert_eq!(alerts.evaluate(60 * DOLLAR), vec![50 * DOLLAR]);
+        // Still above: no repeat
+        assert_eq!(alerts.evaluate(70 * DOLLAR), Vec::<u64>::new());
+        assert_eq!(alerts.evaluate(120 * DOLLAR), vec![100 * DOLLAR]);
+        assert_eq!(alerts.evaluate(130 * DOLLAR), Vec::<u64>::new());
+
+        // Spend leaves the window: re-armed below, fires again on the next crossing
+        assert_eq!(alerts.evaluate(80 * DOLLAR), Vec::<u64>::new());
+        assert_eq!(alerts.evaluate(110 * DOLLAR), vec![100 * DOLLAR]);
+        assert_eq!(alerts.evaluate(DOLLAR), Vec::<u64>::new());
+        assert_eq!(
+            alerts.evaluate(200 * DOLLAR),
+            vec![50 * DOLLAR, 100 * DOLLAR]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_webhook_receives_each_crossing_once() {
+        with_db(async {
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let sink = received.clone();
+            let app = Router::new().route(
+                "/hook",
+                post(move |Json(body): Json<serde_json::Value>| {
+                    let sink = sink.clone();
+                    async move { sink.lock().unwrap().push(body) }
+                }),
+            );
+            let url = format!("http://{}/hook", spawn_mock(app).await);
+
+            let now = timestamp_millis();
+            let day_ms = 86_400_000;
+            // Spend other tests already logged in the window
+            let base = total_since(now - day_ms).await.unwrap();
+            let mut alerts = alerts(&[base + 50 * DOLLAR, base + 100 * DOLLAR], Some(url));
+            let spend = |cost_microdollars, created_at| SeedRequest {
+                key_id: "spend-alert-key",
+                model: "claude-sonnet-4-5",
+                cost_microdollars,
+                created_at,
+                ..SeedRequest::default()
+            };
+
+            // Outside the window: never counted
+            seed_request(spend(500 * DOLLAR, now - 2 * day_ms)).await;
+            seed_request(spend(40 * DOLLAR, now)).await;
+            alerts.tick().await;
+            seed_request(spend(20 * DOLLAR, now)).await;
+            alerts.tick().await;
+            alerts.tick().await;
+            seed_request(spend(50 * DOLLAR, now)).await;
+            alerts.tick().await;
+
+            let received = received.lock().unwrap();
+            let fired: Vec<_> = received
+                .iter()
+                .map(|alert| {
+                    (
+                        alert["thresholdMicrodollars"].as_u64().unwrap(),
+                        alert["totalMicrodollars"].as_u64().unwrap(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                fired,
+                [
+                    (base + 50 * DOLLAR, base + 60 * DOLLAR),
+                    (base + 100 * DOLLAR, base + 110 * DOLLAR)
+                ]
+            );
+            assert_eq!(received[0]["windowSecs"], 86_400);
+        });
+    }
+}
@@ -11,7 +11,7 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use serde_json::Value;
 
 use crate::AppState;
-use crate::constants::{ANTHROPIC_PROFILE_URL, OAUTH_USAGE_BETA};
+use crate::constants::OAUTH_USAGE_BETA;
 
 /// Current time since the Unix epoch, in milliseconds.
 pub fn timestamp_millis() -> u64 {
@@ -27,7 +27,7 @@ pub async fn fetch_plan_name(state: &AppState) -> Option<String> {
     let token = state.oauth.refresh_if_needed().await.ok()??;
     let resp = state
         .http_client
-        .get(ANTHROPIC_PROFILE_URL)
+        .get(&state.upstream.profile)
         .header("authorization", format!("Bearer {token}"))
         .header("anthropic-beta", OAUTH_USAGE_BETA)
         .header("content-type", "application/json")
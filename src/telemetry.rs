@@ -0,0 +1,176 @@
+//! Request spans and optional OpenTelemetry export.
+//!
+//! Every `/v1` request runs inside a `proxy_request` span (see
+//! [`request_span`]). Handlers fill in the key id, model, upstream status and
+//! token counts as they become known; for streamed responses the span stays
+//! open until the body finishes, so token counts land on the same span.
+//!
+//! Exporting those spans over OTLP is opt-in twice over: the binary must be
+//! built with the `otel` feature, and `OTEL_EXPORTER_OTLP_ENDPOINT` (or
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`) must be set at runtime. Without the
+//! feature none of the OpenTelemetry crates are compiled in.
+
+use axum::extract::{OriginalUri, Request};
+use llm_relay::Usage;
+use tracing::field::Empty;
+use tracing::{Span, info_span};
+use tracing_subscriber::{Layer, Registry};
+
+/// Environment variables that enable OTLP trace export.
+const OTLP_ENDPOINT_VARS: &[&str] = &[
+    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+];
+
+pub type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Whether an OTLP endpoint is configured in the environment.
+pub fn otlp_endpoint_configured() -> bool {
+    OTLP_ENDPOINT_VARS
+        .iter()
+        .any(|var| std::env::var(var).is_ok_and(|v| !v.trim().is_empty()))
+}
+
+/// Build the span for one proxied request. Fields start empty and are
+/// recorded by the handlers via the helpers below.
+pub fn request_span(req: &Request) -> Span {
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| req.uri().path(), |uri| uri.path());
+    info_span!(
+        "proxy_request",
+        otel.name = %format!("{} {}", req.method(), path),
+        otel.kind = "server",
+        http.request.method = %req.method(),
+        url.path = %path,
+        key_id = Empty,
+        model = Empty,
+        upstream_status = Empty,
+        input_tokens = Empty,
+        output_tokens = Empty,
+        cache_read_tokens = Empty,
+        cache_write_tokens = Empty,
+    )
+}
+
+/// Record the authenticated key and requested model on the current span.
+pub fn record_key_and_model(key_id: &str, model: &str) {
+    let span = Span::current();
+    span.record("key_id", key_id);
+    span.record("model", model);
+}
+
+/// Record the HTTP status Anthropic answered with.
+pub fn record_upstream_status(status: u16) {
+    Span::current().record("upstream_status", status);
+}
+
+/// Record token counts on the current span.
+pub fn record_usage(usage: &Usage) {
+    let span = Span::current();
+    span.record("input_tokens", usage.input_tokens);
+    span.record("output_tokens", usage.output_tokens);
+    span.record(
+        "cache_read_tokens",
+        usage.cache_read_input_tokens.unwrap_or(0),
+    );
+    span.record(
+        "cache_write_tokens",
+        usage.cache_creation_input_tokens.unwrap_or(0),
+    );
+}
+
+/// Keeps the tracer provider alive; flushes pending spans when dropped.
+#[derive(Default)]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+#[cfg(feature = "otel")]
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush OpenTelemetry spans: {e}");
+        }
+    }
+}
+
+/// Build the OTLP export layer if the feature is compiled in and an endpoint
+/// is configured. Called before the subscriber is installed, so failures are
+/// reported on stderr and the proxy keeps running without export.
+#[cfg(feature = "otel")]
+pub fn otel_layer() -> (Option<BoxedLayer>, TelemetryGuard) {
+    use opentelemetry::trace::TracerProvider;
+
+    if !otlp_endpoint_configured() {
+        return (None, TelemetryGuard::default());
+    }
+    match otel::build_provider(None) {
+        Ok(provider) => {
+            let layer = tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer("claude-proxy"))
+                .boxed();
+            (
+                Some(layer),
+                TelemetryGuard {
+                    provider: Some(provider),
+                },
+            )
+        }
+        Err(e) => {
+            eprintln!("OpenTelemetry export disabled: {e}");
+            (None, TelemetryGuard::default())
+        }
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn otel_layer() -> (Option<BoxedLayer>, TelemetryGuard) {
+    (None, TelemetryGuard::default())
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+
+    /// Build a batching OTLP/HTTP tracer provider. With `endpoint = None` the
+    /// exporter reads the standard `OTEL_EXPORTER_OTLP_*` variables.
+    pub(super) fn build_provider(
+        endpoint: Option<&str>,
+    ) -> Result<SdkTracerProvider, ExporterBuildError> {
+        let mut builder = SpanExporter::builder().with_http();
+        if let Some(endpoint) = endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        let exporter = builder.build()?;
+        Ok(SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name("claude-proxy")
+                    .build(),
+            )
+            .build())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use opentelemetry::trace::TracerProvider;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        #[test]
+        fn test_layer_builds_with_endpoint() {
+            let provider = build_provider(Some("http://127.0.0.1:4318/v1/traces")).unwrap();
+            let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+            let _subscriber = tracing_subscriber::registry().with(layer);
+            provider.shutdown().unwrap();
+        }
+    }
+}
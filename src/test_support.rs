@@ -0,0 +1,184 @@
+//! Helpers shared by unit tests: a whole [`AppState`], mock upstreams and
+//! a real database.
+//!
+//! Tests that need PostgreSQL run through [`with_db`] and are marked
+//! `#[ignore = "needs TEST_DATABASE_URL"]`, so a plain `cargo test` stays
+//! self-contained; `just test-db` (and CI) runs them with `--ignored`.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use axum::Router;
+use reqwest::Client;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::AppState;
+use crate::admin_session::{AdminCredentials, CookieSettings};
+use crate::auth::storage::Auth;
+use crate::auth::{AuthStore, ClientKey, ClientKeysStore, ModelsStore, OAuthManager};
+use crate::capture::CaptureConfig;
+use crate::config::{CloakMode, UnknownModelPolicy};
+use crate::constants::ANTHROPIC_BASE_URL;
+use crate::db;
+use crate::export_links::ExportLinkSigner;
+use crate::idempotency::Idempotency;
+use crate::login_throttle::LoginThrottle;
+use crate::request_queue::RequestQueue;
+use crate::routes::retry::RetryPolicy;
+use crate::routes::{admin, readiness};
+use crate::upstream_urls::UpstreamUrls;
+use crate::usage::UsageCache;
+
+/// The database pool is global, so its connections must outlive any single
+/// test's runtime: every database test runs on this one.
+static DB_RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("database test runtime")
+});
+
+static DB_READY: OnceCell<()> = OnceCell::const_new();
+
+/// Some tests read or reset every key's usage, so they take turns.
+static DB_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Run `test` against the database at `TEST_DATABASE_URL`, migrated into a
+/// schema of its own for this test process. Tests run one at a time but
+/// share that schema, so each creates its own keys rather than expecting
+/// empty tables.
+pub fn with_db(test: impl Future<Output = ()>) {
+    DB_RUNTIME.block_on(async {
+        let _turn = DB_LOCK.lock().await;
+        DB_READY
+            .get_or_init(|| async {
+                let url = std::env::var("TEST_DATABASE_URL")
+                    .expect("TEST_DATABASE_URL must be set for database tests");
+                let schema = format!("test_{}", std::process::id());
+                db::init_db(&url, Some(&schema))
+                    .await
+                    .expect("test database");
+            })
+            .await;
+        test.await;
+    });
+}
+
+/// Serve `router` on an ephemeral localhost port for the rest of the test.
+pub async fn spawn_mock(router: Router) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    addr
+}
+
+/// State with the defaults `Config::from_env` falls back to, admin login
+/// `admin`/`secret`. Override fields with struct update syntax.
+pub fn test_state() -> AppState {
+    let http_client = Client::new();
+    let auth_store = Arc::new(AuthStore::new());
+    AppState {
+        client_keys: Arc::new(ClientKeysStore::new()),
+        models: Arc::new(ModelsStore::new()),
+        oauth: Arc::new(OAuthManager::new(http_client.clone(), auth_store.clone())),
+        readiness: readiness::Readiness::new(false, auth_store.clone()),
+        auth_store,
+        http_client,
+        admin_credentials: AdminCredentials {
+            username: "admin".into(),
+            password: "secret".into(),
+            read_only: None,
+        },
+        session_cookie: CookieSettings::new(None, None, false).expect("default cookie settings"),
+        disable_auth: false,
+        cloak_mode: CloakMode::Auto,
+        usage_cache: UsageCache::new(),
+        session_id: "test-session".into(),
+        capture: CaptureConfig::from_env(),
+        subscription_gate_pct: 100.0,
+        max_stream_duration: None,
+        first_event_timeout: None,
+        cache_min_tokens: crate::transforms::cache_breakpoints::DEFAULT_CACHE_MIN_TOKENS,
+        login_throttle: LoginThrottle::new(5, Duration::from_secs(300), Duration::from_secs(900)),
+        trusted_proxy_hops: 0,
+        reject_unsupported_params: false,
+        default_model: "claude-sonnet-4-5".into(),
+        limit_reference_model: "claude-sonnet-4-5".into(),
+        default_thinking_effort: None,
+        retry_policy: RetryPolicy::NONE,
+        request_queue: Arc::new(RequestQueue::new(None, Duration::from_secs(30))),
+        upstream: UpstreamUrls::new(ANTHROPIC_BASE_URL).expect("default upstream URL"),
+        idempotency: Idempotency::new(),
+        list_thinking_variants: false,
+        unknown_model_policy: UnknownModelPolicy::Reject,
+        export_links: ExportLinkSigner::ephemeral(),
+        ui_config: admin::UiConfig::new(None, None),
+        redact_tool_input_keys: Arc::from([]),
+        count_tokens_cache: None,
+        protected_models: Arc::from([]),
+    }
+}
+
+/// [`test_state`] with every Anthropic endpoint served by the mock at `addr`.
+pub fn state_with_upstream(addr: SocketAddr) -> AppState {
+    AppState {
+        upstream: UpstreamUrls::new(&format!("http://{addr}")).expect("mock upstream URL"),
+        ..test_state()
+    }
+}
+
+/// A new client key, with an upstream API key stored so requests it makes
+/// authenticate. Needs [`with_db`].
+pub async fn create_test_key(state: &AppState, name: &str) -> ClientKey {
+    state
+        .auth_store
+        .set(
+            "anthropic",
+            Auth::Api {
+                key: "sk-ant-test".into(),
+            },
+        )
+        .await
+        .expect("store upstream credential");
+    state
+        .client_keys
+        .create(name.to_string())
+        .await
+        .expect("create client key")
+}
+
+/// A `request_log` row for [`seed_request`]; counts left out are zero.
+#[derive(Default)]
+pub struct SeedRequest<'a> {
+    pub key_id: &'a str,
+    pub model: &'a str,
+    pub input: i64,
+    pub output: i64,
+    pub cache_read: i64,
+    pub cache_write: i64,
+    pub cost_microdollars: i64,
+    /// Epoch ms
+    pub created_at: u64,
+}
+
+/// Insert `row` into `request_log` as is, for reads that aggregate it.
+/// Needs [`with_db`].
+pub async fn seed_request(row: SeedRequest<'_>) {
+    let conn = db::get_conn().await.expect("database connection");
+    sqlx::query!(
+        "INSERT INTO request_log (key_id, model, input_tokens, output_tokens, cache_read_tokens, cache_write_tokens, cost_microdollars, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        row.key_id,
+        row.model,
+        row.input,
+        row.output,
+        row.cache_read,
+        row.cache_write,
+        row.cost_microdollars,
+        row.created_at as i64,
+    )
+    .execute(&conn)
+    .await
+    .expect("seed request_log row");
+}
@@ -0,0 +1,172 @@
+//! Auto-injected `cache_control` breakpoints, skipped where they can't pay off.
+//!
+//! A breakpoint caches the whole prompt prefix up to it (tools, then system,
+//! then messages), and Anthropic won't cache a prefix shorter than its
+//! minimum (about 1024 tokens). A breakpoint below that buys nothing and uses
+//! up one of the request's four slots, so injected ones are dropped again.
+//! Breakpoints the client set itself are always kept.
+
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use llm_relay::convert::cache_control::ensure_cache_control;
+
+/// Anthropic's minimum cacheable prompt length for most models, in tokens.
+pub const DEFAULT_CACHE_MIN_TOKENS: usize = 1024;
+
+/// Rough size proxy: serialized JSON characters per token.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Request sections in prompt order.
+const SEGMENTS: [&str; 3] = ["tools", "system", "messages"];
+
+/// Inject breakpoints via [`ensure_cache_control`], then remove the injected
+/// ones whose cached prefix is estimated below `min_tokens`. `0` keeps every
+/// injected breakpoint.
+pub fn inject_cache_control(body: Value, min_tokens: usize) -> Value {
+    if min_tokens == 0 {
+        return ensure_cache_control(body);
+    }
+
+    let mut client_breakpoints = HashSet::new();
+    let mut prefix_tokens = Vec::with_capacity(SEGMENTS.len());
+    let mut prefix = 0;
+    for segment in SEGMENTS {
+        if let Some(value) = body.get(segment) {
+            collect_breakpoints(value, format!("/{segment}"), &mut client_breakpoints);
+            prefix += estimate_tokens(value);
+        }
+        prefix_tokens.push((segment, prefix));
+    }
+
+    let mut body = ensure_cache_control(body);
+    for (segment, prefix) in prefix_tokens {
+        if prefix < min_tokens
+            && let Some(value) = body.get_mut(segment)
+        {
+            strip_injected(value, format!("/{segment}"), &client_breakpoints);
+        }
+    }
+    body
+}
+
+/// Token estimate for a request section from its serialized length.
+fn estimate_tokens(value: &Value) -> usize {
+    value.to_string().len() / CHARS_PER_TOKEN
+}
+
+/// JSON pointers of every object under `value` that carries `cache_control`.
+fn collect_breakpoints(value: &Value, path: String, out: &mut HashSet<String>) {
+    match value {
+        Value::Object(map) => {
+            if map.contains_key("cache_control") {
+                out.insert(path.clone());
+            }
+            for (key, child) in map {
+                collect_breakpoints(child, format!("{path}/{key}"), out);
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter().enumerate() {
+                collect_breakpoints(child, format!("{path}/{i}"), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Remove `cache_control` from objects not listed in `keep`.
+fn strip_injected(value: &mut Value, path: String, keep: &HashSet<String>) {
+    match value {
+        Value::Object(map) => {
+            if !keep.contains(&path) {
+                map.remove("cache_control");
+            }
+            for (key, child) in map.iter_mut() {
+                strip_injected(child, format!("{path}/{key}"), keep);
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter_mut().enumerate() {
+                strip_injected(child, format!("{path}/{i}"), keep);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn breakpoints(body: &Value) -> HashSet<String> {
+        let mut out = HashSet::new();
+        collect_breakpoints(body, String::new(), &mut out);
+        out
+    }
+
+    fn request(system: &str, message: &str) -> Value {
+        json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "system": [{ "type": "text", "text": system }],
+            "messages": [{
+                "role": "user",
+                "content": [{ "type": "text", "text": message }]
+            }]
+        })
+    }
+
+    #[test]
+    fn test_small_request_gets_no_breakpoints() {
+        let body = request("You are terse.", "hi");
+        let prepared = inject_cache_control(body, DEFAULT_CACHE_MIN_TOKENS);
+        assert!(breakpoints(&prepared).is_empty(), "{prepared}");
+    }
+
+    #[test]
+    fn test_large_request_gets_breakpoints() {
+        let body = request(&"Project conventions. ".repeat(500), "hi");
+        let prepared = inject_cache_control(body.clone(), DEFAULT_CACHE_MIN_TOKENS);
+        assert_eq!(prepared, ensure_cache_control(body));
+        assert!(!breakpoints(&prepared).is_empty());
+    }
+
+    #[test]
+    fn test_zero_threshold_always_injects() {
+        let body = request("You are terse.", "hi");
+        assert_eq!(
+            inject_cache_control(body.clone(), 0),
+            ensure_cache_control(body)
+        );
+    }
+
+    #[test]
+    fn test_client_breakpoints_kept() {
+        let mut body = request("You are terse.", "hi");
+        body["system"][0]["cache_control"] = json!({ "type": "ephemeral" });
+        let prepared = inject_cache_control(body, DEFAULT_CACHE_MIN_TOKENS);
+        assert_eq!(
+            prepared["system"][0]["cache_control"],
+            json!({ "type": "ephemeral" })
+        );
+        assert_eq!(breakpoints(&prepared).len(), 1);
+    }
+
+    #[test]
+    fn test_only_short_prefixes_lose_breakpoints() {
+        // The system prompt alone is long enough, so breakpoints from there on
+        // stay while the (short) tools prefix gets none
+        let mut body = request(&"Project conventions. ".repeat(500), "hi");
+        body["tools"] = json!([{
+            "name": "get_weather",
+            "description": "Weather for a city",
+            "input_schema": { "type": "object" }
+        }]);
+        let prepared = inject_cache_control(body, DEFAULT_CACHE_MIN_TOKENS);
+        assert!(prepared["tools"][0].get("cache_control").is_none());
+        assert!(!breakpoints(&prepared).is_empty());
+    }
+}
@@ -4,16 +4,29 @@
 //! - `prepare`: Prepare any request for Anthropic API (system injection, user ID, etc.)
 //! - `openai_compat`: OpenAI ↔ Anthropic format conversion
 //! - `streaming`: SSE stream transformations
+//! - `stream_events`: Per-event rewrites of native SSE streams
 
+pub mod cache_breakpoints;
 pub mod openai_compat;
 pub mod prepare;
+pub mod stream_events;
 pub mod streaming;
 pub mod tool_aliases;
+pub mod tool_names;
 
-pub use openai_compat::{transform_openai_request, transform_openai_response};
-pub use prepare::{prepare_anthropic_request, prepare_count_tokens_request};
+pub use openai_compat::{
+    applied_thinking, apply_nested_reasoning_effort, apply_structured_tool_results, base_model,
+    count_only_completion, ignored_openai_params, logprobs_error, openai_end_user,
+    parse_anthropic_response, set_end_user_id, transform_openai_request, transform_openai_response,
+    validate_reasoning_effort, wants_count_only, wants_reasoning, wants_stream_usage,
+    with_thinking_echo,
+};
+pub use prepare::{
+    PrepareOptions, prepare_anthropic_request, prepare_count_tokens_request, prepared_service_tier,
+};
 pub use streaming::{
-    stream_anthropic_to_openai_with_usage, stream_restore_native_tool_names_with_usage,
+    OpenAiStreamOptions, stream_anthropic_to_openai_with_usage,
+    stream_restore_native_tool_names_with_usage,
 };
 pub use tool_aliases::{
     ToolNameMap, normalize_claude_code_tool_names, restore_response_tool_names,
@@ -5,7 +5,6 @@
 //! llm-relay; this module adds proxy-specific concerns (model suffix parsing,
 //! thinking config, max_tokens caps, mcp_ prefix stripping).
 
-use llm_relay::MessagesResponse;
 use llm_relay::convert::thinking::{
     build_thinking_for_model, build_thinking_params_json, parse_model_suffix,
     supports_adaptive_thinking,
@@ -18,17 +17,239 @@ use llm_relay::convert::tool_names::strip_mcp_prefix;
 use llm_relay::types::openai::{ChatResponse, InboundChatRequest};
 #[cfg(test)]
 use llm_relay::{EffortLevel, ThinkingConfig};
+use llm_relay::{MessagesResponse, Usage};
+use serde::Deserialize;
 use serde_json::{Value, json};
+use tracing::debug;
+use uuid::Uuid;
 
 use crate::constants::{DEFAULT_MAX_OUTPUT, OPUS_4_6_MAX_OUTPUT};
+use crate::error::openai_error_body;
+use crate::subscription::timestamp_millis;
+use crate::transforms::prepare::end_user_id;
 
-const DEFAULT_MODEL: &str = "claude-sonnet-4-5";
 const DEFAULT_MAX_TOKENS: u32 = 16000;
 
+/// Longest OpenAI `user` kept for the request log, in characters.
+const MAX_END_USER_CHARS: usize = 256;
+
+/// OpenAI request parameters with no Anthropic equivalent.
+///
+/// `logit_bias` is keyed by OpenAI tokenizer ids, which mean nothing to Claude's
+/// tokenizer, so the biases cannot be translated and are dropped instead.
+/// `frequency_penalty` and `presence_penalty` have no counterpart in the
+/// Messages API and no faithful emulation, so they are dropped as well.
+const IGNORED_OPENAI_PARAMS: &[&str] = &["logit_bias", "frequency_penalty", "presence_penalty"];
+
+/// Return the unsupported OpenAI parameters set on a raw request body.
+///
+/// Values that have no effect in OpenAI either are treated as absent: null,
+/// an empty object (many SDKs send `"logit_bias": {}` by default) and a zero
+/// penalty.
+pub fn ignored_openai_params(raw: &Value) -> Vec<&'static str> {
+    IGNORED_OPENAI_PARAMS
+        .iter()
+        .copied()
+        .filter(|name| match raw.get(*name) {
+            None | Some(Value::Null) => false,
+            Some(Value::Object(map)) => !map.is_empty(),
+            Some(Value::Number(n)) => n.as_f64().is_none_or(|v| v.abs() > 0.0),
+            Some(_) => true,
+        })
+        .collect()
+}
+
+/// The OpenAI `user` field: the client's own identifier for the end user
+/// behind a request. Blank values count as absent; long ones are cut to
+/// [`MAX_END_USER_CHARS`].
+pub fn openai_end_user(raw: &Value) -> Option<String> {
+    let user = raw.get("user")?.as_str()?.trim();
+    (!user.is_empty()).then(|| user.chars().take(MAX_END_USER_CHARS).collect())
+}
+
+/// Carry an OpenAI `user` over as Anthropic's abuse-tracking
+/// `metadata.user_id`, which must be in Claude Code format (see
+/// [`end_user_id`]).
+pub fn set_end_user_id(request: &mut Value, key_id: &str, user: &str) {
+    let Some(obj) = request.as_object_mut() else {
+        return;
+    };
+    let user_id = Value::String(end_user_id(key_id, user));
+    match obj.get_mut("metadata") {
+        Some(Value::Object(metadata)) => {
+            metadata.insert("user_id".to_string(), user_id);
+        }
+        _ => {
+            obj.insert("metadata".to_string(), json!({ "user_id": user_id }));
+        }
+    }
+}
+
+/// OpenAI-style 400 body for a request asking for `logprobs` or
+/// `top_logprobs`. Anthropic returns no token probabilities, and clients that
+/// asked for them tend to crash on a missing `choices[].logprobs`, so such
+/// requests are refused rather than answered without them.
+pub fn logprobs_error(raw: &Value) -> Option<Value> {
+    let truthy = |name: &str| match raw.get(name) {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+        Some(Value::Number(n)) => n.as_f64().is_none_or(|v| v != 0.0),
+        Some(_) => true,
+    };
+    let param = ["logprobs", "top_logprobs"]
+        .into_iter()
+        .find(|name| truthy(name))?;
+    Some(openai_error_body(
+        "invalid_request_error",
+        &format!(
+            "`{param}` is not supported by this proxy: Claude does not return token log probabilities"
+        ),
+        Some(param),
+        None,
+    ))
+}
+
+/// Named `reasoning_effort` levels. Any other value must be a thinking budget
+/// in tokens.
+const REASONING_EFFORT_LEVELS: &[&str] = &["none", "low", "medium", "high", "xhigh", "max", "auto"];
+
+/// Reject `reasoning_effort` values the thinking config builder would
+/// otherwise silently replace with a default.
+pub fn validate_reasoning_effort(effort: &str) -> Result<(), String> {
+    let effort = effort.trim();
+    if REASONING_EFFORT_LEVELS
+        .iter()
+        .any(|level| level.eq_ignore_ascii_case(effort))
+        || effort.parse::<u32>().is_ok()
+    {
+        return Ok(());
+    }
+    Err(format!(
+        "Invalid reasoning_effort '{effort}': expected one of {} or a token budget",
+        REASONING_EFFORT_LEVELS.join("/")
+    ))
+}
+
+/// The thinking configuration actually sent upstream, as echoed back to the
+/// client in `x_thinking`. Read from the prepared body, so it reflects
+/// capability stripping as well as the requested effort.
+pub fn applied_thinking(prepared_body: &Value) -> Value {
+    json!({
+        "thinking": prepared_body.get("thinking").cloned().unwrap_or(Value::Null),
+        "effort": prepared_body
+            .pointer("/output_config/effort")
+            .cloned()
+            .unwrap_or(Value::Null),
+    })
+}
+
+/// Convert one OpenAI content part of a tool message to an Anthropic
+/// `tool_result` content block. Unknown part types are kept as their JSON
+/// text so nothing the tool returned is silently lost.
+fn tool_result_block(part: &Value) -> Value {
+    match part.get("type").and_then(Value::as_str) {
+        Some("text") => json!({
+            "type": "text",
+            "text": part.get("text").and_then(Value::as_str).unwrap_or_default(),
+        }),
+        Some("image_url") => {
+            let url = part
+                .pointer("/image_url/url")
+                .or_else(|| part.get("image_url"))
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            image_block(url)
+        }
+        _ => json!({ "type": "text", "text": part.to_string() }),
+    }
+}
+
+/// Anthropic image block for an OpenAI image URL, inline for `data:` URLs.
+fn image_block(url: &str) -> Value {
+    if let Some((meta, data)) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(','))
+        && let Some(media_type) = meta.strip_suffix(";base64")
+    {
+        return json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": media_type, "data": data },
+        });
+    }
+    json!({ "type": "image", "source": { "type": "url", "url": url } })
+}
+
+/// Structured `tool_result` content for an OpenAI tool message, or `None`
+/// when the plain-string conversion is already faithful.
+fn structured_tool_content(content: Option<&Value>) -> Option<Value> {
+    match content? {
+        Value::Array(parts) => Some(Value::Array(parts.iter().map(tool_result_block).collect())),
+        object @ Value::Object(_) => Some(json!([{ "type": "text", "text": object.to_string() }])),
+        _ => None,
+    }
+}
+
+/// Carry structured OpenAI tool results over to the converted request.
+///
+/// The core conversion flattens a `tool` message's content into one string.
+/// Tools can return images or several parts, so array (or object) content
+/// from the raw request replaces the flattened `tool_result` content with
+/// Anthropic blocks. A tool message with `"is_error": true` marks its
+/// `tool_result` as an error.
+pub fn apply_structured_tool_results(raw: &Value, request: &mut Value) {
+    let Some(raw_messages) = raw.get("messages").and_then(Value::as_array) else {
+        return;
+    };
+    for message in raw_messages {
+        if message.get("role").and_then(Value::as_str) != Some("tool") {
+            continue;
+        }
+        let Some(tool_call_id) = message.get("tool_call_id").and_then(Value::as_str) else {
+            continue;
+        };
+        let content = structured_tool_content(message.get("content"));
+        let is_error = message.get("is_error").and_then(Value::as_bool) == Some(true);
+        if content.is_none() && !is_error {
+            continue;
+        }
+        if let Some(block) = find_tool_result(request, tool_call_id) {
+            if let Some(content) = content {
+                block.insert("content".to_string(), content);
+            }
+            if is_error {
+                block.insert("is_error".to_string(), Value::Bool(true));
+            }
+        }
+    }
+}
+
+fn find_tool_result<'a>(
+    request: &'a mut Value,
+    tool_use_id: &str,
+) -> Option<&'a mut serde_json::Map<String, Value>> {
+    request
+        .get_mut("messages")?
+        .as_array_mut()?
+        .iter_mut()
+        .filter_map(|m| m.get_mut("content").and_then(Value::as_array_mut))
+        .flatten()
+        .filter_map(Value::as_object_mut)
+        .find(|block| {
+            block.get("type").and_then(Value::as_str) == Some("tool_result")
+                && block.get("tool_use_id").and_then(Value::as_str) == Some(tool_use_id)
+        })
+}
+
 // ============================================================================
 // Transform Functions
 // ============================================================================
 
+/// The model a (possibly suffixed) OpenAI model id is sent upstream as.
+/// Key access and limit checks must use this, not the raw id, so they gate
+/// exactly the model that will be billed.
+pub fn base_model(model: &str) -> String {
+    parse_model_suffix(model).0
+}
+
 /// Transform an OpenAI chat request to Anthropic format.
 ///
 /// Returns a JSON Value that can be further processed by `prepare_anthropic_request()`.
@@ -38,18 +259,27 @@ const DEFAULT_MAX_TOKENS: u32 = 16000;
 /// - Model suffix parsing for thinking configuration
 /// - reasoning_effort conversion to thinking config
 /// - max_tokens adjustment for thinking headroom
+/// - temperature/top_p adjustment when thinking is on
+///
+/// The caller is expected to have resolved `req.model` already (aliases and
+/// the configured default, see `ModelsStore::resolve_model`).
+/// `default_max_tokens` replaces the built-in default when the request sets
+/// no `max_tokens` (per-key setting). `default_effort` is the configured
+/// default thinking effort (the model's, else the global one), used when the
+/// request sets neither `reasoning_effort` nor a model suffix.
 ///
 /// Note: This does NOT add mcp_ prefix, system injection, or user ID.
 /// Those are handled by `prepare_anthropic_request()`.
-pub fn transform_openai_request(req: InboundChatRequest) -> Value {
+pub fn transform_openai_request(
+    req: InboundChatRequest,
+    default_max_tokens: Option<u32>,
+    default_effort: Option<&str>,
+) -> Value {
     // Save proxy-specific fields before consuming
     let stream = req.stream;
     let top_p = req.top_p;
     let reasoning_effort = req.reasoning_effort.clone();
-    let raw_model = req
-        .model
-        .clone()
-        .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    let raw_model = req.model.clone().unwrap_or_default();
 
     // Parse model suffix for thinking config (e.g., "claude-sonnet-4-5(medium)")
     let (base_model, suffix_effort) = parse_model_suffix(&raw_model);
@@ -68,15 +298,15 @@ pub fn transform_openai_request(req: InboundChatRequest) -> Value {
         set_field(&mut request, "top_p", json!(p));
     }
 
-    // Convert reasoning_effort or suffix to thinking config
-    // reasoning_effort takes priority over suffix
-    let thinking_config = if let Some(effort) = reasoning_effort.as_ref() {
-        build_thinking_for_model(&base_model, effort)
-    } else {
-        suffix_effort
-            .as_ref()
-            .and_then(|effort| build_thinking_for_model(&base_model, effort))
-    };
+    // Convert reasoning_effort, suffix or the default to thinking config (in
+    // that order of priority). An explicit "none" disables thinking even when
+    // a default is configured.
+    let thinking_config = reasoning_effort
+        .as_deref()
+        .or(suffix_effort.as_deref())
+        .or(default_effort)
+        .filter(|effort| !effort.trim().eq_ignore_ascii_case("none"))
+        .and_then(|effort| build_thinking_for_model(&base_model, effort));
 
     // Set thinking and output_config on the request
     if let Some(ref config) = thinking_config {
@@ -87,6 +317,7 @@ pub fn transform_openai_request(req: InboundChatRequest) -> Value {
         if let Some(v) = output_config_json {
             set_field(&mut request, "output_config", v);
         }
+        fit_sampling_to_thinking(&mut request);
     }
 
     // Determine appropriate max_tokens based on model capabilities
@@ -105,7 +336,7 @@ pub fn transform_openai_request(req: InboundChatRequest) -> Value {
         .get("max_tokens")
         .and_then(|v| v.as_u64())
         .map(|v| v as u32)
-        .unwrap_or(DEFAULT_MAX_TOKENS);
+        .unwrap_or(default_max_tokens.unwrap_or(DEFAULT_MAX_TOKENS));
 
     // For manual thinking (older models): ensure max_tokens > budget_tokens
     if let Some(t) = request.get("thinking")
@@ -124,15 +355,207 @@ pub fn transform_openai_request(req: InboundChatRequest) -> Value {
     max_tokens = max_tokens.min(model_max_output);
     set_field(&mut request, "max_tokens", json!(max_tokens));
 
+    if let Some(object) = request.as_object_mut() {
+        for name in IGNORED_OPENAI_PARAMS {
+            object.remove(*name);
+        }
+    }
+    drop_empty_text_blocks(&mut request);
+
     request
 }
 
+/// Anthropic rejects thinking requests with a `temperature` other than 1 or
+/// with `top_p` set. Clients that always send their sampling defaults would
+/// otherwise get a 400 as soon as thinking is on, so force `temperature` to 1
+/// and drop `top_p`.
+fn fit_sampling_to_thinking(request: &mut Value) {
+    let Some(object) = request.as_object_mut() else {
+        return;
+    };
+    if let Some(temperature) = object.get_mut("temperature")
+        && temperature.as_f64().is_some_and(|t| t != 1.0)
+    {
+        debug!("Thinking enabled: forcing temperature {temperature} to 1");
+        *temperature = json!(1);
+    }
+    if let Some(top_p) = object.remove("top_p") {
+        debug!("Thinking enabled: dropping top_p {top_p}");
+    }
+}
+
+/// Remove the empty text block the core conversion adds to keep content
+/// non-empty (e.g. assistant turns with `content: null` and only
+/// `tool_calls`). Anthropic can reject empty text blocks, so it's kept only
+/// when it is the message's sole block.
+fn drop_empty_text_blocks(request: &mut Value) {
+    let Some(messages) = request.get_mut("messages").and_then(Value::as_array_mut) else {
+        return;
+    };
+    let is_empty_text = |block: &Value| {
+        block.get("type").and_then(Value::as_str) == Some("text")
+            && block
+                .get("text")
+                .and_then(Value::as_str)
+                .is_some_and(str::is_empty)
+    };
+    for content in messages
+        .iter_mut()
+        .filter_map(|m| m.get_mut("content").and_then(Value::as_array_mut))
+    {
+        if content.iter().any(|block| !is_empty_text(block)) {
+            content.retain(|block| !is_empty_text(block));
+        }
+    }
+}
+
 fn set_field(request: &mut Value, key: &str, value: Value) {
     if let Some(object) = request.as_object_mut() {
         object.insert(key.to_string(), value);
     }
 }
 
+/// A fresh OpenAI-style completion id (`chatcmpl-<uuid>`).
+pub(crate) fn completion_id() -> String {
+    format!("chatcmpl-{}", Uuid::new_v4().simple())
+}
+
+/// OpenAI `stream_options`. Keys not listed here are ignored, so options
+/// newer than the proxy never fail a request.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub struct StreamOptionsParam {
+    /// Final usage chunk before `[DONE]`
+    #[serde(default)]
+    pub include_usage: bool,
+    /// Random padding of chunks against length side channels. Accepted but
+    /// not applied: chunks are sent unpadded.
+    #[serde(default)]
+    pub include_obfuscation: Option<bool>,
+}
+
+/// The request's `stream_options`; defaults when absent, `null` or not an
+/// object of the expected shape.
+pub fn stream_options_param(raw: &Value) -> StreamOptionsParam {
+    raw.get("stream_options")
+        .and_then(|options| StreamOptionsParam::deserialize(options).ok())
+        .unwrap_or_default()
+}
+
+/// Whether a streaming request asked for a final usage chunk
+/// (`stream_options.include_usage`).
+pub fn wants_stream_usage(raw: &Value) -> bool {
+    stream_options_param(raw).include_usage
+}
+
+/// Fill `req.reasoning_effort` from the nested `reasoning: {"effort": ..}`
+/// shape newer OpenAI clients send. The flat `reasoning_effort` is the Chat
+/// Completions field and wins when both are present. Returns whether the
+/// nested value was used, so validation errors can name the right param.
+pub fn apply_nested_reasoning_effort(req: &mut InboundChatRequest, raw: &Value) -> bool {
+    let nested = raw
+        .get("reasoning")
+        .and_then(|reasoning| reasoning.get("effort"))
+        .and_then(Value::as_str);
+    match (req.reasoning_effort.as_deref(), nested) {
+        (None, Some(effort)) => {
+            req.reasoning_effort = Some(effort.to_string());
+            true
+        }
+        (Some(flat), Some(effort)) if flat != effort => {
+            debug!(
+                "Both reasoning_effort ({flat}) and reasoning.effort ({effort}) set; \
+                 using reasoning_effort"
+            );
+            false
+        }
+        _ => false,
+    }
+}
+
+/// Whether the client wants thinking returned as `reasoning_content`
+/// (`include_reasoning`, default true).
+pub fn wants_reasoning(raw: &Value) -> bool {
+    raw.get("include_reasoning")
+        .and_then(Value::as_bool)
+        .unwrap_or(true)
+}
+
+/// Whether the client only wants the prompt's token count: `max_tokens: 0`
+/// or `max_completion_tokens: 0`. Anthropic rejects a zero-length
+/// generation, so these requests are answered from count_tokens instead.
+pub fn wants_count_only(raw: &Value) -> bool {
+    ["max_tokens", "max_completion_tokens"]
+        .iter()
+        .any(|field| raw.get(*field).and_then(Value::as_u64) == Some(0))
+}
+
+/// Completion for a count-only request: empty content, `finish_reason`
+/// `length` and `usage.prompt_tokens` from count_tokens. `stream` makes it a
+/// `chat.completion.chunk`, sent as the only chunk before `[DONE]`.
+pub fn count_only_completion(model: &str, prompt_tokens: u64, stream: bool) -> Value {
+    let (object, message_field) = if stream {
+        ("chat.completion.chunk", "delta")
+    } else {
+        ("chat.completion", "message")
+    };
+    let mut choice = json!({ "index": 0, "finish_reason": "length" });
+    set_field(
+        &mut choice,
+        message_field,
+        json!({ "role": "assistant", "content": "" }),
+    );
+    let usage = Usage {
+        input_tokens: prompt_tokens,
+        ..Usage::default()
+    };
+    json!({
+        "id": completion_id(),
+        "object": object,
+        "created": timestamp_millis() / 1000,
+        "model": model,
+        "choices": [choice],
+        "usage": openai_usage(&usage)
+    })
+}
+
+/// OpenAI `usage` object for an Anthropic usage report. Shared by the
+/// streaming usage chunk and non-streaming responses so both report cache
+/// tokens the same way. `prompt_tokens` counts uncached input only, as
+/// Anthropic bills it; cache writes and reads are reported alongside.
+pub(crate) fn openai_usage(usage: &Usage) -> Value {
+    let cache_read = usage.cache_read_input_tokens.unwrap_or(0);
+    json!({
+        "prompt_tokens": usage.input_tokens,
+        "completion_tokens": usage.output_tokens,
+        "total_tokens": usage.input_tokens + usage.output_tokens,
+        "cache_creation_input_tokens": usage.cache_creation_input_tokens.unwrap_or(0),
+        "cache_read_input_tokens": cache_read,
+        "prompt_tokens_details": { "cached_tokens": cache_read }
+    })
+}
+
+/// Parse an upstream Messages response. Without `include_reasoning`, thinking
+/// blocks are dropped first, so the converted response carries no
+/// `reasoning_content`.
+pub fn parse_anthropic_response(
+    text: &str,
+    include_reasoning: bool,
+) -> Result<MessagesResponse, serde_json::Error> {
+    if include_reasoning {
+        return serde_json::from_str(text);
+    }
+    let mut value: Value = serde_json::from_str(text)?;
+    if let Some(Value::Array(blocks)) = value.get_mut("content") {
+        blocks.retain(|block| {
+            !matches!(
+                block.get("type").and_then(Value::as_str),
+                Some("thinking" | "redacted_thinking")
+            )
+        });
+    }
+    serde_json::from_value(value)
+}
+
 /// Transform an Anthropic response to OpenAI format.
 ///
 /// Uses llm-relay's core conversion and adds mcp_ prefix stripping for tool names.
@@ -140,8 +563,7 @@ pub fn transform_openai_response(resp: MessagesResponse) -> ChatResponse {
     let mut response = anthropic_response_to_openai(resp);
 
     // Override id to use OpenAI chatcmpl-* format instead of Anthropic's msg_* id
-    let now = response.created.unwrap_or(0);
-    response.id = Some(format!("chatcmpl-{now}"));
+    response.id = Some(completion_id());
 
     // Strip mcp_ prefix from tool call names (proxy-specific)
     for choice in &mut response.choices {
@@ -155,9 +577,23 @@ pub fn transform_openai_response(resp: MessagesResponse) -> ChatResponse {
     response
 }
 
+/// Serialize an OpenAI response with the applied thinking configuration
+/// added as `x_thinking` and `usage` rebuilt from the Anthropic `usage`.
+pub fn with_thinking_echo(
+    response: &ChatResponse,
+    applied_thinking: Value,
+    usage: &Usage,
+) -> Result<Value, serde_json::Error> {
+    let mut value = serde_json::to_value(response)?;
+    set_field(&mut value, "x_thinking", applied_thinking);
+    set_field(&mut value, "usage", openai_usage(usage));
+    Ok(value)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde::Deserialize;
 
     #[test]
     fn test_parse_model_suffix() {
@@ -179,6 +615,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_openai_user_mapped_to_metadata() {
+        assert_eq!(
+            openai_end_user(&json!({ "user": " user-123 " })).as_deref(),
+            Some("user-123")
+        );
+        assert_eq!(openai_end_user(&json!({ "user": "  " })), None);
+        assert_eq!(openai_end_user(&json!({ "user": 7 })), None);
+        assert_eq!(openai_end_user(&json!({})), None);
+        let long = "u".repeat(MAX_END_USER_CHARS + 10);
+        assert_eq!(
+            openai_end_user(&json!({ "user": long })).map(|u| u.len()),
+            Some(MAX_END_USER_CHARS)
+        );
+
+        let mut request = json!({ "model": "claude-sonnet-4-5", "metadata": { "other": 1 } });
+        set_end_user_id(&mut request, "key-1", "user-123");
+        assert_eq!(
+            request["metadata"],
+            json!({ "other": 1, "user_id": end_user_id("key-1", "user-123") })
+        );
+        let mut request = json!({ "model": "claude-sonnet-4-5" });
+        set_end_user_id(&mut request, "key-1", "user-123");
+        assert_eq!(
+            request["metadata"]["user_id"],
+            end_user_id("key-1", "user-123")
+        );
+    }
+
+    #[test]
+    fn test_reasoning_omitted_when_not_wanted() {
+        let text = json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-opus-4-6",
+            "content": [
+                {"type": "thinking", "thinking": "Let me think", "signature": "sig"},
+                {"type": "text", "text": "Hello"}
+            ],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 5, "output_tokens": 2}
+        })
+        .to_string();
+
+        assert!(!wants_reasoning(&json!({"include_reasoning": false})));
+        assert!(wants_reasoning(&json!({})));
+
+        let response = transform_openai_response(parse_anthropic_response(&text, false).unwrap());
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(
+            value["choices"][0]["message"].get("reasoning_content"),
+            None
+        );
+        assert!(!value.to_string().contains("Let me think"));
+        assert!(value.to_string().contains("Hello"));
+    }
+
+    #[test]
+    fn test_base_model_matches_transformed_model() {
+        for model in [
+            "claude-sonnet-4-5",
+            "claude-opus-4-6(high)",
+            "claude-sonnet-4-5(1000)",
+        ] {
+            let req: InboundChatRequest = serde_json::from_value(json!({
+                "model": model,
+                "stream": true,
+                "messages": [{"role": "user", "content": "hi"}]
+            }))
+            .unwrap();
+            let result = transform_openai_request(req, None, None);
+            assert_eq!(result["model"], base_model(model));
+        }
+    }
+
     #[test]
     fn test_supports_adaptive_thinking() {
         assert!(supports_adaptive_thinking("claude-opus-4-6"));
@@ -254,6 +767,172 @@ mod tests {
         assert!(config.is_none());
     }
 
+    #[test]
+    fn test_ignored_openai_params() {
+        let raw = json!({"model": "claude-sonnet-4-5", "logit_bias": {"50256": -100}});
+        assert_eq!(ignored_openai_params(&raw), vec!["logit_bias"]);
+
+        let raw = json!({"model": "claude-sonnet-4-5", "logit_bias": {}});
+        assert!(ignored_openai_params(&raw).is_empty());
+
+        let raw = json!({"model": "claude-sonnet-4-5", "logit_bias": null});
+        assert!(ignored_openai_params(&raw).is_empty());
+    }
+
+    #[test]
+    fn test_logprobs_rejected() {
+        let error = logprobs_error(&json!({"logprobs": true})).unwrap();
+        assert_eq!(error["error"]["type"], "invalid_request_error");
+        assert_eq!(error["error"]["param"], "logprobs");
+
+        let error = logprobs_error(&json!({"top_logprobs": 5})).unwrap();
+        assert_eq!(error["error"]["param"], "top_logprobs");
+
+        // Explicitly off is what many SDKs send by default
+        assert_eq!(
+            logprobs_error(&json!({"logprobs": false, "top_logprobs": 0})),
+            None
+        );
+        assert_eq!(logprobs_error(&json!({"logprobs": null})), None);
+        assert_eq!(logprobs_error(&json!({})), None);
+    }
+
+    #[test]
+    fn test_ignored_penalties() {
+        let raw = json!({
+            "model": "claude-sonnet-4-5",
+            "frequency_penalty": 0.5,
+            "presence_penalty": -1
+        });
+        assert_eq!(
+            ignored_openai_params(&raw),
+            vec!["frequency_penalty", "presence_penalty"]
+        );
+
+        // Zero is the OpenAI default and a no-op, so it is not reported
+        let raw = json!({"frequency_penalty": 0, "presence_penalty": 0.0});
+        assert!(ignored_openai_params(&raw).is_empty());
+    }
+
+    #[test]
+    fn test_stream_options_with_unknown_keys_parse() {
+        let raw = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "stream": true,
+            "stream_options": {
+                "include_usage": true,
+                "include_obfuscation": false,
+                "some_future_option": { "nested": 1 }
+            }
+        });
+        let req = InboundChatRequest::deserialize(&raw).unwrap();
+        assert_eq!(req.stream, Some(true));
+        assert_eq!(
+            stream_options_param(&raw),
+            StreamOptionsParam {
+                include_usage: true,
+                include_obfuscation: Some(false),
+            }
+        );
+        assert!(wants_stream_usage(&raw));
+
+        for options in [json!(null), json!({}), json!("bogus")] {
+            let raw = json!({ "stream_options": options });
+            assert_eq!(stream_options_param(&raw), StreamOptionsParam::default());
+        }
+    }
+
+    #[test]
+    fn test_penalties_are_stripped() {
+        let raw = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "frequency_penalty": 0.7,
+            "presence_penalty": 0.3
+        });
+        let req = InboundChatRequest::deserialize(&raw).unwrap();
+        let result = transform_openai_request(req, None, None);
+        assert!(result.get("frequency_penalty").is_none());
+        assert!(result.get("presence_penalty").is_none());
+        assert_eq!(result["model"], "claude-sonnet-4-5");
+        assert_eq!(result["messages"][0]["content"], "Hi");
+    }
+
+    #[test]
+    fn test_sampling_fitted_to_thinking() {
+        let raw = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "temperature": 0.2,
+            "top_p": 0.9,
+            "reasoning_effort": "high"
+        });
+        let req = InboundChatRequest::deserialize(&raw).unwrap();
+        let result = transform_openai_request(req, None, None);
+        assert!(result.get("thinking").is_some());
+        assert_eq!(result["temperature"], 1);
+        assert!(result.get("top_p").is_none());
+    }
+
+    #[test]
+    fn test_sampling_kept_without_thinking() {
+        let raw = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "temperature": 0.2,
+            "top_p": 0.9
+        });
+        let req = InboundChatRequest::deserialize(&raw).unwrap();
+        let result = transform_openai_request(req, None, None);
+        assert!(result.get("thinking").is_none());
+        assert_eq!(result["temperature"], 0.2);
+        assert_eq!(result["top_p"], 0.9);
+    }
+
+    #[test]
+    fn test_logit_bias_is_stripped() {
+        let raw = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "logit_bias": {"50256": -100, "1734": 5}
+        });
+        let req = InboundChatRequest::deserialize(&raw).unwrap();
+        let result = transform_openai_request(req, None, None);
+        assert!(result.get("logit_bias").is_none());
+        assert_eq!(result["model"], "claude-sonnet-4-5");
+    }
+
+    #[test]
+    fn test_default_max_tokens_override() {
+        let raw = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi"}]
+        });
+        let builtin =
+            transform_openai_request(InboundChatRequest::deserialize(&raw).unwrap(), None, None);
+        assert_eq!(builtin["max_tokens"], DEFAULT_MAX_TOKENS);
+
+        let overridden = transform_openai_request(
+            InboundChatRequest::deserialize(&raw).unwrap(),
+            Some(4096),
+            None,
+        );
+        assert_eq!(overridden["max_tokens"], 4096);
+    }
+
+    #[test]
+    fn test_explicit_max_tokens_beats_key_default() {
+        let raw = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "max_tokens": 1000
+        });
+        let req = InboundChatRequest::deserialize(&raw).unwrap();
+        let result = transform_openai_request(req, Some(4096), None);
+        assert_eq!(result["max_tokens"], 1000);
+    }
+
     #[test]
     fn test_convert_openai_tool() {
         let openai_tool = json!({
@@ -268,4 +947,278 @@ mod tests {
         assert_eq!(result["name"], "get_weather");
         assert_eq!(result["description"], "Get weather");
     }
+
+    #[test]
+    fn test_validate_reasoning_effort() {
+        for effort in [
+            "none", "low", "medium", "high", "xhigh", "max", "auto", "High", "8000",
+        ] {
+            assert_eq!(validate_reasoning_effort(effort), Ok(()), "{effort}");
+        }
+        for effort in ["", "extreme", "-5", "1.5"] {
+            assert!(
+                validate_reasoning_effort(effort).is_err_and(|e| e.contains("reasoning_effort")),
+                "{effort}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_applied_thinking_echo() {
+        let req: InboundChatRequest = serde_json::from_value(json!({
+            "model": "claude-opus-4-6",
+            "messages": [{"role": "user", "content": "hi"}],
+            "reasoning_effort": "high"
+        }))
+        .unwrap();
+        let applied = applied_thinking(&transform_openai_request(req, None, None));
+        assert_eq!(applied["thinking"]["type"], "adaptive");
+        assert_eq!(applied["effort"], "high");
+
+        let plain = applied_thinking(&json!({ "model": "claude-opus-4-6" }));
+        assert_eq!(plain, json!({ "thinking": null, "effort": null }));
+    }
+
+    fn opus_request(extra: Value) -> InboundChatRequest {
+        let mut raw = json!({
+            "model": "claude-opus-4-6",
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        if let (Some(obj), Value::Object(extra)) = (raw.as_object_mut(), extra) {
+            obj.extend(extra);
+        }
+        serde_json::from_value(raw).unwrap()
+    }
+
+    #[test]
+    fn test_tool_call_only_assistant_turn_has_no_empty_text() {
+        let req: InboundChatRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [
+                {"role": "user", "content": "weather?"},
+                {"role": "assistant", "content": null, "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "get_weather", "arguments": "{}"}
+                }]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "sunny"}
+            ]
+        }))
+        .unwrap();
+        let result = transform_openai_request(req, None, None);
+        let assistant = result["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|m| m["role"] == "assistant")
+            .unwrap();
+        let blocks = assistant["content"].as_array().unwrap();
+        assert!(blocks.iter().any(|b| b["type"] == "tool_use"));
+        assert!(!blocks.iter().any(|b| b["type"] == "text"), "{blocks:?}");
+    }
+
+    #[test]
+    fn test_lone_empty_text_block_kept() {
+        let mut request = json!({ "messages": [
+            {"role": "assistant", "content": [{"type": "text", "text": ""}]},
+            {"role": "assistant", "content": [
+                {"type": "text", "text": ""},
+                {"type": "image", "source": {"type": "url", "url": "https://example.com/a.png"}}
+            ]}
+        ]});
+        drop_empty_text_blocks(&mut request);
+        assert_eq!(
+            request["messages"][0]["content"].as_array().unwrap().len(),
+            1
+        );
+        assert_eq!(request["messages"][1]["content"][0]["type"], "image");
+        assert_eq!(
+            request["messages"][1]["content"].as_array().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_model_default_effort_applied_when_omitted() {
+        let result = transform_openai_request(opus_request(json!({})), None, Some("low"));
+        assert_eq!(result["thinking"]["type"], "adaptive");
+        assert_eq!(result["output_config"]["effort"], "low");
+
+        let without_default = transform_openai_request(opus_request(json!({})), None, None);
+        assert!(without_default.get("thinking").is_none());
+    }
+
+    #[test]
+    fn test_client_effort_overrides_model_default() {
+        let result = transform_openai_request(
+            opus_request(json!({ "reasoning_effort": "high" })),
+            None,
+            Some("low"),
+        );
+        assert_eq!(result["output_config"]["effort"], "high");
+
+        let mut suffixed = opus_request(json!({}));
+        suffixed.model = Some("claude-opus-4-6(medium)".into());
+        let result = transform_openai_request(suffixed, None, Some("low"));
+        assert_eq!(result["output_config"]["effort"], "medium");
+    }
+
+    #[test]
+    fn test_explicit_none_disables_default_effort() {
+        let result = transform_openai_request(
+            opus_request(json!({ "reasoning_effort": "none" })),
+            None,
+            Some("medium"),
+        );
+        assert!(result.get("thinking").is_none());
+        assert!(result.get("output_config").is_none());
+    }
+
+    fn nested_effort_request(model: &str, extra: Value) -> Value {
+        let mut raw = json!({
+            "model": model,
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        if let (Some(obj), Value::Object(extra)) = (raw.as_object_mut(), extra) {
+            obj.extend(extra);
+        }
+        let mut req: InboundChatRequest = serde_json::from_value(raw.clone()).unwrap();
+        apply_nested_reasoning_effort(&mut req, &raw);
+        transform_openai_request(req, None, Some("low"))
+    }
+
+    #[test]
+    fn test_nested_reasoning_effort_on_opus_4_6() {
+        let result = nested_effort_request(
+            "claude-opus-4-6",
+            json!({ "reasoning": { "effort": "high" } }),
+        );
+        assert_eq!(result["thinking"]["type"], "adaptive");
+        assert_eq!(result["output_config"]["effort"], "high");
+    }
+
+    #[test]
+    fn test_nested_reasoning_effort_on_older_model() {
+        let nested = nested_effort_request(
+            "claude-sonnet-4-5",
+            json!({ "reasoning": { "effort": "high" } }),
+        );
+        let flat =
+            nested_effort_request("claude-sonnet-4-5", json!({ "reasoning_effort": "high" }));
+        assert_eq!(nested["thinking"]["type"], "enabled");
+        assert_eq!(nested["thinking"], flat["thinking"]);
+    }
+
+    #[test]
+    fn test_flat_reasoning_effort_wins_over_nested() {
+        let result = nested_effort_request(
+            "claude-opus-4-6",
+            json!({ "reasoning_effort": "medium", "reasoning": { "effort": "max" } }),
+        );
+        assert_eq!(result["output_config"]["effort"], "medium");
+
+        let raw = json!({ "reasoning": { "effort": "high" } });
+        let mut req = opus_request(json!({ "reasoning_effort": "low" }));
+        assert!(!apply_nested_reasoning_effort(&mut req, &raw));
+        let mut req = opus_request(json!({}));
+        assert!(apply_nested_reasoning_effort(&mut req, &raw));
+        assert_eq!(req.reasoning_effort.as_deref(), Some("high"));
+    }
+
+    #[test]
+    fn test_nested_effort_beats_suffix() {
+        let result = nested_effort_request(
+            "claude-opus-4-6(low)",
+            json!({ "reasoning": { "effort": "high" } }),
+        );
+        assert_eq!(result["output_config"]["effort"], "high");
+    }
+
+    fn converted_with_tool_result() -> Value {
+        json!({
+            "messages": [
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "call_1", "name": "screenshot", "input": {}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "call_1", "content": "flattened"}
+                ]}
+            ]
+        })
+    }
+
+    fn raw_tool_message(message: Value) -> Value {
+        let mut message = message;
+        message["role"] = json!("tool");
+        message["tool_call_id"] = json!("call_1");
+        json!({ "messages": [message] })
+    }
+
+    #[test]
+    fn test_plain_text_tool_result_untouched() {
+        let mut request = converted_with_tool_result();
+        apply_structured_tool_results(
+            &raw_tool_message(json!({ "content": "flattened" })),
+            &mut request,
+        );
+        assert_eq!(request, converted_with_tool_result());
+    }
+
+    #[test]
+    fn test_structured_tool_result_text_and_json_parts() {
+        let mut request = converted_with_tool_result();
+        apply_structured_tool_results(
+            &raw_tool_message(json!({ "content": [
+                {"type": "text", "text": "found 2 rows"},
+                {"type": "json", "json": {"rows": 2}}
+            ]})),
+            &mut request,
+        );
+        let content = &request["messages"][1]["content"][0]["content"];
+        assert_eq!(content[0], json!({"type": "text", "text": "found 2 rows"}));
+        assert_eq!(content[1]["type"], "text");
+        let embedded: Value = serde_json::from_str(content[1]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(embedded["json"]["rows"], 2);
+
+        let mut request = converted_with_tool_result();
+        apply_structured_tool_results(
+            &raw_tool_message(json!({ "content": {"rows": 2} })),
+            &mut request,
+        );
+        let content = &request["messages"][1]["content"][0]["content"];
+        assert_eq!(content[0]["text"], r#"{"rows":2}"#);
+    }
+
+    #[test]
+    fn test_structured_tool_result_images() {
+        let mut request = converted_with_tool_result();
+        apply_structured_tool_results(
+            &raw_tool_message(json!({ "content": [
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0KGgo="}},
+                {"type": "image_url", "image_url": {"url": "https://example.com/a.png"}}
+            ]})),
+            &mut request,
+        );
+        let content = &request["messages"][1]["content"][0]["content"];
+        assert_eq!(
+            content[0],
+            json!({"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="}})
+        );
+        assert_eq!(
+            content[1],
+            json!({"type": "image", "source": {"type": "url", "url": "https://example.com/a.png"}})
+        );
+    }
+
+    #[test]
+    fn test_tool_error_sets_is_error() {
+        let mut request = converted_with_tool_result();
+        apply_structured_tool_results(
+            &raw_tool_message(json!({ "content": "boom", "is_error": true })),
+            &mut request,
+        );
+        let block = &request["messages"][1]["content"][0];
+        assert_eq!(block["is_error"], true);
+        assert_eq!(block["content"], "flattened");
+    }
 }
@@ -3,20 +3,25 @@
 //! This module provides a unified pipeline for transforming any request
 //! before sending it to the Anthropic API, including:
 //! - Extracting betas from request body to headers
+//! - Stripping features the target model doesn't support
 //! - Disabling thinking when tool_choice forces tool use
-//! - Injecting fake user ID for OAuth
-//! - Adding mcp_ prefix to tool names
+//! - Enforcing the `metadata.user_id` policy (fake ID when cloaking)
+//! - Adding mcp_ prefix to tool names (built-in tools excepted)
 //! - Injecting system message prefix
-//! - Auto-injecting cache_control breakpoints for optimal caching
+//! - Applying the key's default `service_tier`
+//! - Auto-injecting cache_control breakpoints where the prefix is cacheable
 
 use rand::RngExt;
 use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tracing::warn;
 use uuid::Uuid;
 
-use llm_relay::convert::cache_control::ensure_cache_control;
-use llm_relay::convert::tool_names::transform_request_tool_names;
-
+use crate::auth::{ModelCapabilities, ServiceTier};
 use crate::constants::SYSTEM_PREFIX;
+use crate::error::ProxyError;
+use crate::transforms::cache_breakpoints::{DEFAULT_CACHE_MIN_TOKENS, inject_cache_control};
+use crate::transforms::tool_names::prefix_tool_names;
 
 /// Result of preparing a request for Anthropic API.
 pub struct PreparedRequest {
@@ -26,37 +31,74 @@ pub struct PreparedRequest {
     pub betas: Vec<String>,
 }
 
+/// Per-request knobs for the prepare pipeline.
+pub struct PrepareOptions<'a> {
+    /// Apply Claude Code cloaking (fake user ID, system prefix)
+    pub cloak: bool,
+    /// System prefix injected when cloaking
+    pub system_prefix: &'a str,
+    /// What the target model supports
+    pub capabilities: ModelCapabilities,
+    /// `service_tier` for requests that don't set a valid one
+    pub service_tier: Option<ServiceTier>,
+    /// Smallest estimated prefix (tokens) worth an injected cache breakpoint
+    pub cache_min_tokens: usize,
+}
+
+impl PrepareOptions<'_> {
+    /// Default options: the Claude Code system prefix and a fully capable model.
+    pub fn new(cloak: bool) -> Self {
+        Self {
+            cloak,
+            system_prefix: SYSTEM_PREFIX,
+            capabilities: ModelCapabilities::default(),
+            service_tier: None,
+            cache_min_tokens: DEFAULT_CACHE_MIN_TOKENS,
+        }
+    }
+
+    /// Override the cache breakpoint threshold (`CLAUDE_PROXY_CACHE_MIN_TOKENS`).
+    pub fn with_cache_min_tokens(mut self, cache_min_tokens: usize) -> Self {
+        self.cache_min_tokens = cache_min_tokens;
+        self
+    }
+}
+
 /// Prepare a request body for the Anthropic API.
 ///
 /// This applies all necessary transformations:
 /// 1. Extract and remove `betas` array from body
-/// 2. Disable thinking if `tool_choice` forces tool use
-/// 3. Inject fake user ID in metadata (if cloaking)
-/// 4. Add mcp_ prefix to tool names
-/// 5. Inject system message prefix (if cloaking)
-/// 6. Auto-inject cache_control breakpoints (tools, system, messages)
+/// 2. Strip images, tools or thinking if the model lacks that capability
+/// 3. Disable thinking if `tool_choice` forces tool use
+/// 4. Apply the `metadata.user_id` policy (see [`apply_user_id_policy`])
+/// 5. Add mcp_ prefix to tool names, except Anthropic built-in tools
+/// 6. Inject system message prefix (if cloaking)
+/// 7. Validate `service_tier`, falling back to the key's default
+/// 8. Auto-inject cache_control breakpoints (tools, system, messages) where
+///    the cached prefix reaches `options.cache_min_tokens`
 ///
-/// When `cloak` is false, steps 3 and 5 are skipped.
-/// Returns the transformed body and extracted betas.
-pub fn prepare_anthropic_request(body: Value, cloak: bool) -> PreparedRequest {
+/// When `options.cloak` is false, step 6 is skipped.
+/// Returns the transformed body and extracted betas, or an error if tool
+/// names collide after step 5.
+pub fn prepare_anthropic_request(
+    body: Value,
+    options: &PrepareOptions,
+) -> Result<PreparedRequest, ProxyError> {
     let (betas, body) = extract_betas(body);
+    let body = strip_unsupported_capabilities(body, &options.capabilities);
     let body = disable_thinking_if_forced(body);
-    let body = if cloak {
-        inject_fake_user_id(body)
-    } else {
-        body
-    };
-    let mut body = body;
-    transform_request_tool_names(&mut body);
-    let body = if cloak {
-        inject_system_message(body)
+    let mut body = apply_user_id_policy(body, options.cloak);
+    prefix_tool_names(&mut body)?;
+    let body = if options.cloak {
+        inject_system_message(body, options.system_prefix)
     } else {
         sanitize_system_only(body)
     };
-    let body = ensure_cache_control(body);
+    let body = apply_service_tier(body, options.service_tier);
+    let body = inject_cache_control(body, options.cache_min_tokens);
     let body = strip_unsupported_fields(body);
 
-    PreparedRequest { body, betas }
+    Ok(PreparedRequest { body, betas })
 }
 
 /// Strip fields not supported by the Anthropic OAuth API endpoint.
@@ -68,22 +110,128 @@ fn strip_unsupported_fields(mut body: Value) -> Value {
     body
 }
 
+/// Keep a known `service_tier` from the client; replace a missing or unknown
+/// one with `default` (or drop it when there is no default).
+fn apply_service_tier(mut body: Value, default: Option<ServiceTier>) -> Value {
+    let Some(obj) = body.as_object_mut() else {
+        return body;
+    };
+    let tier = match obj.remove("service_tier") {
+        None => default,
+        Some(value) => value.as_str().and_then(ServiceTier::parse).or_else(|| {
+            warn!("Ignoring unknown service_tier {value}");
+            default
+        }),
+    };
+    if let Some(tier) = tier {
+        obj.insert("service_tier".to_string(), json!(tier.as_str()));
+    }
+    body
+}
+
+/// The `service_tier` a prepared body will be sent with, for the request log.
+pub fn prepared_service_tier(body: &Value) -> Option<&str> {
+    body.get("service_tier").and_then(Value::as_str)
+}
+
+/// Placeholder left where an image was removed for a non-vision model, so the
+/// conversation still reads sensibly.
+const IMAGE_OMITTED: &str = "[image omitted: model does not support images]";
+
+/// Remove request features the target model doesn't support. Forwarding them
+/// only produces an opaque upstream 400, so each removal is logged instead.
+fn strip_unsupported_capabilities(mut body: Value, capabilities: &ModelCapabilities) -> Value {
+    let model = body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let Some(obj) = body.as_object_mut() else {
+        return body;
+    };
+
+    if !capabilities.thinking {
+        let had_thinking = obj.remove("thinking").is_some();
+        let had_effort = obj
+            .get_mut("output_config")
+            .and_then(|oc| oc.as_object_mut())
+            .is_some_and(|oc| oc.remove("effort").is_some());
+        if obj
+            .get("output_config")
+            .and_then(|oc| oc.as_object())
+            .is_some_and(|oc| oc.is_empty())
+        {
+            obj.remove("output_config");
+        }
+        if had_thinking || had_effort {
+            warn!(model = %model, "Model does not support thinking; dropping thinking config");
+        }
+    }
+
+    if !capabilities.tools {
+        let had_tools = obj.remove("tools").is_some();
+        let had_choice = obj.remove("tool_choice").is_some();
+        if had_tools || had_choice {
+            warn!(model = %model, "Model does not support tools; dropping tool definitions");
+        }
+    }
+
+    if !capabilities.vision
+        && let Some(Value::Array(messages)) = obj.get_mut("messages")
+    {
+        let removed: usize = messages
+            .iter_mut()
+            .filter_map(|m| m.get_mut("content"))
+            .map(replace_images)
+            .sum();
+        if removed > 0 {
+            warn!(model = %model, "Model does not support images; removed {removed} image block(s)");
+        }
+    }
+
+    body
+}
+
+/// Replace image blocks in a content array (including inside `tool_result`
+/// content) with a text placeholder. Returns how many were replaced.
+fn replace_images(content: &mut Value) -> usize {
+    let Value::Array(blocks) = content else {
+        return 0;
+    };
+    let mut replaced = 0;
+    for block in blocks.iter_mut() {
+        match block.get("type").and_then(|t| t.as_str()) {
+            Some("image") => {
+                *block = json!({ "type": "text", "text": IMAGE_OMITTED });
+                replaced += 1;
+            }
+            Some("tool_result") => {
+                if let Some(inner) = block.get_mut("content") {
+                    replaced += replace_images(inner);
+                }
+            }
+            _ => {}
+        }
+    }
+    replaced
+}
+
 /// Prepare a count_tokens request for the Anthropic API.
 ///
 /// This applies only the transformations appropriate for count_tokens:
 /// 1. Extract and remove `betas` array from body
 /// 2. Inject system message prefix (if cloaking)
-/// 3. Auto-inject cache_control breakpoints
+/// 3. Auto-inject cache_control breakpoints (same threshold as above)
 ///
 /// Note: count_tokens doesn't support metadata or thinking.
-pub fn prepare_count_tokens_request(body: Value, cloak: bool) -> PreparedRequest {
+pub fn prepare_count_tokens_request(body: Value, options: &PrepareOptions) -> PreparedRequest {
     let (betas, body) = extract_betas(body);
-    let body = if cloak {
-        inject_system_message(body)
+    let body = if options.cloak {
+        inject_system_message(body, options.system_prefix)
     } else {
         sanitize_system_only(body)
     };
-    let body = ensure_cache_control(body);
+    let body = inject_cache_control(body, options.cache_min_tokens);
 
     PreparedRequest { body, betas }
 }
@@ -135,6 +283,44 @@ fn disable_thinking_if_forced(mut body: Value) -> Value {
     body
 }
 
+/// Decide which `metadata.user_id` goes upstream.
+///
+/// When cloaking, the request must look like Claude Code, so a missing or
+/// malformed ID is replaced with a generated one. Otherwise the client's own
+/// ID is passed through for abuse tracking if it has the Claude Code format,
+/// and dropped (with a warning) if not, since upstream rejects other shapes.
+fn apply_user_id_policy(body: Value, cloak: bool) -> Value {
+    if cloak {
+        inject_fake_user_id(body)
+    } else {
+        drop_invalid_user_id(body)
+    }
+}
+
+/// Remove a client-supplied `metadata.user_id` that isn't in Claude Code
+/// format, along with `metadata` if nothing else is left in it.
+fn drop_invalid_user_id(mut body: Value) -> Value {
+    let Some(obj) = body.as_object_mut() else {
+        return body;
+    };
+    let Some(Value::Object(metadata)) = obj.get_mut("metadata") else {
+        return body;
+    };
+    let valid = match metadata.get("user_id") {
+        None => return body,
+        Some(Value::String(id)) => is_valid_user_id(id),
+        Some(_) => false,
+    };
+    if !valid {
+        warn!("Dropping client metadata.user_id that is not in Claude Code format");
+        metadata.remove("user_id");
+        if metadata.is_empty() {
+            obj.remove("metadata");
+        }
+    }
+    body
+}
+
 /// Inject a fake user ID into request metadata if missing or invalid.
 fn inject_fake_user_id(mut body: Value) -> Value {
     let needs_injection = match body.get("metadata") {
@@ -162,8 +348,8 @@ fn inject_fake_user_id(mut body: Value) -> Value {
 
 /// Inject system message prefix into the request body (Claude Code identity).
 ///
-/// Cache_control is handled separately by ensure_cache_control().
-fn inject_system_message(mut body: Value) -> Value {
+/// Cache_control is handled separately by inject_cache_control().
+fn inject_system_message(mut body: Value, system_prefix: &str) -> Value {
     let obj = match body.as_object_mut() {
         Some(o) => o,
         None => return body,
@@ -171,7 +357,7 @@ fn inject_system_message(mut body: Value) -> Value {
 
     let prefix = json!({
         "type": "text",
-        "text": SYSTEM_PREFIX
+        "text": system_prefix
     });
 
     let new_system = match obj.get("system").cloned() {
@@ -221,6 +407,20 @@ fn generate_fake_user_id() -> String {
     format!("user_{}_account__session_{}", hex_part, uuid_part)
 }
 
+/// A `metadata.user_id` in Claude Code format standing for one end user of a
+/// key. The same key and user always give the same ID, and the user string
+/// itself never goes upstream.
+pub fn end_user_id(key_id: &str, user: &str) -> String {
+    let digest = Sha256::digest(format!("{key_id}\n{user}").as_bytes());
+    let hex_part: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    let session_bytes = Sha256::digest(digest)
+        .first_chunk::<16>()
+        .copied()
+        .unwrap_or_default();
+    let uuid_part = uuid::Builder::from_random_bytes(session_bytes).into_uuid();
+    format!("user_{}_account__session_{}", hex_part, uuid_part)
+}
+
 /// Check if a user ID matches Claude Code format.
 /// Format: user_[64-hex]_account__session_[uuid-v4]
 fn is_valid_user_id(user_id: &str) -> bool {
@@ -306,6 +506,110 @@ mod tests {
         assert!(result.get("thinking").is_some());
     }
 
+    fn request_with_all_features() -> Value {
+        json!({
+            "model": "claude-test",
+            "thinking": {"type": "adaptive"},
+            "output_config": {"effort": "high"},
+            "tools": [{"name": "get_weather", "input_schema": {"type": "object"}}],
+            "tool_choice": {"type": "auto"},
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "What is this?"},
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}},
+                    {"type": "tool_result", "tool_use_id": "t1", "content": [
+                        {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "BBBB"}}
+                    ]}
+                ]
+            }]
+        })
+    }
+
+    #[test]
+    fn test_key_service_tier_injected_into_prepared_body() {
+        let mut options = PrepareOptions::new(false);
+        options.service_tier = Some(ServiceTier::StandardOnly);
+        let body = json!({ "model": "claude-test", "messages": [] });
+        let prepared = prepare_anthropic_request(body, &options).unwrap();
+        assert_eq!(prepared.body["service_tier"], "standard_only");
+        assert_eq!(prepared_service_tier(&prepared.body), Some("standard_only"));
+    }
+
+    #[test]
+    fn test_request_service_tier_wins_and_unknown_falls_back() {
+        let body = json!({ "service_tier": "auto" });
+        let result = apply_service_tier(body, Some(ServiceTier::StandardOnly));
+        assert_eq!(result["service_tier"], "auto");
+
+        let body = json!({ "service_tier": "priority" });
+        let result = apply_service_tier(body, Some(ServiceTier::StandardOnly));
+        assert_eq!(result["service_tier"], "standard_only");
+
+        let result = apply_service_tier(json!({ "service_tier": "priority" }), None);
+        assert_eq!(prepared_service_tier(&result), None);
+    }
+
+    #[test]
+    fn test_capable_model_keeps_everything() {
+        let body = request_with_all_features();
+        let result = strip_unsupported_capabilities(body.clone(), &ModelCapabilities::default());
+        assert_eq!(result, body);
+    }
+
+    #[test]
+    fn test_strip_thinking_when_unsupported() {
+        let caps = ModelCapabilities {
+            thinking: false,
+            ..ModelCapabilities::default()
+        };
+        let result = strip_unsupported_capabilities(request_with_all_features(), &caps);
+        assert!(result.get("thinking").is_none());
+        assert!(result.get("output_config").is_none());
+        assert!(result.get("tools").is_some());
+    }
+
+    #[test]
+    fn test_strip_thinking_keeps_other_output_config() {
+        let caps = ModelCapabilities {
+            thinking: false,
+            ..ModelCapabilities::default()
+        };
+        let body = json!({
+            "model": "claude-test",
+            "output_config": {"effort": "low", "format": {"type": "json_schema"}}
+        });
+        let result = strip_unsupported_capabilities(body, &caps);
+        assert!(result["output_config"].get("effort").is_none());
+        assert_eq!(result["output_config"]["format"]["type"], "json_schema");
+    }
+
+    #[test]
+    fn test_strip_tools_when_unsupported() {
+        let caps = ModelCapabilities {
+            tools: false,
+            ..ModelCapabilities::default()
+        };
+        let result = strip_unsupported_capabilities(request_with_all_features(), &caps);
+        assert!(result.get("tools").is_none());
+        assert!(result.get("tool_choice").is_none());
+        assert!(result.get("thinking").is_some());
+    }
+
+    #[test]
+    fn test_strip_images_when_unsupported() {
+        let caps = ModelCapabilities {
+            vision: false,
+            ..ModelCapabilities::default()
+        };
+        let result = strip_unsupported_capabilities(request_with_all_features(), &caps);
+        let content = &result["messages"][0]["content"];
+        assert_eq!(content[0]["text"], "What is this?");
+        assert_eq!(content[1]["type"], "text");
+        assert_eq!(content[1]["text"], IMAGE_OMITTED);
+        assert_eq!(content[2]["content"][0]["text"], IMAGE_OMITTED);
+    }
+
     #[test]
     fn test_inject_fake_user_id() {
         let body = json!({"model": "claude-3"});
@@ -314,20 +618,114 @@ mod tests {
         assert!(user_id.starts_with("user_"));
     }
 
+    const CLIENT_USER_ID: &str = "user_0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef_account__session_12345678-1234-1234-1234-123456789012";
+
+    #[test]
+    fn test_valid_client_user_id_passes_through() {
+        let body = json!({"model": "claude-3", "metadata": {"user_id": CLIENT_USER_ID}});
+        let result = prepare_anthropic_request(body, &PrepareOptions::new(false))
+            .unwrap()
+            .body;
+        assert_eq!(result["metadata"]["user_id"], CLIENT_USER_ID);
+    }
+
+    #[test]
+    fn test_invalid_client_user_id_dropped() {
+        let body = json!({"model": "claude-3", "metadata": {"user_id": "alice@example.com"}});
+        let result = prepare_anthropic_request(body, &PrepareOptions::new(false))
+            .unwrap()
+            .body;
+        assert_eq!(result.get("metadata"), None);
+
+        let body = json!({"metadata": {"user_id": 42, "other": "kept"}});
+        let result = apply_user_id_policy(body, false);
+        assert_eq!(result["metadata"], json!({"other": "kept"}));
+
+        let body = json!({"model": "claude-3"});
+        assert_eq!(apply_user_id_policy(body.clone(), false), body);
+    }
+
+    #[test]
+    fn test_end_user_id_is_stable_and_valid() {
+        let id = end_user_id("key-1", "alice@example.com");
+        assert!(is_valid_user_id(&id), "{id}");
+        assert!(!id.contains("alice"));
+        assert_eq!(id, end_user_id("key-1", "alice@example.com"));
+        assert_ne!(id, end_user_id("key-1", "bob@example.com"));
+        assert_ne!(id, end_user_id("key-2", "alice@example.com"));
+
+        // Passes the non-cloaking policy untouched
+        let body = json!({"model": "claude-3", "metadata": {"user_id": id}});
+        let result = prepare_anthropic_request(body, &PrepareOptions::new(false))
+            .unwrap()
+            .body;
+        assert_eq!(result["metadata"]["user_id"], id);
+    }
+
+    #[test]
+    fn test_cloak_replaces_invalid_client_user_id() {
+        let body = json!({"model": "claude-3", "metadata": {"user_id": "alice@example.com"}});
+        let result = prepare_anthropic_request(body, &PrepareOptions::new(true))
+            .unwrap()
+            .body;
+        let user_id = result["metadata"]["user_id"].as_str().unwrap();
+        assert_ne!(user_id, "alice@example.com");
+        assert!(is_valid_user_id(user_id));
+    }
+
+    #[test]
+    fn test_code_execution_passthrough() {
+        let result_block = json!({
+            "type": "bash_code_execution_tool_result",
+            "tool_use_id": "srvtoolu_1",
+            "content": {"type": "bash_code_execution_result", "stdout": "4\n", "return_code": 0}
+        });
+        let body = json!({
+            "model": "claude-3",
+            "container": "container_abc",
+            "tools": [{"type": "code_execution_20250825", "name": "code_execution"}],
+            "messages": [
+                {"role": "user", "content": "what is 2+2?"},
+                {"role": "assistant", "content": [
+                    {"type": "server_tool_use", "id": "srvtoolu_1", "name": "bash_code_execution", "input": {"command": "echo 4"}},
+                    result_block.clone()
+                ]}
+            ]
+        });
+        let result = prepare_anthropic_request(body, &PrepareOptions::new(true))
+            .unwrap()
+            .body;
+        assert_eq!(result["container"], "container_abc");
+        assert_eq!(result["tools"][0]["name"], "code_execution");
+        let blocks = result["messages"][1]["content"].as_array().unwrap();
+        assert_eq!(blocks[0]["name"], "bash_code_execution");
+        assert_eq!(blocks[1]["type"], result_block["type"]);
+        assert_eq!(blocks[1]["content"], result_block["content"]);
+    }
+
     #[test]
     fn test_inject_system_message() {
         let body = json!({"model": "claude-3"});
-        let result = inject_system_message(body);
+        let result = inject_system_message(body, SYSTEM_PREFIX);
         let system = result["system"].as_array().unwrap();
         assert_eq!(system[0]["text"], SYSTEM_PREFIX);
     }
 
+    #[test]
+    fn test_inject_system_message_override() {
+        let body = json!({"model": "claude-3", "system": "Be brief."});
+        let result = inject_system_message(body, "You are a support bot.");
+        let system = result["system"].as_array().unwrap();
+        assert_eq!(system[0]["text"], "You are a support bot.");
+        assert_eq!(system[1]["text"], "Be brief.");
+    }
+
     #[test]
     fn test_sanitize_system_replaces_opencode() {
         let body = json!({
             "system": "You are OpenCode, an AI assistant. Use opencode tools."
         });
-        let result = inject_system_message(body);
+        let result = inject_system_message(body, SYSTEM_PREFIX);
         let system = result["system"].as_array().unwrap();
         // Second element is the user-provided system prompt (first is prefix)
         let text = system[1]["text"].as_str().unwrap();
@@ -345,7 +743,7 @@ mod tests {
                 {"type": "text", "text": "Use opencode for help"}
             ]
         });
-        let result = inject_system_message(body);
+        let result = inject_system_message(body, SYSTEM_PREFIX);
         let system = result["system"].as_array().unwrap();
         // Index 0 is prefix, 1 and 2 are user-provided
         assert!(!system[1]["text"].as_str().unwrap().contains("OpenCode"));
@@ -357,7 +755,7 @@ mod tests {
         let body = json!({
             "system": "Here is some useful information about the environment you are running in:\n<env>x</env>"
         });
-        let result = inject_system_message(body);
+        let result = inject_system_message(body, SYSTEM_PREFIX);
         let joined: String = result["system"]
             .as_array()
             .unwrap()
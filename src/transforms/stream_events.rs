@@ -0,0 +1,302 @@
+//! Per-event rewrites of native Anthropic SSE streams.
+//!
+//! The native stream parses each upstream event once and hands it through a
+//! chain of [`NativeEventTransform`]s before re-emitting it, so a new rewrite
+//! only has to implement the trait rather than touch the buffering loop.
+//! Tool-name restoration always runs; tool input redaction runs when
+//! `CLAUDE_PROXY_REDACT_TOOL_INPUT_KEYS` is set.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::{Value, from_str, json, to_string};
+use tracing::warn;
+
+use crate::transforms::tool_aliases::ToolNameMap;
+
+/// Replacement for redacted tool input values.
+pub const REDACTED: &str = "<redacted>";
+
+/// One rewrite step over the events of a native stream. Returns what to send
+/// in place of `event`: normally just `event` (changed or not), nothing to
+/// hold it back, or several events to release held ones.
+pub trait NativeEventTransform: Send {
+    fn apply(&mut self, event: Value) -> Vec<Value>;
+}
+
+/// Run `event` through `transforms` in order.
+pub fn apply_all(transforms: &mut [Box<dyn NativeEventTransform>], event: Value) -> Vec<Value> {
+    let mut events = vec![event];
+    for transform in transforms.iter_mut() {
+        events = events
+            .into_iter()
+            .flat_map(|event| transform.apply(event))
+            .collect();
+    }
+    events
+}
+
+/// The transforms for a native stream: tool names back to the client's,
+/// then redaction of `redact_keys` in tool inputs if any are configured.
+pub fn native_event_transforms(
+    tool_name_map: ToolNameMap,
+    redact_keys: &Arc<[String]>,
+) -> Vec<Box<dyn NativeEventTransform>> {
+    let mut transforms: Vec<Box<dyn NativeEventTransform>> =
+        vec![Box::new(RestoreToolNames(tool_name_map))];
+    if !redact_keys.is_empty() {
+        transforms.push(Box::new(RedactToolInput::new(redact_keys.clone())));
+    }
+    transforms
+}
+
+fn event_type(event: &Value) -> Option<&str> {
+    event.get("type").and_then(Value::as_str)
+}
+
+fn block_index(event: &Value) -> Option<u64> {
+    event.get("index").and_then(Value::as_u64)
+}
+
+/// Renames `tool_use` blocks back to the names the client sent.
+pub struct RestoreToolNames(pub ToolNameMap);
+
+impl NativeEventTransform for RestoreToolNames {
+    fn apply(&mut self, mut event: Value) -> Vec<Value> {
+        if event_type(&event) == Some("content_block_start")
+            && let Some(content_block) = event.get_mut("content_block")
+            && content_block.get("type").and_then(Value::as_str) == Some("tool_use")
+            && let Some(name) = content_block.get("name").and_then(Value::as_str)
+        {
+            let client_name = self.0.restore(name);
+            tracing::info!(tool = %client_name, "tool_use");
+            if let Some(obj) = content_block.as_object_mut() {
+                obj.insert("name".to_string(), Value::String(client_name));
+            }
+        }
+        vec![event]
+    }
+}
+
+/// Replaces the values of configured keys, at any depth, in `tool_use`
+/// inputs. A key can be split across `input_json_delta` fragments, so a
+/// tool's fragments are held back and released as one redacted delta when
+/// its block stops. Text and thinking still stream as they arrive.
+pub struct RedactToolInput {
+    keys: Arc<[String]>,
+    /// Accumulated `partial_json` of open tool_use blocks, by block index
+    pending: HashMap<u64, String>,
+}
+
+impl RedactToolInput {
+    pub fn new(keys: Arc<[String]>) -> Self {
+        Self {
+            keys,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl NativeEventTransform for RedactToolInput {
+    fn apply(&mut self, mut event: Value) -> Vec<Value> {
+        match event_type(&event) {
+            Some("content_block_start") => {
+                if let Some(index) = block_index(&event)
+                    && let Some(block) = event.get_mut("content_block")
+                    && block.get("type").and_then(Value::as_str) == Some("tool_use")
+                {
+                    if let Some(input) = block.get_mut("input") {
+                        redact_keys(input, &self.keys);
+                    }
+                    self.pending.insert(index, String::new());
+                }
+                vec![event]
+            }
+            Some("content_block_delta") => {
+                if let Some(fragment) = event.pointer("/delta/partial_json").and_then(Value::as_str)
+                    && let Some(held) = block_index(&event).and_then(|i| self.pending.get_mut(&i))
+                {
+                    held.push_str(fragment);
+                    return Vec::new();
+                }
+                vec![event]
+            }
+            Some("content_block_stop") => {
+                let Some(index) = block_index(&event) else {
+                    return vec![event];
+                };
+                let Some(raw) = self.pending.remove(&index) else {
+                    return vec![event];
+                };
+                if raw.is_empty() {
+                    return vec![event];
+                }
+                // Unparseable input (e.g. cut off by max_tokens) can't be
+                // redacted, so it is dropped rather than sent as-is
+                let Ok(mut input) = from_str::<Value>(&raw) else {
+                    warn!(
+                        "Dropping unparseable tool input of block {index} instead of redacting it"
+                    );
+                    return vec![event];
+                };
+                redact_keys(&mut input, &self.keys);
+                let partial_json = to_string(&input).unwrap_or_default();
+                let delta = json!({
+                    "type": "content_block_delta",
+                    "index": index,
+                    "delta": { "type": "input_json_delta", "partial_json": partial_json }
+                });
+                vec![delta, event]
+            }
+            _ => vec![event],
+        }
+    }
+}
+
+/// Replace the value of every object entry named in `keys`, at any depth.
+pub fn redact_keys(value: &mut Value, keys: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                if keys.iter().any(|k| k == key) {
+                    *child = Value::String(REDACTED.to_string());
+                } else {
+                    redact_keys(child, keys);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact_keys(item, keys);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Redact `keys` in the `tool_use` inputs of a non-streaming response.
+pub fn redact_response_tool_inputs(body: &mut Value, keys: &[String]) {
+    if keys.is_empty() {
+        return;
+    }
+    if let Some(Value::Array(content)) = body.get_mut("content") {
+        for block in content.iter_mut() {
+            if block.get("type").and_then(Value::as_str) == Some("tool_use")
+                && let Some(input) = block.get_mut("input")
+            {
+                redact_keys(input, keys);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(names: &[&str]) -> Arc<[String]> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn delta(index: u64, fragment: &str) -> Value {
+        json!({
+            "type": "content_block_delta",
+            "index": index,
+            "delta": { "type": "input_json_delta", "partial_json": fragment }
+        })
+    }
+
+    fn stop(index: u64) -> Value {
+        json!({ "type": "content_block_stop", "index": index })
+    }
+
+    #[test]
+    fn test_redact_keys_at_any_depth() {
+        let mut input = json!({
+            "url": "https://example.com",
+            "headers": { "api_key": "sk-live-1", "accept": "json" },
+            "retries": [{ "api_key": "sk-live-2" }]
+        });
+        redact_keys(&mut input, &keys(&["api_key"]));
+        assert_eq!(
+            input,
+            json!({
+                "url": "https://example.com",
+                "headers": { "api_key": REDACTED, "accept": "json" },
+                "retries": [{ "api_key": REDACTED }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_tool_input_held_until_block_stops() {
+        let mut transforms = native_event_transforms(ToolNameMap::default(), &keys(&["token"]));
+        let start = json!({
+            "type": "content_block_start",
+            "index": 1,
+            "content_block": { "type": "tool_use", "id": "toolu_1", "name": "fetch", "input": {} }
+        });
+        assert_eq!(apply_all(&mut transforms, start.clone()), vec![start]);
+
+        // The key itself is split across fragments
+        assert_eq!(
+            apply_all(&mut transforms, delta(1, "{\"to")),
+            Vec::<Value>::new()
+        );
+        assert_eq!(
+            apply_all(&mut transforms, delta(1, "ken\": \"secret\", \"n\": 2}")),
+            Vec::<Value>::new()
+        );
+
+        let released = apply_all(&mut transforms, stop(1));
+        assert_eq!(released.len(), 2);
+        let partial = released[0]["delta"]["partial_json"].as_str().unwrap();
+        assert_eq!(
+            from_str::<Value>(partial).unwrap(),
+            json!({ "token": REDACTED, "n": 2 })
+        );
+        assert_eq!(released[1], stop(1));
+    }
+
+    #[test]
+    fn test_text_and_unconfigured_streams_pass_through() {
+        let text = json!({
+            "type": "content_block_delta",
+            "index": 0,
+            "delta": { "type": "text_delta", "text": "token: abc" }
+        });
+        let mut redacting = native_event_transforms(ToolNameMap::default(), &keys(&["token"]));
+        assert_eq!(apply_all(&mut redacting, text.clone()), vec![text]);
+
+        // Without keys, tool input streams as it arrives
+        let mut plain = native_event_transforms(ToolNameMap::default(), &keys(&[]));
+        let fragment = delta(1, "{\"token\": \"sec");
+        assert_eq!(apply_all(&mut plain, fragment.clone()), vec![fragment]);
+    }
+
+    #[test]
+    fn test_unparseable_tool_input_is_dropped() {
+        let mut transform = RedactToolInput::new(keys(&["token"]));
+        let start = json!({
+            "type": "content_block_start",
+            "index": 0,
+            "content_block": { "type": "tool_use", "name": "fetch", "input": {} }
+        });
+        transform.apply(start);
+        transform.apply(delta(0, "{\"token\": \"sec"));
+        assert_eq!(transform.apply(stop(0)), vec![stop(0)]);
+    }
+
+    #[test]
+    fn test_non_streaming_response_redacted() {
+        let mut body = json!({
+            "content": [
+                { "type": "text", "text": "calling" },
+                { "type": "tool_use", "name": "fetch", "input": { "token": "secret" } }
+            ]
+        });
+        redact_response_tool_inputs(&mut body, &keys(&["token"]));
+        assert_eq!(body["content"][1]["input"]["token"], REDACTED);
+        assert_eq!(body["content"][0]["text"], "calling");
+    }
+}
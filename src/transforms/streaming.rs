@@ -2,29 +2,43 @@
 //!
 //! This module provides:
 //! - `stream_anthropic_to_openai_with_usage`: Convert Anthropic SSE to OpenAI SSE format with usage tracking
-//! - `stream_restore_native_tool_names_with_usage`: Restore native Anthropic SSE tool names with usage tracking
+//! - `stream_restore_native_tool_names_with_usage`: Restore native Anthropic SSE tool names with usage tracking,
+//!   applying the per-event rewrites in `stream_events`
 //!
 //! Both functions include keep-alive pings to prevent connection timeouts
-//! during long-running requests (e.g., extended thinking).
+//! during long-running requests (e.g., extended thinking). An upstream
+//! failure mid-stream ends the stream with a structured SSE error event in
+//! the client's format rather than a truncated body. The OpenAI stream also
+//! gives up with an error event if upstream accepts the request but sends no
+//! event other than pings within the first-event timeout.
 
 use async_stream::stream;
 use bytes::Bytes;
 use futures_util::{Stream, StreamExt};
 use serde::Deserialize;
 use serde_json::{Value, from_str, json, to_string};
+use std::fmt::Display;
+use std::future::pending;
 use std::io::Error as IoError;
 use std::pin::pin;
 use std::str::from_utf8;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::{select, time::interval};
+use tokio::{
+    select,
+    time::{interval, sleep},
+};
 use tracing::warn;
 
 use llm_relay::Usage;
 use llm_relay::convert::tool_names::strip_mcp_prefix;
 
 use crate::AppState;
+use crate::auth::RequestTags;
 use crate::auth::usage::{add_usage, usage_from_json};
+use crate::telemetry;
+use crate::transforms::openai_compat::{completion_id, openai_usage};
+use crate::transforms::stream_events::{NativeEventTransform, apply_all, native_event_transforms};
 use crate::transforms::tool_aliases::ToolNameMap;
 
 /// Keep-alive interval for SSE streams (prevents proxy/load balancer timeouts).
@@ -33,6 +47,9 @@ const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
 /// SSE keep-alive comment (ignored by clients but keeps connection alive).
 const KEEP_ALIVE_COMMENT: &str = ": keep-alive\n\n";
 
+/// Longest incomplete SSE line kept while waiting for its newline.
+const MAX_SSE_LINE_BYTES: usize = 4 * 1024 * 1024;
+
 /// Map Anthropic stop reason to OpenAI finish reason.
 fn map_stop_reason(reason: &str) -> &str {
     match reason {
@@ -97,6 +114,24 @@ struct MessageInfo {
 /// Alias for usage data from streaming events.
 type StreamUsage = Usage;
 
+/// What an OpenAI stream carries besides the answer itself.
+#[derive(Debug, Clone, Copy)]
+pub struct OpenAiStreamOptions {
+    /// Final usage chunk before `[DONE]` (`stream_options.include_usage`)
+    pub include_usage: bool,
+    /// `reasoning_content` deltas for thinking (`include_reasoning`)
+    pub include_reasoning: bool,
+}
+
+impl Default for OpenAiStreamOptions {
+    fn default() -> Self {
+        Self {
+            include_usage: false,
+            include_reasoning: true,
+        }
+    }
+}
+
 // ============================================================================
 // Stream Transformations
 // ============================================================================
@@ -107,15 +142,72 @@ type StreamUsage = Usage;
 /// including stripping the mcp_ prefix from tool names.
 /// Records token usage to the client keys store after the stream ends.
 ///
+/// With `include_usage`, a final chunk carrying the accumulated usage is
+/// sent before `[DONE]`, as OpenAI does for `stream_options.include_usage`.
+/// Without `include_reasoning`, thinking deltas are dropped instead of being
+/// sent as `reasoning_content`.
+///
 /// Includes keep-alive pings every 15 seconds to prevent connection timeouts.
 pub fn stream_anthropic_to_openai_with_usage(
     body: impl Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
     model: String,
+    options: OpenAiStreamOptions,
     state: Arc<AppState>,
     key_id: String,
+    started_at: u64,
+    tags: RequestTags,
 ) -> impl Stream<Item = Result<Bytes, IoError>> + Send {
+    let usage_model = model.clone();
+    let max_duration = state.max_stream_duration;
+    let first_event_timeout = state.first_event_timeout;
+    stream_anthropic_to_openai(
+        body,
+        model,
+        options,
+        max_duration,
+        first_event_timeout,
+        move |usage| async move {
+            record_stream_usage(&state, &key_id, &usage_model, &usage, started_at, &tags).await;
+        },
+    )
+}
+
+/// The trailing `stream_options.include_usage` chunk: no choices, just usage.
+fn usage_chunk(id: &str, created: u64, model: &str, usage: &Usage) -> Value {
+    json!({
+        "id": id,
+        "object": "chat.completion.chunk",
+        "created": created,
+        "model": model,
+        "choices": [],
+        "usage": openai_usage(usage)
+    })
+}
+
+/// Core of [`stream_anthropic_to_openai_with_usage`]. `on_complete` receives
+/// the accumulated usage once the stream has ended, including after an
+/// upstream error or when `max_duration` cuts the stream short.
+///
+/// If no event other than an upstream `ping` arrives within
+/// `first_event_timeout` of the stream opening, it ends with an error event:
+/// our own keep-alives would otherwise hide a stalled upstream forever.
+fn stream_anthropic_to_openai<E, F, Fut>(
+    body: impl Stream<Item = Result<Bytes, E>> + Send + 'static,
+    model: String,
+    options: OpenAiStreamOptions,
+    max_duration: Option<Duration>,
+    first_event_timeout: Option<Duration>,
+    on_complete: F,
+) -> impl Stream<Item = Result<Bytes, IoError>> + Send
+where
+    E: Display + Send,
+    F: FnOnce(Usage) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
     stream! {
         let now = now_secs();
+        // One id for every chunk of this completion, as OpenAI does
+        let id = completion_id();
 
         let mut buffer = String::new();
         let mut current_tool_call_id: Option<String> = None;
@@ -125,31 +217,70 @@ pub fn stream_anthropic_to_openai_with_usage(
         let mut body = pin!(body);
         let mut keep_alive = interval(KEEP_ALIVE_INTERVAL);
         keep_alive.reset(); // Don't fire immediately
+        let mut deadline = pin!(stream_deadline(max_duration));
+        let mut first_event_deadline = pin!(stream_deadline(first_event_timeout));
+        let mut first_event_seen = false;
 
         loop {
             select! {
-                biased; // Prefer data over keep-alive when both ready
+                biased; // Deadline first, then prefer data over keep-alive
+
+                // Total stream duration cap reached
+                _ = &mut deadline => {
+                    warn!("Stream for {model} exceeded max duration {max_duration:?}, closing");
+                    let chunk = json!({
+                        "id": &id,
+                        "object": "chat.completion.chunk",
+                        "created": now,
+                        "model": &model,
+                        "choices": [{
+                            "index": 0,
+                            "delta": {},
+                            "finish_reason": "length"
+                        }]
+                    });
+                    yield Ok(Bytes::from(format!("data: {}\n\ndata: [DONE]\n\n", chunk)));
+                    break;
+                }
+
+                // Upstream accepted the request but has sent nothing real yet
+                _ = &mut first_event_deadline, if !first_event_seen => {
+                    warn!(
+                        "Stream for {model} sent no event within {first_event_timeout:?}, closing"
+                    );
+                    yield Ok(openai_error_event("Upstream sent no events before the first-event timeout"));
+                    break;
+                }
 
                 // Data chunk received
                 chunk_opt = body.next() => {
-                    let Some(chunk_result) = chunk_opt else {
-                        break; // Stream ended
-                    };
-
-                    let chunk = match chunk_result {
-                        Ok(c) => c,
-                        Err(e) => {
-                            yield Err(IoError::other(e));
-                            return;
+                    let ended = match chunk_opt {
+                        None => true,
+                        Some(Ok(chunk)) => {
+                            match from_utf8(&chunk) {
+                                Ok(text) => buffer.push_str(text),
+                                Err(_) => continue,
+                            }
+                            false
+                        }
+                        Some(Err(e)) => {
+                            warn!("Upstream stream error for {model}: {e}");
+                            yield Ok(openai_error_event(&format!("Upstream stream error: {e}")));
+                            break;
                         }
                     };
-
-                    let text = match from_utf8(&chunk) {
-                        Ok(t) => t,
-                        Err(_) => continue,
-                    };
-
-                    buffer.push_str(text);
+                    if ended {
+                        if buffer.trim().is_empty() {
+                            break;
+                        }
+                        // Upstream ended without a final newline; the last
+                        // line may still be a complete event.
+                        warn!(
+                            "Stream for {model} ended mid-line ({} bytes buffered)",
+                            buffer.len()
+                        );
+                        buffer.push('\n');
+                    }
 
                     while let Some((line, rest)) = buffer.split_once('\n') {
                         let line = line.trim().to_string();
@@ -165,8 +296,16 @@ pub fn stream_anthropic_to_openai_with_usage(
 
                         let event: StreamEvent = match from_str(data) {
                             Ok(e) => e,
-                            Err(_) => continue,
+                            Err(e) => {
+                                if ended {
+                                    warn!("Dropping truncated final event for {model}: {e}");
+                                }
+                                continue;
+                            }
                         };
+                        if event.event_type != "ping" {
+                            first_event_seen = true;
+                        }
 
                         // Capture usage from message_start event (input + cache tokens)
                         if event.event_type == "message_start"
@@ -192,7 +331,7 @@ pub fn stream_anthropic_to_openai_with_usage(
                                     let name = block.name.as_ref().map(|n| strip_mcp_prefix(n));
 
                                     let chunk = json!({
-                                        "id": format!("chatcmpl-{}", now),
+                                        "id": &id,
                                         "object": "chat.completion.chunk",
                                         "created": now,
                                         "model": &model,
@@ -220,9 +359,11 @@ pub fn stream_anthropic_to_openai_with_usage(
                             "content_block_delta" => {
                                 if let Some(delta) = &event.delta {
                                     // Handle thinking content
-                                    if let Some(thinking) = &delta.thinking {
+                                    if let Some(thinking) = &delta.thinking
+                                        && options.include_reasoning
+                                    {
                                         let chunk = json!({
-                                            "id": format!("chatcmpl-{}", now),
+                                            "id": &id,
                                             "object": "chat.completion.chunk",
                                             "created": now,
                                             "model": &model,
@@ -242,7 +383,7 @@ pub fn stream_anthropic_to_openai_with_usage(
                                     // Handle regular text content
                                     if let Some(text) = &delta.text {
                                         let chunk = json!({
-                                            "id": format!("chatcmpl-{}", now),
+                                            "id": &id,
                                             "object": "chat.completion.chunk",
                                             "created": now,
                                             "model": &model,
@@ -262,7 +403,7 @@ pub fn stream_anthropic_to_openai_with_usage(
                                     // Handle tool call arguments
                                     if let Some(partial_json) = &delta.partial_json {
                                         let chunk = json!({
-                                            "id": format!("chatcmpl-{}", now),
+                                            "id": &id,
                                             "object": "chat.completion.chunk",
                                             "created": now,
                                             "model": &model,
@@ -296,7 +437,7 @@ pub fn stream_anthropic_to_openai_with_usage(
                                     let finish_reason = map_stop_reason(stop_reason);
 
                                     let chunk = json!({
-                                        "id": format!("chatcmpl-{}", now),
+                                        "id": &id,
                                         "object": "chat.completion.chunk",
                                         "created": now,
                                         "model": &model,
@@ -312,11 +453,29 @@ pub fn stream_anthropic_to_openai_with_usage(
                                 }
                             }
                             "message_stop" => {
+                                if options.include_usage {
+                                    let chunk = usage_chunk(&id, now, &model, &usage_report);
+                                    yield Ok(Bytes::from(format!("data: {}\n\n", chunk)));
+                                }
                                 yield Ok(Bytes::from("data: [DONE]\n\n"));
                             }
                             _ => {}
                         }
                     }
+
+                    if ended {
+                        break;
+                    }
+                    // A line that never ends would otherwise grow the buffer
+                    // without bound.
+                    if buffer.len() > MAX_SSE_LINE_BYTES {
+                        warn!(
+                            "Stream for {model} sent {} bytes without a newline, closing",
+                            buffer.len()
+                        );
+                        yield Ok(openai_error_event("Upstream sent an oversized SSE line"));
+                        break;
+                    }
                 }
 
                 // Keep-alive timer fired
@@ -327,41 +486,119 @@ pub fn stream_anthropic_to_openai_with_usage(
         }
 
         // Record usage after stream ends (per-model; global is derived via aggregation)
-        let window_resets = state.usage_cache.snapshot().await.window_state();
-        if let Err(e) = state.client_keys.record_model_usage(&key_id, &model, &usage_report, &window_resets).await {
-            warn!("Failed to record streaming model usage for key {key_id}/{model}: {e}");
-        }
+        on_complete(usage_report).await;
+    }
+}
+
+/// Resolves once `max_duration` has elapsed; never resolves when unlimited.
+async fn stream_deadline(max_duration: Option<Duration>) {
+    match max_duration {
+        Some(d) => sleep(d).await,
+        None => pending().await,
     }
 }
 
+/// Record usage accumulated over a stream against a key.
+async fn record_stream_usage(
+    state: &AppState,
+    key_id: &str,
+    model: &str,
+    usage: &Usage,
+    started_at: u64,
+    tags: &RequestTags,
+) {
+    telemetry::record_usage(usage);
+    let window_resets = state.usage_cache.snapshot().await.window_state();
+    if let Err(e) = state
+        .client_keys
+        .record_model_usage(key_id, model, usage, &window_resets, started_at, tags)
+        .await
+    {
+        warn!("Failed to record streaming model usage for key {key_id}/{model}: {e}");
+    }
+}
+
+/// Terminal OpenAI-style SSE error event followed by `[DONE]`, so clients see
+/// a structured failure instead of a truncated stream.
+fn openai_error_event(message: &str) -> Bytes {
+    let event = json!({
+        "error": {
+            "message": message,
+            "type": "upstream_error",
+            "code": Value::Null
+        }
+    });
+    Bytes::from(format!("data: {event}\n\ndata: [DONE]\n\n"))
+}
+
+/// Anthropic-native SSE `error` event, the same shape Anthropic itself sends
+/// when a stream fails after it has started.
+fn anthropic_error_event(message: &str) -> Bytes {
+    let event = json!({
+        "type": "error",
+        "error": {
+            "type": "api_error",
+            "message": message
+        }
+    });
+    Bytes::from(format!("event: error\ndata: {event}\n\n"))
+}
+
 pub fn stream_restore_native_tool_names_with_usage(
     body: impl Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
     state: Arc<AppState>,
     key_id: String,
     model: String,
     tool_name_map: ToolNameMap,
+    started_at: u64,
+    tags: RequestTags,
 ) -> impl Stream<Item = Result<Bytes, IoError>> + Send {
-    stream_transform_native_tool_names_with_usage(body, state, key_id, model, tool_name_map)
+    let max_duration = state.max_stream_duration;
+    let transforms = native_event_transforms(tool_name_map, &state.redact_tool_input_keys);
+    stream_transform_native_events(body, transforms, max_duration, move |usage| async move {
+        record_stream_usage(&state, &key_id, &model, &usage, started_at, &tags).await;
+    })
 }
 
-fn stream_transform_native_tool_names_with_usage(
-    body: impl Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
-    state: Arc<AppState>,
-    key_id: String,
-    model: String,
-    tool_name_map: ToolNameMap,
-) -> impl Stream<Item = Result<Bytes, IoError>> + Send {
+/// Core of [`stream_restore_native_tool_names_with_usage`]. Each complete
+/// SSE event goes through `transforms` (see [`NativeEventTransform`]);
+/// `on_complete` receives the accumulated usage once the stream has ended.
+fn stream_transform_native_events<E, F, Fut>(
+    body: impl Stream<Item = Result<Bytes, E>> + Send + 'static,
+    mut transforms: Vec<Box<dyn NativeEventTransform>>,
+    max_duration: Option<Duration>,
+    on_complete: F,
+) -> impl Stream<Item = Result<Bytes, IoError>> + Send
+where
+    E: Display + Send,
+    F: FnOnce(Usage) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
     stream! {
         let mut body = pin!(body);
         let mut buffer = String::new();
+        // Lines of the event being received, up to its terminating blank line
+        let mut event_lines = String::new();
         let mut keep_alive = interval(KEEP_ALIVE_INTERVAL);
         keep_alive.reset();
         let mut usage_report = Usage::default();
+        let mut deadline = pin!(stream_deadline(max_duration));
 
         loop {
             select! {
                 biased;
 
+                _ = &mut deadline => {
+                    warn!("Native stream exceeded max duration {max_duration:?}, closing");
+                    event_lines.push_str(&buffer);
+                    if !event_lines.is_empty() {
+                        yield Ok(Bytes::from(std::mem::take(&mut event_lines)));
+                    }
+                    yield Ok(anthropic_error_event("Stream exceeded the proxy's maximum duration"));
+                    buffer.clear();
+                    break;
+                }
+
                 chunk_opt = body.next() => {
                     let Some(chunk_result) = chunk_opt else {
                         break;
@@ -370,8 +607,14 @@ fn stream_transform_native_tool_names_with_usage(
                     let chunk = match chunk_result {
                         Ok(c) => c,
                         Err(e) => {
-                            yield Err(IoError::other(e));
-                            return;
+                            warn!("Upstream stream error: {e}");
+                            event_lines.push_str(&buffer);
+                            if !event_lines.is_empty() {
+                                yield Ok(Bytes::from(std::mem::take(&mut event_lines)));
+                            }
+                            yield Ok(anthropic_error_event(&format!("Upstream stream error: {e}")));
+                            buffer.clear();
+                            break;
                         }
                     };
 
@@ -387,50 +630,17 @@ fn stream_transform_native_tool_names_with_usage(
 
                     let mut output = String::new();
                     while let Some((line, rest)) = buffer.split_once('\n') {
-                        let line_with_newline = format!("{line}\n");
-
-                        if let Some(data) = line.strip_prefix("data: ") {
-                            let data = data.trim();
-                            if let Ok(event) = from_str::<Value>(data) {
-                                if event.get("type").and_then(|t| t.as_str()) == Some("message_start")
-                                    && let Some(usage) = event
-                                        .get("message")
-                                        .and_then(|m| m.get("usage"))
-                                {
-                                    add_usage(&mut usage_report, &usage_from_json(usage));
-                                }
-
-                                if event.get("type").and_then(|t| t.as_str()) == Some("message_delta")
-                                    && let Some(usage) = event.get("usage")
-                                {
-                                    add_usage(&mut usage_report, &usage_from_json(usage));
-                                }
-                            }
-                        }
-
-                        if line.contains("content_block_start")
-                            && let Some(data) = line.strip_prefix("data: ").map(str::trim)
-                        {
-                            if let Ok(mut event) = from_str::<Value>(data) {
-                                if let Some(content_block) = event.get_mut("content_block")
-                                    && content_block.get("type").and_then(|t| t.as_str()) == Some("tool_use")
-                                    && let Some(name) = content_block.get("name").and_then(|n| n.as_str()).map(|s| s.to_string())
-                                    && let Some(obj) = content_block.as_object_mut()
-                                {
-                                    let client_name = tool_name_map.restore(&name);
-                                    tracing::info!(tool = %client_name, "tool_use");
-                                    obj.insert("name".to_string(), Value::String(client_name));
-                                }
-                                output.push_str("data: ");
-                                output.push_str(&to_string(&event).unwrap_or_else(|_| data.to_string()));
-                                output.push('\n');
-                            } else {
-                                output.push_str(&line_with_newline);
-                            }
+                        if line.trim_end_matches('\r').is_empty() {
+                            output.push_str(&transform_native_event(
+                                &event_lines,
+                                &mut transforms,
+                                &mut usage_report,
+                            ));
+                            event_lines.clear();
                         } else {
-                            output.push_str(&line_with_newline);
+                            event_lines.push_str(line);
+                            event_lines.push('\n');
                         }
-
                         buffer = rest.to_string();
                     }
 
@@ -445,20 +655,391 @@ fn stream_transform_native_tool_names_with_usage(
             }
         }
 
-        if !buffer.is_empty() {
-            yield Ok(Bytes::from(buffer));
+        event_lines.push_str(&buffer);
+        if !event_lines.is_empty() {
+            yield Ok(Bytes::from(event_lines));
         }
 
-        let window_resets = state.usage_cache.snapshot().await.window_state();
-        if let Err(e) = state.client_keys.record_model_usage(&key_id, &model, &usage_report, &window_resets).await {
-            warn!("Failed to record streaming model usage for key {key_id}/{model}: {e}");
+        on_complete(usage_report).await;
+    }
+}
+
+/// Re-emit one SSE event (its lines, without the blank terminator) after
+/// recording its usage and running it through `transforms`. Events the
+/// transforms leave untouched, and anything that isn't a JSON event, are
+/// passed through byte for byte.
+fn transform_native_event(
+    lines: &str,
+    transforms: &mut [Box<dyn NativeEventTransform>],
+    usage_report: &mut Usage,
+) -> String {
+    let verbatim = || format!("{lines}\n");
+    let mut data_lines = lines.lines().filter_map(|l| l.strip_prefix("data: "));
+    let (Some(data), None) = (data_lines.next(), data_lines.next()) else {
+        return verbatim();
+    };
+    let Ok(event) = from_str::<Value>(data.trim()) else {
+        return verbatim();
+    };
+
+    match event.get("type").and_then(|t| t.as_str()) {
+        Some("message_start") => {
+            if let Some(usage) = event.get("message").and_then(|m| m.get("usage")) {
+                add_usage(usage_report, &usage_from_json(usage));
+            }
+        }
+        Some("message_delta") => {
+            if let Some(usage) = event.get("usage") {
+                add_usage(usage_report, &usage_from_json(usage));
+            }
         }
+        _ => {}
     }
+
+    let original = event.clone();
+    let events = apply_all(transforms, event);
+    if events.len() == 1 && events.first() == Some(&original) {
+        return verbatim();
+    }
+
+    let named = lines.lines().any(|l| l.starts_with("event:"));
+    let mut output = String::new();
+    for event in events {
+        if named && let Some(name) = event.get("type").and_then(|t| t.as_str()) {
+            output.push_str(&format!("event: {name}\n"));
+        }
+        output.push_str(&format!(
+            "data: {}\n\n",
+            to_string(&event).unwrap_or_default()
+        ));
+    }
+    output
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures_util::stream;
+    use std::sync::Mutex;
+
+    type TestChunk = Result<Bytes, IoError>;
+
+    /// Run a stream to completion and concatenate its output.
+    async fn collect_output(output: impl Stream<Item = Result<Bytes, IoError>>) -> String {
+        let chunks: Vec<_> = output.collect().await;
+        chunks
+            .into_iter()
+            .map(|c| String::from_utf8(c.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    fn usage_sink() -> (
+        Arc<Mutex<Option<Usage>>>,
+        impl FnOnce(Usage) -> std::future::Ready<()> + Send + 'static,
+    ) {
+        let slot = Arc::new(Mutex::new(None));
+        let sink = slot.clone();
+        (slot, move |usage| {
+            *sink.lock().unwrap() = Some(usage);
+            std::future::ready(())
+        })
+    }
+
+    fn mid_stream_failure() -> Vec<TestChunk> {
+        vec![
+            Ok(Bytes::from(
+                "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12,\"output_tokens\":0}}}\n\n",
+            )),
+            Ok(Bytes::from(
+                "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hel\"}}\n\n",
+            )),
+            Err(IoError::other("connection reset")),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_emits_error_event_on_upstream_failure() {
+        let (usage, sink) = usage_sink();
+        let output = stream_anthropic_to_openai(
+            stream::iter(mid_stream_failure()),
+            "claude-sonnet-4-5".to_string(),
+            OpenAiStreamOptions::default(),
+            None,
+            None,
+            sink,
+        );
+        let text = collect_output(output).await;
+
+        assert!(text.contains("\"content\":\"Hel\""));
+        let error_line = text
+            .lines()
+            .find(|l| l.contains("\"error\""))
+            .expect("error event");
+        let event: Value = from_str(error_line.strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(event["error"]["type"], "upstream_error");
+        assert!(
+            event["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("connection reset")
+        );
+        assert!(text.ends_with("data: [DONE]\n\n"));
+        // Usage seen before the failure is still recorded
+        assert_eq!(usage.lock().unwrap().as_ref().unwrap().input_tokens, 12);
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_chunks_share_one_id() {
+        let (_usage, sink) = usage_sink();
+        let body = stream::iter(vec![
+            Ok::<_, IoError>(Bytes::from(
+                "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":3,\"output_tokens\":0}}}\n\n",
+            )),
+            Ok(Bytes::from(
+                "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+            )),
+            Ok(Bytes::from(
+                "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":1}}\n\n",
+            )),
+            Ok(Bytes::from("data: {\"type\":\"message_stop\"}\n\n")),
+        ]);
+        let output = stream_anthropic_to_openai(
+            body,
+            "claude-sonnet-4-5".to_string(),
+            OpenAiStreamOptions::default(),
+            None,
+            None,
+            sink,
+        );
+        let text = collect_output(output).await;
+
+        let ids: Vec<String> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter_map(|data| from_str::<Value>(data).ok())
+            .filter_map(|chunk| chunk["id"].as_str().map(str::to_string))
+            .collect();
+        assert!(ids.len() >= 2, "{text}");
+        assert!(ids.iter().all(|id| id == &ids[0]));
+        assert!(ids[0].starts_with("chatcmpl-"));
+        assert_ne!(ids[0], format!("chatcmpl-{}", now_secs()));
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_event_split_across_chunks() {
+        let (usage, sink) = usage_sink();
+        let event = "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n";
+        let (first, second) = event.split_at(40);
+        let body = stream::iter(vec![
+            Ok::<_, IoError>(Bytes::from(
+                "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":4,\"output_tokens\":0}}}\n\n",
+            )),
+            Ok(Bytes::from(first.to_string())),
+            Ok(Bytes::from(second.to_string())),
+            // Final event arrives without its trailing newline
+            Ok(Bytes::from(
+                "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":2}}",
+            )),
+        ]);
+        let output = stream_anthropic_to_openai(
+            body,
+            "claude-sonnet-4-5".to_string(),
+            OpenAiStreamOptions::default(),
+            None,
+            None,
+            sink,
+        );
+        let text = collect_output(output).await;
+
+        assert!(text.contains("\"content\":\"Hello\""), "{text}");
+        assert!(text.contains("\"finish_reason\":\"stop\""), "{text}");
+        assert_eq!(usage.lock().unwrap().as_ref().unwrap().output_tokens, 2);
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_truncated_final_line_is_dropped() {
+        let (_usage, sink) = usage_sink();
+        let body = stream::iter(vec![Ok::<_, IoError>(Bytes::from(
+            "data: {\"type\":\"content_block_delta\",\"delta\":{\"te",
+        ))]);
+        let output = stream_anthropic_to_openai(
+            body,
+            "claude-sonnet-4-5".to_string(),
+            OpenAiStreamOptions::default(),
+            None,
+            None,
+            sink,
+        );
+        assert_eq!(collect_output(output).await, "");
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_caps_unterminated_line() {
+        let (_usage, sink) = usage_sink();
+        let chunk = Bytes::from(vec![b'x'; MAX_SSE_LINE_BYTES / 2 + 1]);
+        let body = stream::iter((0..3).map(move |_| Ok::<_, IoError>(chunk.clone())))
+            .chain(stream::pending());
+        let output = stream_anthropic_to_openai(
+            body,
+            "claude-sonnet-4-5".to_string(),
+            OpenAiStreamOptions::default(),
+            None,
+            None,
+            sink,
+        );
+        let text = tokio::time::timeout(Duration::from_secs(5), collect_output(output))
+            .await
+            .expect("stream should close once the line cap is hit");
+        assert!(text.contains("oversized SSE line"));
+        assert!(text.ends_with("data: [DONE]\n\n"));
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_closes_at_max_duration() {
+        let (usage, sink) = usage_sink();
+        // Upstream sends message_start and then stalls forever
+        let body = stream::iter(vec![Ok::<_, IoError>(Bytes::from(
+            "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":7,\"output_tokens\":0}}}\n\n",
+        ))])
+        .chain(stream::pending());
+        let output = stream_anthropic_to_openai(
+            body,
+            "claude-sonnet-4-5".to_string(),
+            OpenAiStreamOptions::default(),
+            Some(Duration::from_millis(50)),
+            None,
+            sink,
+        );
+        let text = tokio::time::timeout(Duration::from_secs(5), collect_output(output))
+            .await
+            .expect("stream should close at the duration cap");
+
+        assert!(text.contains("\"finish_reason\":\"length\""));
+        assert!(text.ends_with("data: [DONE]\n\n"));
+        assert_eq!(usage.lock().unwrap().as_ref().unwrap().input_tokens, 7);
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_closes_when_only_pings_arrive() {
+        let (usage, sink) = usage_sink();
+        // Upstream accepts the request, then only ever sends pings
+        let ping = || Ok::<_, IoError>(Bytes::from("event: ping\ndata: {\"type\": \"ping\"}\n\n"));
+        let body = stream::iter(vec![ping(), ping()]).chain(stream::pending());
+        let output = stream_anthropic_to_openai(
+            body,
+            "claude-sonnet-4-5".to_string(),
+            OpenAiStreamOptions::default(),
+            None,
+            Some(Duration::from_millis(50)),
+            sink,
+        );
+        let text = tokio::time::timeout(Duration::from_secs(5), collect_output(output))
+            .await
+            .expect("stream should close at the first-event timeout");
+
+        assert!(text.contains("\"type\":\"upstream_error\""), "{text}");
+        assert!(text.contains("first-event timeout"));
+        assert!(text.ends_with("data: [DONE]\n\n"));
+        assert!(usage.lock().unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_first_event_disarms_watchdog() {
+        let (_usage, sink) = usage_sink();
+        // message_start arrives at once; the rest only after the timeout
+        let body = stream::iter(vec![Ok::<_, IoError>(Bytes::from(
+            "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":7,\"output_tokens\":0}}}\n\n",
+        ))])
+        .chain(stream::once(async {
+            sleep(Duration::from_millis(150)).await;
+            Ok(Bytes::from("data: {\"type\":\"message_stop\"}\n\n"))
+        }));
+        let output = stream_anthropic_to_openai(
+            body,
+            "claude-sonnet-4-5".to_string(),
+            OpenAiStreamOptions::default(),
+            None,
+            Some(Duration::from_millis(50)),
+            sink,
+        );
+        let text = tokio::time::timeout(Duration::from_secs(5), collect_output(output))
+            .await
+            .expect("stream should finish normally");
+
+        assert!(!text.contains("upstream_error"), "{text}");
+        assert!(text.ends_with("data: [DONE]\n\n"));
+    }
+
+    #[tokio::test]
+    async fn test_native_stream_emits_error_event_on_upstream_failure() {
+        let (usage, sink) = usage_sink();
+        let output = stream_transform_native_events(
+            stream::iter(mid_stream_failure()),
+            native_event_transforms(ToolNameMap::default(), &Arc::from([])),
+            None,
+            sink,
+        );
+        let text = collect_output(output).await;
+
+        assert!(text.contains("text_delta"));
+        assert!(text.contains("event: error\n"));
+        let error_line = text
+            .lines()
+            .rev()
+            .find(|l| l.starts_with("data: "))
+            .unwrap();
+        let event: Value = from_str(error_line.strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(event["type"], "error");
+        assert_eq!(event["error"]["type"], "api_error");
+        assert_eq!(usage.lock().unwrap().as_ref().unwrap().input_tokens, 12);
+    }
+
+    #[tokio::test]
+    async fn test_native_stream_redacts_tool_input_split_across_chunks() {
+        let (usage, sink) = usage_sink();
+        let redact: Arc<[String]> = Arc::from(["password".to_string()]);
+        let chunks: Vec<TestChunk> = vec![
+            Ok(Bytes::from(
+                "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":7,\"output_tokens\":0}}}\n\n",
+            )),
+            Ok(Bytes::from(
+                "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"mcp_Login\",\"input\":{}}}\n\nevent: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"user\\\": \\\"bob\\\", \\\"pass\"}}\n\n",
+            )),
+            Ok(Bytes::from(
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"word\\\": \\\"hunter2\\\"}\"}}\n\nevent: content_block_stop\n",
+            )),
+            Ok(Bytes::from(
+                "data: {\"type\":\"content_block_stop\",\"index\":0}\n\nevent: ping\ndata: {\"type\": \"ping\"}\n\n",
+            )),
+        ];
+        let output = stream_transform_native_events(
+            stream::iter(chunks),
+            native_event_transforms(ToolNameMap::default(), &redact),
+            None,
+            sink,
+        );
+        let text = collect_output(output).await;
+
+        assert!(!text.contains("hunter2"), "{text}");
+        assert!(text.contains("\"name\":\"Login\""), "{text}");
+        // Untouched events pass through byte for byte
+        assert!(text.contains("event: ping\ndata: {\"type\": \"ping\"}\n\n"));
+
+        let events: Vec<Value> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .map(|d| from_str(d).unwrap())
+            .collect();
+        let deltas: Vec<&Value> = events
+            .iter()
+            .filter(|e| e["type"] == "content_block_delta")
+            .collect();
+        assert_eq!(deltas.len(), 1);
+        let input: Value = from_str(deltas[0]["delta"]["partial_json"].as_str().unwrap()).unwrap();
+        assert_eq!(input, json!({ "user": "bob", "password": "<redacted>" }));
+        assert!(text.contains("event: content_block_delta\ndata: "));
+        assert_eq!(usage.lock().unwrap().as_ref().unwrap().input_tokens, 7);
+    }
 
     #[test]
     fn test_map_stop_reason() {
@@ -572,6 +1153,116 @@ mod tests {
         assert_eq!(usage_report.cache_creation_input_tokens, Some(20));
     }
 
+    #[test]
+    fn test_usage_chunk_reports_cache_tokens_like_non_streaming() {
+        let mut usage = Usage::default();
+        add_usage(
+            &mut usage,
+            &usage_from_json(&json!({
+                "input_tokens": 150,
+                "cache_creation_input_tokens": 20,
+                "cache_read_input_tokens": 80
+            })),
+        );
+        add_usage(
+            &mut usage,
+            &usage_from_json(&json!({ "output_tokens": 75 })),
+        );
+
+        let chunk = usage_chunk("chatcmpl-1", 0, "claude-sonnet-4-5", &usage);
+        assert_eq!(chunk["choices"], json!([]));
+        assert_eq!(chunk["usage"]["prompt_tokens"], 150);
+        assert_eq!(chunk["usage"]["completion_tokens"], 75);
+        assert_eq!(chunk["usage"]["cache_creation_input_tokens"], 20);
+        assert_eq!(chunk["usage"]["cache_read_input_tokens"], 80);
+        assert_eq!(chunk["usage"]["prompt_tokens_details"]["cached_tokens"], 80);
+        assert_eq!(chunk["usage"], openai_usage(&usage));
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_sends_usage_chunk_before_done() {
+        let (_usage, sink) = usage_sink();
+        let body = stream::iter(vec![
+            Ok::<_, IoError>(Bytes::from(
+                "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":3,\"output_tokens\":0,\"cache_read_input_tokens\":9}}}\n\n",
+            )),
+            Ok(Bytes::from(
+                "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":1}}\n\n",
+            )),
+            Ok(Bytes::from("data: {\"type\":\"message_stop\"}\n\n")),
+        ]);
+        let output = stream_anthropic_to_openai(
+            body,
+            "claude-sonnet-4-5".to_string(),
+            OpenAiStreamOptions {
+                include_usage: true,
+                ..OpenAiStreamOptions::default()
+            },
+            None,
+            None,
+            sink,
+        );
+        let text = collect_output(output).await;
+
+        let events: Vec<&str> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .collect();
+        assert_eq!(events.last(), Some(&"[DONE]"));
+        let usage: Value = from_str(events[events.len() - 2]).unwrap();
+        assert_eq!(usage["usage"]["cache_read_input_tokens"], 9);
+        assert_eq!(usage["usage"]["completion_tokens"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_omits_reasoning_when_not_wanted() {
+        let events = || {
+            stream::iter(vec![
+                Ok::<_, IoError>(Bytes::from(
+                    "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"thinking_delta\",\"thinking\":\"Let me think\"}}\n\n",
+                )),
+                Ok(Bytes::from(
+                    "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+                )),
+                Ok(Bytes::from("data: {\"type\":\"message_stop\"}\n\n")),
+            ])
+        };
+        let model = || "claude-sonnet-4-5".to_string();
+
+        let (_usage, sink) = usage_sink();
+        let output = stream_anthropic_to_openai(
+            events(),
+            model(),
+            OpenAiStreamOptions::default(),
+            None,
+            None,
+            sink,
+        );
+        assert!(
+            collect_output(output)
+                .await
+                .contains("\"reasoning_content\":\"Let me think\"")
+        );
+
+        let (_usage, sink) = usage_sink();
+        let hidden = OpenAiStreamOptions {
+            include_reasoning: false,
+            ..OpenAiStreamOptions::default()
+        };
+        let text = collect_output(stream_anthropic_to_openai(
+            events(),
+            model(),
+            hidden,
+            None,
+            None,
+            sink,
+        ))
+        .await;
+        assert!(!text.contains("reasoning_content"));
+        assert!(!text.contains("Let me think"));
+        assert!(text.contains("\"content\":\"Hi\""));
+    }
+
     #[test]
     fn test_mcp_prefix_stripping_in_tool_name() {
         let data = r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_abc","name":"mcp_read_file"}}"#;
@@ -0,0 +1,290 @@
+//! mcp_ prefixing that leaves Anthropic built-in tools alone.
+//!
+//! Cloaked requests get their tool names prefixed with `mcp_` (by
+//! llm-relay's `transform_request_tool_names`). Anthropic's built-in tools —
+//! code execution, bash, the text editor, web search and friends — are
+//! matched by name upstream, so a prefixed `mcp_code_execution` is no longer
+//! the built-in tool and the request fails. Tools declared with a built-in
+//! `type` whose name starts with one of [`BUILTIN_TOOL_PREFIXES`] keep their
+//! name, in the `tools` list, in `tool_choice` and in `tool_use` /
+//! `server_tool_use` blocks of the message history.
+//!
+//! Responses undo the prefix, so two tools that end up with the same name
+//! after it (say a built-in `bash` next to a custom `bash`, which becomes
+//! `mcp_bash`) would have their calls routed to the wrong one. Such a tool
+//! set is rejected instead.
+
+use std::collections::{HashMap, HashSet};
+
+use llm_relay::convert::tool_names::{strip_mcp_prefix, transform_request_tool_names};
+use serde_json::Value;
+
+use crate::error::ProxyError;
+
+/// Name prefixes of Anthropic built-in tools (client and server tools).
+/// `bash` also covers `bash_code_execution`, `text_editor` covers
+/// `text_editor_code_execution`.
+pub const BUILTIN_TOOL_PREFIXES: &[&str] = &[
+    "bash",
+    "code_execution",
+    "computer",
+    "memory",
+    "str_replace_based_edit_tool",
+    "str_replace_editor",
+    "text_editor",
+    "tool_search",
+    "web_fetch",
+    "web_search",
+];
+
+const MCP_PREFIX: &str = "mcp_";
+
+fn has_builtin_name(name: &str) -> bool {
+    BUILTIN_TOOL_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+/// Whether a tool definition is an Anthropic built-in: it carries a versioned
+/// `type` (e.g. `code_execution_20250825`) and a reserved name. A custom
+/// tool that happens to be called `bash` is still prefixed.
+fn is_builtin_tool(tool: &Value) -> bool {
+    let typed = tool
+        .get("type")
+        .and_then(Value::as_str)
+        .is_some_and(|t| !t.is_empty() && t != "custom");
+    typed
+        && tool
+            .get("name")
+            .and_then(Value::as_str)
+            .is_some_and(has_builtin_name)
+}
+
+/// Undo the prefix on `value["name"]` if the unprefixed name is in `names`.
+fn restore_name(value: &mut Value, names: &HashSet<String>) {
+    let Some(original) = value
+        .get("name")
+        .and_then(Value::as_str)
+        .and_then(|name| name.strip_prefix(MCP_PREFIX))
+        .filter(|name| names.contains(*name))
+        .map(str::to_string)
+    else {
+        return;
+    };
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("name".to_string(), Value::String(original));
+    }
+}
+
+fn tool_names(body: &Value) -> Vec<String> {
+    body.get("tools")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|tool| {
+            tool.get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect()
+}
+
+/// Fail if two tools map to the same name once responses undo the prefix.
+/// `original` and `prefixed` are the tool names before and after prefixing,
+/// in `tools` order.
+fn check_collisions(
+    original: &[String],
+    prefixed: &[String],
+    builtin: &HashSet<String>,
+) -> Result<(), ProxyError> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    for (client, upstream) in original.iter().zip(prefixed) {
+        let restored = if builtin.contains(upstream) {
+            upstream.clone()
+        } else {
+            strip_mcp_prefix(upstream)
+        };
+        if let Some(other) = seen.insert(restored.clone(), client) {
+            return Err(ProxyError::ToolNameCollision(format!(
+                "tools `{other}` and `{client}` would both be called `{restored}` in responses; \
+                 rename one of them"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Prefix tool names with `mcp_`, except for Anthropic built-in tools.
+/// Rejects tool sets whose names collide after prefixing.
+pub fn prefix_tool_names(body: &mut Value) -> Result<(), ProxyError> {
+    let original = tool_names(body);
+    let mut builtin: HashSet<String> = body
+        .get("tools")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|tool| is_builtin_tool(tool))
+        .filter_map(|tool| tool.get("name").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect();
+
+    transform_request_tool_names(body);
+
+    if let Some(Value::Array(tools)) = body.get_mut("tools") {
+        for tool in tools.iter_mut() {
+            restore_name(tool, &builtin);
+        }
+    }
+    check_collisions(&original, &tool_names(body), &builtin)?;
+    if let Some(tool_choice) = body.get_mut("tool_choice") {
+        restore_name(tool_choice, &builtin);
+    }
+
+    let Some(Value::Array(messages)) = body.get_mut("messages") else {
+        return Ok(());
+    };
+    for message in messages.iter_mut() {
+        let Some(Value::Array(content)) = message.get_mut("content") else {
+            continue;
+        };
+        for block in content.iter_mut() {
+            match block.get("type").and_then(Value::as_str) {
+                Some("tool_use") => restore_name(block, &builtin),
+                // Server tools run upstream and are never client tools
+                Some("server_tool_use") => {
+                    if let Some(name) = block
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .and_then(|name| name.strip_prefix(MCP_PREFIX))
+                        .filter(|name| has_builtin_name(name))
+                    {
+                        builtin.insert(name.to_string());
+                    }
+                    restore_name(block, &builtin);
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request() -> Value {
+        json!({
+            "tools": [
+                {"type": "code_execution_20250825", "name": "code_execution"},
+                {"type": "bash_20250124", "name": "bash"},
+                {"type": "web_search_20250305", "name": "web_search", "max_uses": 3},
+                {"name": "get_weather", "input_schema": {"type": "object"}}
+            ],
+            "messages": [
+                {"role": "user", "content": "run it"},
+                {"role": "assistant", "content": [
+                    {"type": "server_tool_use", "id": "srvtoolu_1", "name": "bash_code_execution", "input": {}},
+                    {"type": "bash_code_execution_tool_result", "tool_use_id": "srvtoolu_1", "content": {}},
+                    {"type": "tool_use", "id": "toolu_1", "name": "bash", "input": {}},
+                    {"type": "tool_use", "id": "toolu_2", "name": "get_weather", "input": {}}
+                ]}
+            ]
+        })
+    }
+
+    fn names(body: &Value) -> Vec<&str> {
+        body["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin_tools_not_prefixed() {
+        let mut body = request();
+        prefix_tool_names(&mut body).unwrap();
+        let names = names(&body);
+        assert_eq!(&names[..3], ["code_execution", "bash", "web_search"]);
+        assert!(names[3].starts_with(MCP_PREFIX), "{names:?}");
+
+        let blocks = body["messages"][1]["content"].as_array().unwrap();
+        assert_eq!(blocks[0]["name"], "bash_code_execution");
+        assert_eq!(blocks[2]["name"], "bash");
+        assert!(
+            blocks[3]["name"].as_str().unwrap().starts_with(MCP_PREFIX),
+            "{blocks:?}"
+        );
+    }
+
+    #[test]
+    fn test_builtin_tool_choice_not_prefixed() {
+        let mut body = request();
+        body["tool_choice"] = json!({"type": "tool", "name": "code_execution"});
+        prefix_tool_names(&mut body).unwrap();
+        assert_eq!(body["tool_choice"]["name"], "code_execution");
+    }
+
+    #[test]
+    fn test_custom_tool_with_builtin_name_is_prefixed() {
+        let mut body = json!({
+            "tools": [{"name": "bash", "input_schema": {"type": "object"}}],
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        prefix_tool_names(&mut body).unwrap();
+        assert_eq!(names(&body), ["mcp_bash"]);
+    }
+
+    #[test]
+    fn test_colliding_tool_names_rejected() {
+        // A built-in `bash` keeps its name; the custom one is stripped back
+        // to `bash` in responses as well
+        let mut body = json!({
+            "tools": [
+                {"type": "bash_20250124", "name": "bash"},
+                {"name": "bash", "input_schema": {"type": "object"}}
+            ],
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        let error = prefix_tool_names(&mut body).unwrap_err();
+        assert!(
+            matches!(&error, ProxyError::ToolNameCollision(message) if message.contains("`bash`")),
+            "{error}"
+        );
+
+        let mut body = json!({
+            "tools": [
+                {"name": "get_weather", "input_schema": {"type": "object"}},
+                {"name": "get_weather", "input_schema": {"type": "object"}}
+            ],
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        assert!(
+            prefix_tool_names(&mut body)
+                .is_err_and(|e| matches!(e, ProxyError::ToolNameCollision(_)))
+        );
+    }
+
+    #[test]
+    fn test_distinct_tool_names_pass() {
+        let mut body = request();
+        let before = names(&request()).len();
+        prefix_tool_names(&mut body).unwrap();
+        assert_eq!(names(&body).len(), before);
+    }
+
+    #[test]
+    fn test_is_builtin_tool() {
+        assert!(is_builtin_tool(
+            &json!({"type": "text_editor_20250728", "name": "str_replace_based_edit_tool"})
+        ));
+        assert!(!is_builtin_tool(&json!({"type": "custom", "name": "bash"})));
+        assert!(!is_builtin_tool(
+            &json!({"type": "mcp_toolset_20251120", "name": "calendar"})
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+//! Anthropic endpoint URLs, built from a configurable base.
+//!
+//! `CLAUDE_PROXY_ANTHROPIC_BASE_URL` points every upstream call (messages,
+//! count_tokens, batches, usage, profile) at a self-hosted
+//! Anthropic-compatible gateway, a regional endpoint or a test double. The
+//! base may carry a path prefix (`https://gateway.internal/anthropic`); it is
+//! checked at startup so a typo fails loudly.
+
+use url::Url;
+
+use crate::constants::{
+    ANTHROPIC_BATCHES_PATH, ANTHROPIC_COUNT_TOKENS_PATH, ANTHROPIC_MESSAGES_PATH,
+    ANTHROPIC_PROFILE_PATH, ANTHROPIC_USAGE_PATH,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamUrls {
+    pub messages: String,
+    pub count_tokens: String,
+    /// Batch status and results are under `{batches}/{id}[/results]`
+    pub batches: String,
+    pub usage: String,
+    pub profile: String,
+}
+
+impl UpstreamUrls {
+    /// Endpoint URLs under `base`, which must be an absolute http(s) URL with
+    /// no query or fragment. A trailing slash is ignored.
+    pub fn new(base: &str) -> Result<Self, String> {
+        let url = Url::parse(base.trim()).map_err(|e| format!("invalid base URL: {e}"))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!(
+                "unsupported base URL scheme `{}` (expected http or https)",
+                url.scheme()
+            ));
+        }
+        if url.host_str().is_none_or(str::is_empty) {
+            return Err("base URL has no host".to_string());
+        }
+        if url.query().is_some() || url.fragment().is_some() {
+            return Err("base URL must not have a query or fragment".to_string());
+        }
+        let base = url.as_str().trim_end_matches('/');
+        Ok(Self {
+            messages: format!("{base}{ANTHROPIC_MESSAGES_PATH}"),
+            count_tokens: format!("{base}{ANTHROPIC_COUNT_TOKENS_PATH}"),
+            batches: format!("{base}{ANTHROPIC_BATCHES_PATH}"),
+            usage: format!("{base}{ANTHROPIC_USAGE_PATH}"),
+            profile: format!("{base}{ANTHROPIC_PROFILE_PATH}"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::ANTHROPIC_BASE_URL;
+    use crate::test_support::spawn_mock;
+    use axum::{Router, http::Uri};
+
+    #[test]
+    fn test_default_is_anthropic() {
+        let urls = UpstreamUrls::new(ANTHROPIC_BASE_URL).unwrap();
+        assert_eq!(
+            urls.messages,
+            "https://api.anthropic.com/v1/messages?beta=true"
+        );
+        assert_eq!(urls.usage, "https://api.anthropic.com/api/oauth/usage");
+    }
+
+    #[test]
+    fn test_base_with_path_prefix() {
+        let urls = UpstreamUrls::new("https://gateway.internal/anthropic/").unwrap();
+        assert_eq!(
+            urls.count_tokens,
+            "https://gateway.internal/anthropic/v1/messages/count_tokens?beta=true"
+        );
+        assert_eq!(
+            urls.batches,
+            "https://gateway.internal/anthropic/v1/messages/batches"
+        );
+    }
+
+    #[test]
+    fn test_invalid_base_rejected() {
+        assert!(UpstreamUrls::new("api.anthropic.com").is_err_and(|e| e.contains("invalid")));
+        assert!(UpstreamUrls::new("ftp://mirror").is_err_and(|e| e.contains("scheme")));
+        assert!(UpstreamUrls::new("https://gw.internal/?x=1").is_err_and(|e| e.contains("query")));
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_to_configured_base() {
+        let app = Router::new().fallback(|uri: Uri| async move { uri.to_string() });
+        let addr = spawn_mock(app).await;
+
+        let urls = UpstreamUrls::new(&format!("http://{addr}/prefix")).unwrap();
+        let body = reqwest::Client::new()
+            .post(&urls.messages)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "/prefix/v1/messages?beta=true");
+    }
+}
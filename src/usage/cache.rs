@@ -31,16 +31,25 @@
 //! inference traffic, the 60-second `util_updated_at` threshold fires and
 //! we fall into the 1-per-minute cadence. The transition is automatic —
 //! no modes, no state machine.
+//!
+//! ## Persistence
+//!
+//! The window reset times are also written to the database whenever they
+//! change and loaded back by [`restore`] at startup, so per-key windows stay
+//! aligned across restarts (see [`resets`](super::resets)).
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use chrono::DateTime;
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 
+use super::error::FetchError;
 use super::fetchers;
 use super::headers::HeaderPatch;
-use super::types::{CachedUsage, SubscriptionUsageResponse, UsageLimit};
+use super::resets::{self, WindowResets};
+use super::samples;
+use super::types::{CachedUsage, SubscriptionUsageResponse, UsageLimit, UsageSource};
 use crate::AppState;
 
 /// Maximum age of `util_updated_at` before [`get_or_refresh`] will trigger
@@ -70,6 +79,28 @@ impl UsageCache {
         }
     }
 
+    /// A cache seeded with the persisted window reset times that are still
+    /// in the future. Utilization stays unknown and the cache counts as
+    /// never fetched, so the first read still triggers a full fetch.
+    pub async fn restore(now: u64) -> Self {
+        let snapshot = match resets::load().await {
+            Ok(resets) => resets.and_then(|r| r.to_snapshot(now)),
+            Err(e) => {
+                warn!("Failed to load persisted window resets: {e}");
+                None
+            }
+        };
+        if snapshot.is_some() {
+            info!("restored subscription window reset times from the database");
+        }
+        Self {
+            state: RwLock::new(CachedUsage {
+                snapshot,
+                ..CachedUsage::default()
+            }),
+        }
+    }
+
     // ----- reads -----
 
     /// O(1) read. Returns the current in-memory state with no I/O.
@@ -97,12 +128,6 @@ impl UsageCache {
         self.state.read().await.clone()
     }
 
-    /// Pure read of the "is the subscription blown" flag. Used in the
-    /// per-request auth path for keys without `allow_extra_usage`. No I/O.
-    pub async fn is_over_subscription_limit(&self) -> bool {
-        self.state.read().await.is_over_subscription_limit()
-    }
-
     // ----- writes -----
 
     /// Patch 5h/7d utilization and reset times from a `/v1/messages`
@@ -118,25 +143,35 @@ impl UsageCache {
             return;
         }
         let now = now_ms();
-        let mut cache = self.state.write().await;
+        let (before, after) = {
+            let mut cache = self.state.write().await;
+            let before = WindowResets::from_state(&cache.window_state());
 
-        let snapshot = cache
-            .snapshot
-            .get_or_insert_with(SubscriptionUsageResponse::default);
-        apply_header_patch(snapshot, &patch);
-        cache.util_updated_at = Some(now);
+            let snapshot = cache
+                .snapshot
+                .get_or_insert_with(SubscriptionUsageResponse::default);
+            apply_header_patch(snapshot, &patch);
+            cache.util_updated_at = Some(now);
+            (before, WindowResets::from_state(&cache.window_state()))
+        };
 
         info!(
             "patched usage from /v1/messages headers: 5h_util={:?} 7d_util={:?}",
             patch.five_hour_utilization, patch.seven_day_utilization,
         );
+        persist_resets(before, after).await;
     }
 
-    /// Clear everything. Called on OAuth logout or any other event that
-    /// invalidates the stored identity.
+    /// Clear everything, including the persisted reset times. Called on
+    /// OAuth logout or any other event that invalidates the stored identity.
     pub async fn invalidate(&self) {
-        let mut cache = self.state.write().await;
-        *cache = CachedUsage::default();
+        let before = {
+            let mut cache = self.state.write().await;
+            let before = WindowResets::from_state(&cache.window_state());
+            *cache = CachedUsage::default();
+            before
+        };
+        persist_resets(before, WindowResets::default()).await;
     }
 
     // ----- query helpers -----
@@ -164,16 +199,34 @@ impl UsageCache {
     }
 
     async fn do_fetch_and_store(&self, state: &AppState) {
-        match fetchers::do_fetch(state).await {
+        self.store_fetched(fetchers::do_fetch(state).await).await;
+    }
+
+    async fn store_fetched(
+        &self,
+        result: Result<(SubscriptionUsageResponse, UsageSource), FetchError>,
+    ) {
+        match result {
             Ok((resp, source)) => {
                 let now = now_ms();
-                let mut cache = self.state.write().await;
-                cache.snapshot = Some(resp);
-                cache.full_fetched_at = Some(now);
-                cache.util_updated_at = Some(now);
-                cache.source = source;
-                cache.last_error = None;
+                let sample = samples::sample_from(&resp, now);
+                let (before, after) = {
+                    let mut cache = self.state.write().await;
+                    let before = WindowResets::from_state(&cache.window_state());
+                    cache.snapshot = Some(resp);
+                    cache.full_fetched_at = Some(now);
+                    cache.util_updated_at = Some(now);
+                    cache.source = source;
+                    cache.last_error = None;
+                    (before, WindowResets::from_state(&cache.window_state()))
+                };
                 info!("fetched subscription usage via {:?}", source);
+                persist_resets(before, after).await;
+                if let Some(sample) = sample
+                    && let Err(e) = samples::record(&sample).await
+                {
+                    warn!("Failed to store subscription utilization sample: {e}");
+                }
             }
             Err(e) => {
                 let mut cache = self.state.write().await;
@@ -186,6 +239,17 @@ impl UsageCache {
     }
 }
 
+/// Store the reset times if they changed. Headers arrive with every
+/// `/v1/messages` response, but the reset times only move once per window.
+async fn persist_resets(before: WindowResets, after: WindowResets) {
+    if before == after {
+        return;
+    }
+    if let Err(e) = resets::save(after).await {
+        warn!("Failed to persist window resets: {e}");
+    }
+}
+
 /// Apply a `HeaderPatch` to a snapshot in place, creating the `five_hour`
 /// and `seven_day` sub-structures if they didn't exist yet.
 fn apply_header_patch(snapshot: &mut SubscriptionUsageResponse, patch: &HeaderPatch) {
@@ -223,3 +287,137 @@ fn now_ms() -> u64 {
         .unwrap_or_default()
         .as_millis() as u64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{spawn_mock, with_db};
+
+    const NOW: u64 = 1_760_000_000_000;
+
+    #[tokio::test]
+    async fn test_forced_fetch_replaces_window_resets() {
+        use axum::{Json, Router, routing::get};
+
+        let app = Router::new().route(
+            "/api/oauth/usage",
+            get(|| async {
+                Json(serde_json::json!({
+                    "five_hour": { "utilization": 42.0, "resets_at": "2025-10-09T12:00:00+00:00" },
+                    "seven_day": { "utilization": 10.0, "resets_at": "2025-10-14T00:00:00+00:00" }
+                }))
+            }),
+        );
+        let addr = spawn_mock(app).await;
+
+        // Stale cache from an earlier window
+        let cache = UsageCache::new();
+        cache
+            .store_fetched(Ok((
+                SubscriptionUsageResponse {
+                    five_hour: Some(UsageLimit {
+                        utilization: Some(5.0),
+                        resets_at: Some("2025-10-09T07:00:00+00:00".into()),
+                    }),
+                    ..SubscriptionUsageResponse::default()
+                },
+                UsageSource::OAuthApi,
+            )))
+            .await;
+
+        let url = format!("http://{addr}/api/oauth/usage");
+        let fetched =
+            fetchers::oauth::fetch_with_token(&reqwest::Client::new(), &url, "token").await;
+        cache
+            .store_fetched(fetched.map(|resp| (resp, UsageSource::OAuthApi)))
+            .await;
+
+        let cached = cache.snapshot().await;
+        assert_eq!(cached.last_error, None);
+        let state = cached.window_state();
+        assert_eq!(state.five_hour_utilization, Some(42.0));
+        assert_eq!(state.five_hour_reset_at, Some(1_760_011_200_000));
+        assert_eq!(state.seven_day_reset_at, Some(1_760_400_000_000));
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_persisted_resets_loaded_on_init() {
+        with_db(async {
+            let saved = WindowResets {
+                five_hour_reset_at: Some(NOW + 3_600_000),
+                seven_day_reset_at: Some(NOW + 86_400_000),
+            };
+            persist_resets(WindowResets::default(), saved).await;
+
+            let cache = UsageCache::restore(NOW).await;
+            let snapshot = cache.snapshot().await;
+            let state = snapshot.window_state();
+            assert_eq!(WindowResets::from_state(&state), saved);
+            assert_eq!(state.five_hour_utilization, None);
+            // Restored data doesn't count as fresh
+            assert!(cache.needs_refresh().await);
+
+            // Logging out clears the stored times too
+            cache.invalidate().await;
+            assert_eq!(resets::load().await.unwrap(), Some(WindowResets::default()));
+            assert!(
+                UsageCache::restore(NOW)
+                    .await
+                    .snapshot()
+                    .await
+                    .snapshot
+                    .is_none()
+            );
+        });
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_past_resets_not_restored() {
+        with_db(async {
+            resets::save(WindowResets {
+                five_hour_reset_at: Some(NOW - 1),
+                seven_day_reset_at: Some(NOW + 86_400_000),
+            })
+            .await
+            .unwrap();
+            let state = UsageCache::restore(NOW)
+                .await
+                .snapshot()
+                .await
+                .window_state();
+            assert_eq!(state.five_hour_reset_at, None);
+            assert_eq!(state.seven_day_reset_at, Some(NOW + 86_400_000));
+
+            resets::save(WindowResets {
+                five_hour_reset_at: Some(NOW - 1),
+                seven_day_reset_at: None,
+            })
+            .await
+            .unwrap();
+            let cache = UsageCache::restore(NOW).await;
+            assert!(cache.snapshot().await.snapshot.is_none());
+        });
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_unchanged_resets_not_saved() {
+        with_db(async {
+            let stored = WindowResets {
+                five_hour_reset_at: Some(NOW - 1),
+                seven_day_reset_at: None,
+            };
+            resets::save(stored).await.unwrap();
+            let current = WindowResets {
+                five_hour_reset_at: Some(NOW),
+                seven_day_reset_at: None,
+            };
+            persist_resets(current, current).await;
+            assert_eq!(resets::load().await.unwrap(), Some(stored));
+            persist_resets(WindowResets::default(), current).await;
+            assert_eq!(resets::load().await.unwrap(), Some(current));
+        });
+    }
+}
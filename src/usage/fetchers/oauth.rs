@@ -12,7 +12,8 @@ use serde_json::from_str;
 use super::super::error::FetchError;
 use super::super::types::SubscriptionUsageResponse;
 use crate::AppState;
-use crate::constants::{ANTHROPIC_USAGE_URL, ANTHROPIC_VERSION, OAUTH_USAGE_BETA, USER_AGENT};
+use crate::auth::RefreshError;
+use crate::constants::{ANTHROPIC_VERSION, OAUTH_USAGE_BETA, USER_AGENT};
 
 /// Fetch the full usage snapshot via the OAuth `/api/oauth/usage` endpoint.
 /// Returns [`FetchError::NotConfigured`] when no OAuth token is stored.
@@ -21,12 +22,24 @@ pub async fn fetch(state: &AppState) -> Result<SubscriptionUsageResponse, FetchE
         .oauth
         .refresh_if_needed()
         .await
-        .map_err(|e| FetchError::Internal(format!("oauth refresh: {e}")))?
+        .map_err(|e| match e {
+            // A rejected login needs reconnecting, same as never connected
+            RefreshError::Revoked(_) => FetchError::NotConfigured,
+            RefreshError::Transient(_) => FetchError::Internal(format!("oauth refresh: {e}")),
+        })?
         .ok_or(FetchError::NotConfigured)?;
 
-    let resp = state
-        .http_client
-        .get(ANTHROPIC_USAGE_URL)
+    fetch_with_token(&state.http_client, &state.upstream.usage, &token).await
+}
+
+/// `GET` the usage endpoint at `url` with an access token in hand.
+pub(in crate::usage) async fn fetch_with_token(
+    client: &reqwest::Client,
+    url: &str,
+    token: &str,
+) -> Result<SubscriptionUsageResponse, FetchError> {
+    let resp = client
+        .get(url)
         .header("authorization", format!("Bearer {token}"))
         .header("anthropic-version", ANTHROPIC_VERSION)
         .header("anthropic-beta", OAUTH_USAGE_BETA)
@@ -90,6 +90,15 @@ impl HistoryPeriod {
         }
     }
 
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Start of the period (epoch ms) relative to `now`.
+    pub fn cutoff(&self, now: u64) -> u64 {
+        now.saturating_sub(self.cutoff_ms)
+    }
+
     pub fn empty_timeseries(&self) -> TimeseriesResponse {
         TimeseriesResponse {
             period: self.label.clone(),
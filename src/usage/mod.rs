@@ -8,15 +8,19 @@
 //!
 //! See the doc on [`cache::UsageCache`] for the freshness model and the
 //! adaptive refresh strategy. See [`fetchers::do_fetch`] for the fetcher
-//! chain (web session → OAuth).
+//! chain (web session → OAuth). Each full fetch is also stored as a
+//! utilization sample, see [`samples`]. Window reset times survive
+//! restarts, see [`resets`].
 
 mod cache;
 mod error;
 mod fetchers;
 mod headers;
 pub mod history;
+mod resets;
+pub mod samples;
 mod types;
 
 pub use cache::UsageCache;
 pub use fetchers::WEB_SESSION_PROVIDER;
-pub use types::{SubscriptionState, SubscriptionUsageResponse};
+pub use types::{CachedUsage, SubscriptionState, SubscriptionUsageResponse, UtilizationGate};
@@ -0,0 +1,74 @@
+//! Subscription window reset times, persisted across restarts.
+//!
+//! Per-key 5-hour and weekly windows are aligned to the subscription's own
+//! reset times, which otherwise live only in the in-memory [`UsageCache`].
+//! Right after a restart the cache is empty until the first usage fetch or
+//! `/v1/messages` response, and keys whose window rolls over in that gap
+//! would start a misaligned one. The last known reset times are stored
+//! whenever they change and loaded back into the cache at startup.
+//!
+//! [`UsageCache`]: super::UsageCache
+
+use serde::{Deserialize, Serialize};
+
+use super::types::{SubscriptionState, SubscriptionUsageResponse, UsageLimit};
+use crate::error::ProxyError;
+use crate::settings;
+
+/// Name of the row in the `settings` table
+const SETTING_NAME: &str = "subscription_window_resets";
+
+/// The persisted reset times (epoch ms).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowResets {
+    pub five_hour_reset_at: Option<u64>,
+    pub seven_day_reset_at: Option<u64>,
+}
+
+impl WindowResets {
+    pub fn from_state(state: &SubscriptionState) -> Self {
+        Self {
+            five_hour_reset_at: state.five_hour_reset_at,
+            seven_day_reset_at: state.seven_day_reset_at,
+        }
+    }
+
+    /// A snapshot carrying only the reset times still ahead of `now`, or
+    /// `None` if all of them have passed. Utilization is left unknown, so
+    /// the utilization gate stays open until real data arrives.
+    pub(super) fn to_snapshot(self, now: u64) -> Option<SubscriptionUsageResponse> {
+        let limit = |reset_at: Option<u64>| {
+            reset_at
+                .filter(|&t| t > now)
+                .and_then(|t| chrono::DateTime::from_timestamp_millis(i64::try_from(t).ok()?))
+                .map(|t| UsageLimit {
+                    utilization: None,
+                    resets_at: Some(t.to_rfc3339()),
+                })
+        };
+        let five_hour = limit(self.five_hour_reset_at);
+        let seven_day = limit(self.seven_day_reset_at);
+        (five_hour.is_some() || seven_day.is_some()).then(|| SubscriptionUsageResponse {
+            five_hour,
+            seven_day,
+            ..SubscriptionUsageResponse::default()
+        })
+    }
+}
+
+/// The reset times stored as JSON in the `settings` table, if any.
+pub(super) async fn load() -> Result<Option<WindowResets>, ProxyError> {
+    let Some(raw) = settings::get(SETTING_NAME).await? else {
+        return Ok(None);
+    };
+    serde_json::from_str(&raw)
+        .map(Some)
+        .map_err(|e| ProxyError::ParseError(format!("Invalid stored window resets: {e}")))
+}
+
+pub(super) async fn save(resets: WindowResets) -> Result<(), ProxyError> {
+    let raw = serde_json::to_string(&resets)
+        .map_err(|e| ProxyError::ParseError(format!("Failed to encode window resets: {e}")))?;
+    settings::set(SETTING_NAME, &raw).await
+}
@@ -0,0 +1,172 @@
+//! Persisted history of subscription utilization.
+//!
+//! Every successful full usage fetch stores the 5-hour and 7-day
+//! utilization, so the admin UI can graph how close the subscription runs
+//! to its limits. Header patches are not sampled: they arrive with every
+//! `/v1/messages` response and would flood the table. Samples older than
+//! [`SAMPLE_RETENTION_MS`] are pruned as new ones are written.
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::types::SubscriptionUsageResponse;
+use crate::auth::client_keys::i64_to_u64;
+use crate::db::{self, Connection};
+use crate::error::{DbResultExt, ProxyError};
+
+/// How long samples are kept (30 days, the longest history period).
+const SAMPLE_RETENTION_MS: u64 = 30 * 24 * 3600 * 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UtilizationSample {
+    /// Epoch ms
+    pub sampled_at: u64,
+    /// Percent of the 5-hour window used
+    pub five_hour_utilization: Option<f64>,
+    /// Percent of the 7-day window used
+    pub seven_day_utilization: Option<f64>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UtilizationSamplesResponse {
+    pub period: String,
+    /// Oldest first
+    pub samples: Vec<UtilizationSample>,
+}
+
+/// The sample a fetched snapshot yields, or `None` if it reports neither
+/// window's utilization.
+pub(super) fn sample_from(
+    snapshot: &SubscriptionUsageResponse,
+    now: u64,
+) -> Option<UtilizationSample> {
+    let five_hour = snapshot.five_hour.as_ref().and_then(|l| l.utilization);
+    let seven_day = snapshot.seven_day.as_ref().and_then(|l| l.utilization);
+    (five_hour.is_some() || seven_day.is_some()).then_some(UtilizationSample {
+        sampled_at: now,
+        five_hour_utilization: five_hour,
+        seven_day_utilization: seven_day,
+    })
+}
+
+/// Store `sample` and drop samples past the retention window.
+pub(super) async fn record(sample: &UtilizationSample) -> Result<(), ProxyError> {
+    let conn = db::get_conn().await?;
+    sqlx::query!(
+        "INSERT INTO subscription_samples (sampled_at, five_hour_utilization, seven_day_utilization) \
+         VALUES ($1, $2, $3)",
+        sample.sampled_at as i64,
+        sample.five_hour_utilization,
+        sample.seven_day_utilization,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to record subscription sample")?;
+
+    let cutoff = sample.sampled_at.saturating_sub(SAMPLE_RETENTION_MS);
+    sqlx::query!(
+        "DELETE FROM subscription_samples WHERE sampled_at < $1",
+        cutoff as i64,
+    )
+    .execute(&conn)
+    .await
+    .db_context("Failed to prune subscription samples")?;
+    Ok(())
+}
+
+/// Samples taken at or after `from` (epoch ms), oldest first.
+pub async fn series(conn: &Connection, from: u64) -> Result<Vec<UtilizationSample>, ProxyError> {
+    let rows = sqlx::query!(
+        "SELECT sampled_at, five_hour_utilization, seven_day_utilization \
+         FROM subscription_samples WHERE sampled_at >= $1 ORDER BY sampled_at",
+        from as i64,
+    )
+    .fetch_all(conn)
+    .await
+    .db_context("Failed to read subscription samples")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| UtilizationSample {
+            sampled_at: i64_to_u64(row.sampled_at),
+            five_hour_utilization: row.five_hour_utilization,
+            seven_day_utilization: row.seven_day_utilization,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::timestamp_millis;
+    use crate::test_support::{create_test_key, spawn_mock, state_with_upstream, with_db};
+    use crate::usage::types::UsageLimit;
+    use axum::{Json, Router, routing::get};
+    use serde_json::json;
+
+    fn limit(utilization: Option<f64>) -> Option<UsageLimit> {
+        Some(UsageLimit {
+            utilization,
+            resets_at: None,
+        })
+    }
+
+    #[test]
+    fn test_snapshot_becomes_sample() {
+        let snapshot = SubscriptionUsageResponse {
+            five_hour: limit(Some(42.0)),
+            seven_day: limit(Some(17.5)),
+            ..SubscriptionUsageResponse::default()
+        };
+        assert_eq!(
+            sample_from(&snapshot, 1_000),
+            Some(UtilizationSample {
+                sampled_at: 1_000,
+                five_hour_utilization: Some(42.0),
+                seven_day_utilization: Some(17.5),
+            })
+        );
+    }
+
+    #[test]
+    fn test_snapshot_without_utilization_is_not_sampled() {
+        let snapshot = SubscriptionUsageResponse {
+            five_hour: limit(None),
+            ..SubscriptionUsageResponse::default()
+        };
+        assert_eq!(sample_from(&snapshot, 1_000), None);
+        assert_eq!(
+            sample_from(&SubscriptionUsageResponse::default(), 1_000),
+            None
+        );
+    }
+
+    #[test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    fn test_refresh_records_sample() {
+        with_db(async {
+            let upstream = Router::new().route(
+                "/api/oauth/usage",
+                get(|| async {
+                    Json(json!({
+                        "five_hour": { "utilization": 42.0, "resets_at": "2025-10-09T12:00:00+00:00" },
+                        "seven_day": { "utilization": 17.5, "resets_at": "2025-10-14T08:00:00+00:00" }
+                    }))
+                }),
+            );
+            let state = state_with_upstream(spawn_mock(upstream).await);
+            create_test_key(&state, "usage-sample").await;
+            let before = timestamp_millis();
+
+            state.usage_cache.force_refresh(&state).await;
+
+            let conn = db::get_conn().await.unwrap();
+            let samples = series(&conn, before).await.unwrap();
+            assert_eq!(samples.len(), 1, "{samples:?}");
+            assert_eq!(samples[0].five_hour_utilization, Some(42.0));
+            assert_eq!(samples[0].seven_day_utilization, Some(17.5));
+        });
+    }
+}
@@ -66,7 +66,7 @@ pub struct SubscriptionUsageResponse {
 /// A subset of [`SubscriptionUsageResponse`] — window reset timestamps and
 /// 5h/7d utilization percentages — used by per-key rate-limit bookkeeping
 /// in `auth/rate_limits.rs`. Derived from a [`CachedUsage`] snapshot.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
 pub struct SubscriptionState {
     pub five_hour_reset_at: Option<u64>,
     pub seven_day_reset_at: Option<u64>,
@@ -74,6 +74,66 @@ pub struct SubscriptionState {
     pub seven_day_utilization: Option<f64>,
 }
 
+/// A tripped subscription utilization gate. See
+/// [`SubscriptionState::utilization_gate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtilizationGate {
+    /// Epoch-ms when the exceeded window(s) reset, if known. When both
+    /// windows are over the threshold this is the later of the two.
+    pub resets_at: Option<u64>,
+}
+
+impl UtilizationGate {
+    /// Seconds until the gate lifts, rounded up, for a `Retry-After` header.
+    /// `None` when the reset time is unknown.
+    pub fn retry_after_secs(&self, now_ms: u64) -> Option<u64> {
+        self.resets_at
+            .map(|reset| reset.saturating_sub(now_ms).div_ceil(1000).max(1))
+    }
+}
+
+/// Points over a gate's threshold within which a tripped gate is rechecked
+/// against a fresh fetch: the cached reading may be a header patch that
+/// rounds up, or predate a window reset.
+const GATE_RECHECK_MARGIN_PCT: f64 = 2.0;
+
+/// Minimum age of the cached utilization before a near-threshold gate is
+/// rechecked, so a burst of blocked requests costs at most one fetch of the
+/// rate-limited usage endpoint.
+const GATE_RECHECK_MIN_AGE_MS: u64 = 30 * 1000;
+
+impl SubscriptionState {
+    /// Whether every window tripping the gate at `threshold` is less than
+    /// [`GATE_RECHECK_MARGIN_PCT`] over it. Far over the threshold, a
+    /// fresher reading wouldn't change the answer.
+    pub fn near_gate(&self, threshold: f64) -> bool {
+        [self.five_hour_utilization, self.seven_day_utilization]
+            .into_iter()
+            .flatten()
+            .filter(|&u| u >= threshold)
+            .all(|u| u < threshold + GATE_RECHECK_MARGIN_PCT)
+    }
+
+    /// Check 5h/7d utilization against `threshold` (percent). Returns the
+    /// tripped gate when either window is at or above it. Keys with
+    /// `allow_extra_usage` are exempt; that check belongs to the caller.
+    pub fn utilization_gate(&self, threshold: f64) -> Option<UtilizationGate> {
+        let five_hour = self.five_hour_utilization.is_some_and(|u| u >= threshold);
+        let seven_day = self.seven_day_utilization.is_some_and(|u| u >= threshold);
+        if !five_hour && !seven_day {
+            return None;
+        }
+        let resets_at = [
+            five_hour.then_some(self.five_hour_reset_at).flatten(),
+            seven_day.then_some(self.seven_day_reset_at).flatten(),
+        ]
+        .into_iter()
+        .flatten()
+        .max();
+        Some(UtilizationGate { resets_at })
+    }
+}
+
 /// Where the most recent successful full fetch came from.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
@@ -106,6 +166,16 @@ pub struct CachedUsage {
 }
 
 impl CachedUsage {
+    /// Whether a gate tripped at `threshold` is worth rechecking with a
+    /// fresh fetch at `now`: the reading is [near](SubscriptionState::near_gate)
+    /// the threshold and at least [`GATE_RECHECK_MIN_AGE_MS`] old.
+    pub fn worth_gate_recheck(&self, threshold: f64, now: u64) -> bool {
+        self.window_state().near_gate(threshold)
+            && self
+                .util_updated_at
+                .is_none_or(|t| now.saturating_sub(t) >= GATE_RECHECK_MIN_AGE_MS)
+    }
+
     /// Project the cache down to the window-reset view used by per-key rate
     /// limit bookkeeping. Handles RFC3339 parsing of the `resets_at` strings.
     pub fn window_state(&self) -> SubscriptionState {
@@ -133,15 +203,6 @@ impl CachedUsage {
         }
     }
 
-    /// Has the user blown through their 5h or 7d subscription window? Used
-    /// to block inference for keys that don't have `allow_extra_usage`.
-    /// Pure read, no I/O.
-    pub fn is_over_subscription_limit(&self) -> bool {
-        let state = self.window_state();
-        state.five_hour_utilization.is_some_and(|u| u >= 100.0)
-            || state.seven_day_utilization.is_some_and(|u| u >= 100.0)
-    }
-
     /// Build the public response the admin UI gets back. Clones the snapshot
     /// (if any) and attaches cache metadata.
     pub fn to_response(&self) -> SubscriptionUsageResponse {
@@ -154,3 +215,84 @@ impl CachedUsage {
         out
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(five_hour: f64, seven_day: f64) -> SubscriptionState {
+        SubscriptionState {
+            five_hour_reset_at: Some(10_000),
+            seven_day_reset_at: Some(500_000),
+            five_hour_utilization: Some(five_hour),
+            seven_day_utilization: Some(seven_day),
+        }
+    }
+
+    #[test]
+    fn test_utilization_gate_boundary() {
+        assert_eq!(state(94.9, 10.0).utilization_gate(95.0), None);
+        assert_eq!(
+            state(95.0, 10.0).utilization_gate(95.0),
+            Some(UtilizationGate {
+                resets_at: Some(10_000)
+            })
+        );
+        assert_eq!(state(99.0, 10.0).utilization_gate(100.0), None);
+    }
+
+    #[test]
+    fn test_utilization_gate_uses_latest_exceeded_reset() {
+        let gate = state(96.0, 97.0).utilization_gate(95.0).unwrap();
+        assert_eq!(gate.resets_at, Some(500_000));
+
+        let gate = state(10.0, 97.0).utilization_gate(95.0).unwrap();
+        assert_eq!(gate.resets_at, Some(500_000));
+    }
+
+    #[test]
+    fn test_near_gate_only_just_over_threshold() {
+        assert!(state(80.5, 10.0).near_gate(80.0));
+        assert!(!state(85.0, 10.0).near_gate(80.0));
+        // One window far over settles it, whatever the other reads
+        assert!(!state(80.5, 90.0).near_gate(80.0));
+    }
+
+    #[test]
+    fn test_gate_recheck_waits_for_stale_reading() {
+        let cached = CachedUsage {
+            snapshot: Some(SubscriptionUsageResponse {
+                five_hour: Some(UsageLimit {
+                    utilization: Some(81.0),
+                    resets_at: None,
+                }),
+                ..SubscriptionUsageResponse::default()
+            }),
+            util_updated_at: Some(1_000_000),
+            ..CachedUsage::default()
+        };
+        assert!(!cached.worth_gate_recheck(80.0, 1_000_000 + 1_000));
+        assert!(cached.worth_gate_recheck(80.0, 1_000_000 + GATE_RECHECK_MIN_AGE_MS));
+        assert!(!cached.worth_gate_recheck(70.0, 1_000_000 + GATE_RECHECK_MIN_AGE_MS));
+    }
+
+    #[test]
+    fn test_utilization_gate_unknown_utilization_passes() {
+        assert_eq!(SubscriptionState::default().utilization_gate(0.0), None);
+    }
+
+    #[test]
+    fn test_retry_after_secs() {
+        let gate = UtilizationGate {
+            resets_at: Some(10_000),
+        };
+        assert_eq!(gate.retry_after_secs(0), Some(10));
+        assert_eq!(gate.retry_after_secs(8_500), Some(2));
+        // Already past the reset: still tell the client to back off briefly.
+        assert_eq!(gate.retry_after_secs(20_000), Some(1));
+        assert_eq!(
+            UtilizationGate { resets_at: None }.retry_after_secs(0),
+            None
+        );
+    }
+}